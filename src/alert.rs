@@ -0,0 +1,186 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+use twilight_http::api_error::ApiError;
+use twilight_http::error::ErrorType;
+use twilight_http::Client as HttpClient;
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use crate::reporting;
+use crate::state::ThreadInfo;
+
+/// 同じ内容の通知を繰り返し送らない期間（障害が続いた場合に管理用チャンネルが埋まらないようにする）
+const DUPLICATE_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// 通知に含める詳細の最大文字数（Discordのメッセージの上限に収めるため）
+const MAX_DETAIL_CHARS: usize = 1500;
+
+/// 最後のエラーの要約に含める詳細の最大文字数
+const MAX_SUMMARY_CHARS: usize = 200;
+
+/// 管理用チャンネルに通知する障害の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlertKind {
+    /// 権限が不足している（403）
+    Permission,
+    /// メッセージの転送に失敗した
+    SendFailed,
+    /// マッピングが一時停止・削除された
+    MappingDisabled,
+    /// バックグラウンドのタスクが続けて異常終了した
+    TaskCrashed,
+}
+
+impl AlertKind {
+    /// 通知の見出し
+    fn title(self) -> &'static str {
+        match self {
+            Self::Permission => "🔒 **権限エラー**",
+            Self::SendFailed => "⚠️ **転送エラー**",
+            Self::MappingDisabled => "⏸️ **マッピングの停止**",
+            Self::TaskCrashed => "💥 **タスクの異常終了**",
+        }
+    }
+
+    /// エラーによる通知かどうか（最後のエラーとして記録する）
+    fn is_error(self) -> bool {
+        !matches!(self, Self::MappingDisabled)
+    }
+}
+
+/// 最後に発生したエラー
+struct LastFailure {
+    /// 発生した日時（UNIX時間）
+    at: i64,
+    /// 発生した場所
+    context: String,
+    /// エラーの内容
+    detail: String,
+}
+
+/// エラーが権限の不足（Discordの 403 応答）によるものかどうか
+fn is_permission_error(error: &(dyn std::error::Error + Send + Sync + 'static)) -> bool {
+    match error.downcast_ref::<twilight_http::Error>().map(twilight_http::Error::kind) {
+        Some(ErrorType::Response { status, error, .. }) => {
+            status.get() == 403 || matches!(error, ApiError::General(general) if general.code == 50013)
+        }
+        // Webhookの送信は reqwest で行うため、エラーの文章からステータスを判定する
+        _ => error.to_string().contains("403 Forbidden"),
+    }
+}
+
+/// Webhook URL のトークンを伏せる（エラーの文章にURLが含まれる場合があるため）
+pub fn redact_webhook_tokens(text: &str) -> String {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN
+        .get_or_init(|| Regex::new(r"(/webhooks/\d+/)[\w-]+").unwrap())
+        .replace_all(text, "${1}***")
+        .into_owned()
+}
+
+/// 管理用チャンネルへの障害の通知
+///
+/// 権限エラー・転送の失敗・マッピングの停止を、発生した場所とあわせて管理用チャンネルに投稿します。
+/// 管理用チャンネルを設定していない場合は何もしません（ログは呼び出し元で出力します）。
+#[derive(Default)]
+pub struct AdminAlerts {
+    /// 通知を投稿するチャンネル
+    channel_id: Option<Id<ChannelMarker>>,
+    /// 通知の内容 -> 最後に通知した時刻
+    recent: Mutex<HashMap<(AlertKind, String), Instant>>,
+    /// 最後に発生したエラー（管理用チャンネルを設定していない場合も記録する）
+    last_failure: Mutex<Option<LastFailure>>,
+}
+
+impl AdminAlerts {
+    /// 通知先のチャンネルを指定して作成する
+    pub fn new(channel_id: Option<Id<ChannelMarker>>) -> Self {
+        Self {
+            channel_id,
+            recent: Mutex::new(HashMap::new()),
+            last_failure: Mutex::new(None),
+        }
+    }
+
+    /// 最後に発生したエラーの要約（発生していない場合は None）
+    pub async fn last_failure_summary(&self) -> Option<String> {
+        let last_failure = self.last_failure.lock().await;
+        let failure = last_failure.as_ref()?;
+        let detail: String = failure.detail.replace('\n', " ").chars().take(MAX_SUMMARY_CHARS).collect();
+        Some(format!("<t:{}:R> {} — {}", failure.at, failure.context, detail))
+    }
+
+    /// 処理中に発生したエラーを通知する（権限の不足によるものは権限エラーとして通知）
+    ///
+    /// Sentry を設定している場合は、Sentry にも報告します。
+    pub async fn failure(&self, http: &HttpClient, context: &str, error: &(dyn std::error::Error + Send + Sync + 'static)) {
+        reporting::capture_error(context, None, error);
+        self.notify_failure(http, context, error).await;
+    }
+
+    /// マッピングの転送中に発生したエラーを通知する（Sentry への報告にはマッピングの情報を付ける）
+    pub async fn mapping_failure(
+        &self,
+        http: &HttpClient,
+        context: &str,
+        thread_id: Id<ChannelMarker>,
+        thread_info: &ThreadInfo,
+        error: &(dyn std::error::Error + Send + Sync + 'static),
+    ) {
+        reporting::capture_error(context, Some((thread_id, thread_info)), error);
+        self.notify_failure(http, context, error).await;
+    }
+
+    /// エラーを管理用チャンネルに通知する
+    async fn notify_failure(&self, http: &HttpClient, context: &str, error: &(dyn std::error::Error + Send + Sync + 'static)) {
+        let kind = if is_permission_error(error) {
+            AlertKind::Permission
+        } else {
+            AlertKind::SendFailed
+        };
+        self.report(http, kind, context, &error.to_string()).await;
+    }
+
+    /// 障害を管理用チャンネルに通知する
+    pub async fn report(&self, http: &HttpClient, kind: AlertKind, context: &str, detail: &str) {
+        if kind.is_error() {
+            *self.last_failure.lock().await = Some(LastFailure {
+                at: chrono::Utc::now().timestamp(),
+                context: context.to_string(),
+                detail: redact_webhook_tokens(detail),
+            });
+        }
+
+        let Some(channel_id) = self.channel_id else {
+            return;
+        };
+
+        {
+            let mut recent = self.recent.lock().await;
+            let now = Instant::now();
+            recent.retain(|_, sent_at| now.duration_since(*sent_at) < DUPLICATE_WINDOW);
+            let key = (kind, context.to_string());
+            if recent.contains_key(&key) {
+                return;
+            }
+            recent.insert(key, now);
+        }
+
+        let detail: String = redact_webhook_tokens(detail).chars().take(MAX_DETAIL_CHARS).collect();
+        let content = if detail.is_empty() {
+            format!("{}\n場所: {}", kind.title(), context)
+        } else {
+            format!("{}\n場所: {}\n```\n{}\n```", kind.title(), context, detail.replace("```", "'''"))
+        };
+
+        let post = async {
+            http.create_message(channel_id).content(&content)?.await?;
+            Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+        };
+        if let Err(e) = post.await {
+            println!("管理用チャンネル {} への通知に失敗しました: {}", channel_id, e);
+        }
+    }
+}
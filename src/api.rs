@@ -0,0 +1,84 @@
+use async_trait::async_trait;
+use twilight_http::Client as HttpClient;
+use twilight_model::channel::{Channel, Message};
+use twilight_model::id::{
+    marker::{ChannelMarker, MessageMarker, UserMarker},
+    Id,
+};
+use twilight_model::user::User;
+
+/// ボットが使用するDiscordのAPI
+///
+/// `BotState` とコマンドの処理はこのトレイトを通してメッセージの送信・取得を行うため、
+/// Discordに接続せずに、記録用の実装に差し替えて動作を確かめることができます（`BotState::with_api` を参照）。
+#[async_trait]
+pub trait DiscordApi: Send + Sync {
+    /// チャンネルにメッセージを送信する
+    async fn create_message(
+        &self,
+        channel_id: Id<ChannelMarker>,
+        content: &str,
+    ) -> Result<Message, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// チャンネルのメッセージを新しい順に取得する（`before` を指定した場合はそれより前のメッセージ）
+    async fn channel_messages(
+        &self,
+        channel_id: Id<ChannelMarker>,
+        before: Option<Id<MessageMarker>>,
+        limit: u16,
+    ) -> Result<Vec<Message>, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// チャンネルの情報を取得する
+    async fn channel(&self, channel_id: Id<ChannelMarker>) -> Result<Channel, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// メッセージを1件取得する（編集・リアクションのイベントには一部の項目しか含まれないため）
+    async fn message(
+        &self,
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+    ) -> Result<Message, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// ユーザーの情報を取得する
+    async fn user(&self, user_id: Id<UserMarker>) -> Result<User, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+#[async_trait]
+impl DiscordApi for HttpClient {
+    async fn create_message(
+        &self,
+        channel_id: Id<ChannelMarker>,
+        content: &str,
+    ) -> Result<Message, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(HttpClient::create_message(self, channel_id).content(content)?.await?.model().await?)
+    }
+
+    async fn channel_messages(
+        &self,
+        channel_id: Id<ChannelMarker>,
+        before: Option<Id<MessageMarker>>,
+        limit: u16,
+    ) -> Result<Vec<Message>, Box<dyn std::error::Error + Send + Sync>> {
+        let request = HttpClient::channel_messages(self, channel_id);
+        let response = match before {
+            Some(before) => request.before(before).limit(limit)?.await?,
+            None => request.limit(limit)?.await?,
+        };
+        Ok(response.models().await?)
+    }
+
+    async fn channel(&self, channel_id: Id<ChannelMarker>) -> Result<Channel, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(HttpClient::channel(self, channel_id).await?.model().await?)
+    }
+
+    async fn message(
+        &self,
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+    ) -> Result<Message, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(HttpClient::message(self, channel_id, message_id).await?.model().await?)
+    }
+
+    async fn user(&self, user_id: Id<UserMarker>) -> Result<User, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(HttpClient::user(self, user_id).await?.model().await?)
+    }
+}
@@ -0,0 +1,56 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use twilight_model::channel::Message;
+use twilight_model::http::attachment::Attachment as HttpAttachment;
+
+/// 元のメッセージをそのままJSONで保存する、機械可読なアーカイブ
+///
+/// `archive=jsonl` のマッピングでは、転送元スレッドごとに `{スレッドID}.jsonl` へ1行ずつ追記します。
+pub struct MessageArchive {
+    dir: PathBuf,
+    /// 同じファイルへの追記が混ざらないようにするためのロック
+    lock: Mutex<()>,
+}
+
+impl MessageArchive {
+    /// 指定したディレクトリに保存するアーカイブを作成する（ディレクトリは最初の追記時に作成）
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir, lock: Mutex::new(()) }
+    }
+
+    /// メッセージをJSONLファイルに1行追記する
+    pub fn append(&self, message: &Message) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let line = serde_json::to_string(message)?;
+        let path = self.dir.join(format!("{}.jsonl", message.channel_id));
+
+        let _guard = self.lock.lock().unwrap();
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("アーカイブのディレクトリ {} を作成できませんでした: {}", self.dir.display(), e))?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("アーカイブ {} を開けませんでした: {}", path.display(), e))?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    }
+
+    /// アーカイブのディレクトリにファイルを書き出し、そのパスを返す
+    pub fn write_file(&self, file_name: &str, contents: &[u8]) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+        let path = self.dir.join(file_name);
+
+        let _guard = self.lock.lock().unwrap();
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("アーカイブのディレクトリ {} を作成できませんでした: {}", self.dir.display(), e))?;
+        fs::write(&path, contents).map_err(|e| format!("ファイル {} を書き出せませんでした: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
+
+/// 転送メッセージに添付する、元のメッセージのJSONファイルを作成する
+pub fn json_attachment(message: &Message, id: u64) -> Result<HttpAttachment, Box<dyn std::error::Error + Send + Sync>> {
+    let json = serde_json::to_vec_pretty(message)?;
+    Ok(HttpAttachment::from_bytes(format!("message-{}.json", message.id), json, id))
+}
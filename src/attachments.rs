@@ -0,0 +1,339 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use twilight_model::channel::{Attachment, Message};
+
+/// 保存する添付ファイルの最大サイズ（これより大きいファイルは保存しない）
+const MAX_ARCHIVE_BYTES: u64 = 100 * 1024 * 1024;
+
+/// 保存した添付ファイルの一覧（1行1件のJSON）のファイル名
+const MANIFEST_FILE_NAME: &str = "attachments.jsonl";
+
+/// バイト列を16進数の文字列にする
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// ファイル名をパスやオブジェクトのキーに使える形にする（区切り文字や制御文字を `_` に置き換える）
+fn safe_file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c })
+        .collect();
+    match name.trim_start_matches('.') {
+        "" => "file".to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// 添付ファイルの保存先
+///
+/// 新しい種類の保存先を追加する場合は、この trait を実装し、[`AttachmentArchive::from_location`] で選択できるようにします。
+#[async_trait]
+pub trait AttachmentStore: Send + Sync {
+    /// 保存先の説明（ログ用）
+    fn describe(&self) -> String;
+
+    /// ファイルを `key`（`/` 区切りの相対パス）に保存し、保存した場所を返す
+    async fn put(&self, key: &str, bytes: &[u8], content_type: Option<&str>) -> Result<String, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// ローカルのディレクトリに保存する
+pub struct LocalStore {
+    pub dir: PathBuf,
+}
+
+#[async_trait]
+impl AttachmentStore for LocalStore {
+    fn describe(&self) -> String {
+        format!("ディレクトリ {}", self.dir.display())
+    }
+
+    async fn put(&self, key: &str, bytes: &[u8], _content_type: Option<&str>) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let path = self.dir.join(key);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("ディレクトリ {} を作成できませんでした: {}", dir.display(), e))?;
+        }
+        fs::write(&path, bytes).map_err(|e| format!("ファイル {} を書き出せませんでした: {}", path.display(), e))?;
+        Ok(path.display().to_string())
+    }
+}
+
+/// S3 互換のストレージに保存する（署名は AWS Signature Version 4）
+///
+/// 認証情報は環境変数 `AWS_ACCESS_KEY_ID`・`AWS_SECRET_ACCESS_KEY`（一時的な認証情報の場合は `AWS_SESSION_TOKEN` も）から読み込みます。
+/// `S3_ENDPOINT` を指定した場合は、そのエンドポイントにパス形式（`/バケット/キー`）で送信します（MinIO・Cloudflare R2 など）。
+pub struct S3Store {
+    pub bucket: String,
+    /// キーの先頭に付けるパス（空の場合はバケットの直下）
+    pub prefix: String,
+    pub region: String,
+    /// S3 互換のストレージのエンドポイント（None の場合は AWS の S3）
+    pub endpoint: Option<String>,
+    pub access_key: String,
+    pub secret_key: String,
+    pub session_token: Option<String>,
+}
+
+impl S3Store {
+    /// `s3://バケット/プレフィックス` と環境変数から作成する
+    fn from_env(location: &str) -> Result<Self, String> {
+        let path = location.trim_start_matches("s3://");
+        let (bucket, prefix) = path.split_once('/').unwrap_or((path, ""));
+        if bucket.is_empty() {
+            return Err(format!("添付ファイルの保存先にバケットを指定してください（例: s3://bucket/attachments）: {}", location));
+        }
+        let access_key = std::env::var("AWS_ACCESS_KEY_ID").map_err(|_| "S3 に保存するには環境変数 AWS_ACCESS_KEY_ID を設定してください".to_string())?;
+        let secret_key =
+            std::env::var("AWS_SECRET_ACCESS_KEY").map_err(|_| "S3 に保存するには環境変数 AWS_SECRET_ACCESS_KEY を設定してください".to_string())?;
+
+        Ok(Self {
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+            region: std::env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+            endpoint: std::env::var("S3_ENDPOINT").ok().filter(|endpoint| !endpoint.is_empty()),
+            access_key,
+            secret_key,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok().filter(|token| !token.is_empty()),
+        })
+    }
+
+    /// オブジェクトのキー（プレフィックスを含む）
+    fn object_key(&self, key: &str) -> String {
+        if self.prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}/{}", self.prefix, key)
+        }
+    }
+
+    /// 送信先のURLと、署名に使うホスト名・パスを返す
+    fn target(&self, object_key: &str) -> (String, String, String) {
+        let encoded_key = object_key.split('/').map(uri_encode).collect::<Vec<_>>().join("/");
+        match &self.endpoint {
+            Some(endpoint) => {
+                let endpoint = endpoint.trim_end_matches('/');
+                let host = endpoint.split_once("://").map_or(endpoint, |(_, host)| host).to_string();
+                let path = format!("/{}/{}", uri_encode(&self.bucket), encoded_key);
+                (format!("{}{}", endpoint, path), host, path)
+            }
+            None => {
+                let host = format!("{}.s3.{}.amazonaws.com", self.bucket, self.region);
+                let path = format!("/{}", encoded_key);
+                (format!("https://{}{}", host, path), host, path)
+            }
+        }
+    }
+
+    /// PUT リクエストの Authorization ヘッダーを作成する
+    fn authorization(&self, host: &str, path: &str, payload_hash: &str, amz_date: &str) -> String {
+        let date = &amz_date[..8];
+        let mut headers = vec![
+            ("host", host.to_string()),
+            ("x-amz-content-sha256", payload_hash.to_string()),
+            ("x-amz-date", amz_date.to_string()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let canonical_headers: String = headers.iter().map(|(name, value)| format!("{}:{}\n", name, value)).collect();
+        let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+
+        let canonical_request = format!("PUT\n{}\n\n{}\n{}\n{}", path, canonical_headers, signed_headers, payload_hash);
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", amz_date, scope, hex(&Sha256::digest(canonical_request.as_bytes())));
+
+        let signing_key = [self.region.as_str(), "s3", "aws4_request"]
+            .iter()
+            .fold(hmac_sha256(format!("AWS4{}", self.secret_key).as_bytes(), date.as_bytes()), |key, part| {
+                hmac_sha256(&key, part.as_bytes())
+            });
+        let signature = hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        )
+    }
+}
+
+/// HMAC-SHA256 を計算する
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMACの鍵は任意の長さを受け付ける");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// 署名用にパスの1区間をURLエンコードする（英数字と `-_.~` 以外をエンコード）
+fn uri_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[async_trait]
+impl AttachmentStore for S3Store {
+    fn describe(&self) -> String {
+        format!("s3://{}/{}", self.bucket, self.prefix)
+    }
+
+    async fn put(&self, key: &str, bytes: &[u8], content_type: Option<&str>) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let object_key = self.object_key(key);
+        let (url, host, path) = self.target(&object_key);
+        let payload_hash = hex(&Sha256::digest(bytes));
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+        let mut request = reqwest::Client::new()
+            .put(&url)
+            .header("x-amz-content-sha256", &payload_hash)
+            .header("x-amz-date", &amz_date)
+            .header("Authorization", self.authorization(&host, &path, &payload_hash, &amz_date))
+            .header("Content-Type", content_type.unwrap_or("application/octet-stream"));
+        if let Some(token) = &self.session_token {
+            request = request.header("x-amz-security-token", token);
+        }
+
+        let response = request
+            .body(bytes.to_vec())
+            .send()
+            .await
+            .map_err(|e| format!("S3 への送信に失敗しました: {}", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("S3 への保存が失敗しました: {} {}", status, body).into());
+        }
+        Ok(format!("s3://{}/{}", self.bucket, object_key))
+    }
+}
+
+/// 保存した添付ファイルの一覧の1件
+#[derive(Serialize)]
+struct ManifestEntry<'a> {
+    thread_id: u64,
+    message_id: u64,
+    attachment_id: u64,
+    filename: &'a str,
+    content_type: Option<&'a str>,
+    size: u64,
+    /// ファイルの SHA-256（16進数）
+    sha256: String,
+    /// Discord の添付ファイルのURL（時間が経つと期限切れになる）
+    original_url: &'a str,
+    /// 保存した場所（ファイルのパスか `s3://` のURL）
+    location: String,
+    archived_at: String,
+}
+
+/// 転送したメッセージの添付ファイルを保存するアーカイブ
+///
+/// Discord の添付ファイルのURLは時間が経つと期限切れになるため、ファイル自体をローカルのディレクトリか S3 互換のストレージに保存します。
+/// 保存したファイルは、元のURL・保存した場所・SHA-256 と共に、アーカイブのディレクトリの `attachments.jsonl` に記録します。
+#[derive(Default)]
+pub struct AttachmentArchive {
+    store: Option<Box<dyn AttachmentStore>>,
+    /// 保存した添付ファイルの一覧の保存先
+    manifest_path: PathBuf,
+    /// 一覧への追記が混ざらないようにするためのロック
+    lock: Mutex<()>,
+}
+
+impl AttachmentArchive {
+    /// 保存先（ディレクトリのパスか `s3://バケット/プレフィックス`）から作成する
+    ///
+    /// 保存先を指定しない場合や、S3 の認証情報が無い場合は添付ファイルを保存しません。
+    pub fn from_location(location: Option<String>, archive_dir: PathBuf) -> Self {
+        let Some(location) = location else {
+            return Self::default();
+        };
+
+        let store: Box<dyn AttachmentStore> = if location.starts_with("s3://") {
+            match S3Store::from_env(&location) {
+                Ok(store) => Box::new(store),
+                Err(e) => {
+                    println!("警告: {}。添付ファイルは保存しません", e);
+                    return Self::default();
+                }
+            }
+        } else {
+            Box::new(LocalStore { dir: PathBuf::from(location) })
+        };
+
+        println!("転送したメッセージの添付ファイルを {} に保存します", store.describe());
+        Self {
+            store: Some(store),
+            manifest_path: archive_dir.join(MANIFEST_FILE_NAME),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// メッセージの添付ファイルを全て保存する（失敗しても転送には影響しないため、警告のみ出力する）
+    pub async fn store_message(&self, message: &Message) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        for attachment in &message.attachments {
+            if attachment.size > MAX_ARCHIVE_BYTES {
+                println!("添付ファイル {} はサイズが大きいため保存しません", attachment.filename);
+                continue;
+            }
+            if let Err(e) = self.store_attachment(&**store, message, attachment).await {
+                println!("警告: 添付ファイル {} を保存できませんでした: {}", attachment.filename, e);
+            }
+        }
+    }
+
+    /// 添付ファイルを1件ダウンロードして保存し、一覧に追記する
+    async fn store_attachment(
+        &self,
+        store: &dyn AttachmentStore,
+        message: &Message,
+        attachment: &Attachment,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let bytes = reqwest::Client::new()
+            .get(&attachment.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let key = format!("{}/{}/{}-{}", message.channel_id, message.id, attachment.id, safe_file_name(&attachment.filename));
+        let location = store.put(&key, &bytes, attachment.content_type.as_deref()).await?;
+
+        let entry = ManifestEntry {
+            thread_id: message.channel_id.get(),
+            message_id: message.id.get(),
+            attachment_id: attachment.id.get(),
+            filename: &attachment.filename,
+            content_type: attachment.content_type.as_deref(),
+            size: bytes.len() as u64,
+            sha256: hex(&Sha256::digest(&bytes)),
+            original_url: &attachment.url,
+            location,
+            archived_at: Utc::now().to_rfc3339(),
+        };
+        let line = serde_json::to_string(&entry)?;
+
+        let _guard = self.lock.lock().unwrap();
+        if let Some(dir) = self.manifest_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| format!("ディレクトリ {} を作成できませんでした: {}", dir.display(), e))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.manifest_path)
+            .map_err(|e| format!("添付ファイルの一覧 {} を開けませんでした: {}", self.manifest_path.display(), e))?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    }
+}
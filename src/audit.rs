@@ -0,0 +1,179 @@
+use twilight_http::Client as HttpClient;
+use twilight_model::channel::message::AllowedMentions;
+use twilight_model::id::{
+    marker::{ChannelMarker, UserMarker},
+    Id,
+};
+
+use crate::alert;
+
+/// 記録するコマンドの最大文字数（長いオプションで監査ログが読みにくくならないようにする）
+const MAX_COMMAND_CHARS: usize = 300;
+
+/// 記録するエラーの最大文字数
+const MAX_REASON_CHARS: usize = 500;
+
+/// コマンドの実行結果
+#[derive(Debug, Clone)]
+pub enum AuditOutcome {
+    /// 実行した
+    Succeeded,
+    /// 実行中にエラーが発生した
+    Failed(String),
+    /// 権限が無いため実行しなかった
+    Denied,
+}
+
+impl AuditOutcome {
+    /// 処理の結果から実行結果を作成する
+    pub fn from_result<T>(result: &Result<T, Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        match result {
+            Ok(_) => Self::Succeeded,
+            Err(e) => Self::Failed(alert::redact_webhook_tokens(&e.to_string()).chars().take(MAX_REASON_CHARS).collect()),
+        }
+    }
+
+    /// 表示・保存用の文字列
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Succeeded => "成功".to_string(),
+            Self::Failed(reason) => format!("エラー: {}", reason),
+            Self::Denied => "権限なし".to_string(),
+        }
+    }
+
+    /// 表示用の絵文字
+    fn emoji(&self) -> &'static str {
+        match self {
+            Self::Succeeded => "✅",
+            Self::Failed(_) => "⚠️",
+            Self::Denied => "🔒",
+        }
+    }
+}
+
+/// コマンドを実行した人・場所
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditActor {
+    /// Discord のユーザー（コマンド・スラッシュコマンド・ボタン）
+    User(Id<UserMarker>),
+    /// 管理画面（管理用の API）
+    Dashboard,
+}
+
+impl AuditActor {
+    /// 監査ログチャンネルに表示する名前
+    fn mention(self) -> String {
+        match self {
+            Self::User(user_id) => format!("<@{}>", user_id),
+            Self::Dashboard => "管理画面".to_string(),
+        }
+    }
+
+    /// データベースに保存するユーザーID（管理画面は 0）
+    pub fn user_id(self) -> u64 {
+        match self {
+            Self::User(user_id) => user_id.get(),
+            Self::Dashboard => 0,
+        }
+    }
+
+    /// データベースに保存する種類
+    pub fn kind(self) -> &'static str {
+        match self {
+            Self::User(_) => "user",
+            Self::Dashboard => "dashboard",
+        }
+    }
+}
+
+impl std::fmt::Display for AuditActor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::User(user_id) => write!(f, "ユーザー {}", user_id),
+            Self::Dashboard => write!(f, "管理画面"),
+        }
+    }
+}
+
+/// 管理用のコマンドの実行記録
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    /// 実行したユーザー（管理画面からの操作は管理画面）
+    pub actor: AuditActor,
+    /// 実行したチャンネル（スレッド）
+    pub channel_id: Id<ChannelMarker>,
+    /// 実行したコマンド（引数を含む）
+    pub command: String,
+    /// 実行結果
+    pub outcome: AuditOutcome,
+}
+
+impl AuditEntry {
+    /// 実行結果を指定して作成する（Webhook URL のトークンは伏せ、長いコマンドは切り詰める）
+    pub fn new(user_id: Id<UserMarker>, channel_id: Id<ChannelMarker>, command: &str, outcome: AuditOutcome) -> Self {
+        Self::with_actor(AuditActor::User(user_id), channel_id, command, outcome)
+    }
+
+    /// 管理画面からの操作の記録を作成する（`channel_id` は操作したマッピングのスレッド）
+    pub fn dashboard(channel_id: Id<ChannelMarker>, command: &str, outcome: AuditOutcome) -> Self {
+        Self::with_actor(AuditActor::Dashboard, channel_id, command, outcome)
+    }
+
+    fn with_actor(actor: AuditActor, channel_id: Id<ChannelMarker>, command: &str, outcome: AuditOutcome) -> Self {
+        let command = alert::redact_webhook_tokens(command);
+        let command = match command.char_indices().nth(MAX_COMMAND_CHARS) {
+            Some((index, _)) => format!("{}…", &command[..index]),
+            None => command.to_string(),
+        };
+        Self {
+            actor,
+            channel_id,
+            command,
+            outcome,
+        }
+    }
+}
+
+/// 管理用のコマンドの実行記録を投稿する監査ログチャンネル
+///
+/// 共有サーバーで、誰が過去メッセージの一括転送やマッピングの変更を行ったかを確認できるようにします。
+/// 記録はデータベースにも保存されます（`BotState::record_command` を参照）。
+#[derive(Default)]
+pub struct AuditLog {
+    /// 記録を投稿するチャンネル
+    channel_id: Option<Id<ChannelMarker>>,
+}
+
+impl AuditLog {
+    /// 投稿先のチャンネルを指定して作成する
+    pub fn new(channel_id: Option<Id<ChannelMarker>>) -> Self {
+        Self { channel_id }
+    }
+
+    /// 監査ログチャンネルに記録を投稿する（チャンネルを設定していない場合は何もしない）
+    pub async fn post(&self, http: &HttpClient, entry: &AuditEntry) {
+        let Some(channel_id) = self.channel_id else {
+            return;
+        };
+
+        let content = format!(
+            "{} {} が <#{}> で `{}` を実行しました（{}）",
+            entry.outcome.emoji(),
+            entry.actor.mention(),
+            entry.channel_id,
+            entry.command.replace('`', "'"),
+            entry.outcome.describe()
+        );
+        let post = async {
+            http.create_message(channel_id)
+                .content(&content)?
+                .allowed_mentions(Some(&AllowedMentions::default()))
+                .await?;
+            Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+        };
+        if let Err(e) = post.await {
+            println!("監査ログチャンネル {} への投稿に失敗しました: {}", channel_id, e);
+        }
+    }
+}
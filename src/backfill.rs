@@ -0,0 +1,329 @@
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+use twilight_http::Client as HttpClient;
+use twilight_model::channel::Message;
+use twilight_model::id::{
+    marker::{ChannelMarker, MessageMarker, UserMarker},
+    Id,
+};
+
+use crate::state::ThreadInfo;
+
+/// JSTとUTCの時差（秒）
+const JST_OFFSET_SECS: i64 = 9 * 3600;
+
+/// 進捗メッセージを編集する間隔（編集のしすぎでレート制限にかからないようにする）
+const PROGRESS_UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 進捗バーの長さ
+const PROGRESS_BAR_WIDTH: usize = 20;
+
+/// 確認ボタンの custom_id の接頭辞
+const CONFIRM_CUSTOM_ID_PREFIX: &str = "backfill:";
+
+/// 過去メッセージの転送で対象にする範囲
+///
+/// `!all 50`（最新の50件）、`!all since:2024-01-01`（JSTのその日以降）、`!all after:<メッセージID>`（そのメッセージより後）のように指定します。
+/// 複数指定した場合は全ての条件を満たすメッセージが対象になります。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BackfillRange {
+    /// 最新から数えて転送する件数
+    pub count: Option<usize>,
+    /// この日時（UNIX時間）以降のメッセージのみ転送する
+    pub since: Option<i64>,
+    /// このメッセージより後のメッセージのみ転送する
+    pub after: Option<Id<MessageMarker>>,
+}
+
+impl BackfillRange {
+    /// コマンドの引数から範囲を読み取る
+    pub fn parse(args: &[&str]) -> Result<Self, String> {
+        let mut range = Self::default();
+
+        for arg in args {
+            if let Some(date) = arg.strip_prefix("since:") {
+                let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .map_err(|_| format!("since: には日付を YYYY-MM-DD の形式で指定してください: {}", date))?;
+                let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc().timestamp();
+                range.since = Some(midnight - JST_OFFSET_SECS);
+            } else if let Some(message_id) = arg.strip_prefix("after:") {
+                let message_id = message_id
+                    .parse::<u64>()
+                    .ok()
+                    .and_then(Id::new_checked)
+                    .ok_or_else(|| format!("after: にはメッセージIDを指定してください: {}", message_id))?;
+                range.after = Some(message_id);
+            } else {
+                match arg.parse::<usize>() {
+                    Ok(count) if count > 0 => range.count = Some(count),
+                    _ => {
+                        return Err(format!(
+                            "引数が正しくありません: {}（件数、since:YYYY-MM-DD、after:メッセージID のいずれかを指定してください）",
+                            arg
+                        ))
+                    }
+                }
+            }
+        }
+
+        Ok(range)
+    }
+
+    /// 全ての履歴が対象かどうか
+    pub fn is_all(&self) -> bool {
+        self.count.is_none() && self.since.is_none() && self.after.is_none()
+    }
+
+    /// メッセージが日時・メッセージIDの条件を満たすかどうか（件数は含まない）
+    pub fn contains(&self, message: &Message) -> bool {
+        self.since.is_none_or(|since| message.timestamp.as_secs() >= since)
+            && self.after.is_none_or(|after| message.id > after)
+    }
+
+    /// 新しい順に取得しているメッセージ履歴で、これより古いメッセージを取得する必要があるかどうか
+    ///
+    /// `fetched` は取得済みで条件を満たすメッセージの件数、`oldest` は取得済みの中で最も古いメッセージです。
+    pub fn needs_older(&self, fetched: usize, oldest: &Message) -> bool {
+        self.count.is_none_or(|count| fetched < count) && self.contains(oldest)
+    }
+
+    /// 古い順のメッセージ履歴から、範囲内のメッセージだけを残す
+    pub fn apply(&self, messages: &mut Vec<Message>) {
+        messages.retain(|message| self.contains(message));
+        if let Some(count) = self.count {
+            let excess = messages.len().saturating_sub(count);
+            messages.drain(..excess);
+        }
+    }
+}
+
+/// 転送中の過去メッセージの転送の状態
+#[derive(Default)]
+struct RunningBackfill {
+    /// 転送中に届いたメッセージ
+    deferred: Vec<Message>,
+    /// `!cancel` で中止が要求されたかどうか
+    cancelled: bool,
+}
+
+/// 過去メッセージの転送中のスレッドを管理する
+///
+/// 転送中に届いた新しいメッセージは保留し、過去メッセージの転送が終わってから順に転送することで、
+/// 転送先でスレッドと同じ順番（古い順）に並ぶようにします。
+#[derive(Default)]
+pub struct BackfillTracker {
+    /// 転送中のスレッドID -> 転送の状態
+    running: Mutex<HashMap<Id<ChannelMarker>, RunningBackfill>>,
+}
+
+impl BackfillTracker {
+    /// 空の状態を作成する
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// スレッドの過去メッセージを転送中かどうか
+    pub async fn is_running(&self, thread_id: Id<ChannelMarker>) -> bool {
+        self.running.lock().await.contains_key(&thread_id)
+    }
+
+    /// 過去メッセージの転送を開始する（既に転送中の場合は false）
+    pub async fn begin(&self, thread_id: Id<ChannelMarker>) -> bool {
+        let mut running = self.running.lock().await;
+        if running.contains_key(&thread_id) {
+            return false;
+        }
+        running.insert(thread_id, RunningBackfill::default());
+        true
+    }
+
+    /// 転送の中止を要求する（転送中でない場合は false）
+    ///
+    /// 転送中のメッセージの送信が終わった時点で中止されます。
+    pub async fn cancel(&self, thread_id: Id<ChannelMarker>) -> bool {
+        match self.running.lock().await.get_mut(&thread_id) {
+            Some(backfill) => {
+                backfill.cancelled = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 転送の中止が要求されたかどうか
+    pub async fn is_cancelled(&self, thread_id: Id<ChannelMarker>) -> bool {
+        self.running
+            .lock()
+            .await
+            .get(&thread_id)
+            .is_some_and(|backfill| backfill.cancelled)
+    }
+
+    /// 転送中のスレッドのメッセージであれば保留する（保留した場合は true）
+    pub async fn defer(&self, message: &Message) -> bool {
+        match self.running.lock().await.get_mut(&message.channel_id) {
+            Some(backfill) => {
+                backfill.deferred.push(message.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 保留中のメッセージを取り出す
+    ///
+    /// 保留中のメッセージが無ければ転送を終了し、以降のメッセージは保留されなくなります。
+    /// 取り出したメッセージを転送している間に届いたメッセージは再び保留されるため、空になるまで繰り返し呼び出してください。
+    pub async fn take_pending_or_finish(&self, thread_id: Id<ChannelMarker>) -> Vec<Message> {
+        let mut running = self.running.lock().await;
+        let messages = running
+            .get_mut(&thread_id)
+            .map(|backfill| std::mem::take(&mut backfill.deferred))
+            .unwrap_or_default();
+        if messages.is_empty() {
+            running.remove(&thread_id);
+        }
+        messages
+    }
+}
+
+/// 確認ボタンが押されるのを待っている過去メッセージの転送
+pub struct PendingBackfill {
+    /// 転送するスレッドID
+    pub thread_id: Id<ChannelMarker>,
+    /// コマンドを実行した時点のスレッド情報
+    pub thread_info: ThreadInfo,
+    /// 転送する範囲
+    pub range: BackfillRange,
+    /// コマンドを実行したユーザー（このユーザーだけがボタンを操作できる）
+    pub requester: Id<UserMarker>,
+    /// 実行したコマンド（実行記録に使用）
+    pub command: String,
+}
+
+/// 件数の多い過去メッセージの転送で、確認ボタンが押されるのを待っている転送を管理する
+#[derive(Default)]
+pub struct BackfillConfirmations {
+    /// 確認メッセージのID -> 確認待ちの転送
+    pending: Mutex<HashMap<Id<MessageMarker>, PendingBackfill>>,
+}
+
+impl BackfillConfirmations {
+    /// 空の状態を作成する
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 確認待ちの転送を登録する
+    pub async fn insert(&self, confirmation_id: Id<MessageMarker>, pending: PendingBackfill) {
+        self.pending.lock().await.insert(confirmation_id, pending);
+    }
+
+    /// 確認待ちの転送を実行できるユーザー
+    pub async fn requester(&self, confirmation_id: Id<MessageMarker>) -> Option<Id<UserMarker>> {
+        self.pending
+            .lock()
+            .await
+            .get(&confirmation_id)
+            .map(|pending| pending.requester)
+    }
+
+    /// 確認待ちの転送を取り出す（確認・中止・期限切れのいずれかで一度だけ取り出せる）
+    pub async fn take(&self, confirmation_id: Id<MessageMarker>) -> Option<PendingBackfill> {
+        self.pending.lock().await.remove(&confirmation_id)
+    }
+}
+
+/// 確認ボタンの custom_id を作成する（例: `backfill:confirm`）
+pub fn confirm_custom_id(confirm: bool) -> String {
+    let action = if confirm { "confirm" } else { "cancel" };
+    format!("{}{}", CONFIRM_CUSTOM_ID_PREFIX, action)
+}
+
+/// 確認ボタンの custom_id を読み取る（確認なら true、中止なら false、確認ボタンでなければ None）
+pub fn parse_confirm_custom_id(custom_id: &str) -> Option<bool> {
+    match custom_id.strip_prefix(CONFIRM_CUSTOM_ID_PREFIX)? {
+        "confirm" => Some(true),
+        "cancel" => Some(false),
+        _ => None,
+    }
+}
+
+/// 進捗バーを作成する（例: `▰▰▰▰▱▱▱▱ 34/120`）
+fn progress_bar(done: usize, total: usize) -> String {
+    let filled = (PROGRESS_BAR_WIDTH * done)
+        .checked_div(total)
+        .unwrap_or(PROGRESS_BAR_WIDTH)
+        .min(PROGRESS_BAR_WIDTH);
+    format!(
+        "`{}{}` {}/{}",
+        "▰".repeat(filled),
+        "▱".repeat(PROGRESS_BAR_WIDTH - filled),
+        done,
+        total
+    )
+}
+
+/// 過去メッセージの転送の進捗を表示するメッセージ
+///
+/// 開始・件数・完了を別々に投稿する代わりに、1件のメッセージを編集して進捗を表示します。
+pub struct ProgressMessage {
+    channel_id: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+    /// 見出し（「🚀 12件のメッセージを転送しています」など）
+    title: String,
+    total: usize,
+    last_update: Instant,
+}
+
+impl ProgressMessage {
+    /// 進捗メッセージを投稿する
+    pub async fn send(
+        http: &HttpClient,
+        channel_id: Id<ChannelMarker>,
+        content: &str,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let message = http.create_message(channel_id).content(content)?.await?.model().await?;
+        Ok(Self {
+            channel_id,
+            message_id: message.id,
+            title: content.to_string(),
+            total: 0,
+            last_update: Instant::now(),
+        })
+    }
+
+    /// 転送する件数が決まったら、見出しと進捗バーを表示する
+    pub async fn start(&mut self, http: &HttpClient, title: String, total: usize) {
+        self.title = title;
+        self.total = total;
+        self.edit(http, &format!("{}\n{}", self.title, progress_bar(0, total))).await;
+    }
+
+    /// 処理した件数を反映する（前回の編集から一定時間経っていない場合は何もしない）
+    pub async fn report(&mut self, http: &HttpClient, done: usize) {
+        if self.last_update.elapsed() < PROGRESS_UPDATE_INTERVAL {
+            return;
+        }
+        self.edit(http, &format!("{}\n{}", self.title, progress_bar(done, self.total))).await;
+    }
+
+    /// 最終的な結果を表示する
+    pub async fn finish(&mut self, http: &HttpClient, content: &str) {
+        self.edit(http, content).await;
+    }
+
+    /// メッセージを編集する（失敗しても転送は続ける）
+    async fn edit(&mut self, http: &HttpClient, content: &str) {
+        self.last_update = Instant::now();
+        let result = match http.update_message(self.channel_id, self.message_id).content(Some(content)) {
+            Ok(request) => request.await.map(|_| ()).map_err(Into::into),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error + Send + Sync>),
+        };
+        if let Err(e) = result {
+            println!("警告: 進捗メッセージ {} を更新できませんでした: {}", self.message_id, e);
+        }
+    }
+}
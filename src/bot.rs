@@ -0,0 +1,392 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::time::Duration;
+use futures_util::StreamExt;
+use twilight_gateway::stream::{self, ShardEventStream, ShardMessageStream};
+use twilight_gateway::error::ReceiveMessageErrorType;
+use twilight_gateway::{CloseFrame, Config, Event, Intents, Message, Shard};
+use twilight_http::Client as HttpClient;
+
+use crate::attachments::AttachmentArchive;
+use crate::backfill::BackfillRange;
+use crate::config::ConfigFile;
+use crate::dispatch::{EventDispatcher, EventHandler};
+use crate::heartbeat::{HeartbeatSettings, ReconnectMonitor};
+use crate::github::GitHubIssues;
+use crate::history::MessageHistory;
+use crate::forwarding::{clear_webhook_name, fetch_all_messages_and_transfer};
+use crate::pool::EventPool;
+use crate::publish::EventPublisher;
+use crate::reporting::ReportingGuard;
+use crate::state::BotState;
+#[cfg(feature = "email")]
+use crate::email;
+use crate::{alert, archive, audit, config, digest, health, heartbeat, links, permissions, reload, reporting, source, storage, watch};
+
+/// ボットが受け取るイベントの種類
+///
+/// GUILDS はスレッドの作成・更新イベント（親チャンネルの把握）に必要
+/// GUILD_MESSAGE_REACTIONS はリアクションの集計を転送先に反映するために必要
+const INTENTS: Intents = Intents::GUILDS
+    .union(Intents::GUILD_MESSAGES)
+    .union(Intents::GUILD_MESSAGE_REACTIONS)
+    .union(Intents::MESSAGE_CONTENT);
+
+/// 終了時に、転送待ちのメッセージの転送が終わるのを待つ最大の時間
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// ゲートウェイとの接続を閉じる時に、Discordからの応答を待つ最大の時間
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// SIGINT（Ctrl+C）または SIGTERM を受信するまで待つ
+async fn shutdown_signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            eprintln!("SIGINTハンドラの登録に失敗しました: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                eprintln!("SIGTERMハンドラの登録に失敗しました: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => println!("SIGINTを受信しました"),
+        _ = terminate => println!("SIGTERMを受信しました"),
+    }
+}
+
+/// スレッドのメッセージをチャンネルに転送するボット
+///
+/// 他のプログラムに組み込む場合は、[`Thread2ChannelBot::builder`] で作成して [`Thread2ChannelBot::run`] で起動します。
+///
+/// ```no_run
+/// # async fn example() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+/// let config = thread2channel::config::load_config_file(&thread2channel::config::config_path())?;
+/// let bot = thread2channel::Thread2ChannelBot::builder().config(config).build().await?;
+/// bot.run().await
+/// # }
+/// ```
+pub struct Thread2ChannelBot {
+    /// ゲートウェイの接続（シャードごと）
+    shards: Vec<Shard>,
+    /// 共有状態
+    state: Arc<BotState>,
+    /// イベントの振り分け
+    dispatcher: EventDispatcher,
+    /// 並行して処理するイベントの数
+    event_concurrency: usize,
+    /// 死活監視用のHTTPサーバーの待ち受けアドレス（指定しない場合は起動しない）
+    health_addr: Option<SocketAddr>,
+    /// 外部からの投稿の認証に使うトークン（HTTPサーバーを起動している場合のみ使用）
+    inbound_token: Option<String>,
+    /// 管理画面と管理用の API の認証に使うトークン（HTTPサーバーを起動している場合のみ使用）
+    dashboard_token: Option<String>,
+    /// 運用チャンネルへの稼働状況の投稿と再接続の通知（運用チャンネルを指定しない場合は None）
+    heartbeat: Option<HeartbeatSettings>,
+    /// 転送したメッセージのまとめのメール送信（SMTP サーバーを指定しない場合は None）
+    #[cfg(feature = "email")]
+    email: Option<email::EmailDigests>,
+    /// Sentry へのエラー報告（ボットの終了時に、送信していない報告を送信する）
+    _reporting: ReportingGuard,
+}
+
+/// [`Thread2ChannelBot`] の設定
+#[derive(Default)]
+pub struct Thread2ChannelBotBuilder {
+    /// 設定ファイルの内容（無い場合は環境変数のみを使用）
+    config: Option<ConfigFile>,
+    /// Discord Bot Token（指定しない場合は設定ファイル・環境変数から取得）
+    token: Option<String>,
+    /// 標準の処理に加えて登録するイベントの処理
+    handlers: Vec<Box<dyn EventHandler>>,
+}
+
+impl Thread2ChannelBotBuilder {
+    /// 設定ファイルの内容を指定する
+    pub fn config(mut self, config: Option<ConfigFile>) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Discord Bot Token を指定する（設定ファイル・環境変数より優先）
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// 標準の処理に加えてイベントの処理を登録する（標準の処理の後に呼ばれる）
+    pub fn event_handler(mut self, handler: Box<dyn EventHandler>) -> Self {
+        self.handlers.push(handler);
+        self
+    }
+
+    /// マッピングとデータベースを読み込んでボットを作成する（まだ接続はしない）
+    pub async fn build(self) -> Result<Thread2ChannelBot, Box<dyn std::error::Error + Send + Sync>> {
+        let config = self.config;
+
+        // 起動中のエラーも報告できるよう、最初に Sentry の設定を行う
+        let reporting = reporting::init(config::sentry_dsn(config.as_ref()));
+        #[cfg(not(feature = "email"))]
+        if config::email_from(config.as_ref()).is_some() {
+            println!("警告: まとめのメールの送信元が指定されていますが、email 機能を有効にしてビルドされていないため送信しません");
+        }
+
+        // BOTトークンを設定ファイルまたは環境変数から取得
+        let token = match self.token {
+            Some(token) => token,
+            None => config::get_discord_token(config.as_ref())?,
+        };
+
+        // HTTPクライアントを作成
+        let http = Arc::new(HttpClient::new(token.clone()));
+
+        // シャードを作成（数を指定しない場合はDiscordの推奨する数）
+        let gateway_config = Config::new(token, INTENTS);
+        let shards: Vec<Shard> = match config::shard_count(config.as_ref()) {
+            Some(total) => stream::create_range(.., total, gateway_config, |_, builder| builder.build()).collect(),
+            None => stream::create_recommended(&http, gateway_config, |_, builder| builder.build())
+                .await?
+                .collect(),
+        };
+        println!("{} 個のシャードで接続します", shards.len());
+
+        // .envファイルと設定ファイルからスレッドマッピングを読み込む
+        let initial_mappings = config::parse_thread_mappings(config.as_ref())?;
+        let parent_mappings = config::parse_parent_mappings(config.as_ref())?;
+        let name_mappings = config::parse_name_mappings(config.as_ref())?;
+
+        // 各ウェブフックの名前を空に設定
+        for thread_info in initial_mappings.values() {
+            if let Some(webhook_url) = &thread_info.webhook_url {
+                println!("設定から読み込んだWebhookの名前をクリアします");
+                if let Err(e) = clear_webhook_name(webhook_url).await {
+                    println!("設定のWebhook名クリア中にエラー: {}", e);
+                }
+            }
+        }
+
+        // 実行時に追加したマッピングを保存するデータベースを開く
+        let database_path = config::database_path(config.as_ref());
+        let store = storage::MappingStore::open(&database_path)?;
+
+        // 転送したメッセージの対応を記録するストア（設定で無効にした場合はメモリ上のみ）
+        let message_links = if config::persist_message_links(config.as_ref()) {
+            links::MessageLinkStore::open(&database_path)?
+        } else {
+            println!("転送記録はメモリ上にのみ保持します（再起動すると失われます）");
+            links::MessageLinkStore::in_memory()
+        };
+
+        // スレッド情報を保持する共有状態を作成（データベースの内容も読み込む）
+        let archive = archive::MessageArchive::new(config::archive_dir(config.as_ref()));
+        let debug_watch = watch::DebugWatch::from_config(config.as_ref());
+        let history = MessageHistory::open(config::message_history(config.as_ref()), &config::history_database_url(config.as_ref())).await;
+        let events = EventPublisher::connect(config::event_publisher(config.as_ref()), config::event_subject(config.as_ref())).await;
+        let state = Arc::new(
+            BotState::new(Arc::clone(&http), store, message_links, archive, initial_mappings, parent_mappings, debug_watch)?
+                .with_command_prefixes(config::command_prefixes(config.as_ref()))
+                .with_command_prefix(config::command_prefix(config.as_ref()), config::guild_command_prefixes(config.as_ref()))
+                .with_name_mappings(name_mappings)
+                .with_auto_backfill(config::auto_backfill(config.as_ref()))
+                .with_attachment_archive(AttachmentArchive::from_location(
+                    config::attachment_archive(config.as_ref()),
+                    config::archive_dir(config.as_ref()),
+                ))
+                .with_history(history)
+                .with_event_publisher(events)
+                .with_github_issues(GitHubIssues::open(&database_path)?)
+                .with_permissions(permissions::CommandPermissions::from_config(config.as_ref()))
+                .with_alerts(alert::AdminAlerts::new(config::admin_channel_id(config.as_ref())))
+                .with_audit_log(audit::AuditLog::new(config::audit_channel_id(config.as_ref()))),
+        );
+
+        let mut dispatcher = EventDispatcher::with_default_handlers();
+        for handler in self.handlers {
+            dispatcher.register(handler);
+        }
+
+        Ok(Thread2ChannelBot {
+            shards,
+            state,
+            dispatcher,
+            event_concurrency: config::event_concurrency(config.as_ref()),
+            health_addr: config::health_addr(config.as_ref()),
+            inbound_token: config::inbound_token(config.as_ref()),
+            dashboard_token: config::dashboard_token(config.as_ref()),
+            heartbeat: HeartbeatSettings::from_config(config.as_ref()),
+            #[cfg(feature = "email")]
+            email: email::EmailDigests::from_config(config.as_ref(), &database_path)?,
+            _reporting: reporting,
+        })
+    }
+}
+
+impl Thread2ChannelBot {
+    /// ボットの設定を開始する
+    pub fn builder() -> Thread2ChannelBotBuilder {
+        Thread2ChannelBotBuilder::default()
+    }
+
+    /// 共有状態（マッピングの追加・削除などに使用）
+    pub fn state(&self) -> Arc<BotState> {
+        Arc::clone(&self.state)
+    }
+
+    /// ゲートウェイに接続してイベントの処理を開始する（SIGINT・SIGTERM を受信すると終了する）
+    pub async fn run(mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let state = Arc::clone(&self.state);
+
+        // SIGHUPまたは設定ファイルの変更でマッピングを再読み込みする
+        reload::spawn_config_reloader(Arc::clone(&state));
+        // digest_minutes を過ぎたまとめ投稿を送信する
+        digest::spawn_digest_flusher(Arc::clone(&state));
+        // /healthz・/readyz で接続状態を返す
+        state.stats.set_shard_total(self.shards.len());
+        if let Some(addr) = self.health_addr {
+            health::spawn_health_server(Arc::clone(&state), addr, self.inbound_token.take(), self.dashboard_token.take());
+        }
+        // 運用チャンネルに稼働状況を定期的に投稿し、再接続が続いた場合は通知する
+        if let Some(settings) = &self.heartbeat {
+            heartbeat::spawn_heartbeat(Arc::clone(&state), settings);
+        }
+        let mut reconnects = self.heartbeat.clone().map(ReconnectMonitor::new);
+        // マッピングの email_schedule に従って、転送したメッセージのまとめをメールで送信する
+        #[cfg(feature = "email")]
+        if let Some(digests) = self.email.take() {
+            email::spawn_email_digests(Arc::clone(&state), digests);
+        }
+
+        println!("Botを起動しました！");
+        println!("Webhook機能を使用して送信者のアバターと名前を複製します");
+        println!(".envファイルと設定ファイルから設定を読み込みました");
+        println!("コマンドでの設定も引き続き利用可能です");
+
+        // マッピングが設定されたスレッドに参加し、メッセージを確実に受信できるようにする
+        source::spawn_prepare_mapped_sources(&state);
+
+        // 前回の終了時に転送できなかったメッセージを、新しいメッセージより先に転送する
+        state.queues.restore(&state).await;
+
+        // 全メッセージ転送フラグが設定されているマッピングの過去メッセージを転送する
+        // 転送済みのメッセージはスキップされるため、再起動のたびに重複して転送されることはない
+        if state.auto_backfill {
+            let state = Arc::clone(&state);
+            tokio::spawn(async move {
+                let mappings: Vec<_> = state
+                    .thread_mappings
+                    .load()
+                    .iter()
+                    .filter(|(_, info)| info.transfer_all_messages && info.active)
+                    .map(|(thread_id, info)| (*thread_id, info.clone()))
+                    .collect();
+
+                for (thread_id, info) in mappings {
+                    match fetch_all_messages_and_transfer(&state, thread_id, &info, &BackfillRange::default()).await {
+                        Ok(_) => println!("スレッド {} の全メッセージ転送が完了しました", thread_id),
+                        Err(e) => {
+                            eprintln!("スレッド {} の全メッセージ転送中にエラーが発生しました: {}", thread_id, e);
+                            state.alerts.mapping_failure(&state.http, &format!("<#{}> の過去メッセージの自動転送", thread_id), thread_id, &info, &*e).await;
+                        }
+                    }
+                }
+            });
+        } else {
+            println!("過去メッセージの自動転送は無効です。`all` を指定したマッピングは `!start` で転送してください");
+        }
+
+        // イベントループ（終了のシグナルを受信するまで続ける）
+        // 全てのシャードのイベントを受信した順に処理タスクに渡す（同じチャンネルのイベントは同じタスクで順番に処理する）
+        let pool = EventPool::spawn(std::mem::take(&mut self.dispatcher), Arc::clone(&state), self.event_concurrency);
+        let shutdown = shutdown_signal();
+        tokio::pin!(shutdown);
+        let mut events = ShardEventStream::new(self.shards.iter_mut());
+        loop {
+            let (shard, event) = tokio::select! {
+                _ = &mut shutdown => break,
+                next = events.next() => match next {
+                    Some(next) => next,
+                    None => break,
+                },
+            };
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    eprintln!("Error receiving event (shard {}): {:?}", shard.id(), e);
+                    if let (Some(reconnects), ReceiveMessageErrorType::Reconnect) = (&mut reconnects, e.kind()) {
+                        reconnects.record(&state, shard.id().number());
+                    }
+                    continue;
+                }
+            };
+            // Discordが接続を閉じた場合、シャードは自動で再接続する
+            if let (Some(reconnects), Event::GatewayClose(_)) = (&mut reconnects, &event) {
+                reconnects.record(&state, shard.id().number());
+            }
+
+            state.stats.set_latency(shard.latency().average());
+            state.stats.record_gateway_event(shard.id().number() as usize, shard.status().is_identified());
+            drop(shard);
+
+            // 受信したイベントを処理（処理が追いつかない場合は、空くまで次のイベントの受信を待つ）
+            pool.submit(event).await;
+        }
+        drop(events);
+
+        if !pool.close(SHUTDOWN_TIMEOUT).await {
+            println!("処理中のイベントが終わらないまま終了します");
+        }
+
+        self.shutdown().await;
+        Ok(())
+    }
+
+    /// 新しいイベントの受信をやめ、転送中のメッセージを送り終えてからゲートウェイとの接続を閉じる
+    ///
+    /// 時間内に転送できなかったメッセージはデータベースに残り、次回の起動時に転送されます。
+    /// 過去メッセージの転送は進捗を記録しているため、次回の `!start` で続きから転送されます。
+    async fn shutdown(mut self) {
+        println!("終了します。転送待ちのメッセージを送信しています…");
+        if !self.state.queues.wait_until_empty(SHUTDOWN_TIMEOUT).await {
+            println!("転送待ちのメッセージが残っています。次回の起動時に転送します");
+        }
+        digest::flush_all(&self.state).await;
+
+        for shard in &mut self.shards {
+            if let Err(e) = shard.close(CloseFrame::NORMAL).await {
+                eprintln!("シャード {} の接続を閉じられませんでした: {}", shard.id(), e);
+            }
+        }
+        // Discordが接続を閉じたことを確認する（応答の無いシャードは待たない）
+        let closed = tokio::time::timeout(CLOSE_TIMEOUT, async {
+            let mut remaining = self.shards.len();
+            let mut messages = ShardMessageStream::new(self.shards.iter_mut());
+            while remaining > 0 {
+                match messages.next().await {
+                    Some((_, Ok(Message::Close(_)) | Err(_))) => remaining -= 1,
+                    Some((_, Ok(Message::Text(_)))) => {}
+                    None => break,
+                }
+            }
+        })
+        .await;
+        if closed.is_err() {
+            println!("ゲートウェイからの応答が無いまま終了します");
+        }
+        println!("Botを終了しました");
+    }
+}
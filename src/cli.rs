@@ -0,0 +1,239 @@
+use clap::{Parser, Subcommand};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use twilight_http::Client as HttpClient;
+use twilight_model::id::{
+    marker::{ChannelMarker, GuildMarker},
+    Id,
+};
+
+use crate::config::{self, ConfigFile};
+use crate::history::{HistoryEntry, MessageHistory};
+use crate::permissions;
+use crate::site::{self, SiteThread};
+use crate::state::ThreadInfo;
+use crate::storage::MappingStore;
+
+/// Discordのスレッドのメッセージを指定したチャンネルにコピーするボット
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// 実行するサブコマンド（省略した場合は run）
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// サブコマンド一覧
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// ボットを起動する
+    Run,
+    /// 設定とチャンネルへのアクセス権限を検証する
+    Validate,
+    /// 解決済みのマッピング一覧を表示する
+    ListMappings,
+    /// メッセージの履歴を静的なHTMLのサイトとして書き出す
+    ExportSite {
+        /// 書き出し先のディレクトリ
+        #[arg(long, default_value = "site")]
+        output: PathBuf,
+        /// 1ページに表示するメッセージの数
+        #[arg(long, default_value_t = 100)]
+        per_page: usize,
+        /// 書き出すスレッドのID（複数指定可、省略した場合は履歴のある全てのスレッド）
+        #[arg(long = "thread")]
+        threads: Vec<u64>,
+        /// サイトのタイトル
+        #[arg(long, default_value = "Thread2Channel アーカイブ")]
+        title: String,
+    },
+}
+
+/// 解決済みマッピング（スレッドID、スレッド情報、読み込み元）
+type ResolvedMapping = (Id<ChannelMarker>, ThreadInfo, &'static str);
+
+/// 設定とデータベースからマッピングを解決する（BotState::new と同じ優先順位）
+fn resolve_mappings(
+    config: Option<&ConfigFile>,
+) -> Result<Vec<ResolvedMapping>, Box<dyn std::error::Error + Send + Sync>> {
+    let configured = config::parse_thread_mappings(config)?;
+    let store = MappingStore::open(&config::database_path(config))?;
+    let stored = store.load_all()?;
+    let removed = store.load_removed()?;
+
+    let mut resolved: HashMap<_, _> = configured
+        .into_iter()
+        .filter(|(thread_id, _)| !removed.contains(thread_id))
+        .map(|(thread_id, info)| (thread_id, (info, "設定")))
+        .collect();
+    resolved.extend(stored.into_iter().map(|(thread_id, info)| (thread_id, (info, "データベース"))));
+
+    let mut resolved: Vec<_> = resolved
+        .into_iter()
+        .map(|(thread_id, (info, source))| (thread_id, info, source))
+        .collect();
+    resolved.sort_by_key(|(thread_id, _, _)| *thread_id);
+    Ok(resolved)
+}
+
+/// list-mappings サブコマンド: 解決済みのマッピングを表示する
+pub fn list_mappings(config: Option<&ConfigFile>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let resolved = resolve_mappings(config)?;
+
+    println!();
+    for (thread_id, info, source) in &resolved {
+        println!("スレッド {} -> チャンネル {} (Webhook: {}, 全メッセージ転送: {}, 読み込み元: {})",
+            thread_id,
+            info.target_channel_id,
+            info.webhook_url.is_some(),
+            info.transfer_all_messages,
+            source
+        );
+    }
+    println!("{} 個のマッピング", resolved.len());
+
+    let parent_mappings = config::parse_parent_mappings(config)?;
+    let mut parent_mappings: Vec<_> = parent_mappings.into_iter().collect();
+    parent_mappings.sort_by_key(|(parent_id, _)| *parent_id);
+    for (parent_id, info) in &parent_mappings {
+        println!("親チャンネル {} 配下の全スレッド -> チャンネル {} (Webhook: {}, 全メッセージ転送: {})",
+            parent_id,
+            info.target_channel_id,
+            info.webhook_url.is_some(),
+            info.transfer_all_messages
+        );
+    }
+    if !parent_mappings.is_empty() {
+        println!("{} 個の親チャンネルマッピング", parent_mappings.len());
+    }
+
+    let name_mappings = config::parse_name_mappings(config)?;
+    for mapping in &name_mappings {
+        println!("名前が /{}/ に一致するスレッド -> チャンネル {} (Webhook: {}, 全メッセージ転送: {})",
+            mapping.pattern.as_str(),
+            mapping.info.target_channel_id,
+            mapping.info.webhook_url.is_some(),
+            mapping.info.transfer_all_messages
+        );
+    }
+    if !name_mappings.is_empty() {
+        println!("{} 個のスレッド名のマッピング", name_mappings.len());
+    }
+    Ok(())
+}
+
+/// チャンネルにボットがアクセスできるか確認する（アクセスできる場合はチャンネルのサーバーを返す）
+async fn check_channel_access(http: &HttpClient, channel_id: Id<ChannelMarker>) -> Result<Option<Id<GuildMarker>>, String> {
+    let response = http.channel(channel_id).await.map_err(|e| permissions::describe_access_error(&e))?;
+    let channel = response.model().await.map_err(|e| e.to_string())?;
+    Ok(channel.guild_id)
+}
+
+/// validate サブコマンド: 設定とチャンネルへのアクセス権限を検証する
+///
+/// 問題が1つでも見つかった場合はエラーを返します。
+pub async fn validate(config: Option<&ConfigFile>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let token = config::get_discord_token(config)?;
+    let resolved = resolve_mappings(config)?;
+    let parent_mappings = config::parse_parent_mappings(config)?;
+    let http = HttpClient::new(token);
+
+    // 検証するチャンネルの一覧（種類, コピー元ID, 転送先）
+    let targets = resolved
+        .iter()
+        .map(|(thread_id, info, _)| ("スレッド", *thread_id, info))
+        .chain(parent_mappings.iter().map(|(parent_id, info)| ("親チャンネル", *parent_id, info)));
+
+    let mut problems = 0;
+    for (source_label, source_id, info) in targets {
+        let channels = [(source_label, source_id), ("転送先チャンネル", info.target_channel_id)]
+            .into_iter()
+            .chain(info.routes.iter().map(|route| ("振り分け先チャンネル", route.channel_id)));
+        let mut source_guild_id = None;
+        for (label, channel_id) in channels {
+            match check_channel_access(&http, channel_id).await {
+                Ok(guild_id) if channel_id == source_id => {
+                    source_guild_id = guild_id;
+                    println!("✅ {} {} にアクセスできます", label, channel_id);
+                }
+                // 転送元と別のサーバーのチャンネルへの転送は、ボットが両方のサーバーに参加していれば動作する
+                Ok(Some(guild_id)) if source_guild_id.is_some_and(|source_guild_id| source_guild_id != guild_id) => {
+                    println!("✅ {} {} にアクセスできます（別のサーバー {}）", label, channel_id, guild_id);
+                }
+                Ok(_) => println!("✅ {} {} にアクセスできます", label, channel_id),
+                Err(e) => {
+                    println!("❌ {} {} にアクセスできません: {}", label, channel_id, e);
+                    problems += 1;
+                }
+            }
+        }
+    }
+
+    if problems > 0 {
+        return Err(format!("{} 件の問題が見つかりました", problems).into());
+    }
+
+    println!("✅ {} 個のマッピングを検証しました。問題はありません", resolved.len() + parent_mappings.len());
+    Ok(())
+}
+
+/// export-site サブコマンド: メッセージの履歴を静的なHTMLのサイトとして書き出す
+///
+/// スレッドの表示名にはマッピングのラベルを使い、無い場合は Discord のトークンがあればスレッド名を取得します。
+/// 本文にはマッピングの変換（`transform`）を適用します。
+pub async fn export_site(
+    config: Option<&ConfigFile>,
+    output: &Path,
+    per_page: usize,
+    threads: &[u64],
+    title: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let store = MessageHistory::open_store(&config::history_database_url(config)).await?;
+    let thread_ids = if threads.is_empty() { store.thread_ids().await? } else { threads.to_vec() };
+    if thread_ids.is_empty() {
+        return Err("書き出すメッセージがありません（bot.message_history で履歴を保存してください）".into());
+    }
+
+    let mappings: HashMap<u64, ThreadInfo> = resolve_mappings(config)?
+        .into_iter()
+        .map(|(thread_id, info, _)| (thread_id.get(), info))
+        .collect();
+    let parent_mappings = config::parse_parent_mappings(config)?;
+    let http = config::get_discord_token(config).ok().map(HttpClient::new);
+
+    let mut site_threads = Vec::new();
+    for thread_id in thread_ids {
+        let mut entries = store.thread_messages(thread_id).await?;
+        if entries.is_empty() {
+            println!("スレッド {} の履歴がないためスキップします", thread_id);
+            continue;
+        }
+        // 個別のマッピングが無いスレッドは、Discord のトークンがあれば親チャンネルのマッピングを探す
+        let channel = match (mappings.get(&thread_id), &http, Id::<ChannelMarker>::new_checked(thread_id)) {
+            (Some(info), _, _) if info.label.is_some() => None,
+            (_, Some(http), Some(channel_id)) => match http.channel(channel_id).await {
+                Ok(response) => response.model().await.ok(),
+                Err(_) => None,
+            },
+            _ => None,
+        };
+        let info = mappings.get(&thread_id).or_else(|| {
+            let parent_id = channel.as_ref()?.parent_id?;
+            parent_mappings.get(&parent_id)
+        });
+        // 履歴は変換前の本文のため、転送時と同じ変換を適用して伏せ字にした内容を書き出さないようにする
+        if let Some(info) = info {
+            HistoryEntry::apply_transforms(&mut entries, &info.transforms).await;
+        }
+        let title = info
+            .and_then(|info| info.label.clone())
+            .or_else(|| channel.and_then(|channel| channel.name))
+            .unwrap_or_else(|| format!("スレッド {}", thread_id));
+        println!("スレッド {}（{}）: {}件のメッセージ", thread_id, title, entries.len());
+        site_threads.push(SiteThread { thread_id, title, entries });
+    }
+
+    let pages = site::write_site(output, title, &site_threads, per_page)?;
+    println!("✅ {} 個のスレッド（{} ページ）を {} に書き出しました", site_threads.len(), pages, output.display());
+    Ok(())
+}
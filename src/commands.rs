@@ -0,0 +1,680 @@
+use std::sync::Arc;
+
+use twilight_model::application::interaction::Interaction;
+use twilight_model::channel::message::component::{ActionRow, Button, ButtonStyle, Component};
+use twilight_model::channel::message::{AllowedMentions, MessageFlags};
+use twilight_model::gateway::payload::incoming::MessageCreate;
+use twilight_model::guild::Permissions;
+use twilight_model::http::interaction::{InteractionResponse, InteractionResponseData, InteractionResponseType};
+use twilight_model::id::Id;
+
+use crate::state::{BotState, MessageFormat, ThreadInfo};
+use crate::backfill::{BackfillRange, PendingBackfill};
+use crate::audit::{AuditEntry, AuditOutcome};
+use crate::transcript::TranscriptFormat;
+use crate::{backfill, config, help, permissions, source, stats};
+use crate::forwarding::{
+    clear_webhook_name, export_transcript, fetch_all_messages_and_transfer, fetch_message_history, spawn_backfill,
+    transfer_all_threads,
+};
+
+/// !thread2channelコマンドを処理します
+pub async fn handle_thread2channel_command(
+    message: Box<MessageCreate>,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let content = &message.content;
+    let parts: Vec<&str> = content.split_whitespace().collect();
+
+    if parts.len() < 2 {
+        // コマンドの使用方法を表示
+        state
+            .api
+            .create_message(message.channel_id, "使用法: !thread2channel <target_channel_id> [all] [format=plain|embed|webhook] [delay=ミリ秒] [include_bots=true|false] [prefix=テキスト]")
+            .await?;
+        return Ok(());
+    }
+
+    // ターゲットチャンネルIDを解析
+    let target_channel_id = match parts[1].parse::<u64>() {
+        Ok(id) => Id::new(id),
+        Err(_) => {
+            state.api.create_message(message.channel_id, "無効なチャンネルIDです。正しい数値IDを入力してください。").await?;
+            return Ok(());
+        }
+    };
+
+    // all フラグと key=value 形式のオプションを反映
+    let mut thread_info = ThreadInfo::new(target_channel_id);
+    for option in &parts[2..] {
+        if let Err(reason) = config::apply_mapping_option(&mut thread_info, option) {
+            state.api.create_message(message.channel_id, &format!("オプションが正しくありません: {}", reason)).await?;
+            return Ok(());
+        }
+    }
+    let transfer_all_messages = thread_info.transfer_all_messages;
+
+    // 転送先にボットが転送できるか（別のサーバーの場合は実行したユーザーの権限も）確認する
+    if let Err(reason) = permissions::check_mapping_target(&state, message.channel_id, message.author.id, target_channel_id).await {
+        state
+            .api
+            .create_message(message.channel_id, &format!("❌ 転送先のチャンネル {} に転送できません: {}", target_channel_id, reason))
+            .await?;
+        return Ok(());
+    }
+
+    // スレッド情報をハッシュマップに追加
+    state.add_thread_mapping(message.channel_id, thread_info).await?;
+    let source = source::prepare(&state, message.channel_id).await;
+    let target = state.channel_link(target_channel_id, message.channel_id).await;
+
+    // 設定完了メッセージを送信
+    let response = if transfer_all_messages {
+        format!(
+            "この{}のメッセージを全てチャンネル {} に転送します",
+            source.noun(),
+            target
+        )
+    } else {
+        format!(
+            "この{}のメッセージをチャンネル {} に転送します",
+            source.noun(),
+            target
+        )
+    };
+
+    state.api.create_message(message.channel_id, &response).await?;
+
+    // all を指定した場合は、過去のメッセージもすぐに転送する
+    if transfer_all_messages && state.auto_backfill {
+        let thread_id = message.channel_id;
+        let Some(thread_info) = state.get_thread_info(thread_id).await else {
+            return Ok(());
+        };
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            if let Err(e) = fetch_all_messages_and_transfer(&state, thread_id, &thread_info, &BackfillRange::default()).await {
+                eprintln!("スレッド {} の全メッセージ転送中にエラーが発生しました: {}", thread_id, e);
+                state.alerts.mapping_failure(&state.http, &format!("<#{}> の過去メッセージの転送", thread_id), thread_id, &thread_info, &*e).await;
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// !set_webhookコマンドを処理します
+pub async fn handle_set_webhook_command(
+    message: Box<MessageCreate>,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let content = &message.content;
+    let parts: Vec<&str> = content.split_whitespace().collect();
+
+    if parts.len() < 2 {
+        // コマンドの使用方法を表示
+        state
+            .api
+            .create_message(message.channel_id, "使用法: !set_webhook <webhook_url>\nWebhook URLは完全なURL（https://discord.com/api/webhooks/...）である必要があります。\n\n**注意**: ウェブフック名は自動的に空に設定されます。")
+            .await?;
+        return Ok(());
+    }
+
+    // Webhook URLを取得
+    let webhook_url = parts[1].to_string();
+    
+    // Webhook URLのバリデーション
+    if !webhook_url.starts_with("http://") && !webhook_url.starts_with("https://") {
+        state
+            .api
+            .create_message(message.channel_id, "無効なWebhook URLです。URLはhttp://またはhttps://で始まる必要があります。")
+            .await?;
+        return Ok(());
+    }
+    
+    // Webhook URLにスペースや余分な文字が含まれている可能性があるため、URLの形式をチェック
+    if webhook_url.contains(" ") || !webhook_url.contains("discord.com/api/webhooks/") {
+        state
+            .api
+            .create_message(message.channel_id, "無効なWebhook URLの形式です。URLに空白が含まれていないか、正しいDiscord Webhook URLであることを確認してください。")
+            .await?;
+        return Ok(());
+    }
+
+    // ウェブフック名を空に設定
+    match clear_webhook_name(&webhook_url).await {
+        Ok(_) => {
+            println!("ウェブフック名の設定が完了しました");
+        },
+        Err(e) => {
+            println!("ウェブフック名の設定中にエラーが発生しました: {}", e);
+            // エラーがあっても処理は続行（警告として表示）
+            state
+                .api
+                .create_message(message.channel_id, &format!("⚠️ ウェブフック名の自動設定中にエラーが発生しました。ウェブフック自体は設定しますが、送信者名が正しく表示されない可能性があります。\nエラー: {}", e))
+                .await?;
+        }
+    }
+
+    // 既存の設定にWebhook URLを追加し、データベースに保存
+    let updated = state
+        .update_thread_mapping(message.channel_id, |info| info.webhook_url = Some(webhook_url.clone()))
+        .await?;
+    if updated {
+        println!("Webhookを設定しました: スレッド={}, URL={}", message.channel_id, webhook_url);
+
+        // 設定完了メッセージを送信
+        state
+            .api
+            .create_message(message.channel_id, "このスレッドにWebhookを設定しました！メッセージは元の送信者のアバターと名前で転送されます。ウェブフック名は自動的に空に設定されました。")
+            .await?;
+    } else {
+        // スレッド情報がまだ設定されていない場合
+        state.api.create_message(message.channel_id, "まず !thread2channel コマンドでチャンネル転送を設定してください。").await?;
+    }
+
+    Ok(())
+}
+
+/// 過去メッセージの転送で、この件数以上の場合は確認ボタンで確認してから転送する
+const BACKFILL_CONFIRM_THRESHOLD: usize = 100;
+
+/// 確認ボタンが押されるのを待つ時間
+const BACKFILL_CONFIRM_TIMEOUT: tokio::time::Duration = tokio::time::Duration::from_secs(60);
+
+/// !start / !all コマンドを処理します（全メッセージ転送を開始）
+///
+/// `!all 50`、`!all since:2024-01-01`、`!all after:<メッセージID>` のように転送する範囲を指定できます。
+pub async fn handle_start_command(
+    message: Box<MessageCreate>,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let args: Vec<&str> = message.content.split_whitespace().skip(1).collect();
+    let range = match BackfillRange::parse(&args) {
+        Ok(range) => range,
+        Err(reason) => {
+            state.api.create_message(message.channel_id, &reason).await?;
+            return Ok(());
+        }
+    };
+    // スレッド情報を取得
+    let thread_info = match state.resolve_thread_info(message.channel_id).await {
+        Some(info) => info,
+        None => {
+            // スレッド情報がない場合は設定を促す
+            state
+                .api
+                .create_message(message.channel_id, "このスレッドは設定されていません。まず `!thread2channel <target_channel_id>` コマンドで設定してください。")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if !thread_info.active {
+        state.api.create_message(message.channel_id, "このスレッドの転送は一時停止中です。`!resume` で再開してから実行してください。").await?;
+        return Ok(());
+    }
+    
+    if state.backfills.is_running(message.channel_id).await {
+        state.api.create_message(message.channel_id, "このスレッドの過去メッセージは既に転送中です。完了するまでお待ちください。").await?;
+        return Ok(());
+    }
+
+    // 件数が多い場合は、確認ボタンが押されてから転送する
+    let count = match range.count {
+        Some(count) if count < BACKFILL_CONFIRM_THRESHOLD => count,
+        _ => fetch_message_history(state.api.as_ref(), message.channel_id, &range).await?.len(),
+    };
+    if count >= BACKFILL_CONFIRM_THRESHOLD {
+        let pending = PendingBackfill {
+            thread_id: message.channel_id,
+            thread_info,
+            range,
+            requester: message.author.id,
+            command: message.content.clone(),
+        };
+        return request_backfill_confirmation(&state, pending, count).await;
+    }
+
+    // 確認メッセージを送信
+    state.api.create_message(message.channel_id, "🔄 このスレッドの過去メッセージの転送を開始します...").await?;
+    
+    spawn_backfill(&state, message.channel_id, thread_info, range);
+    Ok(())
+}
+
+/// 確認・中止ボタンを作成する
+fn confirmation_buttons() -> Vec<Component> {
+    let button = |confirm: bool, label: &str, style: ButtonStyle| {
+        Component::Button(Button {
+            custom_id: Some(backfill::confirm_custom_id(confirm)),
+            disabled: false,
+            emoji: None,
+            label: Some(label.to_string()),
+            style,
+            url: None,
+        })
+    };
+    vec![Component::ActionRow(ActionRow {
+        components: vec![
+            button(true, "転送する", ButtonStyle::Success),
+            button(false, "キャンセル", ButtonStyle::Secondary),
+        ],
+    })]
+}
+
+/// 確認ボタン付きのメッセージを送信し、コマンドを実行したユーザーが確認するのを待つ
+///
+/// 期限内に確認されなかった場合は転送せず、確認メッセージを期限切れの表示に変えます。
+async fn request_backfill_confirmation(
+    state: &Arc<BotState>,
+    pending: PendingBackfill,
+    count: usize,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let channel_id = pending.thread_id;
+    let content = format!(
+        "⚠️ このスレッドの過去メッセージ {}件を転送します。よろしいですか？（{}秒以内に <@{}> が選択してください）",
+        count,
+        BACKFILL_CONFIRM_TIMEOUT.as_secs(),
+        pending.requester
+    );
+    let confirmation = state
+        .http
+        .create_message(channel_id)
+        .content(&content)?
+        .components(&confirmation_buttons())?
+        .allowed_mentions(Some(&AllowedMentions::default()))
+        .await?
+        .model()
+        .await?;
+    state.confirmations.insert(confirmation.id, pending).await;
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(BACKFILL_CONFIRM_TIMEOUT).await;
+        if state.confirmations.take(confirmation.id).await.is_none() {
+            return;
+        }
+        let expire = async {
+            state
+                .http
+                .update_message(channel_id, confirmation.id)
+                .content(Some("⌛ 確認の期限が切れたため、過去メッセージの転送を中止しました。"))?
+                .components(Some(&[]))?
+                .await?;
+            Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+        };
+        if let Err(e) = expire.await {
+            println!("確認メッセージの更新に失敗しました: {}", e);
+        }
+    });
+
+    Ok(())
+}
+
+/// 過去メッセージの転送の確認ボタンが押された時の処理
+pub async fn handle_backfill_button(
+    interaction: &Interaction,
+    confirm: bool,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(confirmation) = &interaction.message else {
+        return Ok(());
+    };
+
+    // コマンドを実行したユーザー以外は操作できない
+    let requester = state.confirmations.requester(confirmation.id).await;
+    if requester.is_some() && requester != interaction.author_id() {
+        let response = InteractionResponse {
+            kind: InteractionResponseType::ChannelMessageWithSource,
+            data: Some(InteractionResponseData {
+                content: Some("🔒 このボタンはコマンドを実行したユーザーのみ操作できます。".to_string()),
+                flags: Some(MessageFlags::EPHEMERAL),
+                ..Default::default()
+            }),
+        };
+        state
+            .http
+            .interaction(interaction.application_id)
+            .create_response(interaction.id, &interaction.token, &response)
+            .await?;
+        return Ok(());
+    }
+
+    let pending = state.confirmations.take(confirmation.id).await;
+    let content = match (&pending, confirm) {
+        (None, _) => "⌛ この確認は既に期限切れか、処理済みです。",
+        (Some(_), true) => "🔄 このスレッドの過去メッセージの転送を開始します...",
+        (Some(_), false) => "⏹️ 過去メッセージの転送をキャンセルしました。",
+    };
+    // ボタンを取り除いて結果を表示する
+    let response = InteractionResponse {
+        kind: InteractionResponseType::UpdateMessage,
+        data: Some(InteractionResponseData {
+            content: Some(content.to_string()),
+            components: Some(Vec::new()),
+            ..Default::default()
+        }),
+    };
+    state
+        .http
+        .interaction(interaction.application_id)
+        .create_response(interaction.id, &interaction.token, &response)
+        .await?;
+
+    if let (Some(pending), true) = (pending, confirm) {
+        let command = format!("{}（確認ボタンで開始）", pending.command);
+        state
+            .record_command(AuditEntry::new(pending.requester, pending.thread_id, &command, AuditOutcome::Succeeded))
+            .await;
+        if state.backfills.is_running(pending.thread_id).await {
+            state
+                .http
+                .create_message(pending.thread_id)
+                .content("このスレッドの過去メッセージは既に転送中です。完了するまでお待ちください。")?
+                .await?;
+            return Ok(());
+        }
+        spawn_backfill(&state, pending.thread_id, pending.thread_info, pending.range);
+    }
+    Ok(())
+}
+
+/// !export コマンドを処理します（スレッドの履歴をファイルにまとめて転送先にアップロード）
+///
+/// `!export [md|html] [範囲]` のように使用します。範囲は `!all` と同じ形式です。
+pub async fn handle_export_command(
+    message: Box<MessageCreate>,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut args: Vec<&str> = message.content.split_whitespace().skip(1).collect();
+
+    let format = match args.first().and_then(|arg| TranscriptFormat::parse(arg)) {
+        Some(format) => {
+            args.remove(0);
+            format
+        }
+        None => TranscriptFormat::default(),
+    };
+    let range = match BackfillRange::parse(&args) {
+        Ok(range) => range,
+        Err(reason) => {
+            state.api.create_message(message.channel_id, &reason).await?;
+            return Ok(());
+        }
+    };
+
+    let Some(thread_info) = state.resolve_thread_info(message.channel_id).await else {
+        state
+            .api
+            .create_message(message.channel_id, "このスレッドは設定されていません。まず `!thread2channel <target_channel_id>` コマンドで設定してください。")
+            .await?;
+        return Ok(());
+    };
+
+    state
+        .api
+        .create_message(message.channel_id, &format!("📄 このスレッドの記録を作成して {} にアップロードします...", state.channel_link(thread_info.target_channel_id, message.channel_id).await))
+        .await?;
+
+    let thread_id = message.channel_id;
+    tokio::spawn(async move {
+        if let Err(e) = export_transcript(&state, thread_id, &thread_info, format, &range).await {
+            eprintln!("スレッド {} の記録の作成中にエラーが発生しました: {}", thread_id, e);
+            state.alerts.failure(&state.http, &format!("<#{}> の記録の作成", thread_id), &*e).await;
+        }
+    });
+
+    Ok(())
+}
+
+/// !all_threads コマンドを処理します（親チャンネル配下の全スレッドの過去メッセージを転送）
+///
+/// `!all_threads <親チャンネルID> [範囲]` のように使用し、親チャンネルのマッピングの転送先に転送します。
+/// フォーラムチャンネルにはメッセージを投稿できないため、任意のチャンネルから実行できます。
+pub async fn handle_all_threads_command(
+    message: Box<MessageCreate>,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let parts: Vec<&str> = message.content.split_whitespace().collect();
+
+    let Some(parent_id) = parts.get(1).and_then(|id| id.parse::<u64>().ok()).and_then(Id::new_checked) else {
+        state
+            .api
+            .create_message(message.channel_id, "使用法: !all_threads <親チャンネルID> [件数] [since:YYYY-MM-DD] [after:メッセージID]")
+            .await?;
+        return Ok(());
+    };
+    let range = match BackfillRange::parse(&parts[2..]) {
+        Ok(range) => range,
+        Err(reason) => {
+            state.api.create_message(message.channel_id, &reason).await?;
+            return Ok(());
+        }
+    };
+
+    let Some(parent_info) = state.parent_mappings.get(parent_id) else {
+        state
+            .api
+            .create_message(message.channel_id, "このチャンネルのマッピングは設定されていません。先に親チャンネルのマッピング（設定ファイルの `parent_id` など）を設定してください。")
+            .await?;
+        return Ok(());
+    };
+
+    state.api.create_message(message.channel_id, &format!(
+            "🔄 <#{}> 配下の全スレッドの過去メッセージを <#{}> に転送します...",
+            parent_id, parent_info.target_channel_id
+        )).await?;
+
+    // スレッドの数によっては時間がかかるため、別のタスクで実行する
+    tokio::spawn(async move {
+        if let Err(e) = transfer_all_threads(&state, parent_id, &range).await {
+            eprintln!("チャンネル {} 配下の全スレッドの転送中にエラーが発生しました: {}", parent_id, e);
+            state.alerts.failure(&state.http, &format!("<#{}> 配下の全スレッドの転送", parent_id), &*e).await;
+        }
+    });
+
+    Ok(())
+}
+
+/// !cancel コマンドを処理します（実行中の過去メッセージの転送を中止）
+pub async fn handle_cancel_command(
+    message: Box<MessageCreate>,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let response = if state.backfills.cancel(message.channel_id).await {
+        println!("スレッド {} の全メッセージ転送のキャンセルを受け付けました", message.channel_id);
+        "⏹️ 過去メッセージの転送をキャンセルします。送信中のメッセージが完了した時点で停止します。"
+    } else {
+        "このスレッドでは過去メッセージの転送は実行されていません。"
+    };
+
+    state.api.create_message(message.channel_id, response).await?;
+
+    Ok(())
+}
+
+/// !help コマンドを処理します（このスレッドで使用できるコマンドを表示）
+pub async fn handle_help_command(
+    message: Box<MessageCreate>,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let text = help::help_text(&state, Some(message.channel_id)).await;
+    state.api.create_message(message.channel_id, &text).await?;
+
+    Ok(())
+}
+
+/// !status コマンドを処理します（稼働時間・転送数などの実行状況を表示）
+pub async fn handle_status_command(
+    message: Box<MessageCreate>,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let report = stats::status_report(&state).await;
+    state.api.create_message(message.channel_id, &report).await?;
+
+    Ok(())
+}
+
+/// !stats コマンドを処理します（マッピングごとの転送の集計を表示）
+pub async fn handle_stats_command(
+    message: Box<MessageCreate>,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let report = stats::mapping_stats_report(&state).await;
+    state.api.create_message(message.channel_id, &report).await?;
+
+    Ok(())
+}
+
+/// テストメッセージを削除するまでの時間
+const TEST_MESSAGE_LIFETIME: tokio::time::Duration = tokio::time::Duration::from_secs(10);
+
+/// !test コマンドを処理します（転送先でのボットの権限を確認し、テストメッセージを送信）
+///
+/// 「何も転送されない」場合の原因を調べるために使用します。テストメッセージはしばらくすると自動で削除されます。
+pub async fn handle_test_command(
+    message: Box<MessageCreate>,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let http = &state.http;
+    let Some(thread_info) = state.resolve_thread_info(message.channel_id).await else {
+        state
+            .api
+            .create_message(message.channel_id, "このスレッドは設定されていません。まず `!thread2channel <target_channel_id>` コマンドで設定してください。")
+            .await?;
+        return Ok(());
+    };
+    let Some(bot_user_id) = state.bot_user_id() else {
+        return Ok(());
+    };
+    let target_channel_id = thread_info.target_channel_id;
+
+    let mut checks = vec![
+        (Permissions::VIEW_CHANNEL, "チャンネルを見る"),
+        (Permissions::SEND_MESSAGES, "メッセージを送信"),
+        (Permissions::EMBED_LINKS, "埋め込みリンク"),
+        (Permissions::ATTACH_FILES, "ファイルを添付"),
+    ];
+    if thread_info.format == MessageFormat::Webhook && thread_info.webhook_url.is_none() {
+        checks.push((Permissions::MANAGE_WEBHOOKS, "ウェブフックの管理（Webhookの自動作成に必要）"));
+    }
+
+    let mut lines = vec![format!("🧪 **転送先 {} の確認**", state.channel_link(target_channel_id, message.channel_id).await)];
+    match permissions::channel_permissions(http, target_channel_id, bot_user_id).await {
+        Ok(granted) => {
+            for (permission, name) in &checks {
+                let mark = if granted.contains(*permission) { "✅" } else { "❌" };
+                lines.push(format!("{} {}", mark, name));
+            }
+        }
+        Err(e) => lines.push(format!("❌ 権限を確認できませんでした: {}", e)),
+    }
+
+    // 実際に送信できるかを確かめ、しばらくしてから削除する
+    let test_message = async {
+        let source = state.channel_link(message.channel_id, target_channel_id).await;
+        let content = format!("🧪 {} からの転送テストです（このメッセージは自動で削除されます）", source);
+        let sent = state.api.create_message(target_channel_id, &content).await?;
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(sent.id)
+    };
+    match test_message.await {
+        Ok(test_message_id) => {
+            lines.push("✅ テストメッセージを送信できました".to_string());
+            let http = Arc::clone(&state.http);
+            tokio::spawn(async move {
+                tokio::time::sleep(TEST_MESSAGE_LIFETIME).await;
+                if let Err(e) = http.delete_message(target_channel_id, test_message_id).await {
+                    println!("テストメッセージの削除に失敗しました: {}", e);
+                }
+            });
+        }
+        Err(e) => lines.push(format!("❌ テストメッセージを送信できませんでした: {}", e)),
+    }
+
+    if !thread_info.active {
+        lines.push("⏸️ このスレッドの転送は一時停止中です".to_string());
+    }
+
+    state.api.create_message(message.channel_id, &lines.join("\n")).await?;
+
+    Ok(())
+}
+
+/// !pause / !resume コマンドを処理します
+pub async fn handle_pause_command(
+    message: Box<MessageCreate>,
+    state: Arc<BotState>,
+    active: bool,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+
+    let response = match state.set_mapping_active(message.channel_id, active).await? {
+        Some(info) if active => {
+            println!("転送を再開しました: スレッド {} -> チャンネル {}", message.channel_id, info.target_channel_id);
+            format!("▶️ {} への転送を再開しました。", state.channel_link(info.target_channel_id, message.channel_id).await)
+        }
+        Some(info) => {
+            println!("転送を一時停止しました: スレッド {} -> チャンネル {}", message.channel_id, info.target_channel_id);
+            format!("⏸️ {} への転送を一時停止しました。`!resume` で再開できます。", state.channel_link(info.target_channel_id, message.channel_id).await)
+        }
+        None => "このスレッドは設定されていません。まず `!thread2channel <target_channel_id>` コマンドで設定してください。".to_string(),
+    };
+
+    state.api.create_message(message.channel_id, &response).await?;
+
+    Ok(())
+}
+
+/// !retry-failed コマンドを処理します（転送に失敗したメッセージを再送）
+///
+/// 実行したスレッドのメッセージを再送します。`all` を指定した場合は全てのスレッドのメッセージを再送します。
+/// 再送には現在のマッピングの設定を使用するため、転送先やWebhookを直してから実行すれば、直した設定で転送されます。
+pub async fn handle_retry_failed_command(
+    message: Box<MessageCreate>,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let all = message.content.split_whitespace().nth(1) == Some("all");
+    let failed = state.take_failed_forwards(if all { None } else { Some(message.channel_id) })?;
+
+    if failed.is_empty() {
+        let response = if all {
+            "転送に失敗したメッセージはありません。"
+        } else {
+            "このスレッドには転送に失敗したメッセージはありません。全てのスレッドを対象にするには `!retry-failed all` を実行してください。"
+        };
+        state.api.create_message(message.channel_id, response).await?;
+        return Ok(());
+    }
+
+    println!("転送に失敗したメッセージを {} 件再送します", failed.len());
+    state
+        .api
+        .create_message(message.channel_id, &format!("🔁 転送に失敗したメッセージを {}件再送します。", failed.len()))
+        .await?;
+
+    for failed in failed {
+        // マッピングが削除されている場合は、失敗したときの設定で再送する
+        let thread_info = state
+            .resolve_thread_info(failed.message.channel_id)
+            .await
+            .unwrap_or(failed.thread_info);
+        state.queues.enqueue(&state, thread_info, failed.message).await;
+    }
+
+    Ok(())
+}
+
+/// 権限（メッセージの管理・スレッドの管理、または管理者ロール）が必要なコマンド（プレフィックスを除いた名前）
+pub const PRIVILEGED_COMMANDS: &[&str] = &[
+    "thread2channel",
+    "set_webhook",
+    "start",
+    "all",
+    "export",
+    "all_threads",
+    "cancel",
+    "pause",
+    "resume",
+    "test",
+    "retry-failed",
+];
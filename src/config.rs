@@ -0,0 +1,1224 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use twilight_model::id::{
+    marker::{ChannelMarker, GuildMarker, RoleMarker, UserMarker},
+    Id,
+};
+
+use crate::filter::MessagePattern;
+use crate::history::HistoryMode;
+use crate::mappings::NameMapping;
+use crate::sink::SinkSpec;
+use crate::{publish, template};
+use crate::transform::TransformSpec;
+use crate::state::{self, ArchiveMode, EmailSchedule, EmojiStyle, MentionPolicy, MessageFormat, Route, SystemMessageKind, ThreadInfo};
+
+/// 設定ファイルのパスを指定する環境変数名
+const CONFIG_PATH_ENV: &str = "CONFIG_PATH";
+
+/// 設定ファイルのデフォルトパス
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// マッピング保存用データベースのデフォルトパス
+const DEFAULT_DATABASE_PATH: &str = "thread2channel.db";
+
+/// JSONLアーカイブのデフォルトの保存先ディレクトリ
+const DEFAULT_ARCHIVE_DIR: &str = "archive";
+
+/// コマンドのデフォルトのプレフィックス（このプレフィックスで始まるメッセージは転送しない）
+const DEFAULT_COMMAND_PREFIX: &str = "!";
+
+/// config.toml の内容
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    /// ボット全体の設定
+    #[serde(default)]
+    pub bot: BotSettings,
+    /// スレッドとチャンネルのマッピング一覧
+    #[serde(default)]
+    pub mappings: Vec<MappingEntry>,
+}
+
+/// `[bot]` セクションの設定
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BotSettings {
+    /// Discord Bot Token（未指定の場合は環境変数 DISCORD_TOKEN を使用）
+    pub token: Option<String>,
+    /// Discord Bot Tokenを記載したファイルのパス
+    pub token_file: Option<PathBuf>,
+    /// 標準出力にDiscord Bot Tokenを出力するコマンド
+    pub token_command: Option<String>,
+    /// マッピング保存用データベースのパス（未指定の場合は環境変数 DATABASE_PATH を使用）
+    pub database_path: Option<PathBuf>,
+    /// 転送したメッセージの対応をデータベースに保存するかどうか（未指定の場合は環境変数 PERSIST_MESSAGE_LINKS、デフォルトは true）
+    pub persist_message_links: Option<bool>,
+    /// JSONLアーカイブの保存先ディレクトリ（未指定の場合は環境変数 ARCHIVE_DIR、デフォルトは archive）
+    pub archive_dir: Option<PathBuf>,
+    /// `all` を指定したマッピングの過去メッセージを自動で転送するかどうか（未指定の場合は環境変数 AUTO_BACKFILL、デフォルトは true）
+    pub auto_backfill: Option<bool>,
+    /// 転送しないコマンドのプレフィックス一覧（未指定の場合は環境変数 COMMAND_PREFIXES、デフォルトは `!`）
+    pub command_prefixes: Option<Vec<String>>,
+    /// ボットのコマンドのプレフィックス（未指定の場合は環境変数 COMMAND_PREFIX、デフォルトは `!`）
+    pub command_prefix: Option<String>,
+    /// サーバーID -> そのサーバーで使用するコマンドのプレフィックス（環境変数 GUILD_COMMAND_PREFIXES と合わせて使用）
+    #[serde(default)]
+    pub guild_command_prefixes: HashMap<String, String>,
+    /// 権限エラー・転送の失敗・マッピングの停止を通知するチャンネルのID（未指定の場合は環境変数 ADMIN_CHANNEL_ID）
+    pub admin_channel_id: Option<u64>,
+    /// 管理用のコマンドの実行記録（誰が・どこで・何を・結果）を投稿するチャンネルのID（未指定の場合は環境変数 AUDIT_CHANNEL_ID）
+    pub audit_channel_id: Option<u64>,
+    /// ゲートウェイに接続するシャードの数（未指定の場合は環境変数 SHARD_COUNT、それも無ければDiscordの推奨する数）
+    pub shard_count: Option<u64>,
+    /// 並行して処理するイベントの数（未指定の場合は環境変数 EVENT_CONCURRENCY、それも無ければ 8）
+    pub event_concurrency: Option<usize>,
+    /// `/healthz`・`/readyz` を返すHTTPサーバーの待ち受けアドレス（未指定の場合は環境変数 HEALTH_ADDR、どちらも無ければ起動しない）
+    pub health_addr: Option<String>,
+    /// 外部からの投稿（`POST /inbound/<スレッドID>`）の認証に使うトークン（未指定の場合は環境変数 INBOUND_TOKEN、どちらも無ければ受け付けない）
+    pub inbound_token: Option<String>,
+    /// 管理画面（`/dashboard`）と管理用の API（`/api/`）の認証に使うトークン（未指定の場合は環境変数 DASHBOARD_TOKEN、どちらも無ければ公開しない）
+    pub dashboard_token: Option<String>,
+    /// エラーとパニックを報告する Sentry の DSN（未指定の場合は環境変数 SENTRY_DSN、`sentry` 機能を有効にしてビルドした場合のみ）
+    pub sentry_dsn: Option<String>,
+    /// 稼働状況を定期的に投稿する運用チャンネルのID（未指定の場合は環境変数 HEARTBEAT_CHANNEL_ID、どちらも無ければ投稿しない）
+    pub heartbeat_channel_id: Option<u64>,
+    /// 稼働状況を投稿する間隔（分、未指定の場合は環境変数 HEARTBEAT_MINUTES、デフォルトは 60）
+    pub heartbeat_minutes: Option<u64>,
+    /// この回数を超えてゲートウェイに再接続したら運用チャンネルに通知する（未指定の場合は環境変数 RECONNECT_ALERT_COUNT、デフォルトは 5）
+    pub reconnect_alert_count: Option<u64>,
+    /// 再接続の回数を数える期間（分、未指定の場合は環境変数 RECONNECT_ALERT_MINUTES、デフォルトは 10）
+    pub reconnect_alert_minutes: Option<u64>,
+    /// 転送したメッセージの添付ファイルの保存先（ディレクトリのパスか `s3://バケット/プレフィックス`、未指定の場合は環境変数 ATTACHMENT_ARCHIVE、どちらも無ければ保存しない）
+    pub attachment_archive: Option<String>,
+    /// メッセージの履歴をデータベースに保存する範囲（off、forwarded、all、未指定の場合は環境変数 MESSAGE_HISTORY、デフォルトは off）
+    pub message_history: Option<String>,
+    /// メッセージの履歴の保存先（SQLiteのファイルのパスか `postgres://` のURL、未指定の場合は環境変数 HISTORY_DATABASE_URL、どちらも無ければ `database_path`）
+    pub history_database_url: Option<String>,
+    /// 転送したメッセージのイベントの送信先（`nats://` か `kafka+http://` のURL、未指定の場合は環境変数 EVENT_PUBLISHER、どちらも無ければ送信しない）
+    pub event_publisher: Option<String>,
+    /// イベントを送信する NATS のサブジェクトの接頭辞・Kafka のトピック（未指定の場合は環境変数 EVENT_SUBJECT、デフォルトは thread2channel.forwarded）
+    pub event_subject: Option<String>,
+    /// まとめのメールの送信元のアドレス（未指定の場合は環境変数 EMAIL_FROM、どちらも無ければメールを送信しない）
+    pub email_from: Option<String>,
+    /// まとめのメールを送信する時刻（UTCの時、0〜23、未指定の場合は環境変数 EMAIL_HOUR、デフォルトは 0）
+    pub email_hour: Option<u32>,
+    /// 権限に関わらず、過去メッセージの転送やマッピングの変更のコマンドを許可するロールのID一覧（環境変数 ADMIN_ROLE_IDS と合わせて使用）
+    #[serde(default)]
+    pub admin_role_ids: Vec<u64>,
+    /// 詳細ログを出力するスレッド・チャンネルのID一覧（環境変数 DEBUG_THREAD_IDS と合わせて使用）
+    #[serde(default)]
+    pub debug_thread_ids: Vec<u64>,
+}
+
+/// `[[mappings]]` の1エントリ
+///
+/// `thread_id` と `parent_id` のどちらか一方を指定します。
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MappingEntry {
+    /// コピー元のスレッドID
+    pub thread_id: Option<u64>,
+    /// コピー元の親チャンネルID（配下の全スレッドを転送）
+    pub parent_id: Option<u64>,
+    /// コピー元のスレッド名に一致させる正規表現（一致したアクティブなスレッドを自動でマッピングに登録）
+    pub name_pattern: Option<MessagePattern>,
+    /// コピー先のチャンネルID
+    pub channel_id: u64,
+    /// Webhook URL (オプション)
+    pub webhook_url: Option<String>,
+    /// 過去のメッセージを全て転送するかどうか
+    #[serde(default)]
+    pub all: bool,
+    /// 転送メッセージの形式（plain、embed、webhook のいずれか）
+    #[serde(default)]
+    pub format: MessageFormat,
+    /// 転送前に待機する時間（ミリ秒）
+    #[serde(default)]
+    pub delay_ms: u64,
+    /// ボットのメッセージも転送するかどうか
+    #[serde(default)]
+    pub include_bots: bool,
+    /// 転送メッセージの本文の先頭に付けるテキスト
+    pub prefix: Option<String>,
+    /// 転送元を示すラベル
+    pub label: Option<String>,
+    /// 親チャンネル配下に新しいスレッドが作成されたとき、転送先に通知するかどうか
+    #[serde(default)]
+    pub notify: bool,
+    /// 投票が終了したとき、転送先のメッセージに結果を反映するかどうか
+    #[serde(default)]
+    pub poll_results: bool,
+    /// 転送メッセージで通知（メンション）を許可する範囲（none、users、all）
+    #[serde(default)]
+    pub mentions: MentionPolicy,
+    /// 転送元サーバーのカスタム絵文字の表示方法（keep、text、link）
+    #[serde(default)]
+    pub emoji: EmojiStyle,
+    /// 転送するシステムメッセージの種類（pin、join、boost、thread、rename）
+    #[serde(default)]
+    pub system_messages: Vec<SystemMessageKind>,
+    /// 元のメッセージを機械可読な形式で保存する方法（off、attach、jsonl）
+    #[serde(default)]
+    pub archive: ArchiveMode,
+    /// 本文がいずれかに一致するメッセージのみ転送する正規表現
+    #[serde(default)]
+    pub include: Vec<MessagePattern>,
+    /// 本文がいずれかに一致するメッセージを転送しない正規表現
+    #[serde(default)]
+    pub exclude: Vec<MessagePattern>,
+    /// メッセージを転送するユーザーのID（空の場合は全員）
+    #[serde(default)]
+    pub allow_users: Vec<Id<UserMarker>>,
+    /// メッセージを転送しないユーザーのID
+    #[serde(default)]
+    pub block_users: Vec<Id<UserMarker>>,
+    /// 送信者がいずれかを持っている場合のみ転送するロールのID
+    #[serde(default)]
+    pub roles: Vec<Id<RoleMarker>>,
+    /// 本文に応じて転送先を切り替えるルール（`[[mappings.routes]]`）
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    /// テキスト形式・Webhookの本文のテンプレート
+    pub template: Option<String>,
+    /// 転送前に本文に適用する変換
+    #[serde(default)]
+    pub transforms: Vec<TransformSpec>,
+    /// この件数のメッセージが溜まったらまとめて投稿する
+    #[serde(default)]
+    pub digest: usize,
+    /// 最初のメッセージからこの時間（分）が経ったらまとめて投稿する
+    #[serde(default)]
+    pub digest_minutes: u64,
+    /// スターボード形式で転送する場合のリアクションの絵文字
+    pub star: Option<String>,
+    /// スターボード形式で転送するのに必要なリアクションの数
+    #[serde(default = "state::default_star_threshold")]
+    pub star_count: u64,
+    /// このスレッドで使用するコマンドのプレフィックス
+    pub command_prefix: Option<String>,
+    /// 転送メッセージの送信先（`{ type = "file", path = "..." }`、`{ type = "http", url = "..." }`、省略した場合はDiscord）
+    #[serde(default)]
+    pub sink: SinkSpec,
+    /// 転送したメッセージを RSS フィードで公開するかどうか
+    #[serde(default)]
+    pub feed: bool,
+    /// 🐛 のリアクションで Issue を作成する GitHub のリポジトリ（`owner/repo`）
+    pub github: Option<String>,
+    /// 転送したメッセージのまとめをメールで送信する宛先
+    #[serde(default)]
+    pub email: Vec<String>,
+    /// まとめをメールで送信する頻度（daily、weekly）
+    #[serde(default)]
+    pub email_schedule: EmailSchedule,
+    /// スレッドがアーカイブされている間、転送を一時停止するかどうか
+    #[serde(default = "state::default_pause_on_archive")]
+    pub pause_archived: bool,
+}
+
+/// 設定ファイルのパスを取得する（環境変数 CONFIG_PATH が優先）
+pub fn config_path() -> PathBuf {
+    env::var(CONFIG_PATH_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_CONFIG_PATH))
+}
+
+/// 設定ファイルを読み込む
+///
+/// デフォルトパスにファイルが無い場合は `None` を返します。
+/// CONFIG_PATH で明示的に指定されたファイルが無い場合や、形式が正しくない場合はエラーになります。
+pub fn load_config_file(path: &Path) -> Result<Option<ConfigFile>, Box<dyn std::error::Error + Send + Sync>> {
+    if !path.exists() {
+        if env::var(CONFIG_PATH_ENV).is_ok() {
+            return Err(format!("設定ファイル {} が見つかりません（CONFIG_PATH を確認してください）", path.display()).into());
+        }
+        return Ok(None);
+    }
+
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("設定ファイル {} を読み込めませんでした: {}", path.display(), e))?;
+
+    let config: ConfigFile = toml::from_str(&text)
+        .map_err(|e| format!("設定ファイル {} の形式が正しくありません:\n{}", path.display(), e))?;
+
+    println!("設定ファイルを読み込みました: {}", path.display());
+    Ok(Some(config))
+}
+
+/// Webhook URLの形式を検証する
+pub fn validate_webhook_url(url: &str) -> Result<(), &'static str> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        Err("URLはhttp://またはhttps://で始まる必要があります")
+    } else if !url.contains("discord.com/api/webhooks/") {
+        Err("正しいDiscord Webhook URLであることを確認してください")
+    } else {
+        Ok(())
+    }
+}
+
+/// GitHub のリポジトリ（`owner/repo`）を読み取る（空の場合は Issue を作成しない）
+fn parse_github_repo(value: &str) -> Result<Option<String>, String> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    let valid = value.split_once('/').is_some_and(|(owner, repo)| {
+        let valid_name = |name: &str| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        valid_name(owner) && valid_name(repo)
+    });
+    if !valid {
+        return Err(format!("github には owner/repo の形式でリポジトリを指定してください: {}", value));
+    }
+    Ok(Some(value.to_string()))
+}
+
+/// メールアドレスを読み取る（送信時に改めて確認するため、ここでは形式のみ確認する）
+fn parse_email_address(value: &str) -> Result<String, String> {
+    match value.split_once('@') {
+        Some((local, domain)) if !local.is_empty() && domain.contains('.') && !value.contains(char::is_whitespace) => {
+            Ok(value.to_string())
+        }
+        _ => Err(format!("無効なメールアドレスです: {}", value)),
+    }
+}
+
+/// 真偽値のオプション値を解析する
+fn parse_bool_option(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "on" | "yes" | "1" => Ok(true),
+        "false" | "off" | "no" | "0" => Ok(false),
+        _ => Err(format!("{} には true または false を指定してください: {}", key, value)),
+    }
+}
+
+/// マッピングのオプション（`all`、Webhook URL、`key=value`）を1つスレッド情報に反映する
+///
+/// `include`、`exclude`、`route`、`transform` は繰り返し指定でき、空の値を指定すると全て解除します。
+///
+/// 使用できるキー: `all`, `webhook`, `format`(plain/embed/webhook), `embed`, `delay`(ミリ秒), `include_bots`, `prefix`, `label`, `notify`, `poll_results`, `mentions`(none/users/all), `emoji`(keep/text/link), `system`(pin/join/boost/thread/rename をカンマ区切り), `archive`(off/attach/jsonl), `include`(正規表現), `exclude`(正規表現), `allow_users`(ユーザーIDをカンマ区切り), `block_users`(ユーザーIDをカンマ区切り), `roles`(ロールIDをカンマ区切り), `route`(チャンネルID=正規表現), `template`(テンプレート), `transform`(strip_links/redact=正規表現/mask=種類/prefix=テキスト/suffix=テキスト), `digest`(件数), `digest_minutes`(分), `star`(絵文字), `star_count`(リアクションの数), `command_prefix`(コマンドのプレフィックス), `sink`(discord/file=パス/http=URL/slack=Webhook URLまたはチャンネル/telegram=チャットID), `feed`, `github`(owner/repo), `email`(メールアドレスをカンマ区切り), `email_schedule`(daily/weekly), `pause_archived`
+pub fn apply_mapping_option(info: &mut ThreadInfo, option: &str) -> Result<(), String> {
+    // 後方互換: 位置指定の all フラグ
+    if option == "all" {
+        info.transfer_all_messages = true;
+        return Ok(());
+    }
+
+    // 後方互換: 位置指定のWebhook URL
+    if option.starts_with("http://") || option.starts_with("https://") {
+        validate_webhook_url(option).map_err(|reason| format!("無効なWebhook URL: {}", reason))?;
+        info.webhook_url = Some(option.to_string());
+        return Ok(());
+    }
+
+    let (key, value) = option
+        .split_once('=')
+        .ok_or_else(|| format!("不明なオプションです: {}", option))?;
+
+    match key {
+        "all" => info.transfer_all_messages = parse_bool_option(key, value)?,
+        "webhook" => return apply_mapping_option(info, value),
+        "format" => {
+            info.format = match value {
+                "plain" => MessageFormat::Plain,
+                "embed" => MessageFormat::Embed,
+                "webhook" => MessageFormat::Webhook,
+                _ => return Err(format!("format には plain、embed、webhook のいずれかを指定してください: {}", value)),
+            }
+        }
+        "embed" => {
+            info.format = if parse_bool_option(key, value)? {
+                MessageFormat::Embed
+            } else {
+                MessageFormat::Plain
+            }
+        }
+        "delay" => {
+            info.forward_delay_ms = value
+                .parse()
+                .map_err(|_| format!("delay にはミリ秒を数値で指定してください: {}", value))?
+        }
+        "include_bots" => info.include_bots = parse_bool_option(key, value)?,
+        "prefix" => info.prefix = if value.is_empty() { None } else { Some(value.to_string()) },
+        "label" => info.label = if value.is_empty() { None } else { Some(value.to_string()) },
+        "notify" => info.notify_new_threads = parse_bool_option(key, value)?,
+        "pause_archived" => info.pause_on_archive = parse_bool_option(key, value)?,
+        "poll_results" => info.poll_results = parse_bool_option(key, value)?,
+        "mentions" => {
+            info.mentions = match value {
+                "none" => MentionPolicy::None,
+                "users" => MentionPolicy::Users,
+                "all" => MentionPolicy::All,
+                _ => return Err(format!("mentions には none、users、all のいずれかを指定してください: {}", value)),
+            }
+        }
+        "emoji" => {
+            info.emoji = match value {
+                "keep" => EmojiStyle::Keep,
+                "text" => EmojiStyle::Text,
+                "link" => EmojiStyle::Link,
+                _ => return Err(format!("emoji には keep、text、link のいずれかを指定してください: {}", value)),
+            }
+        }
+        "system" => {
+            info.system_messages = value
+                .split(',')
+                .map(str::trim)
+                .filter(|kind| !kind.is_empty())
+                .map(|kind| {
+                    SystemMessageKind::parse(kind).ok_or_else(|| {
+                        format!("system には pin、join、boost、thread、rename をカンマ区切りで指定してください: {}", kind)
+                    })
+                })
+                .collect::<Result<_, _>>()?
+        }
+        "archive" => {
+            info.archive = match value {
+                "off" => ArchiveMode::Off,
+                "attach" => ArchiveMode::Attach,
+                "jsonl" => ArchiveMode::Jsonl,
+                _ => return Err(format!("archive には off、attach、jsonl のいずれかを指定してください: {}", value)),
+            }
+        }
+        "include" | "exclude" => {
+            let patterns = if key == "include" { &mut info.include_patterns } else { &mut info.exclude_patterns };
+            if value.is_empty() {
+                patterns.clear();
+            } else {
+                patterns.push(MessagePattern::new(value)?);
+            }
+        }
+        "allow_users" | "block_users" => {
+            let users = value
+                .split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(|id| {
+                    id.parse::<u64>()
+                        .ok()
+                        .and_then(Id::new_checked)
+                        .ok_or_else(|| format!("{} にはユーザーIDをカンマ区切りで指定してください: {}", key, id))
+                })
+                .collect::<Result<_, _>>()?;
+            if key == "allow_users" {
+                info.allowed_users = users;
+            } else {
+                info.blocked_users = users;
+            }
+        }
+        "roles" => {
+            info.required_roles = value
+                .split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(|id| {
+                    id.parse::<u64>()
+                        .ok()
+                        .and_then(Id::new_checked)
+                        .ok_or_else(|| format!("roles にはロールIDをカンマ区切りで指定してください: {}", id))
+                })
+                .collect::<Result<_, _>>()?
+        }
+        "route" => {
+            if value.is_empty() {
+                info.routes.clear();
+                return Ok(());
+            }
+            let (channel_id, pattern) = value
+                .split_once('=')
+                .ok_or_else(|| format!("route は チャンネルID=正規表現 の形式で指定してください: {}", value))?;
+            let channel_id = channel_id
+                .parse::<u64>()
+                .ok()
+                .and_then(Id::new_checked)
+                .ok_or_else(|| format!("route のチャンネルIDが無効です: {}", channel_id))?;
+            info.routes.push(Route {
+                pattern: MessagePattern::new(pattern)?,
+                channel_id,
+            });
+        }
+        "template" => {
+            if let Some(placeholder) = template::unknown_placeholder(value) {
+                return Err(format!("テンプレートに不明なプレースホルダー {{{}}} があります（使用できるもの: {}）", placeholder, template::PLACEHOLDERS.join(", ")));
+            }
+            info.template = if value.is_empty() { None } else { Some(value.to_string()) };
+        }
+        "digest" => {
+            info.digest_count = value
+                .parse()
+                .map_err(|_| format!("digest にはまとめる件数を数値で指定してください: {}", value))?
+        }
+        "digest_minutes" => {
+            info.digest_minutes = value
+                .parse()
+                .map_err(|_| format!("digest_minutes には分を数値で指定してください: {}", value))?
+        }
+        "star" => info.star_emoji = if value.is_empty() { None } else { Some(value.to_string()) },
+        "command_prefix" => {
+            info.command_prefix = if value.is_empty() { None } else { Some(value.to_string()) }
+        }
+        "sink" => info.sink = SinkSpec::parse(value)?,
+        "feed" => info.feed = parse_bool_option(key, value)?,
+        "github" => info.github_repo = parse_github_repo(value)?,
+        "email" => {
+            info.email_recipients = value
+                .split(',')
+                .map(str::trim)
+                .filter(|address| !address.is_empty())
+                .map(parse_email_address)
+                .collect::<Result<_, _>>()?
+        }
+        "email_schedule" => {
+            info.email_schedule = match value {
+                "daily" => EmailSchedule::Daily,
+                "weekly" => EmailSchedule::Weekly,
+                _ => return Err(format!("email_schedule には daily か weekly を指定してください: {}", value)),
+            }
+        }
+        "star_count" => {
+            info.star_threshold = match value.parse() {
+                Ok(count) if count > 0 => count,
+                _ => return Err(format!("star_count には1以上の数値を指定してください: {}", value)),
+            }
+        }
+        "transform" => {
+            if value.is_empty() {
+                info.transforms.clear();
+            } else {
+                info.transforms.push(TransformSpec::parse(value)?);
+            }
+        }
+        _ => return Err(format!("不明なオプションです: {}", key)),
+    }
+
+    Ok(())
+}
+
+/// `:` 区切りのマッピング定義を分割する（Webhook URL内の `://` は分割しない）
+fn split_mapping_entry(value: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+
+    for part in value.split(':') {
+        if part.starts_with("//") {
+            if let Some(last) = tokens.last_mut() {
+                if last == "http" || last == "https" || last.ends_with("=http") || last.ends_with("=https") {
+                    last.push(':');
+                    last.push_str(part);
+                    continue;
+                }
+            }
+        }
+        tokens.push(part.to_string());
+    }
+
+    tokens
+}
+
+/// マッピング定義 `スレッドID:チャンネルID[:Webhook URL][:all][:key=value...]` を解析する
+pub fn parse_thread_mapping_entry(value: &str) -> Result<(Id<ChannelMarker>, ThreadInfo), String> {
+    let tokens = split_mapping_entry(value);
+    if tokens.len() < 2 {
+        return Err("スレッドID:チャンネルID の形式で指定してください".to_string());
+    }
+
+    // スレッドIDとチャンネルIDをパース
+    let thread_id = tokens[0]
+        .parse::<u64>()
+        .ok()
+        .and_then(Id::new_checked)
+        .ok_or_else(|| format!("無効なスレッドIDです: {}", tokens[0]))?;
+    let channel_id = tokens[1]
+        .parse::<u64>()
+        .ok()
+        .and_then(Id::new_checked)
+        .ok_or_else(|| format!("無効なチャンネルIDです: {}", tokens[1]))?;
+
+    let mut info = ThreadInfo::new(channel_id);
+    for option in tokens.iter().skip(2).filter(|option| !option.is_empty()) {
+        apply_mapping_option(&mut info, option)?;
+    }
+    if info.forwards_to(thread_id) {
+        return Err("転送元と同じチャンネルには転送できません".to_string());
+    }
+
+    Ok((thread_id, info))
+}
+
+/// マッピングの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MappingKind {
+    /// 個別のスレッドを転送する（THREAD_MAPPING_*, `thread_id`）
+    Thread,
+    /// 親チャンネル配下の全スレッドを転送する（PARENT_MAPPING_*, `parent_id`）
+    Parent,
+}
+
+impl MappingKind {
+    /// 環境変数名の接頭辞
+    fn env_prefix(self) -> &'static str {
+        match self {
+            MappingKind::Thread => "THREAD_MAPPING_",
+            MappingKind::Parent => "PARENT_MAPPING_",
+        }
+    }
+
+    /// ログ表示用の名称
+    fn source_name(self) -> &'static str {
+        match self {
+            MappingKind::Thread => "スレッド",
+            MappingKind::Parent => "親チャンネル",
+        }
+    }
+}
+
+/// .env ファイルからマッピングを読み込む
+fn load_mappings_from_env(kind: MappingKind) -> HashMap<Id<ChannelMarker>, ThreadInfo> {
+    let mut mappings = HashMap::new();
+
+    // 環境変数をすべて走査
+    for (key, value) in env::vars() {
+        // THREAD_MAPPING_ / PARENT_MAPPING_ で始まる環境変数を処理
+        if !key.starts_with(kind.env_prefix()) {
+            continue;
+        }
+
+        match parse_thread_mapping_entry(&value) {
+            Ok((source_id, info)) => {
+                println!("マッピングを読み込みました: {} {} -> チャンネル {} (Webhook: {}, 全メッセージ転送: {}, 形式: {:?})",
+                    kind.source_name(),
+                    source_id,
+                    info.target_channel_id,
+                    info.webhook_url.is_some(),
+                    info.transfer_all_messages,
+                    info.format
+                );
+
+                // スレッド情報をマップに追加
+                mappings.insert(source_id, info);
+            }
+            Err(reason) => println!("警告: 無効なマッピング定義のため読み込みをスキップしました ({}): {}", key, reason),
+        }
+    }
+
+    mappings
+}
+
+/// 設定ファイルの `[[mappings]]` の1件からスレッド情報を作成する（`position` はエラーメッセージに表示する何番目のエントリか）
+fn entry_thread_info(entry: &MappingEntry, position: usize) -> Result<ThreadInfo, Box<dyn std::error::Error + Send + Sync>> {
+    let channel_id = Id::new_checked(entry.channel_id)
+        .ok_or_else(|| format!("設定ファイルの {} 番目のマッピング: channel_id に 0 は指定できません", position))?;
+
+    if let Some(url) = &entry.webhook_url {
+        validate_webhook_url(url)
+            .map_err(|reason| format!("設定ファイルの {} 番目のマッピング: 無効なWebhook URL: {}", position, reason))?;
+    }
+    entry
+        .sink
+        .validate()
+        .map_err(|reason| format!("設定ファイルの {} 番目のマッピング: {}", position, reason))?;
+    if let Some(address) = entry.email.iter().find(|address| parse_email_address(address).is_err()) {
+        return Err(format!("設定ファイルの {} 番目のマッピング: 無効なメールアドレス: {}", position, address).into());
+    }
+    if let Some(repo) = &entry.github {
+        parse_github_repo(repo).map_err(|reason| format!("設定ファイルの {} 番目のマッピング: {}", position, reason))?;
+    }
+    if entry.star_count == 0 {
+        return Err(format!("設定ファイルの {} 番目のマッピング: star_count には1以上の数値を指定してください: {}", position, entry.star_count).into());
+    }
+    if let Some(placeholder) = entry.template.as_deref().and_then(template::unknown_placeholder) {
+        return Err(format!("設定ファイルの {} 番目のマッピング: テンプレートに不明なプレースホルダー {{{}}} があります", position, placeholder).into());
+    }
+
+    Ok(ThreadInfo {
+        target_channel_id: channel_id,
+        transfer_all_messages: entry.all,
+        webhook_url: entry.webhook_url.clone(),
+        format: entry.format,
+        forward_delay_ms: entry.delay_ms,
+        include_bots: entry.include_bots,
+        prefix: entry.prefix.clone(),
+        label: entry.label.clone(),
+        notify_new_threads: entry.notify,
+        poll_results: entry.poll_results,
+        mentions: entry.mentions,
+        emoji: entry.emoji,
+        system_messages: entry.system_messages.clone(),
+        archive: entry.archive,
+        include_patterns: entry.include.clone(),
+        exclude_patterns: entry.exclude.clone(),
+        allowed_users: entry.allow_users.clone(),
+        blocked_users: entry.block_users.clone(),
+        required_roles: entry.roles.clone(),
+        routes: entry.routes.clone(),
+        template: entry.template.clone(),
+        transforms: entry.transforms.clone(),
+        digest_count: entry.digest,
+        digest_minutes: entry.digest_minutes,
+        star_emoji: entry.star.clone(),
+        star_threshold: entry.star_count,
+        command_prefix: entry.command_prefix.clone(),
+        sink: entry.sink.clone(),
+        feed: entry.feed,
+        github_repo: entry.github.clone(),
+        email_recipients: entry.email.clone(),
+        email_schedule: entry.email_schedule,
+        pause_on_archive: entry.pause_archived,
+        active: true,
+        paused_by_archive: false,
+    })
+}
+
+/// 設定ファイルの `[[mappings]]` からマッピングを読み込む
+fn load_mappings_from_file(
+    config: &ConfigFile,
+    kind: MappingKind,
+) -> Result<HashMap<Id<ChannelMarker>, ThreadInfo>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut mappings = HashMap::new();
+
+    for (index, entry) in config.mappings.iter().enumerate() {
+        // エラーメッセージで何番目のエントリか分かるようにする（1始まり）
+        let position = index + 1;
+
+        let (source_id, entry_kind) = match (entry.thread_id, entry.parent_id, &entry.name_pattern) {
+            (Some(thread_id), None, None) => (thread_id, MappingKind::Thread),
+            (None, Some(parent_id), None) => (parent_id, MappingKind::Parent),
+            // スレッド名のパターンのマッピングは parse_name_mappings で読み込む
+            (None, None, Some(_)) => continue,
+            _ => {
+                return Err(format!("設定ファイルの {} 番目のマッピング: thread_id・parent_id・name_pattern のどれか1つを指定してください", position).into())
+            }
+        };
+        if entry_kind != kind {
+            continue;
+        }
+
+        let source_id = Id::new_checked(source_id)
+            .ok_or_else(|| format!("設定ファイルの {} 番目のマッピング: {}IDに 0 は指定できません", position, kind.source_name()))?;
+        let info = entry_thread_info(entry, position)?;
+        if info.forwards_to(source_id) {
+            return Err(format!("設定ファイルの {} 番目のマッピング: 転送元と同じチャンネルには転送できません", position).into());
+        }
+
+        if mappings.contains_key(&source_id) {
+            return Err(format!("設定ファイルの {} 番目のマッピング: {} {} が重複しています", position, kind.source_name(), source_id).into());
+        }
+
+        println!("マッピングを読み込みました（設定ファイル）: {} {} -> チャンネル {} (Webhook: {}, 全メッセージ転送: {})",
+            kind.source_name(),
+            source_id,
+            info.target_channel_id,
+            entry.webhook_url.is_some(),
+            entry.all
+        );
+        mappings.insert(source_id, info);
+    }
+
+    Ok(mappings)
+}
+
+/// 環境変数と設定ファイルの両方からマッピングを読み込む
+fn parse_mappings(
+    config: Option<&ConfigFile>,
+    kind: MappingKind,
+) -> Result<HashMap<Id<ChannelMarker>, ThreadInfo>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut mappings = load_mappings_from_env(kind);
+
+    if let Some(config) = config {
+        for (source_id, info) in load_mappings_from_file(config, kind)? {
+            if mappings.insert(source_id, info).is_some() {
+                println!("{} {} のマッピングは設定ファイルの内容で上書きされました", kind.source_name(), source_id);
+            }
+        }
+    }
+
+    Ok(mappings)
+}
+
+/// 環境変数と設定ファイルの両方からスレッドマッピングを読み込む
+///
+/// 同じスレッドが両方に定義されている場合は設定ファイルの内容が優先されます。
+pub fn parse_thread_mappings(
+    config: Option<&ConfigFile>,
+) -> Result<HashMap<Id<ChannelMarker>, ThreadInfo>, Box<dyn std::error::Error + Send + Sync>> {
+    let thread_mappings = parse_mappings(config, MappingKind::Thread)?;
+    println!("合計 {} 個のスレッドマッピングを読み込みました", thread_mappings.len());
+    Ok(thread_mappings)
+}
+
+/// 環境変数と設定ファイルの両方から親チャンネルのマッピングを読み込む
+///
+/// 親チャンネルのマッピングは、そのチャンネル配下に作成された全てのスレッドに適用されます。
+pub fn parse_parent_mappings(
+    config: Option<&ConfigFile>,
+) -> Result<HashMap<Id<ChannelMarker>, ThreadInfo>, Box<dyn std::error::Error + Send + Sync>> {
+    let parent_mappings = parse_mappings(config, MappingKind::Parent)?;
+    if !parent_mappings.is_empty() {
+        println!("合計 {} 個の親チャンネルマッピングを読み込みました", parent_mappings.len());
+    }
+    Ok(parent_mappings)
+}
+
+/// 設定ファイルからスレッド名のパターンのマッピングを読み込む（`name_pattern` を指定した `[[mappings]]`）
+///
+/// 設定ファイルに書いた順に並べて返します。スレッド名が複数のパターンに一致する場合は最初のものを使います。
+pub fn parse_name_mappings(config: Option<&ConfigFile>) -> Result<Vec<NameMapping>, Box<dyn std::error::Error + Send + Sync>> {
+    let Some(config) = config else {
+        return Ok(Vec::new());
+    };
+
+    let mut name_mappings = Vec::new();
+    for (index, entry) in config.mappings.iter().enumerate() {
+        let Some(pattern) = &entry.name_pattern else {
+            continue;
+        };
+        let info = entry_thread_info(entry, index + 1)?;
+        println!("スレッド名のマッピングを読み込みました（設定ファイル）: /{}/ -> チャンネル {}", pattern.as_str(), info.target_channel_id);
+        name_mappings.push(NameMapping { pattern: pattern.clone(), info });
+    }
+    if !name_mappings.is_empty() {
+        println!("合計 {} 個のスレッド名のマッピングを読み込みました", name_mappings.len());
+    }
+    Ok(name_mappings)
+}
+
+/// ファイルからトークンを読み込む（Docker/Kubernetes のシークレット用）
+fn read_token_file(path: &Path) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let token = fs::read_to_string(path)
+        .map_err(|e| format!("トークンファイル {} を読み込めませんでした: {}", path.display(), e))?;
+    let token = token.trim().to_string();
+
+    if token.is_empty() {
+        return Err(format!("トークンファイル {} が空です", path.display()).into());
+    }
+    Ok(token)
+}
+
+/// コマンドを実行し、標準出力をトークンとして読み込む（シークレットマネージャー用）
+fn run_token_command(command: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let output = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .map_err(|e| format!("トークン取得コマンドを実行できませんでした: {}", e))?;
+
+    if !output.status.success() {
+        // 標準出力にはトークンが含まれる可能性があるため、エラー時は標準エラー出力のみ表示する
+        return Err(format!(
+            "トークン取得コマンドが失敗しました ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    let token = String::from_utf8(output.stdout)
+        .map_err(|_| "トークン取得コマンドの出力がUTF-8ではありません")?
+        .trim()
+        .to_string();
+
+    if token.is_empty() {
+        return Err("トークン取得コマンドの出力が空です".into());
+    }
+    Ok(token)
+}
+
+/// Discord Bot Tokenを取得する
+///
+/// 以下の順に確認し、最初に見つかったものを使用します。
+/// 1. 設定ファイルの `bot.token` / `bot.token_file` / `bot.token_command`
+/// 2. 環境変数 DISCORD_TOKEN / DISCORD_TOKEN_FILE / DISCORD_TOKEN_COMMAND
+pub fn get_discord_token(config: Option<&ConfigFile>) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(bot) = config.map(|c| &c.bot) {
+        if let Some(token) = &bot.token {
+            return Ok(token.clone());
+        }
+        if let Some(path) = &bot.token_file {
+            return read_token_file(path);
+        }
+        if let Some(command) = &bot.token_command {
+            return run_token_command(command);
+        }
+    }
+
+    if let Ok(token) = env::var("DISCORD_TOKEN") {
+        return Ok(token);
+    }
+    if let Ok(path) = env::var("DISCORD_TOKEN_FILE") {
+        return read_token_file(Path::new(&path));
+    }
+    if let Ok(command) = env::var("DISCORD_TOKEN_COMMAND") {
+        return run_token_command(&command);
+    }
+
+    Err("Botトークンが設定されていません。環境変数 DISCORD_TOKEN / DISCORD_TOKEN_FILE / DISCORD_TOKEN_COMMAND か、設定ファイルの bot.token / bot.token_file / bot.token_command を設定してください".into())
+}
+
+/// マッピング保存用データベースのパスを取得する（設定ファイルの `bot.database_path` が優先）
+pub fn database_path(config: Option<&ConfigFile>) -> PathBuf {
+    if let Some(path) = config.and_then(|c| c.bot.database_path.clone()) {
+        return path;
+    }
+
+    env::var("DATABASE_PATH")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_DATABASE_PATH))
+}
+
+/// 転送したメッセージの添付ファイルの保存先を取得する（設定ファイルの `bot.attachment_archive` が優先）
+pub fn attachment_archive(config: Option<&ConfigFile>) -> Option<String> {
+    config
+        .and_then(|c| c.bot.attachment_archive.clone())
+        .or_else(|| env::var("ATTACHMENT_ARCHIVE").ok())
+        .filter(|location| !location.trim().is_empty())
+}
+
+/// メッセージの履歴を保存する範囲を取得する（設定ファイルの `bot.message_history` が優先）
+pub fn message_history(config: Option<&ConfigFile>) -> HistoryMode {
+    let value = match config.and_then(|c| c.bot.message_history.clone()) {
+        Some(value) => value,
+        None => match env::var("MESSAGE_HISTORY") {
+            Ok(value) if !value.trim().is_empty() => value,
+            _ => return HistoryMode::Off,
+        },
+    };
+
+    HistoryMode::parse(value.trim()).unwrap_or_else(|| {
+        println!("警告: メッセージの履歴の設定が無効です: {}。off、forwarded、all のいずれかを指定してください。履歴は保存しません", value);
+        HistoryMode::Off
+    })
+}
+
+/// メッセージの履歴の保存先を取得する（設定ファイルの `bot.history_database_url` が優先、どちらも無ければマッピングと同じデータベース）
+pub fn history_database_url(config: Option<&ConfigFile>) -> String {
+    config
+        .and_then(|c| c.bot.history_database_url.clone())
+        .or_else(|| env::var("HISTORY_DATABASE_URL").ok())
+        .filter(|url| !url.trim().is_empty())
+        .unwrap_or_else(|| database_path(config).display().to_string())
+}
+
+/// 転送したメッセージのイベントの送信先を取得する（設定ファイルの `bot.event_publisher` が優先）
+pub fn event_publisher(config: Option<&ConfigFile>) -> Option<String> {
+    config
+        .and_then(|c| c.bot.event_publisher.clone())
+        .or_else(|| env::var("EVENT_PUBLISHER").ok())
+        .filter(|url| !url.trim().is_empty())
+}
+
+/// イベントを送信するサブジェクトの接頭辞（Kafka のトピック）を取得する（設定ファイルの `bot.event_subject` が優先）
+pub fn event_subject(config: Option<&ConfigFile>) -> String {
+    config
+        .and_then(|c| c.bot.event_subject.clone())
+        .or_else(|| env::var("EVENT_SUBJECT").ok())
+        .map(|subject| subject.trim().trim_end_matches('.').to_string())
+        .filter(|subject| !subject.is_empty())
+        .unwrap_or_else(|| publish::DEFAULT_SUBJECT.to_string())
+}
+
+/// まとめのメールの送信元のアドレスを取得する（設定ファイルの `bot.email_from` が優先）
+pub fn email_from(config: Option<&ConfigFile>) -> Option<String> {
+    config
+        .and_then(|c| c.bot.email_from.clone())
+        .or_else(|| env::var("EMAIL_FROM").ok())
+        .filter(|address| !address.trim().is_empty())
+}
+
+/// まとめのメールを送信する時刻（UTCの時）を取得する（設定ファイルの `bot.email_hour` が優先）
+pub fn email_hour(config: Option<&ConfigFile>) -> u32 {
+    let value = match config.and_then(|c| c.bot.email_hour) {
+        Some(hour) => Some(hour),
+        None => match env::var("EMAIL_HOUR") {
+            Ok(value) if !value.trim().is_empty() => value.trim().parse().ok().or(Some(24)),
+            _ => None,
+        },
+    };
+    match value {
+        Some(hour) if hour < 24 => hour,
+        Some(_) => {
+            println!("警告: まとめのメールを送信する時刻には 0〜23 を指定してください。0 時（UTC）に送信します");
+            0
+        }
+        None => 0,
+    }
+}
+
+/// JSONLアーカイブの保存先ディレクトリを取得する（設定ファイルの `bot.archive_dir` が優先）
+pub fn archive_dir(config: Option<&ConfigFile>) -> PathBuf {
+    if let Some(dir) = config.and_then(|c| c.bot.archive_dir.clone()) {
+        return dir;
+    }
+
+    env::var("ARCHIVE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_ARCHIVE_DIR))
+}
+
+/// 転送しないコマンドのプレフィックス一覧を取得する（設定ファイルの `bot.command_prefixes` が優先）
+///
+/// 環境変数 COMMAND_PREFIXES ではカンマ区切りで指定します。空の場合はコマンドも転送します。
+pub fn command_prefixes(config: Option<&ConfigFile>) -> Vec<String> {
+    let prefixes = match config.and_then(|c| c.bot.command_prefixes.clone()) {
+        Some(prefixes) => prefixes,
+        None => match env::var("COMMAND_PREFIXES") {
+            Ok(value) => value.split(',').map(|prefix| prefix.trim().to_string()).collect(),
+            Err(_) => vec![DEFAULT_COMMAND_PREFIX.to_string()],
+        },
+    };
+
+    prefixes.into_iter().filter(|prefix| !prefix.is_empty()).collect()
+}
+
+/// ボットのコマンドのプレフィックスを取得する（設定ファイルの `bot.command_prefix` が優先）
+pub fn command_prefix(config: Option<&ConfigFile>) -> String {
+    let prefix = match config.and_then(|c| c.bot.command_prefix.clone()) {
+        Some(prefix) => prefix,
+        None => env::var("COMMAND_PREFIX").unwrap_or_default(),
+    };
+
+    match prefix.trim() {
+        "" => DEFAULT_COMMAND_PREFIX.to_string(),
+        prefix => prefix.to_string(),
+    }
+}
+
+/// サーバーごとのコマンドのプレフィックスを取得する
+///
+/// 環境変数 GUILD_COMMAND_PREFIXES（`サーバーID:プレフィックス` をカンマ区切り）と設定ファイルの `bot.guild_command_prefixes` を読み込みます。
+/// 同じサーバーを両方に指定した場合は設定ファイルが優先されます。
+pub fn guild_command_prefixes(config: Option<&ConfigFile>) -> HashMap<Id<GuildMarker>, String> {
+    let mut entries = Vec::new();
+    if let Ok(value) = env::var("GUILD_COMMAND_PREFIXES") {
+        for part in value.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            match part.split_once(':') {
+                Some((guild_id, prefix)) => entries.push((guild_id.trim().to_string(), prefix.trim().to_string())),
+                None => println!("警告: GUILD_COMMAND_PREFIXES の形式が正しくありません（サーバーID:プレフィックス）: {}", part),
+            }
+        }
+    }
+    if let Some(config) = config {
+        entries.extend(config.bot.guild_command_prefixes.iter().map(|(id, prefix)| (id.clone(), prefix.clone())));
+    }
+
+    let mut prefixes = HashMap::new();
+    for (guild_id, prefix) in entries {
+        match (guild_id.parse::<u64>().ok().and_then(Id::new_checked), prefix.is_empty()) {
+            (Some(guild_id), false) => {
+                prefixes.insert(guild_id, prefix);
+            }
+            _ => println!("警告: サーバーごとのコマンドのプレフィックスが正しくありません: {} = {:?}", guild_id, prefix),
+        }
+    }
+    prefixes
+}
+
+/// 障害を通知する管理用チャンネルを取得する（設定ファイルの `bot.admin_channel_id` が優先）
+pub fn admin_channel_id(config: Option<&ConfigFile>) -> Option<Id<ChannelMarker>> {
+    channel_setting(config.and_then(|c| c.bot.admin_channel_id), "ADMIN_CHANNEL_ID")
+}
+
+/// 管理用のコマンドの実行記録を投稿する監査ログチャンネルを取得する（設定ファイルの `bot.audit_channel_id` が優先）
+pub fn audit_channel_id(config: Option<&ConfigFile>) -> Option<Id<ChannelMarker>> {
+    channel_setting(config.and_then(|c| c.bot.audit_channel_id), "AUDIT_CHANNEL_ID")
+}
+
+/// 稼働状況を定期的に投稿する運用チャンネルを取得する（設定ファイルの `bot.heartbeat_channel_id` が優先）
+pub fn heartbeat_channel_id(config: Option<&ConfigFile>) -> Option<Id<ChannelMarker>> {
+    channel_setting(config.and_then(|c| c.bot.heartbeat_channel_id), "HEARTBEAT_CHANNEL_ID")
+}
+
+/// 稼働状況を投稿する間隔（分）を取得する（設定ファイルの `bot.heartbeat_minutes` が優先）
+pub fn heartbeat_minutes(config: Option<&ConfigFile>) -> u64 {
+    positive_setting(config.and_then(|c| c.bot.heartbeat_minutes), "HEARTBEAT_MINUTES", 60)
+}
+
+/// 再接続を通知する回数を取得する（設定ファイルの `bot.reconnect_alert_count` が優先）
+pub fn reconnect_alert_count(config: Option<&ConfigFile>) -> u64 {
+    positive_setting(config.and_then(|c| c.bot.reconnect_alert_count), "RECONNECT_ALERT_COUNT", 5)
+}
+
+/// 再接続の回数を数える期間（分）を取得する（設定ファイルの `bot.reconnect_alert_minutes` が優先）
+pub fn reconnect_alert_minutes(config: Option<&ConfigFile>) -> u64 {
+    positive_setting(config.and_then(|c| c.bot.reconnect_alert_minutes), "RECONNECT_ALERT_MINUTES", 10)
+}
+
+/// 設定ファイルの値、無ければ環境変数から1以上の数値を読み込む（無効な場合はデフォルト）
+fn positive_setting(configured: Option<u64>, env_name: &str, default: u64) -> u64 {
+    let value = match configured {
+        Some(value) => value,
+        None => match env::var(env_name) {
+            Ok(value) if !value.trim().is_empty() => match value.trim().parse() {
+                Ok(value) => value,
+                Err(_) => {
+                    println!("警告: {} が無効です: {}。{} を使用します", env_name, value, default);
+                    return default;
+                }
+            },
+            _ => return default,
+        },
+    };
+
+    if value == 0 {
+        println!("警告: {} に 0 は指定できません。{} を使用します", env_name, default);
+        return default;
+    }
+    value
+}
+
+/// 設定ファイルの値、無ければ環境変数からチャンネルIDを読み込む
+fn channel_setting(configured: Option<u64>, env_name: &str) -> Option<Id<ChannelMarker>> {
+    let id = match configured {
+        Some(id) => id,
+        None => match env::var(env_name) {
+            Ok(value) if !value.trim().is_empty() => match value.trim().parse() {
+                Ok(id) => id,
+                Err(_) => {
+                    println!("警告: {} が無効です: {}", env_name, value);
+                    return None;
+                }
+            },
+            _ => return None,
+        },
+    };
+
+    let channel_id = Id::new_checked(id);
+    if channel_id.is_none() {
+        println!("警告: {} に 0 は指定できません", env_name);
+    }
+    channel_id
+}
+
+/// ゲートウェイに接続するシャードの数を取得する（設定ファイルの `bot.shard_count` が優先）
+///
+/// 指定しない場合は None を返し、Discordの推奨する数で接続します。
+pub fn shard_count(config: Option<&ConfigFile>) -> Option<u64> {
+    let count = match config.and_then(|c| c.bot.shard_count) {
+        Some(count) => count,
+        None => match env::var("SHARD_COUNT") {
+            Ok(value) if !value.trim().is_empty() => match value.trim().parse() {
+                Ok(count) => count,
+                Err(_) => {
+                    println!("警告: SHARD_COUNT が無効です: {}。Discordの推奨する数で接続します", value);
+                    return None;
+                }
+            },
+            _ => return None,
+        },
+    };
+
+    if count == 0 {
+        println!("警告: シャードの数に 0 は指定できません。Discordの推奨する数で接続します");
+        return None;
+    }
+    Some(count)
+}
+
+/// 並行して処理するイベントの数のデフォルト
+const DEFAULT_EVENT_CONCURRENCY: usize = 8;
+
+/// 並行して処理するイベントの数を取得する（設定ファイルの `bot.event_concurrency` が優先）
+///
+/// 同じチャンネルのイベントは並行して処理せず、受信した順に処理します。
+pub fn event_concurrency(config: Option<&ConfigFile>) -> usize {
+    let count = match config.and_then(|c| c.bot.event_concurrency) {
+        Some(count) => count,
+        None => match env::var("EVENT_CONCURRENCY") {
+            Ok(value) if !value.trim().is_empty() => match value.trim().parse() {
+                Ok(count) => count,
+                Err(_) => {
+                    println!("警告: EVENT_CONCURRENCY が無効です: {}。{} を使用します", value, DEFAULT_EVENT_CONCURRENCY);
+                    return DEFAULT_EVENT_CONCURRENCY;
+                }
+            },
+            _ => return DEFAULT_EVENT_CONCURRENCY,
+        },
+    };
+
+    if count == 0 {
+        println!("警告: 並行して処理するイベントの数に 0 は指定できません。{} を使用します", DEFAULT_EVENT_CONCURRENCY);
+        return DEFAULT_EVENT_CONCURRENCY;
+    }
+    count
+}
+
+/// 死活監視用のHTTPサーバーの待ち受けアドレスを取得する（設定ファイルの `bot.health_addr` が優先）
+///
+/// 指定しない場合は None を返し、HTTPサーバーを起動しません。
+pub fn health_addr(config: Option<&ConfigFile>) -> Option<SocketAddr> {
+    let value = match config.and_then(|c| c.bot.health_addr.clone()) {
+        Some(value) => value,
+        None => match env::var("HEALTH_ADDR") {
+            Ok(value) if !value.trim().is_empty() => value,
+            _ => return None,
+        },
+    };
+
+    match value.trim().parse() {
+        Ok(addr) => Some(addr),
+        Err(_) => {
+            println!("警告: 死活監視のアドレスが無効です: {}。HTTPサーバーは起動しません（例: 0.0.0.0:8080）", value);
+            None
+        }
+    }
+}
+
+/// 外部からの投稿の認証に使うトークンを取得する（設定ファイルの `bot.inbound_token` が優先）
+///
+/// 指定しない場合は None を返し、外部からの投稿を受け付けません（HTTPサーバーを起動している場合のみ使用）。
+pub fn inbound_token(config: Option<&ConfigFile>) -> Option<String> {
+    config
+        .and_then(|c| c.bot.inbound_token.clone())
+        .or_else(|| env::var("INBOUND_TOKEN").ok())
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+/// 管理画面と管理用の API の認証に使うトークンを取得する（設定ファイルの `bot.dashboard_token` が優先）
+///
+/// 指定しない場合は None を返し、管理画面を公開しません（HTTPサーバーを起動している場合のみ使用）。
+pub fn dashboard_token(config: Option<&ConfigFile>) -> Option<String> {
+    config
+        .and_then(|c| c.bot.dashboard_token.clone())
+        .or_else(|| env::var("DASHBOARD_TOKEN").ok())
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+/// エラー報告に使用する Sentry の DSN を取得する（設定ファイルの `bot.sentry_dsn` が優先）
+pub fn sentry_dsn(config: Option<&ConfigFile>) -> Option<String> {
+    config
+        .and_then(|c| c.bot.sentry_dsn.clone())
+        .or_else(|| env::var("SENTRY_DSN").ok())
+        .filter(|dsn| !dsn.trim().is_empty())
+}
+
+/// `all` を指定したマッピングの過去メッセージを、起動時とマッピングの追加時に自動で転送するかどうか（設定ファイルの `bot.auto_backfill` が優先）
+pub fn auto_backfill(config: Option<&ConfigFile>) -> bool {
+    if let Some(enabled) = config.and_then(|c| c.bot.auto_backfill) {
+        return enabled;
+    }
+
+    match env::var("AUTO_BACKFILL") {
+        Ok(value) => parse_bool_option("AUTO_BACKFILL", value.trim()).unwrap_or_else(|e| {
+            println!("警告: {}。自動で転送します", e);
+            true
+        }),
+        Err(_) => true,
+    }
+}
+
+/// 転送したメッセージの対応をデータベースに保存するかどうか（設定ファイルの `bot.persist_message_links` が優先）
+pub fn persist_message_links(config: Option<&ConfigFile>) -> bool {
+    if let Some(persist) = config.and_then(|c| c.bot.persist_message_links) {
+        return persist;
+    }
+
+    match env::var("PERSIST_MESSAGE_LINKS") {
+        Ok(value) => parse_bool_option("PERSIST_MESSAGE_LINKS", value.trim()).unwrap_or_else(|e| {
+            println!("警告: {}。データベースに保存します", e);
+            true
+        }),
+        Err(_) => true,
+    }
+}
@@ -0,0 +1,143 @@
+use serde_json::json;
+use std::sync::Arc;
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use crate::audit::{AuditEntry, AuditOutcome};
+use crate::backfill::BackfillRange;
+use crate::forwarding::spawn_backfill;
+use crate::health::{json_response, Request, Response};
+use crate::inbound::token_matches;
+use crate::state::BotState;
+
+/// 管理画面のHTML（トークンを入力すると、管理用の API からマッピングと転送の状況を読み込む）
+const PAGE: &str = include_str!("dashboard.html");
+
+/// HTMLの応答の Content-Type
+const HTML: &str = "text/html; charset=utf-8";
+
+/// 管理画面（`GET /dashboard`）を返す
+///
+/// ページ自体には情報を含めず、表示する内容は全て認証の必要な管理用の API から読み込みます。
+pub fn page() -> Response {
+    ("200 OK", HTML, PAGE.to_string())
+}
+
+/// マッピングの一覧と転送の集計（`GET /api/mappings`）
+async fn mappings(state: &BotState) -> Response {
+    let stats = match state.mapping_stats() {
+        Ok(stats) => stats,
+        Err(e) => {
+            println!("管理画面のマッピングの集計を読み込めませんでした: {}", e);
+            return json_response("500 Internal Server Error", json!({ "error": "集計を読み込めませんでした" }));
+        }
+    };
+
+    let thread_mappings = state.thread_mappings.load();
+    let mut mappings = Vec::with_capacity(thread_mappings.len());
+    for (thread_id, thread_info) in thread_mappings.iter() {
+        let stats = stats.iter().find(|stats| stats.thread_id == *thread_id);
+        mappings.push(json!({
+            "thread_id": thread_id,
+            "target_channel_id": thread_info.target_channel_id,
+            "label": thread_info.label,
+            "active": thread_info.active,
+            "summary": thread_info.summary(),
+            "backfill_running": state.backfills.is_running(*thread_id).await,
+            "forwarded": stats.map_or(0, |stats| stats.forwarded),
+            "failures": stats.map_or(0, |stats| stats.failures),
+            "bytes": stats.map_or(0, |stats| stats.bytes),
+            "attachments": stats.map_or(0, |stats| stats.attachments),
+            "last_forwarded_at": stats.and_then(|stats| stats.last_forwarded_at.clone()),
+        }));
+    }
+    mappings.sort_by(|a, b| a["label"].as_str().cmp(&b["label"].as_str()).then(a["thread_id"].as_str().cmp(&b["thread_id"].as_str())));
+    json_response("200 OK", json!({ "mappings": mappings }))
+}
+
+/// 起動後の転送とエラーの数・直近の転送（`GET /api/activity`）
+fn activity(state: &BotState) -> Response {
+    let (connected, total) = state.stats.connected_shards();
+    json_response(
+        "200 OK",
+        json!({
+            "uptime_secs": state.stats.uptime().as_secs(),
+            "forwarded": state.stats.forwarded_count(),
+            "errors": state.stats.error_count(),
+            "errors_last_hour": state.stats.recent_error_count(),
+            "shards": total,
+            "connected_shards": connected,
+            "reload_error": state.stats.reload_error(),
+            "recent_forwards": state.stats.recent_forwards(),
+        }),
+    )
+}
+
+/// マッピングへの操作（`POST /api/mappings/<スレッドID>/pause`・`resume`・`backfill`）
+async fn mapping_action(state: &Arc<BotState>, thread_id: &str, action: &str) -> Response {
+    let Some(thread_id) = thread_id.parse().ok().and_then(Id::<ChannelMarker>::new_checked) else {
+        return json_response("404 Not Found", json!({ "error": "スレッドIDが正しくありません" }));
+    };
+    let not_found = || json_response("404 Not Found", json!({ "error": format!("スレッド {} のマッピングがありません", thread_id) }));
+
+    let command = format!("POST /api/mappings/{}/{}", thread_id, action);
+    match action {
+        "pause" | "resume" => {
+            let active = action == "resume";
+            let result = state.set_mapping_active(thread_id, active).await;
+            // マッピングが無い場合は何も変更していないため記録しない
+            if !matches!(result, Ok(None)) {
+                state.record_command(AuditEntry::dashboard(thread_id, &command, AuditOutcome::from_result(&result))).await;
+            }
+            match result {
+                Ok(Some(info)) => {
+                    let verb = if active { "再開" } else { "一時停止" };
+                    println!("管理画面から転送を{}しました: スレッド {} -> チャンネル {}", verb, thread_id, info.target_channel_id);
+                    json_response("200 OK", json!({ "thread_id": thread_id, "active": info.active }))
+                }
+                Ok(None) => not_found(),
+                Err(e) => json_response("500 Internal Server Error", json!({ "error": format!("マッピングを保存できませんでした: {}", e) })),
+            }
+        }
+        "backfill" => {
+            let Some(thread_info) = state.resolve_thread_info(thread_id).await else {
+                return not_found();
+            };
+            if !thread_info.active {
+                return json_response("409 Conflict", json!({ "error": "このスレッドの転送は一時停止中です。再開してから実行してください" }));
+            }
+            if state.backfills.is_running(thread_id).await {
+                return json_response("409 Conflict", json!({ "error": "このスレッドの過去メッセージは既に転送中です" }));
+            }
+            println!("管理画面から過去メッセージの転送を開始しました: スレッド {}", thread_id);
+            state.record_command(AuditEntry::dashboard(thread_id, &command, AuditOutcome::Succeeded)).await;
+            spawn_backfill(state, thread_id, thread_info, BackfillRange::default());
+            json_response("202 Accepted", json!({ "thread_id": thread_id, "backfill_running": true }))
+        }
+        _ => json_response("404 Not Found", json!({ "error": "not found" })),
+    }
+}
+
+/// 管理用の API（`/api/...`）を処理する
+///
+/// 管理画面から `Authorization: Bearer <トークン>` で呼び出します。
+/// マッピングの一覧・転送の状況の取得と、マッピングの一時停止・再開・過去メッセージの転送の開始ができます。
+pub async fn handle_api(state: &Arc<BotState>, request: &Request, endpoint: &str, token: &str) -> Response {
+    let authorized = request
+        .header("Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| token_matches(given.trim(), token));
+    if !authorized {
+        return json_response("401 Unauthorized", json!({ "error": "トークンが正しくありません" }));
+    }
+
+    let segments: Vec<&str> = endpoint.split('/').collect();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["mappings"]) => mappings(state).await,
+        ("GET", ["activity"]) => activity(state),
+        ("POST", ["mappings", thread_id, action]) => mapping_action(state, thread_id, action).await,
+        (_, ["mappings"] | ["activity"] | ["mappings", _, _]) => {
+            json_response("405 Method Not Allowed", json!({ "error": "method not allowed" }))
+        }
+        _ => json_response("404 Not Found", json!({ "error": "not found" })),
+    }
+}
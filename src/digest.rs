@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{interval, Duration, Instant};
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use crate::state::{BotState, ThreadInfo};
+use crate::supervisor;
+
+/// 時間経過によるまとめ投稿を確認する間隔
+const DIGEST_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// まとめ投稿1件あたりの最大文字数（Discordの制限は2000文字）
+const MAX_DIGEST_CHARS: usize = 1900;
+
+/// まとめ投稿のバッファのキー（転送元スレッドID, 転送先チャンネルID）
+type DigestKey = (Id<ChannelMarker>, Id<ChannelMarker>);
+
+/// まとめ投稿を待っているメッセージ
+struct DigestBuffer {
+    /// 投稿に使うマッピングの設定（最初のメッセージを受け取った時点のもの）
+    thread_info: ThreadInfo,
+    /// 1メッセージ1行に整形した本文
+    lines: Vec<String>,
+    /// 最初のメッセージを受け取った時刻
+    started_at: Instant,
+}
+
+/// `digest` を設定したマッピングのメッセージをまとめて投稿するためのキュー
+#[derive(Default)]
+pub struct DigestQueue {
+    buffers: Mutex<HashMap<DigestKey, DigestBuffer>>,
+}
+
+impl DigestQueue {
+    /// 空のキューを作成する
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// メッセージを追加し、件数が `digest` に達した場合はまとめ投稿する
+    pub async fn push(
+        &self,
+        state: &BotState,
+        thread_id: Id<ChannelMarker>,
+        thread_info: &ThreadInfo,
+        line: String,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let key = (thread_id, thread_info.target_channel_id);
+        let ready = {
+            let mut buffers = self.buffers.lock().await;
+            let buffer = buffers.entry(key).or_insert_with(|| DigestBuffer {
+                thread_info: thread_info.clone(),
+                lines: Vec::new(),
+                started_at: Instant::now(),
+            });
+            buffer.lines.push(line);
+
+            if thread_info.digest_count > 0 && buffer.lines.len() >= thread_info.digest_count {
+                buffers.remove(&key)
+            } else {
+                None
+            }
+        };
+
+        match ready {
+            Some(buffer) => post_digest(state, thread_id, buffer).await,
+            None => Ok(()),
+        }
+    }
+
+    /// 全てのバッファを取り出す（終了時に投稿するため）
+    async fn take_all(&self) -> Vec<(DigestKey, DigestBuffer)> {
+        self.buffers.lock().await.drain().collect()
+    }
+
+    /// `digest_minutes` を過ぎたバッファを取り出す
+    async fn take_expired(&self) -> Vec<(DigestKey, DigestBuffer)> {
+        let mut buffers = self.buffers.lock().await;
+        let expired: Vec<_> = buffers
+            .iter()
+            .filter(|(_, buffer)| {
+                let minutes = buffer.thread_info.digest_minutes;
+                minutes > 0 && buffer.started_at.elapsed() >= Duration::from_secs(minutes * 60)
+            })
+            .map(|(key, _)| *key)
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|key| buffers.remove(&key).map(|buffer| (key, buffer)))
+            .collect()
+    }
+}
+
+/// 行をDiscordの文字数制限に収まるように複数の投稿に分ける
+fn split_into_posts(header: &str, lines: &[String]) -> Vec<String> {
+    let mut posts = Vec::new();
+    let mut current = header.to_string();
+
+    for line in lines {
+        // 1行が長すぎる場合は切り詰める
+        let line: String = if line.chars().count() > MAX_DIGEST_CHARS - header.chars().count() {
+            let mut truncated: String = line.chars().take(MAX_DIGEST_CHARS - header.chars().count() - 1).collect();
+            truncated.push('…');
+            truncated
+        } else {
+            line.clone()
+        };
+
+        if current.chars().count() + line.chars().count() + 1 > MAX_DIGEST_CHARS {
+            posts.push(current);
+            current = header.to_string();
+        }
+        current.push('\n');
+        current.push_str(&line);
+    }
+
+    posts.push(current);
+    posts
+}
+
+/// まとめたメッセージを転送先に投稿する
+async fn post_digest(
+    state: &BotState,
+    thread_id: Id<ChannelMarker>,
+    buffer: DigestBuffer,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let thread_info = &buffer.thread_info;
+    let source = state.channel_link(thread_id, thread_info.target_channel_id).await;
+    let header = match &thread_info.label {
+        Some(label) => format!("📰 `[{}]` {} のまとめ（{}件）", label, source, buffer.lines.len()),
+        None => format!("📰 {} のまとめ（{}件）", source, buffer.lines.len()),
+    };
+    let allowed_mentions = thread_info.mentions.allowed_mentions();
+
+    for post in split_into_posts(&header, &buffer.lines) {
+        state
+            .http
+            .create_message(thread_info.target_channel_id)
+            .content(&post)?
+            .allowed_mentions(Some(&allowed_mentions))
+            .await?;
+    }
+
+    state.stats.record_forwarded(buffer.lines.len() as u64);
+    let bytes = buffer.lines.iter().map(|line| line.len() as u64).sum();
+    state.record_mapping_forwards(thread_id, buffer.lines.len() as u64, bytes, 0);
+    println!("スレッド {} のメッセージ {} 件をまとめて転送しました", thread_id, buffer.lines.len());
+    Ok(())
+}
+
+/// `digest_minutes` を過ぎたまとめ投稿を定期的に送信するタスクを起動する
+pub fn spawn_digest_flusher(state: Arc<BotState>) {
+    let flusher_state = Arc::clone(&state);
+    supervisor::supervise(&state, "まとめ投稿の送信", move || {
+        let state = Arc::clone(&flusher_state);
+        async move {
+            let mut ticker = interval(DIGEST_CHECK_INTERVAL);
+
+            loop {
+                ticker.tick().await;
+
+                for ((thread_id, _), buffer) in state.digests.take_expired().await {
+                    if let Err(e) = post_digest(&state, thread_id, buffer).await {
+                        eprintln!("スレッド {} のまとめ投稿に失敗しました: {}", thread_id, e);
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// まとめ投稿を待っているメッセージを全て投稿する（終了時に使用）
+pub async fn flush_all(state: &BotState) {
+    for ((thread_id, _), buffer) in state.digests.take_all().await {
+        if let Err(e) = post_digest(state, thread_id, buffer).await {
+            eprintln!("スレッド {} のまとめ投稿に失敗しました: {}", thread_id, e);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use twilight_gateway::{Event, EventType};
+
+use crate::state::BotState;
+
+/// ゲートウェイのイベントを処理する処理
+///
+/// 新しいイベントを使う機能を追加する場合は、この trait を実装して [`EventDispatcher::register`] で登録します。
+#[async_trait]
+pub trait EventHandler: Send + Sync {
+    /// 処理の名前（ログ用）
+    fn name(&self) -> &'static str;
+
+    /// 処理するイベントの種類
+    fn event_types(&self) -> &'static [EventType];
+
+    /// イベントを処理する
+    async fn handle(&self, event: Event, state: Arc<BotState>) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// 受信したイベントを、その種類を処理する登録済みの処理に振り分ける
+#[derive(Default)]
+pub struct EventDispatcher {
+    /// 登録順の処理の一覧
+    handlers: Vec<Box<dyn EventHandler>>,
+}
+
+impl EventDispatcher {
+    /// 処理を登録していない状態で作成する
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ボットの標準の処理（コマンド・転送・スレッドの管理など）を登録した状態で作成する
+    pub fn with_default_handlers() -> Self {
+        let mut dispatcher = Self::new();
+        for handler in crate::events::default_handlers() {
+            dispatcher.register(handler);
+        }
+        dispatcher
+    }
+
+    /// 処理を登録する（同じ種類のイベントは登録した順に処理する）
+    pub fn register(&mut self, handler: Box<dyn EventHandler>) {
+        self.handlers.push(handler);
+    }
+
+    /// イベントを振り分けて処理する
+    ///
+    /// いずれかの処理でエラーが発生しても残りの処理は続け、最初のエラーを返します。
+    pub async fn dispatch(&self, event: Event, state: Arc<BotState>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let kind = event.kind();
+        let handlers: Vec<&dyn EventHandler> = self
+            .handlers
+            .iter()
+            .map(Box::as_ref)
+            .filter(|handler| handler.event_types().contains(&kind))
+            .collect();
+
+        let mut first_error = None;
+        let mut event = Some(event);
+        for (index, handler) in handlers.iter().enumerate() {
+            // 最後の処理にはイベントをそのまま渡し、それ以外には複製を渡す
+            let event = if index + 1 == handlers.len() {
+                event.take()
+            } else {
+                event.clone()
+            };
+            let Some(event) = event else {
+                break;
+            };
+
+            if let Err(e) = handler.handle(event, Arc::clone(&state)).await {
+                if first_error.is_some() {
+                    eprintln!("{} の処理中にエラーが発生しました: {}", handler.name(), e);
+                } else {
+                    first_error = Some(e);
+                }
+            }
+        }
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use twilight_model::channel::message::embed::{EmbedAuthor, EmbedField};
+use twilight_model::channel::message::Embed;
+use twilight_model::channel::{Channel, Message};
+use twilight_model::id::{marker::UserMarker, Id};
+use twilight_model::user::User;
+
+use crate::state::{BotState, ThreadInfo};
+use crate::{emoji, poll};
+use crate::forwarding::attachment_line;
+
+/// ユーザーのアバターURLを取得する
+pub fn get_user_avatar_url(user_id: Id<UserMarker>, avatar_hash: Option<&str>) -> String {
+    if let Some(hash) = avatar_hash {
+        // ユーザーがアバターを設定している場合は、そのアバターのURLを返す
+        let url = format!("https://cdn.discordapp.com/avatars/{}/{}.webp?size=128", user_id, hash);
+        println!("🖼️ アバターURL生成（カスタム）: {}", url);
+        url
+    } else {
+        // アバターが設定されていない場合は、デフォルトのアバターURLを返す
+        let default_avatar = user_id.get() % 5;
+        let url = format!("https://cdn.discordapp.com/embed/avatars/{}.png", default_avatar);
+        println!("🖼️ アバターURL生成（デフォルト）: {}", url);
+        url
+    }
+}
+
+/// 本文の `||ネタバレ||` の中にリンクが含まれているかどうか
+fn contains_spoilered_link(content: &str) -> bool {
+    let segments: Vec<&str> = content.split("||").collect();
+    // 奇数番目の区間が `||` で囲まれた部分（閉じられていない最後の区間は除く）
+    segments
+        .iter()
+        .enumerate()
+        .any(|(index, segment)| index % 2 == 1 && index + 1 < segments.len() && segment.contains("://"))
+}
+
+/// Webhookの送信者名の最大文字数（Discordの制限）
+const WEBHOOK_USERNAME_MAX_CHARS: usize = 80;
+
+/// Webhookで表示する送信者名を作成する（ラベルがある場合は名前の後ろに付ける）
+pub fn webhook_display_name(author_name: &str, label: Option<&str>) -> String {
+    let name = match label {
+        Some(label) => format!("{} [{}]", author_name, label),
+        None => author_name.to_string(),
+    };
+    name.chars().take(WEBHOOK_USERNAME_MAX_CHARS).collect()
+}
+
+/// 埋め込み形式の転送メッセージを作成する
+pub fn build_forward_embed(message: &Message, content: &str, avatar_url: &str, label: Option<&str>) -> Embed {
+    let mut fields = Vec::new();
+
+    // 転送元のラベルをフィールドとして表示
+    if let Some(label) = label {
+        fields.push(EmbedField {
+            inline: true,
+            name: "転送元".to_string(),
+            value: label.to_string(),
+        });
+    }
+
+    // 添付ファイルはフィールドとしてリンクを表示
+    if !message.attachments.is_empty() {
+        let links: Vec<String> = message.attachments.iter().map(attachment_line).collect();
+        fields.push(EmbedField {
+            inline: false,
+            name: "添付ファイル".to_string(),
+            value: links.join("\n"),
+        });
+    }
+
+    Embed {
+        author: Some(EmbedAuthor {
+            icon_url: Some(avatar_url.to_string()),
+            name: message.author.name.clone(),
+            proxy_icon_url: None,
+            url: None,
+        }),
+        color: None,
+        description: if content.is_empty() { None } else { Some(content.to_string()) },
+        fields,
+        footer: None,
+        image: None,
+        kind: "rich".to_string(),
+        provider: None,
+        thumbnail: None,
+        timestamp: Some(message.timestamp),
+        title: None,
+        url: None,
+        video: None,
+    }
+}
+
+/// 親チャンネル配下に作成されたスレッドを転送先で知らせる埋め込みを作成する（`notify=true` のマッピングで使用）
+///
+/// タイトルにスレッド名とスレッドへのリンク、作成者にスレッドを作成したユーザーを表示します。
+pub fn build_thread_announcement_embed(
+    thread: &Channel,
+    noun: &str,
+    creator: Option<&User>,
+    url: Option<String>,
+    parent: &str,
+    label: Option<&str>,
+) -> Embed {
+    let mut fields = vec![EmbedField {
+        inline: true,
+        name: "親チャンネル".to_string(),
+        value: parent.to_string(),
+    }];
+    if let Some(label) = label {
+        fields.push(EmbedField {
+            inline: true,
+            name: "転送元".to_string(),
+            value: label.to_string(),
+        });
+    }
+
+    let author = creator.map(|user| {
+        let avatar_hash = user.avatar.as_ref().map(|hash| hash.to_string());
+        EmbedAuthor {
+            icon_url: Some(get_user_avatar_url(user.id, avatar_hash.as_deref())),
+            name: user.name.clone(),
+            proxy_icon_url: None,
+            url: None,
+        }
+    });
+
+    Embed {
+        author,
+        color: None,
+        description: Some(format!("新しい{}が作成されました。このチャンネルへのミラーリングを開始します。", noun)),
+        fields,
+        footer: None,
+        image: None,
+        kind: "rich".to_string(),
+        provider: None,
+        thumbnail: None,
+        timestamp: thread.thread_metadata.as_ref().and_then(|metadata| metadata.create_timestamp),
+        title: Some(thread.name.clone().unwrap_or_else(|| "(名前なし)".to_string())),
+        url,
+        video: None,
+    }
+}
+
+/// 1件のメッセージに含められる埋め込みの最大数（Discordの制限）
+const MAX_EMBEDS_PER_MESSAGE: usize = 10;
+
+/// 1件のメッセージに含められる埋め込み全体の最大文字数（Discordの制限）
+const MAX_EMBED_TOTAL_CHARS: usize = 6000;
+
+/// 埋め込みの文字数を数える（Discordの文字数制限の対象となる項目のみ）
+fn embed_length(embed: &Embed) -> usize {
+    let text_length = |text: &Option<String>| text.as_ref().map_or(0, |text| text.chars().count());
+
+    text_length(&embed.title)
+        + text_length(&embed.description)
+        + embed.author.as_ref().map_or(0, |author| author.name.chars().count())
+        + embed.footer.as_ref().map_or(0, |footer| footer.text.chars().count())
+        + embed
+            .fields
+            .iter()
+            .map(|field| field.name.chars().count() + field.value.chars().count())
+            .sum::<usize>()
+}
+
+/// 元のメッセージの埋め込みを、転送メッセージに含められる範囲で取り出す
+///
+/// `reserved` には転送メッセージ自体の埋め込み（埋め込み形式の場合）や投票の埋め込みを渡します。
+/// リンクのプレビューは本文のURLから転送先でも自動的に作られるため含めません。
+/// ネタバレで隠されたリンクがある場合は、プレビューの内容が見えてしまわないよう rich 以外の埋め込みを全て除外します。
+fn forwardable_embeds(message: &Message, reserved: &[Embed]) -> Vec<Embed> {
+    let mut remaining_count = MAX_EMBEDS_PER_MESSAGE.saturating_sub(reserved.len());
+    let mut remaining_chars = MAX_EMBED_TOTAL_CHARS.saturating_sub(reserved.iter().map(embed_length).sum());
+    let mut embeds = Vec::new();
+    let has_spoilered_link = contains_spoilered_link(&message.content);
+
+    for embed in &message.embeds {
+        let is_link_preview = embed.kind != "rich"
+            && (has_spoilered_link || embed.url.as_ref().is_some_and(|url| message.content.contains(url.as_str())));
+        if is_link_preview {
+            continue;
+        }
+
+        let length = embed_length(embed);
+        if remaining_count == 0 || length > remaining_chars {
+            break;
+        }
+        remaining_count -= 1;
+        remaining_chars -= length;
+
+        // ボットが送信できるのは rich 形式のみで、動画やプロバイダーは指定できない
+        let mut embed = embed.clone();
+        embed.kind = "rich".to_string();
+        embed.provider = None;
+        embed.video = None;
+        embeds.push(embed);
+    }
+
+    if embeds.len() < message.embeds.len() {
+        println!("メッセージ {} の埋め込み {} 件のうち {} 件を転送します", message.id, message.embeds.len(), embeds.len());
+    }
+    embeds
+}
+
+/// 転送メッセージに付ける埋め込みを作成する
+///
+/// `reserved` の後ろに、投票の埋め込みと元のメッセージの埋め込みを続けた一覧を返します。
+pub async fn mirror_embeds(
+    state: &BotState,
+    thread_info: &ThreadInfo,
+    message: &Message,
+    reserved: Vec<Embed>,
+) -> Vec<Embed> {
+    let mut embeds = reserved;
+
+    if poll::may_contain_poll(message) {
+        match poll::fetch_poll(&state.http, message.channel_id, message.id).await {
+            Ok(Some(poll)) => embeds.push(poll::build_poll_embed(&poll, thread_info.poll_results)),
+            Ok(None) => {}
+            Err(e) => println!("警告: メッセージ {} の投票を取得できませんでした: {}", message.id, e),
+        }
+    }
+
+    if let Some(embed) = emoji::build_emoji_only_embed(&message.content, thread_info.emoji) {
+        embeds.push(embed);
+    }
+
+    let forwarded = forwardable_embeds(message, &embeds);
+    embeds.extend(forwarded);
+    embeds
+}
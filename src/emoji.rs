@@ -0,0 +1,70 @@
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+use twilight_model::channel::message::embed::EmbedImage;
+use twilight_model::channel::message::Embed;
+
+use crate::state::EmojiStyle;
+
+/// カスタム絵文字 `<:name:123>` / `<a:name:123>` の正規表現
+fn custom_emoji_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"<(a?):(\w+):(\d+)>").unwrap())
+}
+
+/// カスタム絵文字の画像のURL（アニメーション絵文字はGIF）
+fn emoji_image_url(animated: bool, id: &str) -> String {
+    let extension = if animated { "gif" } else { "png" };
+    format!("https://cdn.discordapp.com/emojis/{}.{}", id, extension)
+}
+
+/// 本文中のカスタム絵文字を、転送先でも読める形式に置き換える
+///
+/// 転送先のサーバーで使えない絵文字は `<:name:123>` のまま表示されるため、
+/// `text` では `:name:`、`link` では画像へのリンク付きの `:name:` に置き換えます。
+pub fn convert_custom_emoji(content: &str, style: EmojiStyle) -> String {
+    if style == EmojiStyle::Keep {
+        return content.to_string();
+    }
+
+    custom_emoji_pattern()
+        .replace_all(content, |captures: &Captures| match style {
+            EmojiStyle::Link => format!("[:{}:]({})", &captures[2], emoji_image_url(!captures[1].is_empty(), &captures[3])),
+            _ => format!(":{}:", &captures[2]),
+        })
+        .into_owned()
+}
+
+/// カスタム絵文字1つだけのメッセージの場合、その絵文字の画像を埋め込みにする
+///
+/// 変換した `:name:` だけでは分かりにくいため、`keep` 以外の設定では絵文字の画像も表示します。
+pub fn build_emoji_only_embed(content: &str, style: EmojiStyle) -> Option<Embed> {
+    if style == EmojiStyle::Keep {
+        return None;
+    }
+
+    let captures = custom_emoji_pattern().captures(content.trim())?;
+    if captures[0].len() != content.trim().len() {
+        return None;
+    }
+
+    Some(Embed {
+        author: None,
+        color: None,
+        description: None,
+        fields: Vec::new(),
+        footer: None,
+        image: Some(EmbedImage {
+            height: None,
+            proxy_url: None,
+            url: emoji_image_url(!captures[1].is_empty(), &captures[3]),
+            width: None,
+        }),
+        kind: "rich".to_string(),
+        provider: None,
+        thumbnail: None,
+        timestamp: None,
+        title: None,
+        url: None,
+        video: None,
+    })
+}
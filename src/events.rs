@@ -0,0 +1,732 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use twilight_gateway::{Event, EventType};
+use twilight_model::application::interaction::InteractionData;
+use twilight_model::channel::message::AllowedMentions;
+use twilight_model::channel::Channel;
+use twilight_model::gateway::payload::incoming::{MessageCreate, MessageUpdate};
+use twilight_model::id::{
+    marker::{ChannelMarker, MessageMarker},
+    Id,
+};
+
+use crate::dispatch::EventHandler;
+use crate::state::{BotState, SystemMessageKind, ThreadInfo};
+use crate::alert::AlertKind;
+use crate::audit::{AuditEntry, AuditOutcome};
+use crate::embed::build_thread_announcement_embed;
+use crate::markdown::escape_markdown;
+use crate::{backfill, github, slash, source};
+use crate::commands::{
+    handle_all_threads_command, handle_backfill_button, handle_cancel_command, handle_export_command, handle_help_command,
+    handle_pause_command, handle_retry_failed_command, handle_set_webhook_command, handle_start_command,
+    handle_stats_command, handle_status_command, handle_test_command, handle_thread2channel_command, PRIVILEGED_COMMANDS,
+};
+use crate::forwarding::{
+    digest_line, refresh_forwarded_message, should_forward, spawn_missed_message_recovery,
+};
+
+/// メッセージ編集イベントを処理します
+///
+/// 転送済みのメッセージが編集された場合、転送先のメッセージにも同じ変更を反映します。
+/// 履歴を保存している場合は、履歴の本文と編集日時も更新します（フィード・メール・書き出したサイトに編集後の本文を使うため）。
+async fn handle_message_update(
+    update: Box<MessageUpdate>,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // リンクの埋め込み展開など、本文以外の更新は対象外
+    if update.content.is_none() {
+        return Ok(());
+    }
+
+    let Some(thread_info) = state.resolve_thread_info(update.channel_id).await else {
+        return Ok(());
+    };
+    let link = state.message_links.get(update.id)?;
+    let refresh = link.as_ref().filter(|_| thread_info.active);
+    if refresh.is_none() && !state.history.records(link.is_some()) {
+        return Ok(());
+    }
+
+    // 更新イベントには一部の項目しか含まれないため、メッセージ全体を取得し直す
+    let message = state.api.message(update.channel_id, update.id).await?;
+    state.history.record(&message, link.is_some()).await;
+
+    if let Some(link) = refresh {
+        refresh_forwarded_message(&state, &thread_info, link, &message).await?;
+        println!("編集を反映しました: メッセージ {} -> 転送先メッセージ {}", message.id, link.message_id);
+    }
+    Ok(())
+}
+
+/// リアクションの追加・削除イベントを処理します
+///
+/// 転送済みのメッセージのリアクションが変化した場合、転送先のメッセージに最新の集計を表示します。
+async fn handle_reaction_change(
+    channel_id: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let link = state.message_links.get(message_id)?;
+    let Some(thread_info) = state.resolve_thread_info(channel_id).await else {
+        return Ok(());
+    };
+    if !thread_info.active {
+        return Ok(());
+    }
+    // 転送していないメッセージは、スターボード形式のマッピングの場合のみ転送するか確認する
+    if link.is_none() && thread_info.star_emoji.is_none() {
+        return Ok(());
+    }
+
+    // イベントには変化したリアクションしか含まれないため、集計済みのメッセージを取得する
+    let message = state.api.message(channel_id, message_id).await?;
+    match link {
+        Some(link) => refresh_forwarded_message(&state, &thread_info, &link, &message).await,
+        None => {
+            if !should_forward(&state, &message, &thread_info).await || !state.claim_starred_message(message.id).await {
+                return Ok(());
+            }
+            println!("メッセージ {} のリアクションが {} 個に達したため転送します", message.id, thread_info.star_threshold);
+            state.queues.enqueue(&state, thread_info, message).await;
+            Ok(())
+        }
+    }
+}
+
+/// ユーザーからのメッセージイベントを処理します
+async fn handle_message_create(
+    message: Box<MessageCreate>,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // 対象のチャンネルがスレッドマッピングに登録されているか確認
+    let thread_info = state.resolve_thread_info(message.channel_id).await;
+    state.debug_watch.log_message(&message, thread_info.as_ref());
+
+    let thread_info = match thread_info {
+        Some(info) => info,
+        None => return Ok(()),
+    };
+
+    // 履歴に全てのメッセージを保存する設定の場合は、転送しないメッセージも保存する
+    state.history.record(&message, false).await;
+
+    // 一時停止中のマッピングは転送しない
+    if !thread_info.active {
+        return Ok(());
+    }
+
+    if !should_forward(&state, &message, &thread_info).await {
+        return Ok(());
+    }
+
+    // 過去メッセージの転送中は、順番が入れ替わらないよう転送が終わるまで保留する
+    if state.backfills.defer(&message).await {
+        return Ok(());
+    }
+
+    // まとめ投稿のマッピングは、1行に整形してキューに追加する
+    if thread_info.digest_enabled() {
+        let thread_info = thread_info.routed(&message.content);
+        let line = digest_line(&state, &thread_info, &message).await;
+        return state.digests.push(&state, message.channel_id, &thread_info, line).await;
+    }
+
+    // 転送はマッピングごとの列で順番に行う（遅いマッピングが他のマッピングの転送を待たせないようにする）
+    state.queues.enqueue(&state, thread_info, message.0).await;
+    Ok(())
+}
+
+/// スレッド作成イベントを処理します
+///
+/// マッピングが設定された親チャンネル配下に新しいスレッドが作成された場合、そのスレッドのマッピングを自動的に登録します。
+async fn handle_thread_create(thread: &Channel, state: Arc<BotState>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    state.cache_channel(thread).await;
+
+    // 既存スレッドへの参加などで届いたイベントは対象外
+    if thread.newly_created != Some(true) {
+        return Ok(());
+    }
+    let Some(parent_id) = thread.parent_id else {
+        return Ok(());
+    };
+    // 親チャンネルのマッピングが無ければ、スレッド名のパターンで登録する
+    let thread_info = match state.auto_map_thread(thread.id, parent_id).await? {
+        Some(info) => info,
+        None => match auto_map_by_name(&state, thread).await? {
+            Some(info) => info,
+            None => return Ok(()),
+        },
+    };
+    let source = source::prepare(&state, thread.id).await;
+
+    println!("新しい{}のマッピングを登録しました: {} {} (親チャンネル {}) -> チャンネル {}",
+        source.noun(),
+        source.noun(),
+        thread.id,
+        parent_id,
+        thread_info.target_channel_id
+    );
+
+    // メッセージが投稿される前に、転送先に新しいスレッドを知らせる
+    if thread_info.notify_new_threads {
+        let creator = match thread.owner_id {
+            Some(owner_id) => match state.api.user(owner_id).await {
+                Ok(user) => Some(user),
+                Err(e) => {
+                    println!("スレッド {} の作成者 {} を取得できませんでした: {}", thread.id, owner_id, e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let url = state
+            .channel_guild(thread.id)
+            .await
+            .map(|guild_id| format!("https://discord.com/channels/{}/{}", guild_id, thread.id));
+        let parent = state.channel_link(parent_id, thread_info.target_channel_id).await;
+        let embed = build_thread_announcement_embed(
+            thread,
+            source.noun(),
+            creator.as_ref(),
+            url,
+            &parent,
+            thread_info.label.as_deref(),
+        );
+        state
+            .http
+            .create_message(thread_info.target_channel_id)
+            .content(&format!("{} 新しい{}", source.icon(), source.noun()))?
+            .embeds(&[embed])?
+            .allowed_mentions(Some(&AllowedMentions::default()))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// スレッド名が設定ファイルの `name_pattern` に一致した場合、スレッドのマッピングを登録する
+async fn auto_map_by_name(
+    state: &BotState,
+    thread: &Channel,
+) -> Result<Option<ThreadInfo>, Box<dyn std::error::Error + Send + Sync>> {
+    let Some(name) = thread.name.as_deref() else {
+        return Ok(None);
+    };
+    let thread_info = state.auto_map_thread_by_name(thread.id, thread.parent_id, name).await?;
+    if let Some(info) = &thread_info {
+        println!("スレッド名「{}」がパターンに一致したため、マッピングを登録しました: スレッド {} -> チャンネル {}", name, thread.id, info.target_channel_id);
+    }
+    Ok(thread_info)
+}
+
+/// サーバーのアクティブなスレッドのうち、名前が `name_pattern` に一致するものをマッピングに登録する
+///
+/// ボットの起動時とサーバーへの参加時に受信するサーバーの作成イベントで、スレッドのIDを1つずつ設定しなくても済むようにします。
+async fn auto_map_active_threads(state: &BotState, threads: &[Channel]) {
+    let mut mapped = 0;
+    for thread in threads {
+        match auto_map_by_name(state, thread).await {
+            Ok(Some(_)) => {
+                source::prepare(state, thread.id).await;
+                mapped += 1;
+            }
+            Ok(None) => {}
+            Err(e) => println!("スレッド {} のマッピングを登録できませんでした: {}", thread.id, e),
+        }
+    }
+    if mapped > 0 {
+        println!("スレッド名のパターンに一致した {} 個のスレッドのマッピングを登録しました", mapped);
+    }
+}
+
+/// スレッド名（転送元のチャンネル名）の変更を転送先に通知する
+///
+/// 長く続くミラーでも転送先でどのスレッドか分かるよう、新しい名前を知らせます。
+/// `system=rename` のマッピングでは名前の変更のシステムメッセージが転送されるため、通知しません。
+async fn notify_thread_rename(
+    state: &BotState,
+    thread: &Channel,
+    previous_name: &str,
+    name: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(thread_info) = state.get_thread_info(thread.id).await else {
+        return Ok(());
+    };
+    if !thread_info.active || thread_info.system_messages.contains(&SystemMessageKind::Rename) {
+        return Ok(());
+    }
+
+    println!("スレッド {} の名前が「{}」から「{}」に変更されました", thread.id, previous_name, name);
+    let source = source::resolve(state, thread.id).await;
+    let link = state.channel_link(thread.id, thread_info.target_channel_id).await;
+    let notice = format!(
+        "✏️ {}の名前が **{}** から **{}** ({}) に変更されました",
+        source.noun(),
+        escape_markdown(previous_name),
+        escape_markdown(name),
+        link
+    );
+    let notice = match thread_info.label.as_deref() {
+        Some(label) => format!("`[{}]` {}", label, notice),
+        None => notice,
+    };
+    state.api.create_message(thread_info.target_channel_id, &notice).await?;
+
+    Ok(())
+}
+
+/// スレッドのアーカイブを処理する
+///
+/// `pause_archived`（デフォルト）のマッピングは転送を一時停止し、アーカイブが解除されたら再開します。
+/// 手動で一時停止しているマッピングでは何もしません。
+async fn handle_thread_archived(
+    state: &BotState,
+    thread: &Channel,
+    thread_info: &ThreadInfo,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if !thread_info.active {
+        return Ok(());
+    }
+
+    let source = source::resolve(state, thread.id).await;
+    let name = thread.name.as_deref().map(escape_markdown).unwrap_or_else(|| "(名前なし)".to_string());
+    let link = state.channel_link(thread.id, thread_info.target_channel_id).await;
+    let notice = if thread_info.pause_on_archive {
+        state
+            .update_thread_mapping(thread.id, |info| {
+                info.active = false;
+                info.paused_by_archive = true;
+            })
+            .await?;
+        println!("スレッド {} がアーカイブされたため転送を一時停止しました", thread.id);
+        state.alerts.report(
+            &state.http,
+            AlertKind::MappingDisabled,
+            &format!("<#{}> -> <#{}>", thread.id, thread_info.target_channel_id),
+            "スレッドがアーカイブされたため、転送を一時停止しました",
+        ).await;
+        format!("📦 {} **{}** ({}) がアーカイブされたため、転送を一時停止しました。アーカイブが解除されると転送を再開します。", source.noun(), name, link)
+    } else {
+        println!("スレッド {} がアーカイブされました", thread.id);
+        format!("📦 {} **{}** ({}) がアーカイブされました。", source.noun(), name, link)
+    };
+    state.api.create_message(thread_info.target_channel_id, &notice).await?;
+
+    Ok(())
+}
+
+/// スレッドのアーカイブの解除を処理する（アーカイブで一時停止していた転送は再開する）
+async fn handle_thread_unarchived(
+    state: &BotState,
+    thread: &Channel,
+    thread_info: &ThreadInfo,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // アーカイブ中は参加できないため、解除されたらスレッドに参加し直す
+    let source = source::prepare(state, thread.id).await;
+    let name = thread.name.as_deref().map(escape_markdown).unwrap_or_else(|| "(名前なし)".to_string());
+    let link = state.channel_link(thread.id, thread_info.target_channel_id).await;
+    let notice = if thread_info.paused_by_archive {
+        state
+            .update_thread_mapping(thread.id, |info| {
+                info.active = true;
+                info.paused_by_archive = false;
+            })
+            .await?;
+        println!("スレッド {} のアーカイブが解除されたため転送を再開しました", thread.id);
+        format!("📤 {} **{}** ({}) のアーカイブが解除されたため、転送を再開しました。", source.noun(), name, link)
+    } else if thread_info.active {
+        println!("スレッド {} のアーカイブが解除されました", thread.id);
+        format!("📤 {} **{}** ({}) のアーカイブが解除されました。", source.noun(), name, link)
+    } else {
+        return Ok(());
+    };
+    state.api.create_message(thread_info.target_channel_id, &notice).await?;
+
+    Ok(())
+}
+
+/// スレッドのロック・ロックの解除を転送先に通知する（一時停止中のマッピングでは通知しない）
+async fn notify_thread_lock(
+    state: &BotState,
+    thread: &Channel,
+    thread_info: &ThreadInfo,
+    locked: bool,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if !thread_info.active {
+        return Ok(());
+    }
+
+    let source = source::resolve(state, thread.id).await;
+    let name = thread.name.as_deref().map(escape_markdown).unwrap_or_else(|| "(名前なし)".to_string());
+    let link = state.channel_link(thread.id, thread_info.target_channel_id).await;
+    let notice = if locked {
+        format!("🔒 {} **{}** ({}) がロックされました。", source.noun(), name, link)
+    } else {
+        format!("🔓 {} **{}** ({}) のロックが解除されました。", source.noun(), name, link)
+    };
+    println!("スレッド {} が{}", thread.id, if locked { "ロックされました" } else { "ロックを解除されました" });
+    state.api.create_message(thread_info.target_channel_id, &notice).await?;
+
+    Ok(())
+}
+
+/// スレッド更新イベントを処理します
+///
+/// マッピングが設定されたスレッドの名前が変更された場合は転送先に新しい名前を通知します。
+/// アーカイブ・アーカイブの解除・ロック・ロックの解除も転送先に通知し、アーカイブ中は転送を一時停止します（`pause_archived=false` を除く）。
+async fn handle_thread_update(thread: &Channel, state: Arc<BotState>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let previous_name = state.cached_channel_name(thread.id).await;
+    let previous_state = state.cached_thread_state(thread.id).await;
+    state.cache_channel(thread).await;
+
+    if let (Some(previous_name), Some(name)) = (previous_name, thread.name.as_deref()) {
+        if previous_name != name {
+            notify_thread_rename(&state, thread, &previous_name, name).await?;
+        }
+    }
+
+    let Some(metadata) = &thread.thread_metadata else {
+        return Ok(());
+    };
+    let Some(thread_info) = state.get_thread_info(thread.id).await else {
+        return Ok(());
+    };
+
+    // 以前の状態が分からない場合は、アーカイブされていなかったものとして扱う
+    let was_archived = previous_state.is_some_and(|previous| previous.archived);
+    if metadata.archived && !was_archived {
+        handle_thread_archived(&state, thread, &thread_info).await?;
+    } else if !metadata.archived && (was_archived || thread_info.paused_by_archive) {
+        handle_thread_unarchived(&state, thread, &thread_info).await?;
+    }
+
+    if let Some(previous) = previous_state {
+        if previous.locked != metadata.locked {
+            let thread_info = state.get_thread_info(thread.id).await.unwrap_or(thread_info);
+            notify_thread_lock(&state, thread, &thread_info, metadata.locked).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// スレッド削除イベントを処理します
+///
+/// マッピングが設定されたスレッドが削除された場合、マッピングを削除して転送先に通知します。
+async fn handle_thread_delete(thread_id: Id<ChannelMarker>, state: Arc<BotState>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    handle_source_delete(thread_id, "スレッド", state).await
+}
+
+/// 転送元（スレッド・テキストチャンネル）の削除を処理します
+///
+/// マッピングを削除して、管理用チャンネルと転送先に通知します。`noun` は通知に表示する転送元の種類です。
+async fn handle_source_delete(
+    channel_id: Id<ChannelMarker>,
+    noun: &str,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    state.forget_channel(channel_id).await;
+
+    let Some(thread_info) = state.get_thread_info(channel_id).await else {
+        return Ok(());
+    };
+
+    state.remove_thread_mapping(channel_id).await?;
+    println!("{} {} が削除されたためマッピングを削除しました", noun, channel_id);
+    state.alerts.report(
+        &state.http,
+        AlertKind::MappingDisabled,
+        &format!("{} {} -> <#{}>", noun, channel_id, thread_info.target_channel_id),
+        &format!("{}が削除されたため、マッピングを削除しました", noun),
+    ).await;
+
+    state
+        .api
+        .create_message(
+            thread_info.target_channel_id,
+            &format!("🗑️ 転送元の{} (ID: {}) が削除されたため、マッピングを削除しました。", noun, channel_id),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// 管理用チャンネルへの通知に含めるイベントの説明
+pub fn describe_event(event: &Event) -> String {
+    let channel_id = match event {
+        Event::MessageCreate(message) => Some(message.channel_id),
+        Event::MessageUpdate(update) => Some(update.channel_id),
+        Event::ReactionAdd(reaction) => Some(reaction.channel_id),
+        Event::ReactionRemove(reaction) => Some(reaction.channel_id),
+        Event::ThreadCreate(thread) => Some(thread.id),
+        Event::ThreadUpdate(thread) => Some(thread.id),
+        _ => None,
+    };
+
+    match channel_id {
+        Some(channel_id) => format!("{:?} イベントの処理（<#{}>）", event.kind(), channel_id),
+        None => format!("{:?} イベントの処理", event.kind()),
+    }
+}
+
+/// コマンドの実行と通常メッセージの転送
+struct MessageCreateHandler;
+
+#[async_trait]
+impl EventHandler for MessageCreateHandler {
+    fn name(&self) -> &'static str {
+        "message_create"
+    }
+
+    fn event_types(&self) -> &'static [EventType] {
+        &[EventType::MessageCreate]
+    }
+
+    async fn handle(&self, event: Event, state: Arc<BotState>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let Event::MessageCreate(message) = event else {
+            return Ok(());
+        };
+
+        // プレフィックスはマッピング・サーバーごとに変更できる（他のボットのコマンドと区別するため）
+        let prefix = state.command_prefix(message.channel_id, message.guild_id).await;
+        let command = message
+            .content
+            .split_whitespace()
+            .next()
+            .and_then(|word| word.strip_prefix(prefix.as_str()))
+            .unwrap_or_default();
+
+        // 過去メッセージの転送やマッピングの変更は、権限を持つユーザーのみ実行できる
+        let privileged = PRIVILEGED_COMMANDS.contains(&command);
+        if privileged && !state.permissions.allows(&state.http, &message).await {
+            println!("ユーザー {} にはコマンド {}{} を実行する権限がありません", message.author.id, prefix, command);
+            state.record_command(AuditEntry::new(message.author.id, message.channel_id, &message.content, AuditOutcome::Denied)).await;
+            state
+                .api
+                .create_message(message.channel_id, "🔒 申し訳ありませんが、このコマンドを実行するにはメッセージの管理またはスレッドの管理の権限（または管理者ロール）が必要です。")
+                .await?;
+            return Ok(());
+        }
+
+        // 権限の必要なコマンドは、誰がどこで実行したかと結果を記録する
+        let audit = privileged.then(|| (message.author.id, message.channel_id, message.content.clone()));
+
+        let result = match command {
+            // コマンドの処理
+            "thread2channel" => handle_thread2channel_command(message, state.clone()).await,
+            // webhookの設定コマンド
+            "set_webhook" => handle_set_webhook_command(message, state.clone()).await,
+            // 全メッセージ転送開始コマンド
+            "start" | "all" => handle_start_command(message, state.clone()).await,
+            // スレッドの記録をファイルにまとめるコマンド
+            "export" => handle_export_command(message, state.clone()).await,
+            // 親チャンネル配下の全スレッドの転送コマンド
+            "all_threads" => handle_all_threads_command(message, state.clone()).await,
+            // コマンドの一覧の表示
+            "help" => handle_help_command(message, state.clone()).await,
+            // 実行状況の表示コマンド
+            "status" => handle_status_command(message, state.clone()).await,
+            "stats" => handle_stats_command(message, state.clone()).await,
+            // 転送先の権限の確認コマンド
+            "test" => handle_test_command(message, state.clone()).await,
+            // 過去メッセージの転送の中止コマンド
+            "cancel" => handle_cancel_command(message, state.clone()).await,
+            // 転送の一時停止・再開コマンド
+            "pause" => handle_pause_command(message, state.clone(), false).await,
+            "resume" => handle_pause_command(message, state.clone(), true).await,
+            // 転送に失敗したメッセージの再送コマンド
+            "retry-failed" => handle_retry_failed_command(message, state.clone()).await,
+            // 通常メッセージの転送処理
+            _ => handle_message_create(message, state.clone()).await,
+        };
+
+        if let Some((user_id, channel_id, content)) = audit {
+            state.record_command(AuditEntry::new(user_id, channel_id, &content, AuditOutcome::from_result(&result))).await;
+        }
+        result
+    }
+}
+
+/// メッセージ編集・リアクションの転送先への反映
+struct MessageChangeHandler;
+
+#[async_trait]
+impl EventHandler for MessageChangeHandler {
+    fn name(&self) -> &'static str {
+        "message_change"
+    }
+
+    fn event_types(&self) -> &'static [EventType] {
+        &[
+            EventType::MessageUpdate,
+            EventType::ReactionAdd,
+            EventType::ReactionRemove,
+            EventType::ReactionRemoveAll,
+            EventType::ReactionRemoveEmoji,
+        ]
+    }
+
+    async fn handle(&self, event: Event, state: Arc<BotState>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match event {
+            // メッセージ編集の反映
+            Event::MessageUpdate(update) => handle_message_update(update, state).await,
+            // リアクションの集計を転送先に反映
+            Event::ReactionAdd(reaction) => {
+                // 🐛 のリアクションで GitHub の Issue を作成（失敗してもリアクションの集計の反映は続ける）
+                if let Some(thread_info) = state.resolve_thread_info(reaction.channel_id).await {
+                    if let Err(e) = github::handle_reaction(&reaction, &thread_info, &state).await {
+                        println!("メッセージ {} から Issue を作成できませんでした: {}", reaction.message_id, e);
+                    }
+                }
+                handle_reaction_change(reaction.channel_id, reaction.message_id, state).await
+            }
+            Event::ReactionRemove(reaction) => handle_reaction_change(reaction.channel_id, reaction.message_id, state).await,
+            Event::ReactionRemoveAll(reaction) => handle_reaction_change(reaction.channel_id, reaction.message_id, state).await,
+            Event::ReactionRemoveEmoji(reaction) => handle_reaction_change(reaction.channel_id, reaction.message_id, state).await,
+            _ => Ok(()),
+        }
+    }
+}
+
+/// スラッシュコマンド・ボタン・選択メニューの処理
+struct InteractionHandler;
+
+#[async_trait]
+impl EventHandler for InteractionHandler {
+    fn name(&self) -> &'static str {
+        "interaction"
+    }
+
+    fn event_types(&self) -> &'static [EventType] {
+        &[EventType::InteractionCreate]
+    }
+
+    async fn handle(&self, event: Event, state: Arc<BotState>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let Event::InteractionCreate(interaction) = event else {
+            return Ok(());
+        };
+
+        // 過去メッセージの転送の確認ボタン
+        let button = match &interaction.data {
+            Some(InteractionData::MessageComponent(data)) => backfill::parse_confirm_custom_id(&data.custom_id),
+            _ => None,
+        };
+        match button {
+            Some(confirm) => handle_backfill_button(&interaction, confirm, state).await,
+            None => slash::handle_interaction(interaction.0, state).await,
+        }
+    }
+}
+
+/// 接続・再接続時の処理
+struct ConnectionHandler;
+
+#[async_trait]
+impl EventHandler for ConnectionHandler {
+    fn name(&self) -> &'static str {
+        "connection"
+    }
+
+    fn event_types(&self) -> &'static [EventType] {
+        &[EventType::Ready, EventType::Resumed]
+    }
+
+    async fn handle(&self, event: Event, state: Arc<BotState>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match event {
+            // 接続完了時にスラッシュコマンドを登録
+            Event::Ready(ready) => {
+                state.set_identity(ready.user.id, ready.application.id);
+                // 複数のシャードで接続している場合、登録と未転送のメッセージの確認は最初のシャードでのみ行う
+                if ready.shard.is_some_and(|shard| shard.number() != 0) {
+                    return Ok(());
+                }
+                if let Err(e) = slash::register_commands(&state.http, ready.application.id).await {
+                    eprintln!("スラッシュコマンドの登録に失敗しました: {}", e);
+                }
+                spawn_missed_message_recovery(&state);
+            }
+            // 再開できなかったイベントがある場合に備えて、未転送のメッセージを確認する
+            Event::Resumed => spawn_missed_message_recovery(&state),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// チャンネル更新イベントを処理します
+///
+/// テキストチャンネルを転送元とするマッピング（チャンネル同士のミラー）では、スレッドと同じように名前の変更を転送先に通知します。
+async fn handle_channel_update(channel: &Channel, state: Arc<BotState>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let previous_name = state.cached_channel_name(channel.id).await;
+    state.cache_channel(channel).await;
+
+    if let (Some(previous_name), Some(name)) = (previous_name, channel.name.as_deref()) {
+        if previous_name != name {
+            notify_thread_rename(&state, channel, &previous_name, name).await?;
+        }
+    }
+    Ok(())
+}
+
+/// スレッド・チャンネルの作成・更新・削除の処理
+struct ThreadHandler;
+
+#[async_trait]
+impl EventHandler for ThreadHandler {
+    fn name(&self) -> &'static str {
+        "thread"
+    }
+
+    fn event_types(&self) -> &'static [EventType] {
+        &[
+            EventType::ThreadCreate,
+            EventType::ThreadUpdate,
+            EventType::ThreadDelete,
+            EventType::ThreadListSync,
+            EventType::ChannelCreate,
+            EventType::ChannelUpdate,
+            EventType::ChannelDelete,
+            EventType::GuildCreate,
+        ]
+    }
+
+    async fn handle(&self, event: Event, state: Arc<BotState>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match event {
+            // スレッドの情報（親チャンネル・名前）をキャッシュ（親チャンネルのマッピング・テンプレート用）
+            Event::ThreadCreate(thread) => handle_thread_create(&thread, state).await?,
+            Event::ThreadUpdate(thread) => handle_thread_update(&thread, state).await?,
+            Event::ThreadDelete(thread) => handle_thread_delete(thread.id, state).await?,
+            Event::ThreadListSync(sync) => {
+                for thread in &sync.threads {
+                    state.cache_channel(thread).await;
+                }
+            }
+            // チャンネルの情報をキャッシュ（メッセージの受信時にHTTP APIでスレッドかどうかを問い合わせないようにする）
+            Event::ChannelCreate(channel) => state.cache_channel(&channel).await,
+            Event::ChannelUpdate(channel) => handle_channel_update(&channel, state).await?,
+            // チャンネル同士のミラーの転送元のチャンネルが削除された場合は、スレッドと同じくマッピングを削除する
+            Event::ChannelDelete(channel) => handle_source_delete(channel.id, "チャンネル", state).await?,
+            // 接続時・サーバーへの参加時は、名前がパターンに一致するアクティブなスレッドをマッピングに登録する
+            Event::GuildCreate(guild) => {
+                state.cache_guild_channels(guild.id, &guild.channels).await;
+                state.cache_guild_channels(guild.id, &guild.threads).await;
+                auto_map_active_threads(&state, &guild.threads).await;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// ボットの標準のイベントの処理
+pub fn default_handlers() -> Vec<Box<dyn EventHandler>> {
+    vec![
+        Box::new(MessageCreateHandler),
+        Box::new(MessageChangeHandler),
+        Box::new(InteractionHandler),
+        Box::new(ConnectionHandler),
+        Box::new(ThreadHandler),
+    ]
+}
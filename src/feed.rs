@@ -0,0 +1,111 @@
+use chrono::DateTime;
+use serde_json::json;
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use crate::health::{json_response, Response};
+use crate::history::HistoryEntry;
+use crate::state::BotState;
+
+/// フィードに含めるメッセージの数
+const FEED_ITEMS: usize = 50;
+
+/// 項目のタイトルにする本文の最大の長さ（文字数）
+const TITLE_LENGTH: usize = 80;
+
+/// RSS の応答の Content-Type
+const RSS: &str = "application/rss+xml; charset=utf-8";
+
+/// XML で特別な意味を持つ文字をエスケープする
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// メッセージへのリンク（サーバー外のメッセージは `@me`）
+fn message_url(entry: &HistoryEntry) -> String {
+    let guild = entry.guild_id.map_or_else(|| "@me".to_string(), |id| id.to_string());
+    format!("https://discord.com/channels/{}/{}/{}", guild, entry.thread_id, entry.message_id)
+}
+
+/// 項目のタイトル（送信者名と本文の1行目）
+fn item_title(entry: &HistoryEntry) -> String {
+    let first_line = entry.content.lines().find(|line| !line.trim().is_empty()).unwrap_or("");
+    let mut title: String = first_line.chars().take(TITLE_LENGTH).collect();
+    if first_line.chars().count() > TITLE_LENGTH {
+        title.push('…');
+    }
+    if title.is_empty() {
+        title = "(本文なし)".to_string();
+    }
+    format!("{}: {}", entry.author_name, title)
+}
+
+/// 1件のメッセージを RSS の項目にする
+fn rss_item(entry: &HistoryEntry) -> String {
+    let mut description = escape_xml(&entry.content).replace('\n', "<br>");
+    for attachment in &entry.attachments {
+        description.push_str(&format!(
+            "<br>📎 <a href=\"{}\">{}</a>",
+            escape_xml(&attachment.url),
+            escape_xml(&attachment.filename)
+        ));
+    }
+    let pub_date = DateTime::parse_from_rfc3339(&entry.created_at)
+        .map(|date| format!("\n      <pubDate>{}</pubDate>", date.to_rfc2822()))
+        .unwrap_or_default();
+
+    format!(
+        "    <item>\n      <title>{}</title>\n      <link>{}</link>\n      <guid isPermaLink=\"false\">{}</guid>{}\n      <description>{}</description>\n    </item>\n",
+        escape_xml(&item_title(entry)),
+        escape_xml(&message_url(entry)),
+        entry.message_id,
+        pub_date,
+        escape_xml(&description)
+    )
+}
+
+/// スレッドの転送したメッセージの RSS フィード（`GET /feeds/<スレッドID>.xml`）を返す
+///
+/// `feed=true` を指定したマッピングのみ公開します。メッセージは履歴（`bot.message_history`）から新しい順に読み込むため、履歴を保存していない場合は 404 を返します。
+pub async fn handle(state: &BotState, file_name: &str) -> Response {
+    let not_found = || json_response("404 Not Found", json!({ "error": "not found" }));
+    let Some(thread_id) = file_name
+        .strip_suffix(".xml")
+        .and_then(|id| id.parse().ok())
+        .and_then(Id::<ChannelMarker>::new_checked)
+    else {
+        return not_found();
+    };
+    let Some(thread_info) = state.resolve_thread_info(thread_id).await.filter(|info| info.feed) else {
+        return not_found();
+    };
+
+    let mut entries = match state.history.recent_forwarded(thread_id.get(), 0, FEED_ITEMS).await {
+        Some(Ok(entries)) => entries,
+        Some(Err(e)) => {
+            println!("スレッド {} のフィードを作成できませんでした: {}", thread_id, e);
+            return json_response("500 Internal Server Error", json!({ "error": "履歴を読み込めませんでした" }));
+        }
+        None => return not_found(),
+    };
+    HistoryEntry::apply_transforms(&mut entries, &thread_info.transforms).await;
+
+    let title = match thread_info.label {
+        Some(label) => label,
+        None => state.channel_name(thread_id).await.unwrap_or_else(|| thread_id.to_string()),
+    };
+    let guild = entries.first().and_then(|entry| entry.guild_id).map_or_else(|| "@me".to_string(), |id| id.to_string());
+    let items: String = entries.iter().map(rss_item).collect();
+
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\">\n  <channel>\n    <title>{}</title>\n    <link>{}</link>\n    <description>{}</description>\n{}  </channel>\n</rss>\n",
+        escape_xml(&title),
+        escape_xml(&format!("https://discord.com/channels/{}/{}", guild, thread_id)),
+        escape_xml(&format!("Thread2Channel で転送した「{}」のメッセージ", title)),
+        items
+    );
+    ("200 OK", RSS, body)
+}
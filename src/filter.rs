@@ -0,0 +1,53 @@
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// 転送するメッセージを絞り込む正規表現
+///
+/// 設定やデータベースには文字列として保存し、読み込み時にコンパイルします。
+#[derive(Debug, Clone)]
+pub struct MessagePattern(Regex);
+
+impl MessagePattern {
+    /// 正規表現をコンパイルする
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        Regex::new(pattern)
+            .map(Self)
+            .map_err(|e| format!("正規表現 {} が無効です: {}", pattern, e))
+    }
+
+    /// 本文が正規表現に一致するかどうか
+    pub fn is_match(&self, content: &str) -> bool {
+        self.0.is_match(content)
+    }
+
+    /// 正規表現の文字列
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// 正規表現に一致した部分を全て置き換える
+    pub fn replace_all(&self, content: &str, replacement: &str) -> String {
+        self.0.replace_all(content, regex::NoExpand(replacement)).into_owned()
+    }
+}
+
+impl Serialize for MessagePattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for MessagePattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Self::new(&pattern).map_err(serde::de::Error::custom)
+    }
+}
+
+/// 本文が絞り込みの条件を満たすかどうか
+///
+/// `include` が空でなければいずれかに一致する必要があり、`exclude` のいずれかに一致した場合は除外します。
+pub fn matches_filters(include: &[MessagePattern], exclude: &[MessagePattern], content: &str) -> bool {
+    let included = include.is_empty() || include.iter().any(|pattern| pattern.is_match(content));
+    included && !exclude.iter().any(|pattern| pattern.is_match(content))
+}
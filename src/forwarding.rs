@@ -0,0 +1,1480 @@
+use async_trait::async_trait;
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use chrono::{Utc, TimeZone};
+
+use twilight_model::channel::message::embed::EmbedFooter;
+use twilight_model::channel::message::{AllowedMentions, Embed, MessageType, Reaction, ReactionType};
+use twilight_model::channel::{Attachment, Channel, Message};
+use twilight_model::http::attachment::Attachment as HttpAttachment;
+use twilight_model::util::Timestamp;
+use twilight_model::id::{
+    marker::{ChannelMarker, MessageMarker},
+    Id,
+};
+
+use crate::api::DiscordApi;
+use crate::state::{ArchiveMode, BotState, MessageFormat, SystemMessageKind, ThreadInfo};
+use crate::links::MessageLink;
+use crate::backfill::{BackfillRange, ProgressMessage};
+use crate::markdown::escape_markdown;
+use crate::pacing::Pacer;
+use crate::retry::{self, SendError};
+use crate::sink::MessageSink;
+use crate::stats::RecentForward;
+use crate::transcript::{TranscriptEntry, TranscriptFormat};
+use crate::{archive, emoji, filter, pacing, source, supervisor, template, transcript, transform};
+use crate::embed::{get_user_avatar_url, webhook_display_name, build_forward_embed, mirror_embeds};
+
+/// 再アップロードするボイスメッセージの最大サイズ（Discordの通常のアップロード上限）
+const MAX_REUPLOAD_BYTES: u64 = 10 * 1024 * 1024;
+
+/// ボイスメッセージの添付ファイルかどうか（ボイスメッセージには波形データが付く）
+fn is_voice_attachment(attachment: &Attachment) -> bool {
+    attachment.waveform.is_some()
+}
+
+/// ネタバレ指定の添付ファイルかどうか（Discordはファイル名の `SPOILER_` で判定する）
+fn is_spoiler_attachment(attachment: &Attachment) -> bool {
+    attachment.filename.starts_with("SPOILER_")
+}
+
+/// 添付ファイルを一覧表示用の1行にする（ボイスメッセージは長さと共にラベルを付ける）
+pub fn attachment_line(attachment: &Attachment) -> String {
+    // ネタバレ指定のファイルは、リンクもネタバレとして隠す
+    let url = if is_spoiler_attachment(attachment) {
+        format!("||{}||", attachment.url)
+    } else {
+        attachment.url.clone()
+    };
+
+    if !is_voice_attachment(attachment) {
+        return format!("- {}", url);
+    }
+
+    match attachment.duration_secs {
+        Some(duration) => format!("- 🎤 ボイスメッセージ（{}秒）: {}", duration.round() as u64, url),
+        None => format!("- 🎤 ボイスメッセージ: {}", url),
+    }
+}
+
+/// ボイスメッセージの音声ファイルをダウンロードし、転送先に再アップロードできる形にする
+///
+/// 添付ファイルのURLは時間が経つと期限切れになるため、音声ファイル自体を転送先に残します。
+/// ダウンロードに失敗したファイルはリンクのみの転送になります。
+async fn download_voice_files(message: &Message) -> Vec<HttpAttachment> {
+    let client = reqwest::Client::new();
+    let mut files = Vec::new();
+
+    for attachment in message.attachments.iter().filter(|attachment| is_voice_attachment(attachment)) {
+        if attachment.size > MAX_REUPLOAD_BYTES {
+            println!("ボイスメッセージ {} はサイズが大きいため再アップロードしません", attachment.filename);
+            continue;
+        }
+
+        let bytes = match client.get(&attachment.url).send().await.and_then(|response| response.error_for_status()) {
+            Ok(response) => response.bytes().await,
+            Err(e) => Err(e),
+        };
+        match bytes {
+            // ネタバレ指定が引き継がれるよう、ファイル名（`SPOILER_` を含む）はそのまま使う
+            Ok(bytes) => files.push(HttpAttachment::from_bytes(attachment.filename.clone(), bytes.to_vec(), files.len() as u64)),
+            Err(e) => println!("警告: ボイスメッセージ {} をダウンロードできませんでした: {}", attachment.filename, e),
+        }
+    }
+
+    files
+}
+
+/// Webhookに添付ファイル付きで送信するためのmultipart/form-dataの本文を作成する
+///
+/// 戻り値は (Content-Type, 本文) です。
+fn build_multipart_body(payload: &serde_json::Value, files: &[HttpAttachment]) -> (String, Vec<u8>) {
+    let boundary = format!("thread2channel-{}", Utc::now().timestamp_nanos_opt().unwrap_or_default());
+    let mut body = Vec::new();
+
+    body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+    body.extend_from_slice(b"Content-Disposition: form-data; name=\"payload_json\"\r\n");
+    body.extend_from_slice(b"Content-Type: application/json\r\n\r\n");
+    body.extend_from_slice(payload.to_string().as_bytes());
+    body.extend_from_slice(b"\r\n");
+
+    for file in files {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        body.extend_from_slice(
+            format!(
+                "Content-Disposition: form-data; name=\"files[{}]\"; filename=\"{}\"\r\n",
+                file.id,
+                file.filename.replace('"', "")
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(b"Content-Type: application/octet-stream\r\n\r\n");
+        body.extend_from_slice(&file.file);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    (format!("multipart/form-data; boundary={}", boundary), body)
+}
+
+/// Webhookで送信する本文を作成する（タイムスタンプと添付ファイルのリンクを付ける）
+fn build_webhook_content(
+    content: &str,
+    attachments: &[Attachment],
+    timestamp: Option<&Timestamp>,
+) -> String {
+    let mut full_content = content.to_string();
+
+    // タイムスタンプがある場合は追加
+    if let Some(ts) = timestamp {
+        full_content.push_str(&format_original_timestamp(ts));
+    }
+
+    // 添付ファイルがある場合はリンクとして追加する
+    if !attachments.is_empty() {
+        full_content.push_str("\n\n**添付ファイル:**\n");
+        for attachment in attachments {
+            full_content.push_str(&attachment_line(attachment));
+            full_content.push('\n');
+        }
+    }
+
+    full_content
+}
+
+/// Webhookを使用してメッセージを送信する
+///
+/// `content` には `build_webhook_content` で作成した本文を渡します。
+/// 送信したメッセージのIDを返します（後から編集できるように `wait=true` で送信します）。
+async fn send_webhook_message(
+    webhook_url: &str,
+    username: &str,
+    avatar_url: &str,
+    content: &str,
+    embeds: &[Embed],
+    files: &[HttpAttachment],
+    allowed_mentions: &AllowedMentions,
+) -> Result<Id<MessageMarker>, Box<dyn std::error::Error + Send + Sync>> {
+    // Webhook URLのバリデーション
+    if !webhook_url.starts_with("http://") && !webhook_url.starts_with("https://") {
+        return Err(format!("無効なWebhook URL: URLはhttp://またはhttps://で始まる必要があります: {}", webhook_url).into());
+    }
+
+    println!("🚀 Webhookリクエスト送信開始:");
+    println!("🔗 URL: {}", webhook_url);
+    println!("👤 送信者名: \"{}\"", username);
+    println!("🖼️ アバターURL: {}", avatar_url);
+    
+    let client = reqwest::Client::new();
+
+    // WebhookにPOSTするJSONデータを作成
+    let mut webhook_data = json!({
+        "content": content,
+        "username": username,
+        "avatar_url": avatar_url,
+        "embeds": embeds,
+        "allowed_mentions": allowed_mentions,
+    });
+
+    println!("📦 Webhookデータ:");
+    println!("{}", serde_json::to_string_pretty(&webhook_data).unwrap_or_else(|_| webhook_data.to_string()));
+
+    // WebhookにPOSTリクエストを送信（ファイルがある場合はmultipart形式）
+    let request = client.post(webhook_url).query(&[("wait", "true")]);
+    let request = if files.is_empty() {
+        request.json(&webhook_data)
+    } else {
+        webhook_data["attachments"] = files
+            .iter()
+            .map(|file| json!({ "id": file.id, "filename": file.filename }))
+            .collect();
+        let (content_type, body) = build_multipart_body(&webhook_data, files);
+        request.header(reqwest::header::CONTENT_TYPE, content_type).body(body)
+    };
+    // レート制限（429）を受けた場合は指定された時間だけ、一時的な失敗（5xx・通信エラー）の場合は間隔を延ばしながら待って再送する
+    let mut attempts = 0;
+    let response = loop {
+        let attempt = request.try_clone().ok_or("Webhookリクエストを複製できませんでした")?;
+        attempts += 1;
+        let response = match attempt.send().await {
+            Ok(resp) => resp,
+            Err(e) if attempts < retry::MAX_SEND_ATTEMPTS => {
+                let delay = retry::backoff_delay(attempts);
+                println!("⏳ Webhookリクエスト送信エラー: {}。{}ミリ秒後に再送します", e, delay.as_millis());
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+            Err(e) => {
+                println!("❌ Webhookリクエスト送信エラー: {}", e);
+                return Err(SendError::network(format!("Webhook送信失敗: {} - URL: {}", e, webhook_url)).into());
+            }
+        };
+
+        match pacing::observe(response.status(), response.headers()) {
+            Some(retry_after) if attempts < retry::MAX_SEND_ATTEMPTS => {
+                println!("⏳ Webhookがレート制限を受けました。{}ミリ秒後に再送します", retry_after.as_millis());
+                tokio::time::sleep(retry_after).await;
+            }
+            None if response.status().is_server_error() && attempts < retry::MAX_SEND_ATTEMPTS => {
+                let delay = retry::backoff_delay(attempts);
+                println!("⏳ Webhookリクエストがサーバーエラー（{}）になりました。{}ミリ秒後に再送します", response.status(), delay.as_millis());
+                tokio::time::sleep(delay).await;
+            }
+            _ => break response,
+        }
+    };
+
+    if !response.status().is_success() {
+        // ステータス情報を保存
+        let status = response.status();
+        
+        // エラーボディを取得
+        let error_body = match response.text().await {
+            Ok(body) => body,
+            Err(_) => "レスポンスボディを取得できませんでした".to_string()
+        };
+        
+        let error_msg = format!("❌ Webhookリクエスト失敗 ステータス: {} - URL: {} - レスポンス: {}", 
+                               status, webhook_url, error_body);
+        println!("{}", error_msg);
+        return Err(SendError::status(status.as_u16(), error_msg).into());
+    }
+
+    // 送信したメッセージのIDを取得
+    let body: serde_json::Value = response.json().await?;
+    let message_id = body["id"]
+        .as_str()
+        .and_then(|id| id.parse().ok())
+        .and_then(Id::new_checked)
+        .ok_or("WebhookのレスポンスにメッセージIDが含まれていません")?;
+
+    println!("✅ Webhookリクエスト送信成功!");
+    Ok(message_id)
+}
+
+/// Webhookで送信したメッセージの本文を編集する
+async fn edit_webhook_message(
+    webhook_url: &str,
+    message_id: Id<MessageMarker>,
+    content: &str,
+    embeds: &[Embed],
+    allowed_mentions: &AllowedMentions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let client = reqwest::Client::new();
+    let response = client
+        .patch(format!("{}/messages/{}", webhook_url.trim_end_matches('/'), message_id))
+        .json(&json!({
+            "content": content,
+            "embeds": embeds,
+            "allowed_mentions": allowed_mentions,
+        }))
+        .send()
+        .await?;
+    pacing::observe(response.status(), response.headers());
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_body = match response.text().await {
+            Ok(body) => body,
+            Err(_) => "レスポンスボディを取得できませんでした".to_string()
+        };
+        return Err(format!("Webhookメッセージの編集に失敗しました: ステータス={}, レスポンス={}", status, error_body).into());
+    }
+
+    Ok(())
+}
+
+/// タイムスタンプをJSTの表示用文字列に変換する
+fn format_jst_timestamp(timestamp: &Timestamp) -> String {
+    // タイムスタンプをUNIX時間として解釈し、JSTに変換
+    let dt = Utc.timestamp_opt(timestamp.as_secs(), 0).unwrap();
+    let jst = dt + chrono::Duration::hours(9);
+    jst.format("%Y/%m/%d %H:%M:%S").to_string()
+}
+
+/// 元のメッセージの投稿日時を本文の末尾に付ける形式にする
+///
+/// JSTの日時に加えて、Discordの相対タイムスタンプ（`<t:UNIX時間:R>`）を付けるため、
+/// 過去のメッセージを転送した場合でも、元の投稿がどれくらい前かが閲覧者に分かります。
+fn format_original_timestamp(timestamp: &Timestamp) -> String {
+    format!(" (`{}` <t:{}:R>)", format_jst_timestamp(timestamp), timestamp.as_secs())
+}
+
+/// リアクションが設定した絵文字（Unicode絵文字、`<:name:id>`、カスタム絵文字の名前またはID）と一致するか判定する
+fn reaction_matches(reaction: &ReactionType, emoji: &str) -> bool {
+    match reaction {
+        ReactionType::Unicode { name } => name == emoji,
+        ReactionType::Custom { animated, id, name } => {
+            let name = name.as_deref().unwrap_or_default();
+            let mention = if *animated { format!("<a:{}:{}>", name, id) } else { format!("<:{}:{}>", name, id) };
+            emoji == name || emoji == id.to_string() || emoji == mention
+        }
+    }
+}
+
+/// マッピングの設定に従ってメッセージを転送すべきか判定する
+pub async fn should_forward(state: &BotState, message: &Message, thread_info: &ThreadInfo) -> bool {
+    // システムメッセージは、マッピングで指定された種類のみ転送する
+    if message.kind != MessageType::Regular && message.kind != MessageType::Reply {
+        let allowed = SystemMessageKind::of(message.kind)
+            .is_some_and(|kind| thread_info.system_messages.contains(&kind));
+        if !allowed {
+            return false;
+        }
+    }
+
+    // コマンドの呼び出しは転送しない（過去メッセージの一括転送でも同様）
+    if state.is_command(&message.content, thread_info) {
+        return false;
+    }
+
+    // ボット・Webhookのメッセージは include_bots が有効な場合のみ転送
+    if message.author.bot && !thread_info.include_bots {
+        return false;
+    }
+
+    // ユーザーによる絞り込み
+    let author_id = message.author.id;
+    if !thread_info.allowed_users.is_empty() && !thread_info.allowed_users.contains(&author_id) {
+        return false;
+    }
+    if thread_info.blocked_users.contains(&author_id) {
+        return false;
+    }
+
+    // ロールによる絞り込み（ロールを取得できない場合は転送しない）
+    if !thread_info.required_roles.is_empty() {
+        let roles = match state.message_guild(message).await {
+            Some(guild_id) => state.member_roles.author_roles(&state.http, guild_id, message).await,
+            None => None,
+        };
+        let has_role = roles.is_some_and(|roles| roles.iter().any(|role| thread_info.required_roles.contains(role)));
+        if !has_role {
+            return false;
+        }
+    }
+
+    // スターボード形式では、指定したリアクションが集まったメッセージのみ転送する
+    if let Some(emoji) = &thread_info.star_emoji {
+        let count = message
+            .reactions
+            .iter()
+            .find(|reaction| reaction_matches(&reaction.emoji, emoji))
+            .map_or(0, |reaction| reaction.count);
+        if count < thread_info.star_threshold {
+            return false;
+        }
+    }
+
+    // 正規表現による絞り込み
+    if !filter::matches_filters(&thread_info.include_patterns, &thread_info.exclude_patterns, &message.content) {
+        return false;
+    }
+
+    // このボット自身が送信・転送したメッセージは、転送のループを防ぐため常に除外
+    !state.is_own_message(message).await
+}
+
+/// システムメッセージを転送用の斜体の通知文にする（転送対象外の種類は None）
+fn system_notice(message: &Message) -> Option<String> {
+    let author = escape_markdown(&message.author.name);
+    let target = message
+        .mentions
+        .first()
+        .map(|mention| escape_markdown(&mention.name))
+        .unwrap_or_else(|| "メンバー".to_string());
+    // スレッド作成・名前の変更では本文が新しい名前になる
+    let name = escape_markdown(&message.content);
+
+    let notice = match message.kind {
+        MessageType::ChannelMessagePinned => format!("📌 {} がメッセージをピン留めしました", author),
+        MessageType::UserJoin => format!("👋 {} が参加しました", author),
+        MessageType::RecipientAdd => format!("👋 {} が {} をスレッドに追加しました", author, target),
+        MessageType::RecipientRemove => format!("👋 {} が {} をスレッドから削除しました", author, target),
+        MessageType::GuildBoost => format!("🚀 {} がサーバーをブーストしました", author),
+        MessageType::GuildBoostTier1 => format!("🚀 {} がサーバーをブーストしました（レベル1に到達）", author),
+        MessageType::GuildBoostTier2 => format!("🚀 {} がサーバーをブーストしました（レベル2に到達）", author),
+        MessageType::GuildBoostTier3 => format!("🚀 {} がサーバーをブーストしました（レベル3に到達）", author),
+        MessageType::ThreadCreated => format!("🧵 {} がスレッド「{}」を作成しました", author, name),
+        MessageType::ChannelNameChange => format!("✏️ {} が名前を「{}」に変更しました", author, name),
+        _ => return None,
+    };
+    Some(format!("*{}*", notice))
+}
+
+/// 転送メッセージの本文を準備する
+///
+/// メンションを表示名に置き換え、カスタム絵文字とマッピングの変換（`transform`）を適用した後、
+/// 転送元の見出し（フォーラムの投稿のタイトルなど）とプレフィックスを先頭に付けます。
+pub async fn forward_content(state: &BotState, thread_info: &ThreadInfo, message: &Message) -> String {
+    let content = state
+        .mentions
+        .resolve(&state.http, message, thread_info.mentions, &message.content)
+        .await;
+    let content = emoji::convert_custom_emoji(&content, thread_info.emoji);
+    let content = transform::apply_pipeline(&thread_info.transforms, content).await;
+    let content = match source::resolve(state, message.channel_id).await.heading(state, message).await {
+        Some(heading) => format!("{}\n{}", heading, content),
+        None => content,
+    };
+
+    match &thread_info.prefix {
+        Some(prefix) => format!("{} {}", prefix, content),
+        None => content,
+    }
+}
+
+/// テキスト形式の転送メッセージを作成する（ラベルがある場合は送信者名の前に付ける）
+fn build_plain_content(message: &Message, content: &str, label: Option<&str>) -> String {
+    let author_name = escape_markdown(&message.author.name);
+    let mut forward_message = match label {
+        Some(label) => format!("`[{}]` **{}**\n{}", label, author_name, content),
+        None => format!("**{}**\n{}", author_name, content),
+    };
+
+    // タイムスタンプを追加
+    forward_message.push_str(&format_original_timestamp(&message.timestamp));
+
+    // 添付ファイルがある場合はリンクを追加
+    if !message.attachments.is_empty() {
+        forward_message.push_str("\n\n**添付ファイル:**\n");
+        for attachment in &message.attachments {
+            forward_message.push_str(&attachment_line(attachment));
+            forward_message.push('\n');
+        }
+    }
+
+    forward_message
+}
+
+/// マッピングにテンプレートが設定されている場合、テキスト形式・Webhookで送信する本文をテンプレートから作成する
+///
+/// スレッド名やメッセージへのリンクは、テンプレートで使用している場合のみ取得します。
+async fn render_template(state: &BotState, thread_info: &ThreadInfo, message: &Message, content: &str) -> Option<String> {
+    let template = thread_info.template.as_deref()?;
+
+    let thread_name = if template.contains("{thread_name}") {
+        state.channel_name(message.channel_id).await.map(|name| escape_markdown(&name)).unwrap_or_default()
+    } else {
+        String::new()
+    };
+    let jump_url = if template.contains("{jump_url}") {
+        state.message_url(message).await.unwrap_or_default()
+    } else {
+        String::new()
+    };
+
+    let values = template::TemplateValues {
+        author: &escape_markdown(&message.author.name),
+        thread_name: &thread_name,
+        timestamp: &format_jst_timestamp(&message.timestamp),
+        relative_time: &format!("<t:{}:R>", message.timestamp.as_secs()),
+        jump_url: &jump_url,
+        label: thread_info.label.as_deref().unwrap_or_default(),
+        content,
+    };
+    Some(template::render(template, &values))
+}
+
+/// まとめ投稿用に、メッセージを1行に整形する
+pub async fn digest_line(state: &BotState, thread_info: &ThreadInfo, message: &Message) -> String {
+    let content = forward_content(state, thread_info, message).await.replace('\n', " ");
+    let mut line = format!(
+        "**{}** <t:{}:t>: {}",
+        escape_markdown(&message.author.name),
+        message.timestamp.as_secs(),
+        content
+    );
+    for attachment in &message.attachments {
+        if is_spoiler_attachment(attachment) {
+            line.push_str(&format!(" 📎 ||{}||", attachment.url));
+        } else {
+            line.push_str(&format!(" 📎 {}", attachment.url));
+        }
+    }
+    line
+}
+
+/// 転送が完了したメッセージの対応を記録し、必要であればJSONLアーカイブ・添付ファイル・履歴を保存する
+///
+/// 記録できなくても転送自体は成功しているので、警告のみ出力します。
+/// Discord以外の転送先では転送先のメッセージが無いため、対応は記録しません。
+async fn record_forwarded(state: &BotState, thread_info: &ThreadInfo, message: &Message, link: Option<MessageLink>) {
+    state.stats.record_forwarded(1);
+    state.stats.record_recent_forward(RecentForward {
+        thread_id: message.channel_id,
+        message_id: message.id,
+        author: message.author.name.clone(),
+        forwarded_at: Utc::now().to_rfc3339(),
+    });
+    let bytes = message.content.len() as u64 + message.attachments.iter().map(|attachment| attachment.size).sum::<u64>();
+    state.record_mapping_forwards(message.channel_id, 1, bytes, message.attachments.len() as u64);
+    if let Some(link) = link {
+        if let Err(e) = state.message_links.record(message.id, link) {
+            println!("警告: メッセージ {} の転送先を記録できませんでした: {}", message.id, e);
+        }
+    }
+
+    if thread_info.archive == ArchiveMode::Jsonl {
+        if let Err(e) = state.archive.append(message) {
+            println!("警告: メッセージ {} をアーカイブに保存できませんでした: {}", message.id, e);
+        }
+    }
+
+    state.attachments.store_message(message).await;
+    state.history.record(message, true).await;
+    state.events.publish(state, thread_info, message).await;
+}
+
+/// 1件のメッセージをマッピングの設定に従って転送先に送信する
+///
+/// 転送先のメッセージIDを記録し、元のメッセージが編集されたときに反映できるようにします。
+pub async fn transfer_single_message(
+    state: &BotState,
+    thread_info: &ThreadInfo,
+    message: &Message,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // 振り分けルールに一致した場合は転送先を切り替える
+    let thread_info = &*thread_info.routed(&message.content);
+
+    // 転送前の待機時間が設定されている場合は待つ
+    if thread_info.forward_delay_ms > 0 {
+        tokio::time::sleep(tokio::time::Duration::from_millis(thread_info.forward_delay_ms)).await;
+    }
+
+    let sink = thread_info.sink.build(thread_info);
+    let link = sink.send(state, thread_info, message).await?;
+    record_forwarded(state, thread_info, message, link).await;
+    Ok(())
+}
+
+/// Discordのチャンネルに送信する転送先（`format` に従って通常のメッセージ・埋め込み・自動作成したWebhookで送信）
+pub struct DiscordChannelSink;
+
+#[async_trait]
+impl MessageSink for DiscordChannelSink {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    async fn send(
+        &self,
+        state: &BotState,
+        thread_info: &ThreadInfo,
+        message: &Message,
+    ) -> Result<Option<MessageLink>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Some(send_to_discord(state, thread_info, message, None).await?))
+    }
+}
+
+/// マッピングに設定したDiscordのWebhookで送信する転送先
+pub struct DiscordWebhookSink {
+    pub url: String,
+}
+
+#[async_trait]
+impl MessageSink for DiscordWebhookSink {
+    fn name(&self) -> &'static str {
+        "discord_webhook"
+    }
+
+    async fn send(
+        &self,
+        state: &BotState,
+        thread_info: &ThreadInfo,
+        message: &Message,
+    ) -> Result<Option<MessageLink>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Some(send_to_discord(state, thread_info, message, Some(&self.url)).await?))
+    }
+}
+
+/// 1件のメッセージをDiscordに送信する（`webhook_url` を指定した場合はそのWebhookで送信）
+async fn send_to_discord(
+    state: &BotState,
+    thread_info: &ThreadInfo,
+    message: &Message,
+    webhook_url: Option<&str>,
+) -> Result<MessageLink, Box<dyn std::error::Error + Send + Sync>> {
+    let http = &state.http;
+
+    // ボイスメッセージは音声ファイルを転送先に再アップロードする
+    let mut files = download_voice_files(message).await;
+    // アーカイブ用に元のメッセージのJSONを添付する
+    if thread_info.archive == ArchiveMode::Attach {
+        match archive::json_attachment(message, files.len() as u64) {
+            Ok(file) => files.push(file),
+            Err(e) => println!("警告: メッセージ {} のJSONを作成できませんでした: {}", message.id, e),
+        }
+    }
+
+    // システムメッセージは形式に関わらず、通知文だけを通常のメッセージとして送信する
+    if let Some(notice) = system_notice(message) {
+        let notice = match thread_info.label.as_deref() {
+            Some(label) => format!("`[{}]` {}", label, notice),
+            None => notice,
+        };
+        let notice = format!("{}{}", notice, format_original_timestamp(&message.timestamp));
+        let mirror = retry::with_backoff("メッセージの送信", || async {
+            Ok(http.create_message(thread_info.target_channel_id)
+                .content(&notice)?
+                .attachments(&files)?
+                .allowed_mentions(Some(&AllowedMentions::default()))
+                .await?
+                .model()
+                .await?)
+        })
+        .await?;
+        return Ok(MessageLink {
+            source_channel_id: message.channel_id,
+            channel_id: mirror.channel_id,
+            message_id: mirror.id,
+            webhook_url: None,
+            forwarded_at: Utc::now(),
+        });
+    }
+
+    let content = forward_content(state, thread_info, message).await;
+    let author_name = &message.author.name;
+    let label = thread_info.label.as_deref();
+    // ImageHashからString形式のハッシュを取得
+    let avatar_hash = message.author.avatar.as_ref().map(|hash| hash.to_string());
+    let avatar_url = get_user_avatar_url(message.author.id, avatar_hash.as_deref());
+
+    let templated = render_template(state, thread_info, message, &content).await;
+    let webhook_content = match &templated {
+        Some(text) => build_webhook_content(text, &message.attachments, None),
+        None => build_webhook_content(&content, &message.attachments, Some(&message.timestamp)),
+    };
+    // マッピングの設定に従って、転送先で通知するメンションを制限する
+    let allowed_mentions = thread_info.mentions.allowed_mentions();
+
+    // WebhookまたはRegularメッセージとして送信
+    let link = if webhook_url.is_none() && thread_info.format == MessageFormat::Webhook {
+        let embeds = mirror_embeds(state, thread_info, message, Vec::new()).await;
+        // 転送先チャンネルのWebhookを自動作成（または再利用）して送信
+        let webhook_url = state.channel_webhook_url(thread_info.target_channel_id).await?;
+        let result = send_webhook_message(
+            &webhook_url,
+            &webhook_display_name(author_name, label),
+            &avatar_url,
+            &webhook_content,
+            &embeds,
+            &files,
+            &allowed_mentions,
+        )
+        .await;
+
+        // Webhookが削除された可能性があるため、失敗した場合は次回作り直す
+        if result.is_err() {
+            state.forget_channel_webhook(thread_info.target_channel_id).await;
+        }
+        MessageLink {
+            source_channel_id: message.channel_id,
+            channel_id: thread_info.target_channel_id,
+            message_id: result?,
+            webhook_url: Some(webhook_url),
+            forwarded_at: Utc::now(),
+        }
+    } else if let Some(webhook_url) = webhook_url {
+        // Webhookを使用してメッセージを送信
+        let embeds = mirror_embeds(state, thread_info, message, Vec::new()).await;
+        let message_id = send_webhook_message(
+            webhook_url,
+            &webhook_display_name(author_name, label),
+            &avatar_url,
+            &webhook_content,
+            &embeds,
+            &files,
+            &allowed_mentions,
+        )
+        .await?;
+        MessageLink {
+            source_channel_id: message.channel_id,
+            channel_id: thread_info.target_channel_id,
+            message_id,
+            webhook_url: Some(webhook_url.to_string()),
+            forwarded_at: Utc::now(),
+        }
+    } else if thread_info.format == MessageFormat::Embed {
+        // 埋め込み形式で送信
+        // 元のメッセージの埋め込みは転送メッセージの埋め込みの後ろに付ける
+        let embed = build_forward_embed(message, &content, &avatar_url, label);
+        let embeds = mirror_embeds(state, thread_info, message, vec![embed]).await;
+        let mirror = retry::with_backoff("メッセージの送信", || async {
+            Ok(http.create_message(thread_info.target_channel_id)
+                .embeds(&embeds)?
+                .attachments(&files)?
+                .allowed_mentions(Some(&allowed_mentions))
+                .await?
+                .model()
+                .await?)
+        })
+        .await?;
+        MessageLink {
+            source_channel_id: message.channel_id,
+            channel_id: mirror.channel_id,
+            message_id: mirror.id,
+            webhook_url: None,
+            forwarded_at: Utc::now(),
+        }
+    } else {
+        // 旧方式：通常のメッセージとして送信
+        let forward_message = match &templated {
+            Some(_) => webhook_content,
+            None => build_plain_content(message, &content, label),
+        };
+        let embeds = mirror_embeds(state, thread_info, message, Vec::new()).await;
+        let mirror = retry::with_backoff("メッセージの送信", || async {
+            Ok(http.create_message(thread_info.target_channel_id)
+                .content(&forward_message)?
+                .embeds(&embeds)?
+                .attachments(&files)?
+                .allowed_mentions(Some(&allowed_mentions))
+                .await?
+                .model()
+                .await?)
+        })
+        .await?;
+        MessageLink {
+            source_channel_id: message.channel_id,
+            channel_id: mirror.channel_id,
+            message_id: mirror.id,
+            webhook_url: None,
+            forwarded_at: Utc::now(),
+        }
+    };
+
+    Ok(link)
+}
+
+/// リアクションの集計を表示用の文字列にまとめる（リアクションが無い場合は None）
+fn format_reaction_summary(reactions: &[Reaction]) -> Option<String> {
+    let parts: Vec<String> = reactions
+        .iter()
+        .filter(|reaction| reaction.count > 0)
+        .map(|reaction| {
+            let emoji = match &reaction.emoji {
+                ReactionType::Unicode { name } => name.clone(),
+                ReactionType::Custom { animated: true, id, name: Some(name) } => format!("<a:{}:{}>", name, id),
+                ReactionType::Custom { id, name: Some(name), .. } => format!("<:{}:{}>", name, id),
+                ReactionType::Custom { name: None, .. } => "❔".to_string(),
+            };
+            format!("{} {}", emoji, reaction.count)
+        })
+        .collect();
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("  "))
+    }
+}
+
+/// 転送済みのメッセージを元のメッセージの最新の内容（本文・リアクション）で更新する
+pub async fn refresh_forwarded_message(
+    state: &BotState,
+    thread_info: &ThreadInfo,
+    link: &MessageLink,
+    message: &Message,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // システムメッセージの通知文は編集やリアクションを反映しない
+    if system_notice(message).is_some() {
+        return Ok(());
+    }
+
+    let http = &state.http;
+    let content = forward_content(state, thread_info, message).await;
+    let label = thread_info.label.as_deref();
+    let reactions = format_reaction_summary(&message.reactions);
+    let allowed_mentions = thread_info.mentions.allowed_mentions();
+    let templated = render_template(state, thread_info, message, &content)
+        .await
+        .map(|text| build_webhook_content(&text, &message.attachments, None));
+
+    if let Some(webhook_url) = &link.webhook_url {
+        let mut full_content = templated
+            .unwrap_or_else(|| build_webhook_content(&content, &message.attachments, Some(&message.timestamp)));
+        if let Some(reactions) = &reactions {
+            full_content.push_str(&format!("\n-# リアクション: {}", reactions));
+        }
+        let embeds = mirror_embeds(state, thread_info, message, Vec::new()).await;
+        edit_webhook_message(webhook_url, link.message_id, &full_content, &embeds, &allowed_mentions).await?;
+    } else if thread_info.format == MessageFormat::Embed {
+        let avatar_hash = message.author.avatar.as_ref().map(|hash| hash.to_string());
+        let avatar_url = get_user_avatar_url(message.author.id, avatar_hash.as_deref());
+        let mut embed = build_forward_embed(message, &content, &avatar_url, label);
+        // リアクションの集計はフッターに表示する
+        embed.footer = reactions.map(|text| EmbedFooter {
+            icon_url: None,
+            proxy_icon_url: None,
+            text,
+        });
+        let embeds = mirror_embeds(state, thread_info, message, vec![embed]).await;
+        http.update_message(link.channel_id, link.message_id)
+            .embeds(Some(&embeds))?
+            .allowed_mentions(Some(&allowed_mentions))
+            .await?;
+    } else {
+        let mut forward_message = templated.unwrap_or_else(|| build_plain_content(message, &content, label));
+        if let Some(reactions) = &reactions {
+            forward_message.push_str(&format!("\n-# リアクション: {}", reactions));
+        }
+        http.update_message(link.channel_id, link.message_id)
+            .content(Some(&forward_message))?
+            .embeds(Some(&mirror_embeds(state, thread_info, message, Vec::new()).await))?
+            .allowed_mentions(Some(&allowed_mentions))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// ウェブフックの名前を空に設定する
+pub async fn clear_webhook_name(webhook_url: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // webhookのIDとトークンを抽出
+    let parts: Vec<&str> = webhook_url.split('/').collect();
+    if parts.len() >= 7 {
+        let webhook_id = parts[5];
+        let webhook_token = parts[6];
+        
+        println!("🔄 ウェブフック名を空に設定します: ID={}", webhook_id);
+        
+        // Webhookを更新するAPIリクエスト
+        let client = reqwest::Client::new();
+        let response = client.patch(format!("https://discord.com/api/webhooks/{}/{}", webhook_id, webhook_token))
+            .json(&json!({
+                "name": ""  // 名前を空に設定
+            }))
+            .send()
+            .await?;
+            
+        if response.status().is_success() {
+            println!("✅ ウェブフック名を空に設定しました: ID={}", webhook_id);
+            Ok(())
+        } else {
+            let status = response.status();
+            let error_body = match response.text().await {
+                Ok(body) => body,
+                Err(_) => "レスポンスボディを取得できませんでした".to_string()
+            };
+            
+            let error_msg = format!("❌ ウェブフック名設定失敗: ステータス={}, レスポンス={}", status, error_body);
+            println!("{}", error_msg);
+            Err(error_msg.into())
+        }
+    } else {
+        Err(format!("ウェブフックURLの形式が正しくありません: {}", webhook_url).into())
+    }
+}
+
+/// 1回のリクエストで取得するメッセージの最大件数（Discordの制限）
+const MESSAGE_PAGE_SIZE: u16 = 100;
+
+/// スレッドの過去のメッセージのうち、範囲内のものを全て取得する（古い順）
+///
+/// Discordは1回に100件までしか返さないため、取得した中で最も古いメッセージより前を繰り返し取得します。
+pub async fn fetch_message_history(
+    api: &dyn DiscordApi,
+    thread_id: Id<ChannelMarker>,
+    range: &BackfillRange,
+) -> Result<Vec<Message>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut messages = Vec::new();
+    let mut before = None;
+
+    loop {
+        let page = api.channel_messages(thread_id, before, MESSAGE_PAGE_SIZE).await?;
+
+        // 新しい順に返されるため、最後のメッセージが最も古い
+        let page_len = page.len();
+        before = page.last().map(|message| message.id);
+        messages.extend(page);
+        if page_len < usize::from(MESSAGE_PAGE_SIZE) {
+            break;
+        }
+        // 範囲より古いメッセージに達した場合は、それ以上取得しない
+        if messages.last().is_some_and(|oldest| !range.needs_older(messages.len(), oldest)) {
+            break;
+        }
+        println!("スレッド {} のメッセージを {} 件取得しました（続きを取得します）", thread_id, messages.len());
+    }
+
+    messages.reverse();
+    range.apply(&mut messages);
+    Ok(messages)
+}
+
+/// 過去のメッセージを全て取得して転送する
+///
+/// 転送中に届いた新しいメッセージは保留し、過去のメッセージを全て転送してから順に転送します。
+pub async fn fetch_all_messages_and_transfer(
+    state: &BotState,
+    thread_id: Id<ChannelMarker>,
+    thread_info: &ThreadInfo,
+    range: &BackfillRange,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if !state.backfills.begin(thread_id).await {
+        return Err(format!("スレッド {} の過去メッセージは既に転送中です", thread_id).into());
+    }
+    continue_backfill(state, thread_id, thread_info, range).await
+}
+
+/// 開始した過去メッセージの転送を、記録した進捗の続きから行う
+///
+/// 異常終了した転送を再開する場合は、転送中として登録したまま（保留したメッセージも残したまま）呼び出します。
+async fn continue_backfill(
+    state: &BotState,
+    thread_id: Id<ChannelMarker>,
+    thread_info: &ThreadInfo,
+    range: &BackfillRange,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // 転送中として登録されていない場合（登録を外した後に異常終了した場合）は登録し直す
+    state.backfills.begin(thread_id).await;
+
+    let result = transfer_message_history(state, thread_id, thread_info, range).await;
+    // 途中で失敗した場合も、保留していたメッセージは転送する
+    transfer_deferred_messages(state, thread_id, thread_info).await;
+    result
+}
+
+/// メッセージが現在の転送先に転送済みかどうか（リアルタイムの転送・過去の一括転送のどちらでも）
+///
+/// 転送先を変更した後は、新しい転送先には未転送として扱います。
+pub(crate) fn already_forwarded(state: &BotState, thread_info: &ThreadInfo, message: &Message) -> bool {
+    match state.message_links.get(message.id) {
+        Ok(Some(link)) => link.channel_id == thread_info.routed(&message.content).target_channel_id,
+        Ok(None) => false,
+        Err(e) => {
+            println!("警告: メッセージ {} の転送記録を確認できませんでした: {}", message.id, e);
+            false
+        }
+    }
+}
+
+/// 過去メッセージの転送中に保留したメッセージを、届いた順に転送する
+async fn transfer_deferred_messages(state: &BotState, thread_id: Id<ChannelMarker>, thread_info: &ThreadInfo) {
+    loop {
+        let messages = state.backfills.take_pending_or_finish(thread_id).await;
+        if messages.is_empty() {
+            break;
+        }
+
+        for message in messages {
+            // 履歴の取得と同時に届いたメッセージは、過去のメッセージとして転送済みの場合がある
+            if already_forwarded(state, thread_info, &message) {
+                continue;
+            }
+            if let Err(e) = transfer_single_message(state, thread_info, &message).await {
+                eprintln!("保留していたメッセージ {} の転送に失敗しました: {}", message.id, e);
+                state.alerts.mapping_failure(&state.http, &format!("<#{}> の保留していたメッセージの転送", thread_id), thread_id, thread_info, &*e).await;
+            }
+        }
+    }
+}
+
+/// スレッドの履歴を古い順に1件ずつ転送する
+async fn transfer_message_history(
+    state: &BotState,
+    thread_id: Id<ChannelMarker>,
+    thread_info: &ThreadInfo,
+    range: &BackfillRange,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let http = &state.http;
+    println!("スレッド {} の全メッセージ転送を開始します...", thread_id);
+
+    // 進捗を表示するメッセージを送信（以降はこのメッセージを編集して進捗を表示する）
+    let mut progress = ProgressMessage::send(
+        http,
+        thread_info.target_channel_id,
+        "🔍 過去のメッセージを検索しています...",
+    )
+    .await?;
+
+    // メッセージ履歴を全て取得
+    let mut messages = fetch_message_history(state.api.as_ref(), thread_id, range).await?;
+    println!("{} 件のメッセージを取得しました", messages.len());
+
+    // 前回の転送が途中で中断していた場合は、その続きから転送する
+    let checkpoint = state.backfill_checkpoint(thread_id)?;
+    if let Some(checkpoint) = checkpoint {
+        messages.retain(|message| message.id > checkpoint);
+        println!("スレッド {} の前回の転送はメッセージ {} まで完了しているため、続きから転送します", thread_id, checkpoint);
+    }
+    let message_count = messages.len();
+    
+    // 転送開始
+    let title = if checkpoint.is_some() {
+        format!("🚀 前回中断したところから **{}件** のメッセージを転送しています", message_count)
+    } else if !range.is_all() {
+        format!("🚀 指定された範囲の **{}件** のメッセージを転送しています", message_count)
+    } else {
+        format!("🚀 **{}件** のメッセージを転送しています", message_count)
+    };
+    progress.start(http, title, message_count).await;
+
+    // 転送先で順番が入れ替わらないよう、古い順に1件ずつ送信を完了してから次に進む
+    let mut skipped = 0;
+    let mut pacer = Pacer::new();
+    for (index, message) in messages.iter().enumerate() {
+        // `!cancel` で中止された場合は、記録した進捗を残して終了する
+        if state.backfills.is_cancelled(thread_id).await {
+            progress
+                .finish(
+                    http,
+                    &format!("⏹️ {}/{}件目で転送をキャンセルしました。もう一度実行すると続きから転送します", index, message_count),
+                )
+                .await;
+            println!("スレッド {} の全メッセージ転送がキャンセルされました", thread_id);
+            return Ok(());
+        }
+
+        // 転送済みのメッセージは、再実行しても重複して転送しない
+        if already_forwarded(state, thread_info, message) {
+            skipped += 1;
+        }
+        // システムメッセージや（設定によっては）ボットのメッセージは除外
+        else if should_forward(state, message, thread_info).await {
+            // 転送処理
+            if let Err(e) = transfer_single_message(state, thread_info, message).await {
+                progress
+                    .finish(
+                        http,
+                        &format!("⚠️ {}/{}件目で転送が中断しました。もう一度実行すると続きから転送します", index, message_count),
+                    )
+                    .await;
+                return Err(e);
+            }
+
+            // レート制限の状態に合わせて間隔を空ける
+            pacer.pace().await;
+        }
+
+        // 中断した場合に続きから再開できるよう、処理済みのメッセージを記録する
+        if let Err(e) = state.save_backfill_checkpoint(thread_id, message.id) {
+            println!("警告: スレッド {} の転送の進捗を記録できませんでした: {}", thread_id, e);
+        }
+        progress.report(http, index + 1).await;
+    }
+
+    if let Err(e) = state.clear_backfill_checkpoint(thread_id) {
+        println!("警告: スレッド {} の転送の進捗を削除できませんでした: {}", thread_id, e);
+    }
+    
+    // 転送完了
+    let complete_message = if skipped > 0 {
+        format!("✅ **{}件** のメッセージの転送が完了しました（転送済みの {}件 はスキップしました）", message_count, skipped)
+    } else {
+        format!("✅ **{}件** のメッセージの転送が完了しました", message_count)
+    };
+    progress.finish(http, &complete_message).await;
+        
+    println!("スレッド {} の全メッセージ転送が完了しました", thread_id);
+    
+    Ok(())
+}
+
+/// 過去メッセージの転送を開始する
+///
+/// 転送中も他のイベントを処理できるよう、全メッセージ転送処理は別のタスクで実行する
+/// 転送中にパニックした場合は、記録した進捗の続きから転送し直します。
+pub fn spawn_backfill(state: &Arc<BotState>, thread_id: Id<ChannelMarker>, thread_info: ThreadInfo, range: BackfillRange) {
+    let task_state = Arc::clone(state);
+    let started = Arc::new(AtomicBool::new(false));
+    supervisor::supervise(state, format!("<#{}> の過去メッセージの転送", thread_id), move || {
+        let (state, thread_info, range, started) =
+            (Arc::clone(&task_state), thread_info.clone(), range, Arc::clone(&started));
+        async move {
+            // 2回目以降は異常終了した転送の再開
+            let result = if started.swap(true, Ordering::Relaxed) {
+                continue_backfill(&state, thread_id, &thread_info, &range).await
+            } else {
+                fetch_all_messages_and_transfer(&state, thread_id, &thread_info, &range).await
+            };
+            if let Err(e) = result {
+                eprintln!("スレッド {} の全メッセージ転送中にエラーが発生しました: {}", thread_id, e);
+                state.alerts.mapping_failure(&state.http, &format!("<#{}> の過去メッセージの転送", thread_id), thread_id, &thread_info, &*e).await;
+            }
+        }
+    });
+}
+
+/// Discordにアップロードできるファイルの最大サイズ（これを超える記録はディスクに書き出す）
+const MAX_UPLOAD_BYTES: usize = 25 * 1024 * 1024;
+
+/// スレッドの履歴を1つのファイル（Markdown・HTML）にまとめて転送先にアップロードする
+///
+/// 大量のメッセージを1件ずつ転送する代わりに使用します。アップロードできない大きさの場合はアーカイブのディレクトリに書き出します。
+pub async fn export_transcript(
+    state: &BotState,
+    thread_id: Id<ChannelMarker>,
+    thread_info: &ThreadInfo,
+    format: TranscriptFormat,
+    range: &BackfillRange,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let http = &state.http;
+    let messages = fetch_message_history(state.api.as_ref(), thread_id, range).await?;
+
+    // 転送と同じ絞り込み・変換を適用する
+    let mut entries = Vec::new();
+    for message in &messages {
+        if !should_forward(state, message, thread_info).await {
+            continue;
+        }
+        entries.push(TranscriptEntry {
+            author: message.author.global_name.clone().unwrap_or_else(|| message.author.name.clone()),
+            timestamp: format_jst_timestamp(&message.timestamp),
+            content: forward_content(state, thread_info, message).await,
+            attachments: message
+                .attachments
+                .iter()
+                .map(|attachment| (attachment.filename.clone(), attachment.url.clone()))
+                .collect(),
+        });
+    }
+
+    let thread_name = state.channel_name(thread_id).await.unwrap_or_else(|| thread_id.to_string());
+    let transcript = transcript::render(format, &thread_name, &entries);
+    let file_name = format!("transcript-{}.{}", thread_id, format.extension());
+    let link = state.channel_link(thread_id, thread_info.target_channel_id).await;
+    let header = format!("📄 スレッド **{}** ({}) の記録（{}件）", escape_markdown(&thread_name), link, entries.len());
+
+    if transcript.len() > MAX_UPLOAD_BYTES {
+        let path = state.archive.write_file(&file_name, transcript.as_bytes())?;
+        println!("スレッド {} の記録が大きすぎるため {} に書き出しました", thread_id, path.display());
+        http.create_message(thread_info.target_channel_id)
+            .content(&format!("{}\nファイルが大きすぎてアップロードできないため、ボットのサーバーの `{}` に保存しました。", header, path.display()))?
+            .await?;
+        return Ok(());
+    }
+
+    let files = [HttpAttachment::from_bytes(file_name, transcript.into_bytes(), 1)];
+    http.create_message(thread_info.target_channel_id)
+        .content(&header)?
+        .attachments(&files)?
+        .await?;
+
+    println!("スレッド {} の記録（{}件）を転送先にアップロードしました", thread_id, entries.len());
+    Ok(())
+}
+
+/// ボットの停止中や切断中に投稿されたメッセージを転送する
+///
+/// 転送の記録があるスレッドごとに、最後に転送したメッセージより後のメッセージを取得して転送待ちの列に追加します。
+/// 列に残っているメッセージと重複しても、転送時に転送済みかどうかを確認するため二重には転送されません。
+async fn recover_missed_messages(state: &Arc<BotState>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let latest = state.message_links.latest_per_channel()?;
+
+    for (thread_id, last_message_id) in latest {
+        let Some(thread_info) = state.resolve_thread_info(thread_id).await else {
+            continue;
+        };
+        if !thread_info.active {
+            continue;
+        }
+        // 過去メッセージの転送中のスレッドは、その転送で取得される
+        if !state.backfills.begin(thread_id).await {
+            continue;
+        }
+
+        let range = BackfillRange {
+            after: Some(last_message_id),
+            ..BackfillRange::default()
+        };
+        let result = recover_thread_messages(state, thread_id, &thread_info, &range).await;
+        enqueue_deferred_messages(state, thread_id, &thread_info).await;
+        if let Err(e) = result {
+            eprintln!("スレッド {} の未転送メッセージの転送中にエラーが発生しました: {}", thread_id, e);
+            state.alerts.mapping_failure(&state.http, &format!("<#{}> の未転送メッセージの転送", thread_id), thread_id, &thread_info, &*e).await;
+        }
+    }
+
+    Ok(())
+}
+
+/// 回収中に保留したメッセージを、届いた順に転送待ちの列に追加する（回収したメッセージより後に転送する）
+async fn enqueue_deferred_messages(state: &Arc<BotState>, thread_id: Id<ChannelMarker>, thread_info: &ThreadInfo) {
+    loop {
+        let messages = state.backfills.take_pending_or_finish(thread_id).await;
+        if messages.is_empty() {
+            break;
+        }
+        for message in messages {
+            state.queues.enqueue(state, thread_info.clone(), message).await;
+        }
+    }
+}
+
+/// 1つのスレッドの未転送メッセージを取得して転送待ちの列に追加する
+///
+/// リアルタイムの転送と同じ列を通すことで、スレッド内の順番を保ちます。
+async fn recover_thread_messages(
+    state: &Arc<BotState>,
+    thread_id: Id<ChannelMarker>,
+    thread_info: &ThreadInfo,
+    range: &BackfillRange,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let messages = fetch_message_history(state.api.as_ref(), thread_id, range).await?;
+    if messages.is_empty() {
+        return Ok(());
+    }
+    println!("スレッド {} の未転送のメッセージ {} 件を転送します", thread_id, messages.len());
+
+    for message in &messages {
+        if already_forwarded(state, thread_info, message) || !should_forward(state, message, thread_info).await {
+            continue;
+        }
+
+        // まとめ投稿のマッピングは、リアルタイムの転送と同じくキューに追加する
+        if thread_info.digest_enabled() {
+            let thread_info = thread_info.routed(&message.content);
+            let line = digest_line(state, &thread_info, message).await;
+            state.digests.push(state, thread_id, &thread_info, line).await?;
+            continue;
+        }
+
+        state.queues.enqueue(state, thread_info.clone(), message.clone()).await;
+    }
+
+    Ok(())
+}
+
+/// 親チャンネル配下のスレッドを全て取得する（アクティブなスレッドとアーカイブ済みのスレッド、作成順）
+async fn fetch_child_threads(
+    state: &BotState,
+    parent_id: Id<ChannelMarker>,
+) -> Result<Vec<Channel>, Box<dyn std::error::Error + Send + Sync>> {
+    let http = &state.http;
+    let guild_id = state
+        .channel_guild(parent_id)
+        .await
+        .ok_or_else(|| format!("チャンネル {} のサーバーを取得できませんでした", parent_id))?;
+
+    // アクティブなスレッドはサーバー全体の一覧から絞り込む
+    let mut threads: Vec<Channel> = http
+        .active_threads(guild_id)
+        .await?
+        .model()
+        .await?
+        .threads
+        .into_iter()
+        .filter(|thread| thread.parent_id == Some(parent_id))
+        .collect();
+
+    // アーカイブ済みのスレッドは、アーカイブされた日時をさかのぼって繰り返し取得する
+    for private in [false, true] {
+        let mut before: Option<String> = None;
+        loop {
+            let listing = match (private, before.as_deref()) {
+                (false, Some(before)) => http.public_archived_threads(parent_id).before(before).await,
+                (false, None) => http.public_archived_threads(parent_id).await,
+                (true, Some(before)) => http.private_archived_threads(parent_id).before(before).await,
+                (true, None) => http.private_archived_threads(parent_id).await,
+            };
+            // プライベートスレッドの一覧にはスレッドの管理権限が必要なため、取得できなくても続ける
+            let listing = match listing {
+                Ok(response) => response.model().await?,
+                Err(e) if private => {
+                    println!("警告: チャンネル {} のアーカイブ済みプライベートスレッドを取得できませんでした: {}", parent_id, e);
+                    break;
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            before = listing
+                .threads
+                .last()
+                .and_then(|thread| thread.thread_metadata.as_ref())
+                .map(|metadata| metadata.archive_timestamp.iso_8601().to_string());
+            threads.extend(listing.threads);
+            if listing.has_more != Some(true) || before.is_none() {
+                break;
+            }
+        }
+    }
+
+    threads.sort_by_key(|thread| thread.id);
+    threads.dedup_by_key(|thread| thread.id);
+    Ok(threads)
+}
+
+/// 親チャンネル配下の全スレッドの過去メッセージを、スレッドごとに見出しを付けて転送する
+pub async fn transfer_all_threads(
+    state: &BotState,
+    parent_id: Id<ChannelMarker>,
+    range: &BackfillRange,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let threads = fetch_child_threads(state, parent_id).await?;
+    println!("チャンネル {} 配下のスレッドを {} 件取得しました", parent_id, threads.len());
+
+    for thread in threads {
+        state.cache_channel(&thread).await;
+        // スレッド個別のマッピングがあればそちらを使う
+        let Some(thread_info) = state.resolve_thread_info(thread.id).await else {
+            continue;
+        };
+        if !thread_info.active {
+            println!("スレッド {} の転送は一時停止中のためスキップします", thread.id);
+            continue;
+        }
+
+        let name = thread.name.as_deref().map(escape_markdown).unwrap_or_default();
+        let link = state.channel_link(thread.id, thread_info.target_channel_id).await;
+        state
+            .http
+            .create_message(thread_info.target_channel_id)
+            .content(&format!("📂 スレッド **{}** ({}) の過去メッセージ", name, link))?
+            .await?;
+
+        // 1つのスレッドで失敗しても、残りのスレッドの転送は続ける
+        if let Err(e) = fetch_all_messages_and_transfer(state, thread.id, &thread_info, range).await {
+            eprintln!("スレッド {} の全メッセージ転送中にエラーが発生しました: {}", thread.id, e);
+            state.alerts.mapping_failure(&state.http, &format!("<#{}> の過去メッセージの転送", thread.id), thread.id, &thread_info, &*e).await;
+        }
+    }
+
+    println!("チャンネル {} 配下の全スレッドの転送が完了しました", parent_id);
+    Ok(())
+}
+
+/// 未転送のメッセージの転送を別のタスクで開始する（起動時・再接続時）
+pub fn spawn_missed_message_recovery(state: &Arc<BotState>) {
+    let state = Arc::clone(state);
+    tokio::spawn(async move {
+        if let Err(e) = recover_missed_messages(&state).await {
+            eprintln!("未転送のメッセージの確認中にエラーが発生しました: {}", e);
+            state.alerts.failure(&state.http, "未転送のメッセージの確認", &*e).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use twilight_model::id::marker::UserMarker;
+    use twilight_model::user::User;
+
+    /// 指定した件数のメッセージを持つチャンネルとして応答する `DiscordApi`（取得の要求を記録する）
+    struct FakeApi {
+        /// チャンネルのメッセージ（新しい順）
+        messages: Vec<Message>,
+        /// `channel_messages` に渡された `before`
+        requests: Mutex<Vec<Option<Id<MessageMarker>>>>,
+    }
+
+    impl FakeApi {
+        fn with_messages(count: u64) -> Self {
+            Self {
+                messages: (1..=count).rev().map(fake_message).collect(),
+                requests: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    fn fake_message(id: u64) -> Message {
+        serde_json::from_value(serde_json::json!({
+            "id": id.to_string(),
+            "channel_id": "1",
+            "author": { "id": "2", "username": "user", "discriminator": "0", "avatar": null },
+            "content": format!("message {}", id),
+            "timestamp": "2024-01-01T00:00:00+00:00",
+            "edited_timestamp": null,
+            "tts": false,
+            "mention_everyone": false,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": [],
+            "embeds": [],
+            "pinned": false,
+            "type": 0
+        }))
+        .expect("テスト用のメッセージを作成できませんでした")
+    }
+
+    #[async_trait::async_trait]
+    impl DiscordApi for FakeApi {
+        async fn create_message(
+            &self,
+            _channel_id: Id<ChannelMarker>,
+            _content: &str,
+        ) -> Result<Message, Box<dyn std::error::Error + Send + Sync>> {
+            Err("テストでは送信しない".into())
+        }
+
+        async fn channel_messages(
+            &self,
+            _channel_id: Id<ChannelMarker>,
+            before: Option<Id<MessageMarker>>,
+            limit: u16,
+        ) -> Result<Vec<Message>, Box<dyn std::error::Error + Send + Sync>> {
+            self.requests.lock().unwrap().push(before);
+            Ok(self
+                .messages
+                .iter()
+                .filter(|message| before.is_none_or(|before| message.id < before))
+                .take(usize::from(limit))
+                .cloned()
+                .collect())
+        }
+
+        async fn channel(&self, _channel_id: Id<ChannelMarker>) -> Result<Channel, Box<dyn std::error::Error + Send + Sync>> {
+            Err("テストではチャンネルを取得しない".into())
+        }
+
+        async fn message(
+            &self,
+            _channel_id: Id<ChannelMarker>,
+            message_id: Id<MessageMarker>,
+        ) -> Result<Message, Box<dyn std::error::Error + Send + Sync>> {
+            Ok(fake_message(message_id.get()))
+        }
+
+        async fn user(&self, _user_id: Id<UserMarker>) -> Result<User, Box<dyn std::error::Error + Send + Sync>> {
+            Err("テストではユーザーを取得しない".into())
+        }
+    }
+
+    fn ids(messages: &[Message]) -> Vec<u64> {
+        messages.iter().map(|message| message.id.get()).collect()
+    }
+
+    #[tokio::test]
+    async fn fetch_message_history_pages_through_all_messages_oldest_first() {
+        let api = FakeApi::with_messages(250);
+        let thread_id = Id::new(1);
+
+        let messages = fetch_message_history(&api, thread_id, &BackfillRange::default()).await.unwrap();
+
+        assert_eq!(ids(&messages), (1..=250).collect::<Vec<_>>());
+        assert_eq!(*api.requests.lock().unwrap(), vec![None, Some(Id::new(151)), Some(Id::new(51))]);
+    }
+
+    #[tokio::test]
+    async fn fetch_message_history_stops_once_count_is_reached() {
+        let api = FakeApi::with_messages(250);
+        let range = BackfillRange {
+            count: Some(120),
+            ..BackfillRange::default()
+        };
+
+        let messages = fetch_message_history(&api, Id::new(1), &range).await.unwrap();
+
+        assert_eq!(ids(&messages), (131..=250).collect::<Vec<_>>());
+        assert_eq!(api.requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn fetch_message_history_stops_at_after() {
+        let api = FakeApi::with_messages(250);
+        let range = BackfillRange {
+            after: Some(Id::new(180)),
+            ..BackfillRange::default()
+        };
+
+        let messages = fetch_message_history(&api, Id::new(1), &range).await.unwrap();
+
+        assert_eq!(ids(&messages), (181..=250).collect::<Vec<_>>());
+        assert_eq!(api.requests.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,211 @@
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Mutex;
+use twilight_model::channel::message::{AllowedMentions, ReactionType};
+use twilight_model::channel::Message;
+use twilight_model::gateway::GatewayReaction;
+use twilight_model::id::{marker::MessageMarker, Id};
+
+use crate::retry::{self, SendError};
+use crate::state::{BotState, ThreadInfo};
+
+/// GitHub のトークンを指定する環境変数
+pub const TOKEN_ENV: &str = "GITHUB_TOKEN";
+
+/// GitHub の API のURL（GitHub Enterprise Server の場合に変更する）
+const API_URL_ENV: &str = "GITHUB_API_URL";
+
+/// Issue を作成するリアクション
+pub const ISSUE_EMOJI: &str = "🐛";
+
+/// Issue のタイトルにする本文の最大の長さ（文字数）
+const TITLE_LENGTH: usize = 80;
+
+/// メッセージから作成した GitHub の Issue の記録
+///
+/// 同じメッセージに何度リアクションを付けても、Issue は1回だけ作成します。
+/// 記録はマッピングと同じデータベースの `github_issues` テーブルに保存します。
+#[derive(Default)]
+pub struct GitHubIssues {
+    conn: Option<Mutex<Connection>>,
+    /// 作成中・作成済みのメッセージ（同時に付いたリアクションで二重に作成しないため）
+    claimed: Mutex<HashSet<Id<MessageMarker>>>,
+}
+
+impl GitHubIssues {
+    /// 作成した Issue を記録するデータベースを開く
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = Connection::open(path)
+            .map_err(|e| format!("データベース {} を開けませんでした: {}", path.display(), e))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS github_issues (
+                message_id INTEGER PRIMARY KEY,
+                repository TEXT NOT NULL,
+                issue_url TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );",
+        )?;
+        Ok(Self {
+            conn: Some(Mutex::new(conn)),
+            claimed: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// メッセージから作成済みの Issue のURL
+    fn issue_url(&self, message_id: Id<MessageMarker>) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(conn) = &self.conn else {
+            return Ok(None);
+        };
+        let url = conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT issue_url FROM github_issues WHERE message_id = ?1", params![message_id.get() as i64], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        Ok(url)
+    }
+
+    /// 作成した Issue を記録する
+    fn record(&self, message_id: Id<MessageMarker>, repository: &str, issue_url: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let Some(conn) = &self.conn else {
+            return Ok(());
+        };
+        conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO github_issues (message_id, repository, issue_url, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![message_id.get() as i64, repository, issue_url, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+}
+
+/// Issue のタイトル（スレッド名と本文の1行目）
+fn issue_title(thread_name: &str, message: &Message) -> String {
+    let first_line = message.content.lines().find(|line| !line.trim().is_empty()).unwrap_or("");
+    let mut title: String = first_line.chars().take(TITLE_LENGTH).collect();
+    if first_line.chars().count() > TITLE_LENGTH {
+        title.push('…');
+    }
+    if title.is_empty() {
+        title = format!("{} のメッセージ", message.author.name);
+    }
+    format!("[{}] {}", thread_name, title)
+}
+
+/// Issue の本文（メッセージの本文・送信者・添付ファイル・元のメッセージへのリンク）
+fn issue_body(message: &Message, jump_url: Option<&str>) -> String {
+    let quoted: Vec<String> = message.content.lines().map(|line| format!("> {}", line)).collect();
+    let mut body = vec![
+        quoted.join("\n"),
+        String::new(),
+        format!("- 送信者: {} (`{}`)", message.author.name, message.author.id),
+        format!("- 投稿日時: {}", message.timestamp.iso_8601()),
+    ];
+    if let Some(url) = jump_url {
+        body.push(format!("- 元のメッセージ: {}", url));
+    }
+    for attachment in &message.attachments {
+        body.push(format!("- 添付ファイル: [{}]({})", attachment.filename, attachment.url));
+    }
+    body.push(String::new());
+    body.push("_Thread2Channel で Discord のメッセージから作成しました_".to_string());
+    body.join("\n")
+}
+
+/// GitHub に Issue を作成し、そのURLを返す
+async fn create_issue(repository: &str, title: &str, body: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let token = std::env::var(TOKEN_ENV)
+        .map_err(|_| format!("GitHub の Issue を作成するには環境変数 {} を設定してください", TOKEN_ENV))?;
+    let api_url = std::env::var(API_URL_ENV).unwrap_or_else(|_| "https://api.github.com".to_string());
+    let url = format!("{}/repos/{}/issues", api_url.trim_end_matches('/'), repository);
+    let payload = json!({ "title": title, "body": body });
+    let client = reqwest::Client::new();
+
+    retry::with_backoff("GitHub の Issue の作成", || async {
+        let response = client
+            .post(&url)
+            .bearer_auth(&token)
+            .header("Accept", "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .header("User-Agent", "Thread2Channel")
+            .json(&payload)
+            .send()
+            .await
+            .map_err(|e| SendError::network(format!("GitHub の Issue の作成に失敗しました: {}", e)))?;
+        let status = response.status();
+        let created: Value = response.json().await.unwrap_or_default();
+        if status.is_success() {
+            return created["html_url"]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| "GitHub の応答に Issue のURLがありません".into());
+        }
+        let detail = created["message"].as_str().unwrap_or("unknown error");
+        Err(SendError::status(status.as_u16(), format!("GitHub の Issue の作成に失敗しました: {} {}", status, detail)).into())
+    })
+    .await
+}
+
+/// 🐛 のリアクションが付いたメッセージから GitHub の Issue を作成する
+///
+/// `github=owner/repo` を指定したマッピングで、権限の必要なコマンドを実行できるユーザー（管理者・メッセージの管理の権限など）がリアクションを付けた場合のみ作成します。
+/// 作成した Issue のURLは、元のメッセージへの返信でスレッドに投稿します。
+pub async fn handle_reaction(
+    reaction: &GatewayReaction,
+    thread_info: &ThreadInfo,
+    state: &BotState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(repository) = &thread_info.github_repo else {
+        return Ok(());
+    };
+    if !matches!(&reaction.emoji, ReactionType::Unicode { name } if name == ISSUE_EMOJI) {
+        return Ok(());
+    }
+    let (Some(guild_id), Some(member)) = (reaction.guild_id, &reaction.member) else {
+        return Ok(());
+    };
+    if member.user.bot || !state.permissions.allows_member(&state.http, guild_id, reaction.user_id, &member.roles).await {
+        return Ok(());
+    }
+
+    let issues = &state.github_issues;
+    if issues.issue_url(reaction.message_id)?.is_some() || !issues.claimed.lock().unwrap().insert(reaction.message_id) {
+        return Ok(());
+    }
+    let result = open_issue(reaction, repository, state).await;
+    if result.is_err() {
+        // 失敗した場合は、もう一度リアクションを付けると作成し直せるようにする
+        issues.claimed.lock().unwrap().remove(&reaction.message_id);
+    }
+    result
+}
+
+/// メッセージを取得して Issue を作成し、スレッドに通知する
+async fn open_issue(reaction: &GatewayReaction, repository: &str, state: &BotState) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let message = state.http.message(reaction.channel_id, reaction.message_id).await?.model().await?;
+    let thread_name = state.channel_name(reaction.channel_id).await.unwrap_or_else(|| reaction.channel_id.to_string());
+    let jump_url = state.message_url(&message).await;
+
+    let issue_url = create_issue(repository, &issue_title(&thread_name, &message), &issue_body(&message, jump_url.as_deref())).await?;
+    println!("メッセージ {} から {} の Issue を作成しました: {}", message.id, repository, issue_url);
+    if let Err(e) = state.github_issues.record(message.id, repository, &issue_url) {
+        println!("警告: メッセージ {} から作成した Issue を記録できませんでした: {}", message.id, e);
+    }
+
+    let allowed_mentions = AllowedMentions::default();
+    let notice = format!("{} Issue を作成しました: <{}>", ISSUE_EMOJI, issue_url);
+    if let Err(e) = state
+        .http
+        .create_message(reaction.channel_id)
+        .reply(message.id)
+        .allowed_mentions(Some(&allowed_mentions))
+        .content(&notice)?
+        .await
+    {
+        println!("警告: 作成した Issue をスレッド {} に通知できませんでした: {}", reaction.channel_id, e);
+    }
+    Ok(())
+}
@@ -0,0 +1,256 @@
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::Duration;
+
+use crate::state::BotState;
+use crate::{dashboard, feed, inbound, stats, supervisor};
+
+/// この時間ゲートウェイから何も受信しなければ、接続が切れたものとみなす
+///
+/// 接続中はハートビートの応答が1分以内の間隔で届くため、余裕を持たせています。
+const GATEWAY_STALE_AFTER: Duration = Duration::from_secs(3 * 60);
+
+/// JSONの応答の Content-Type
+const JSON: &str = "application/json";
+
+/// `/metrics` の応答の Content-Type（Prometheus のテキスト形式）
+const METRICS: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// リクエストの受信を待つ最大の時間
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 読み込むリクエスト行とヘッダーの最大の長さ
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+
+/// 読み込む本文の最大の長さ（外部からの投稿のJSONに使用する）
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// 受信したHTTPリクエスト
+pub struct Request {
+    pub method: String,
+    /// パス（クエリは除く）
+    pub path: String,
+    /// ヘッダー（名前は小文字）
+    headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// ヘッダーの値を取得する（名前の大文字・小文字は区別しない）
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        self.headers.iter().find(|(key, _)| *key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// 応答（ステータス・Content-Type・本文）
+pub type Response = (&'static str, &'static str, String);
+
+/// JSONの応答を作成する
+pub fn json_response(status: &'static str, body: Value) -> Response {
+    (status, JSON, body.to_string())
+}
+
+/// ボットが動き続けているかどうか（`/healthz`）
+///
+/// ゲートウェイから一定時間何も受信していない場合は、シャードが応答しなくなったものとみなします。
+/// 起動直後でまだ何も受信していない場合は、同じ時間だけ猶予を設けます。
+fn is_alive(state: &BotState) -> bool {
+    match state.stats.last_event_age() {
+        Some(age) => age < GATEWAY_STALE_AFTER,
+        None => state.stats.uptime() < GATEWAY_STALE_AFTER,
+    }
+}
+
+/// ボットがメッセージを転送できる状態かどうか（`/readyz`）
+fn is_ready(state: &BotState) -> bool {
+    let (connected, total) = state.stats.connected_shards();
+    state.stats.last_event_age().is_some_and(|age| age < GATEWAY_STALE_AFTER) && total > 0 && connected == total
+}
+
+/// 応答の本文（ゲートウェイの接続状態・最後にイベントを受信した時刻・マッピングの読み込み状態）
+fn status_body(state: &BotState, ok: bool) -> Value {
+    let (connected, total) = state.stats.connected_shards();
+    json!({
+        "status": if ok { "ok" } else { "unavailable" },
+        "uptime_secs": state.stats.uptime().as_secs(),
+        "gateway": {
+            "shards": total,
+            "connected_shards": connected,
+            "last_event_secs_ago": state.stats.last_event_age().map(|age| age.as_secs()),
+        },
+        "mappings": {
+            "threads": state.thread_mappings.load().len(),
+            "parents": state.parent_mappings.load().len(),
+            "reload_error": state.stats.reload_error(),
+        },
+    })
+}
+
+/// リクエスト行・ヘッダー・本文を読み込む（タイムアウトした場合や形式が正しくない場合は None）
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<Request>> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 4096];
+    let header_end = loop {
+        if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position;
+        }
+        if buffer.len() > MAX_HEADER_SIZE {
+            return Ok(None);
+        }
+        let received = match tokio::time::timeout(REQUEST_TIMEOUT, stream.read(&mut chunk)).await {
+            Ok(received) => received?,
+            Err(_) => return Ok(None),
+        };
+        if received == 0 {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk[..received]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(target)) = (request_line.next(), request_line.next()) else {
+        return Ok(None);
+    };
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    if content_length > MAX_BODY_SIZE {
+        return Ok(None);
+    }
+    let mut body = buffer[header_end + 4..].to_vec();
+    while body.len() < content_length {
+        let received = match tokio::time::timeout(REQUEST_TIMEOUT, stream.read(&mut chunk)).await {
+            Ok(received) => received?,
+            Err(_) => return Ok(None),
+        };
+        if received == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..received]);
+    }
+    body.truncate(content_length);
+
+    Ok(Some(Request {
+        method: method.to_string(),
+        path: target.split('?').next().unwrap_or(target).to_string(),
+        headers,
+        body,
+    }))
+}
+
+/// リクエストのパスに応じた応答を作成する
+async fn route(state: &Arc<BotState>, request: &Request, inbound_token: Option<&str>, dashboard_token: Option<&str>) -> Response {
+    match request.path.as_str() {
+        "/healthz" => {
+            let ok = is_alive(state);
+            json_response(if ok { "200 OK" } else { "503 Service Unavailable" }, status_body(state, ok))
+        }
+        "/readyz" => {
+            let ok = is_ready(state);
+            json_response(if ok { "200 OK" } else { "503 Service Unavailable" }, status_body(state, ok))
+        }
+        "/metrics" => match stats::metrics_text(state) {
+            Ok(text) => ("200 OK", METRICS, text),
+            Err(e) => json_response("500 Internal Server Error", json!({ "error": e.to_string() })),
+        },
+        path => {
+            if let Some(file_name) = path.strip_prefix("/feeds/") {
+                return feed::handle(state, file_name).await;
+            }
+            if let Some(token) = dashboard_token {
+                if path == "/dashboard" {
+                    return dashboard::page();
+                }
+                if let Some(endpoint) = path.strip_prefix("/api/") {
+                    return dashboard::handle_api(state, request, endpoint, token).await;
+                }
+            }
+            match (path.strip_prefix("/inbound/"), inbound_token) {
+                (Some(thread_id), Some(token)) => inbound::handle(state, request, thread_id, token).await,
+                _ => json_response("404 Not Found", json!({ "error": "not found" })),
+            }
+        }
+    }
+}
+
+/// 1件のリクエストに応答して接続を閉じる
+async fn handle_connection(
+    state: &Arc<BotState>,
+    mut stream: TcpStream,
+    inbound_token: Option<&str>,
+    dashboard_token: Option<&str>,
+) -> std::io::Result<()> {
+    let (status, content_type, body) = match read_request(&mut stream).await? {
+        Some(request) => route(state, &request, inbound_token, dashboard_token).await,
+        None => json_response("400 Bad Request", json!({ "error": "bad request" })),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// `/healthz`・`/readyz`・`/metrics` を返すHTTPサーバーを起動する
+///
+/// `/healthz` はシャードが応答しなくなった場合に、`/readyz` は全てのシャードの接続が済んでいない場合に 503 を返します。
+/// Docker のヘルスチェックや Kubernetes の liveness / readiness probe に使用できます。
+/// `/metrics` はマッピングごとの転送の集計を Prometheus のテキスト形式で返します。
+/// `inbound_token` を指定した場合は、外部からの投稿を `POST /inbound/<スレッドID>` で受け付けます。
+/// `dashboard_token` を指定した場合は、管理画面を `/dashboard`、管理用の API を `/api/` で公開します。
+pub fn spawn_health_server(state: Arc<BotState>, addr: SocketAddr, inbound_token: Option<String>, dashboard_token: Option<String>) {
+    let server_inbound_token: Option<Arc<str>> = inbound_token.map(Arc::from);
+    let server_dashboard_token: Option<Arc<str>> = dashboard_token.map(Arc::from);
+    let server_state = Arc::clone(&state);
+    supervisor::supervise(&state, "死活監視のHTTPサーバー", move || {
+        let state = Arc::clone(&server_state);
+        let inbound_token = server_inbound_token.clone();
+        let dashboard_token = server_dashboard_token.clone();
+        async move {
+            let listener = match TcpListener::bind(addr).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("死活監視のHTTPサーバーを {} で起動できませんでした: {}", addr, e);
+                    return;
+                }
+            };
+            println!("死活監視のHTTPサーバーを起動しました: http://{}/healthz", addr);
+
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        eprintln!("死活監視のHTTPサーバーで接続を受け付けられませんでした: {}", e);
+                        continue;
+                    }
+                };
+                let state = Arc::clone(&state);
+                let inbound_token = inbound_token.clone();
+                let dashboard_token = dashboard_token.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_connection(&state, stream, inbound_token.as_deref(), dashboard_token.as_deref()).await {
+                        println!("死活監視のリクエストに応答できませんでした: {}", e);
+                    }
+                });
+            }
+        }
+    });
+}
@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::time::{interval_at, Duration, Instant};
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use crate::config::{self, ConfigFile};
+use crate::state::BotState;
+use crate::{stats, supervisor};
+
+/// 運用チャンネルへの稼働状況の投稿とゲートウェイの再接続の通知の設定
+#[derive(Debug, Clone)]
+pub struct HeartbeatSettings {
+    /// 投稿先の運用チャンネル
+    pub channel_id: Id<ChannelMarker>,
+    /// 稼働状況を投稿する間隔
+    pub interval: Duration,
+    /// この回数を超えて再接続したら通知する
+    pub reconnect_alert_count: usize,
+    /// 再接続の回数を数える期間
+    pub reconnect_alert_window: Duration,
+}
+
+impl HeartbeatSettings {
+    /// 環境変数と設定ファイルから読み込む（運用チャンネルを指定していない場合は None）
+    pub fn from_config(config: Option<&ConfigFile>) -> Option<Self> {
+        let channel_id = config::heartbeat_channel_id(config)?;
+        Some(Self {
+            channel_id,
+            interval: Duration::from_secs(config::heartbeat_minutes(config) * 60),
+            reconnect_alert_count: config::reconnect_alert_count(config) as usize,
+            reconnect_alert_window: Duration::from_secs(config::reconnect_alert_minutes(config) * 60),
+        })
+    }
+}
+
+/// 運用チャンネルにメッセージを投稿する（失敗してもボットの動作には影響しないため、ログのみ出力する）
+async fn post(state: &BotState, channel_id: Id<ChannelMarker>, content: &str) {
+    if let Err(e) = state.api.create_message(channel_id, content).await {
+        println!("運用チャンネル {} への投稿に失敗しました: {}", channel_id, e);
+    }
+}
+
+/// 稼働状況のメッセージを作成する（`forwarded_since` は前回の投稿からの転送数）
+async fn heartbeat_text(state: &BotState, forwarded_since: u64, errors_since: u64) -> String {
+    let last_failure = state.alerts.last_failure_summary().await.unwrap_or_else(|| "なし".to_string());
+    [
+        "💓 **Thread2Channel 稼働中**".to_string(),
+        format!("稼働時間: {}", stats::format_uptime(state.stats.uptime())),
+        format!("前回の投稿からの転送: {}件（起動後 {}件）", forwarded_since, state.stats.forwarded_count()),
+        format!("前回の投稿からのエラー: {}件（起動後 {}件）", errors_since, state.stats.error_count()),
+        format!("最後のエラー: {}", last_failure),
+    ]
+    .join("\n")
+}
+
+/// 稼働状況を一定の間隔で運用チャンネルに投稿するタスクを起動する
+pub fn spawn_heartbeat(state: Arc<BotState>, settings: &HeartbeatSettings) {
+    let (channel_id, period) = (settings.channel_id, settings.interval);
+    let heartbeat_state = Arc::clone(&state);
+    supervisor::supervise(&state, "稼働状況の投稿", move || {
+        let state = Arc::clone(&heartbeat_state);
+        async move {
+            let mut ticker = interval_at(Instant::now() + period, period);
+            let (mut forwarded, mut errors) = (state.stats.forwarded_count(), state.stats.error_count());
+            loop {
+                ticker.tick().await;
+                let (now_forwarded, now_errors) = (state.stats.forwarded_count(), state.stats.error_count());
+                let content = heartbeat_text(&state, now_forwarded - forwarded, now_errors - errors).await;
+                post(&state, channel_id, &content).await;
+                (forwarded, errors) = (now_forwarded, now_errors);
+            }
+        }
+    });
+}
+
+/// ゲートウェイの再接続を数え、短い間に繰り返した場合は運用チャンネルに通知する
+pub struct ReconnectMonitor {
+    settings: HeartbeatSettings,
+    /// 期間内に再接続した時刻
+    reconnects: VecDeque<Instant>,
+    /// 最後に通知した時刻（同じ期間内には繰り返し通知しない）
+    alerted_at: Option<Instant>,
+}
+
+impl ReconnectMonitor {
+    pub fn new(settings: HeartbeatSettings) -> Self {
+        Self {
+            settings,
+            reconnects: VecDeque::new(),
+            alerted_at: None,
+        }
+    }
+
+    /// 再接続を記録する（回数が設定を超えた場合は、イベントの受信を止めないよう別のタスクで通知する）
+    pub fn record(&mut self, state: &Arc<BotState>, shard: u64) {
+        let now = Instant::now();
+        let window = self.settings.reconnect_alert_window;
+        self.reconnects.push_back(now);
+        while self.reconnects.front().is_some_and(|time| now.duration_since(*time) > window) {
+            self.reconnects.pop_front();
+        }
+
+        if self.reconnects.len() <= self.settings.reconnect_alert_count {
+            return;
+        }
+        if self.alerted_at.is_some_and(|time| now.duration_since(time) < window) {
+            return;
+        }
+        self.alerted_at = Some(now);
+
+        let content = format!(
+            "🔌 **ゲートウェイの再接続が続いています**\n直近{}分で{}回再接続しました（最後はシャード {}）。Discordの障害かネットワークの問題の可能性があります。",
+            window.as_secs() / 60,
+            self.reconnects.len(),
+            shard
+        );
+        println!("ゲートウェイの再接続が続いています（直近{}分で{}回）", window.as_secs() / 60, self.reconnects.len());
+        let state = Arc::clone(state);
+        let channel_id = self.settings.channel_id;
+        tokio::spawn(async move { post(&state, channel_id, &content).await });
+    }
+}
@@ -0,0 +1,55 @@
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use crate::state::BotState;
+
+/// 実行したチャンネルで使用できるコマンドの説明を作成する（`!help` と `/help` で使用）
+///
+/// マッピングが設定されているかどうか、一時停止中か、過去メッセージの転送中かによって表示するコマンドを変えます。
+/// コマンドはそのチャンネルで使用するプレフィックスで表示します。
+pub async fn help_text(state: &BotState, channel_id: Option<Id<ChannelMarker>>) -> String {
+    let mut lines = vec!["📖 **Thread2Channel のコマンド**".to_string()];
+
+    let (thread_info, prefix) = match channel_id {
+        Some(channel_id) => (
+            state.resolve_thread_info(channel_id).await,
+            state.command_prefix(channel_id, None).await,
+        ),
+        None => (None, state.default_command_prefix().to_string()),
+    };
+
+    match (channel_id, thread_info) {
+        (Some(channel_id), Some(info)) => {
+            let target = state.channel_link(info.target_channel_id, channel_id).await;
+            lines.push(format!("このスレッドは {} に転送されています（{}）", target, info.summary()));
+            lines.push(String::new());
+
+            if state.backfills.is_running(channel_id).await {
+                lines.push(format!("`{prefix}cancel` — 実行中の過去メッセージの転送を中止します"));
+            } else if info.active {
+                lines.push(format!("`{prefix}start [範囲]` / `{prefix}all [範囲]` — 過去のメッセージを転送します（例: `{prefix}all 50`、`{prefix}all since:2024-01-01`、`{prefix}all after:<メッセージID>`）"));
+            }
+            lines.push(format!("`{prefix}export [md|html] [範囲]` — 過去のメッセージを1つのファイルにまとめて転送先にアップロードします"));
+            if info.active {
+                lines.push(format!("`{prefix}pause` — このスレッドの転送を一時停止します"));
+            } else {
+                lines.push(format!("`{prefix}resume` — 一時停止中の転送を再開します"));
+            }
+            lines.push(format!("`{prefix}test` — 転送先でのボットの権限を確認し、テストメッセージを送信します"));
+            lines.push(format!("`{prefix}set_webhook <Webhook URL>` — 転送に使用するWebhookを設定します"));
+            lines.push(format!("`{prefix}thread2channel <チャンネルID> [オプション...]` — 転送先やオプションを設定し直します"));
+        }
+        _ => {
+            lines.push("このチャンネルにはマッピングが設定されていません。".to_string());
+            lines.push(String::new());
+            lines.push(format!("`{prefix}thread2channel <チャンネルID> [all] [オプション...]` — このスレッドのメッセージを転送するチャンネルを設定します"));
+        }
+    }
+
+    lines.push(format!("`{prefix}all_threads <親チャンネルID> [範囲]` — 親チャンネル配下の全スレッドの過去メッセージを転送します"));
+    lines.push(format!("`{prefix}retry-failed [all]` — 転送に失敗したメッセージを再送します（`all` で全てのスレッド）"));
+    lines.push(format!("`{prefix}status` — ボットの実行状況を表示します"));
+    lines.push(format!("`{prefix}stats` — マッピングごとの転送件数・容量・失敗件数を表示します"));
+    lines.push("`/map add|remove|pause|resume|list` — スラッシュコマンドでマッピングを管理します".to_string());
+
+    lines.join("\n")
+}
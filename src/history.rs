@@ -0,0 +1,475 @@
+use async_trait::async_trait;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use twilight_model::channel::Message;
+
+use crate::transform::{self, TransformSpec};
+
+/// メッセージの履歴を保存する範囲
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HistoryMode {
+    /// 保存しない
+    #[default]
+    Off,
+    /// 転送したメッセージのみ保存する
+    Forwarded,
+    /// マッピングの対象のスレッドで受信したメッセージを全て保存する（転送しなかったメッセージを含む）
+    All,
+}
+
+impl HistoryMode {
+    /// 設定の値（`off`、`forwarded`、`all`）から読み取る
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "off" => Some(Self::Off),
+            "forwarded" => Some(Self::Forwarded),
+            "all" => Some(Self::All),
+            _ => None,
+        }
+    }
+}
+
+/// 履歴に保存する添付ファイルの情報
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryAttachment {
+    pub filename: String,
+    pub url: String,
+    /// ファイルサイズ（バイト）
+    pub size: u64,
+    pub content_type: Option<String>,
+}
+
+/// 履歴に保存する1件のメッセージ
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub message_id: u64,
+    /// 転送元のスレッド（チャンネル）のID
+    pub thread_id: u64,
+    pub guild_id: Option<u64>,
+    pub author_id: u64,
+    pub author_name: String,
+    /// 送信者のアバターのハッシュ
+    pub author_avatar: Option<String>,
+    pub author_bot: bool,
+    /// 元のメッセージの本文（変換を適用する前のもの）
+    pub content: String,
+    pub attachments: Vec<HistoryAttachment>,
+    /// 投稿日時（RFC 3339）
+    pub created_at: String,
+    /// 最後に編集した日時（RFC 3339）
+    pub edited_at: Option<String>,
+    /// 転送したかどうか
+    pub forwarded: bool,
+}
+
+impl HistoryEntry {
+    /// 本文にマッピングの変換（`transform`）を適用する
+    ///
+    /// 履歴には変換前の本文を保存しているため、フィード・メール・静的なサイトなど外部に出す前に適用し、伏せ字にした内容が漏れないようにします。
+    pub async fn apply_transforms(entries: &mut [HistoryEntry], specs: &[TransformSpec]) {
+        if specs.is_empty() {
+            return;
+        }
+        for entry in entries {
+            entry.content = transform::apply_pipeline(specs, std::mem::take(&mut entry.content)).await;
+        }
+    }
+
+    pub fn from_message(message: &Message, forwarded: bool) -> Self {
+        Self {
+            message_id: message.id.get(),
+            thread_id: message.channel_id.get(),
+            guild_id: message.guild_id.map(|id| id.get()),
+            author_id: message.author.id.get(),
+            author_name: message.author.name.clone(),
+            author_avatar: message.author.avatar.as_ref().map(|hash| hash.to_string()),
+            author_bot: message.author.bot,
+            content: message.content.clone(),
+            attachments: message
+                .attachments
+                .iter()
+                .map(|attachment| HistoryAttachment {
+                    filename: attachment.filename.clone(),
+                    url: attachment.url.clone(),
+                    size: attachment.size,
+                    content_type: attachment.content_type.clone(),
+                })
+                .collect(),
+            created_at: message.timestamp.iso_8601().to_string(),
+            edited_at: message.edited_timestamp.map(|timestamp| timestamp.iso_8601().to_string()),
+            forwarded,
+        }
+    }
+}
+
+/// メッセージの履歴の保存先
+///
+/// 新しい種類のデータベースを追加する場合は、この trait を実装し、[`MessageHistory::open`] で選択できるようにします。
+#[async_trait]
+pub trait HistoryStore: Send + Sync {
+    /// 保存先の名前（ログ用）
+    fn name(&self) -> &'static str;
+
+    /// メッセージを保存する（保存済みの場合は内容を更新し、一度転送したメッセージは転送済みのままにする）
+    async fn record(&self, entry: &HistoryEntry) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+    /// スレッドの転送したメッセージのうち、メッセージIDが `after` より大きいものを新しい順に `limit` 件まで読み込む
+    async fn recent_forwarded(&self, thread_id: u64, after: u64, limit: usize) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// 履歴のあるスレッドのIDの一覧
+    async fn thread_ids(&self) -> Result<Vec<u64>, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// スレッドの全てのメッセージを古い順に読み込む
+    async fn thread_messages(&self, thread_id: u64) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// 履歴を読み込むときの列（`HistoryStore` の実装で共通）
+const HISTORY_COLUMNS: &str = "message_id, thread_id, guild_id, author_id, author_name, author_avatar, author_bot,
+     content, attachments, created_at, edited_at, forwarded";
+
+/// 添付ファイルの列（JSON）を読み取る（読み取れない場合は添付ファイル無しとして扱う）
+fn parse_attachments(json: &str) -> Vec<HistoryAttachment> {
+    serde_json::from_str(json).unwrap_or_default()
+}
+
+/// SQLiteに保存する履歴
+pub struct SqliteHistory {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteHistory {
+    /// データベースを開く（存在しない場合は作成する）
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = Connection::open(path)
+            .map_err(|e| format!("履歴のデータベース {} を開けませんでした: {}", path.display(), e))?;
+        // マッピングの保存と同じファイルを使う場合に、書き込みが重なっても失敗しないようにする
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS message_history (
+                message_id    INTEGER PRIMARY KEY,
+                thread_id     INTEGER NOT NULL,
+                guild_id      INTEGER,
+                author_id     INTEGER NOT NULL,
+                author_name   TEXT NOT NULL,
+                author_avatar TEXT,
+                author_bot    INTEGER NOT NULL,
+                content       TEXT NOT NULL,
+                attachments   TEXT NOT NULL,
+                created_at    TEXT NOT NULL,
+                edited_at     TEXT,
+                forwarded     INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS message_history_thread ON message_history (thread_id, message_id);
+            CREATE INDEX IF NOT EXISTS message_history_author ON message_history (author_id);",
+        )?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+}
+
+#[async_trait]
+impl HistoryStore for SqliteHistory {
+    fn name(&self) -> &'static str {
+        "SQLite"
+    }
+
+    async fn record(&self, entry: &HistoryEntry) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let attachments = serde_json::to_string(&entry.attachments)?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO message_history
+                (message_id, thread_id, guild_id, author_id, author_name, author_avatar, author_bot,
+                 content, attachments, created_at, edited_at, forwarded)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+             ON CONFLICT(message_id) DO UPDATE SET
+                author_name = excluded.author_name,
+                author_avatar = excluded.author_avatar,
+                content = excluded.content,
+                attachments = excluded.attachments,
+                edited_at = excluded.edited_at,
+                forwarded = MAX(forwarded, excluded.forwarded)",
+            params![
+                entry.message_id as i64,
+                entry.thread_id as i64,
+                entry.guild_id.map(|id| id as i64),
+                entry.author_id as i64,
+                entry.author_name,
+                entry.author_avatar,
+                entry.author_bot,
+                entry.content,
+                attachments,
+                entry.created_at,
+                entry.edited_at,
+                entry.forwarded,
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn recent_forwarded(&self, thread_id: u64, after: u64, limit: usize) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM message_history WHERE thread_id = ?1 AND forwarded = 1 AND message_id > ?2 ORDER BY message_id DESC LIMIT ?3",
+            HISTORY_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![thread_id as i64, after as i64, limit as i64], sqlite_entry)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    async fn thread_ids(&self) -> Result<Vec<u64>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT DISTINCT thread_id FROM message_history ORDER BY thread_id")?;
+        let rows = stmt.query_map([], |row| row.get::<_, i64>(0).map(|id| id as u64))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    async fn thread_messages(&self, thread_id: u64) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM message_history WHERE thread_id = ?1 ORDER BY message_id",
+            HISTORY_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![thread_id as i64], sqlite_entry)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
+
+/// SQLiteの行（`HISTORY_COLUMNS` の順）を読み取る
+fn sqlite_entry(row: &rusqlite::Row) -> rusqlite::Result<HistoryEntry> {
+    Ok(HistoryEntry {
+        message_id: row.get::<_, i64>(0)? as u64,
+        thread_id: row.get::<_, i64>(1)? as u64,
+        guild_id: row.get::<_, Option<i64>>(2)?.map(|id| id as u64),
+        author_id: row.get::<_, i64>(3)? as u64,
+        author_name: row.get(4)?,
+        author_avatar: row.get(5)?,
+        author_bot: row.get(6)?,
+        content: row.get(7)?,
+        attachments: parse_attachments(&row.get::<_, String>(8)?),
+        created_at: row.get(9)?,
+        edited_at: row.get(10)?,
+        forwarded: row.get(11)?,
+    })
+}
+
+/// PostgreSQLに保存する履歴
+#[cfg(feature = "postgres")]
+pub struct PostgresHistory {
+    client: tokio_postgres::Client,
+}
+
+#[cfg(feature = "postgres")]
+impl PostgresHistory {
+    /// データベースに接続し、テーブルが無い場合は作成する
+    pub async fn connect(url: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let (client, connection) = tokio_postgres::connect(url, tokio_postgres::NoTls)
+            .await
+            .map_err(|e| format!("履歴のデータベース（PostgreSQL）に接続できませんでした: {}", e))?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                eprintln!("履歴のデータベース（PostgreSQL）との接続が切れました: {}", e);
+            }
+        });
+
+        client
+            .batch_execute(
+                "CREATE TABLE IF NOT EXISTS message_history (
+                    message_id    BIGINT PRIMARY KEY,
+                    thread_id     BIGINT NOT NULL,
+                    guild_id      BIGINT,
+                    author_id     BIGINT NOT NULL,
+                    author_name   TEXT NOT NULL,
+                    author_avatar TEXT,
+                    author_bot    BOOLEAN NOT NULL,
+                    content       TEXT NOT NULL,
+                    attachments   TEXT NOT NULL,
+                    created_at    TEXT NOT NULL,
+                    edited_at     TEXT,
+                    forwarded     BOOLEAN NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS message_history_thread ON message_history (thread_id, message_id);
+                CREATE INDEX IF NOT EXISTS message_history_author ON message_history (author_id);",
+            )
+            .await?;
+        Ok(Self { client })
+    }
+}
+
+#[cfg(feature = "postgres")]
+#[async_trait]
+impl HistoryStore for PostgresHistory {
+    fn name(&self) -> &'static str {
+        "PostgreSQL"
+    }
+
+    async fn record(&self, entry: &HistoryEntry) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let attachments = serde_json::to_string(&entry.attachments)?;
+        self.client
+            .execute(
+                "INSERT INTO message_history
+                    (message_id, thread_id, guild_id, author_id, author_name, author_avatar, author_bot,
+                     content, attachments, created_at, edited_at, forwarded)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                 ON CONFLICT (message_id) DO UPDATE SET
+                    author_name = EXCLUDED.author_name,
+                    author_avatar = EXCLUDED.author_avatar,
+                    content = EXCLUDED.content,
+                    attachments = EXCLUDED.attachments,
+                    edited_at = EXCLUDED.edited_at,
+                    forwarded = message_history.forwarded OR EXCLUDED.forwarded",
+                &[
+                    &(entry.message_id as i64),
+                    &(entry.thread_id as i64),
+                    &entry.guild_id.map(|id| id as i64),
+                    &(entry.author_id as i64),
+                    &entry.author_name,
+                    &entry.author_avatar,
+                    &entry.author_bot,
+                    &entry.content,
+                    &attachments,
+                    &entry.created_at,
+                    &entry.edited_at,
+                    &entry.forwarded,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn recent_forwarded(&self, thread_id: u64, after: u64, limit: usize) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error + Send + Sync>> {
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT {} FROM message_history WHERE thread_id = $1 AND forwarded AND message_id > $2 ORDER BY message_id DESC LIMIT $3",
+                    HISTORY_COLUMNS
+                ),
+                &[&(thread_id as i64), &(after as i64), &(limit as i64)],
+            )
+            .await?;
+        Ok(rows.iter().map(postgres_entry).collect())
+    }
+
+    async fn thread_ids(&self) -> Result<Vec<u64>, Box<dyn std::error::Error + Send + Sync>> {
+        let rows = self
+            .client
+            .query("SELECT DISTINCT thread_id FROM message_history ORDER BY thread_id", &[])
+            .await?;
+        Ok(rows.iter().map(|row| row.get::<_, i64>(0) as u64).collect())
+    }
+
+    async fn thread_messages(&self, thread_id: u64) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error + Send + Sync>> {
+        let rows = self
+            .client
+            .query(
+                &format!("SELECT {} FROM message_history WHERE thread_id = $1 ORDER BY message_id", HISTORY_COLUMNS),
+                &[&(thread_id as i64)],
+            )
+            .await?;
+        Ok(rows.iter().map(postgres_entry).collect())
+    }
+}
+
+/// PostgreSQLの行（`HISTORY_COLUMNS` の順）を読み取る
+#[cfg(feature = "postgres")]
+fn postgres_entry(row: &tokio_postgres::Row) -> HistoryEntry {
+    HistoryEntry {
+        message_id: row.get::<_, i64>(0) as u64,
+        thread_id: row.get::<_, i64>(1) as u64,
+        guild_id: row.get::<_, Option<i64>>(2).map(|id| id as u64),
+        author_id: row.get::<_, i64>(3) as u64,
+        author_name: row.get(4),
+        author_avatar: row.get(5),
+        author_bot: row.get(6),
+        content: row.get(7),
+        attachments: parse_attachments(row.get(8)),
+        created_at: row.get(9),
+        edited_at: row.get(10),
+        forwarded: row.get(11),
+    }
+}
+
+/// 転送したメッセージ（設定によっては受信した全てのメッセージ）の履歴
+///
+/// 投稿日時・送信者・本文・添付ファイルなどをデータベースに保存し、後から検索や集計に使えるようにします。
+#[derive(Default)]
+pub struct MessageHistory {
+    mode: HistoryMode,
+    store: Option<Box<dyn HistoryStore>>,
+}
+
+impl MessageHistory {
+    /// 履歴の保存先を開く
+    ///
+    /// `database_url` が `postgres://` で始まる場合は PostgreSQL（`postgres` 機能が必要）、それ以外はSQLiteのファイルのパスとして扱います。
+    /// 保存先を開けない場合は警告を出力し、履歴を保存せずに起動します。
+    pub async fn open(mode: HistoryMode, database_url: &str) -> Self {
+        if mode == HistoryMode::Off {
+            return Self::default();
+        }
+
+        match Self::open_store(database_url).await {
+            Ok(store) => {
+                println!("メッセージの履歴を {} に保存します", store.name());
+                Self { mode, store: Some(store) }
+            }
+            Err(e) => {
+                println!("警告: {}。メッセージの履歴は保存しません", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// 履歴の保存先を開く（`export-site` など、ボットを起動せずに履歴を読み込む場合に使用）
+    pub async fn open_store(database_url: &str) -> Result<Box<dyn HistoryStore>, Box<dyn std::error::Error + Send + Sync>> {
+        if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+            open_postgres(database_url).await
+        } else {
+            Ok(Box::new(SqliteHistory::open(Path::new(database_url))?))
+        }
+    }
+
+    /// メッセージを履歴に保存するかどうか（`forwarded` は転送したかどうか）
+    pub fn records(&self, forwarded: bool) -> bool {
+        self.store.is_some() && (forwarded || self.mode == HistoryMode::All)
+    }
+
+    /// メッセージを履歴に保存する（`forwarded` は転送したかどうか、失敗しても転送には影響しないため警告のみ出力する）
+    ///
+    /// 保存済みのメッセージは本文・添付ファイル・編集日時を更新します（編集の反映に使用）。
+    pub async fn record(&self, message: &Message, forwarded: bool) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        if !self.records(forwarded) {
+            return;
+        }
+        if let Err(e) = store.record(&HistoryEntry::from_message(message, forwarded)).await {
+            println!("警告: メッセージ {} を履歴に保存できませんでした: {}", message.id, e);
+        }
+    }
+
+    /// スレッドの転送したメッセージのうち、メッセージIDが `after` より大きいものを新しい順に読み込む（履歴を保存していない場合は None）
+    ///
+    /// メッセージIDには投稿日時が含まれるため、`after` に日時から計算したIDを指定すると、その日時以降のメッセージを読み込めます。
+    pub async fn recent_forwarded(
+        &self,
+        thread_id: u64,
+        after: u64,
+        limit: usize,
+    ) -> Option<Result<Vec<HistoryEntry>, Box<dyn std::error::Error + Send + Sync>>> {
+        Some(self.store.as_ref()?.recent_forwarded(thread_id, after, limit).await)
+    }
+}
+
+#[cfg(feature = "postgres")]
+async fn open_postgres(url: &str) -> Result<Box<dyn HistoryStore>, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(Box::new(PostgresHistory::connect(url).await?))
+}
+
+#[cfg(not(feature = "postgres"))]
+async fn open_postgres(_url: &str) -> Result<Box<dyn HistoryStore>, Box<dyn std::error::Error + Send + Sync>> {
+    Err("PostgreSQL に保存するには postgres 機能を有効にしてビルドしてください".into())
+}
@@ -0,0 +1,101 @@
+use serde::Deserialize;
+use serde_json::json;
+use twilight_model::channel::message::AllowedMentions;
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use crate::health::{json_response, Request, Response};
+use crate::markdown::escape_markdown;
+use crate::state::BotState;
+
+/// Discordのメッセージの最大の長さ
+const MAX_CONTENT_LENGTH: usize = 2000;
+
+/// 投稿先
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum InboundTarget {
+    /// 転送元のスレッド
+    #[default]
+    Thread,
+    /// マッピングの転送先のチャンネル
+    Channel,
+}
+
+/// 外部から投稿するメッセージ
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InboundMessage {
+    /// 本文
+    content: String,
+    /// 送信元の名前（指定した場合は本文の前に太字で表示する）
+    username: Option<String>,
+    /// 投稿先（`thread` または `channel`、デフォルトは `thread`）
+    #[serde(default)]
+    to: InboundTarget,
+}
+
+/// トークンを比較する（比較にかかる時間から一致した長さを推測されないよう、全ての文字を比較する）
+pub(crate) fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len() && given.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// 外部からの投稿（`POST /inbound/<スレッドID>`）を処理する
+///
+/// `Authorization: Bearer <トークン>` で認証し、マッピングに登録されたスレッドか、その転送先のチャンネルに投稿します。
+/// CIの結果の通知など、外部のツールからスレッドにメッセージを送る場合に使用します。メンションは通知しません。
+pub async fn handle(state: &BotState, request: &Request, thread_id: &str, token: &str) -> Response {
+    if request.method != "POST" {
+        return json_response("405 Method Not Allowed", json!({ "error": "POST で送信してください" }));
+    }
+    let authorized = request
+        .header("Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| token_matches(given.trim(), token));
+    if !authorized {
+        return json_response("401 Unauthorized", json!({ "error": "トークンが正しくありません" }));
+    }
+
+    let Some(thread_id) = thread_id.parse().ok().and_then(Id::<ChannelMarker>::new_checked) else {
+        return json_response("404 Not Found", json!({ "error": "スレッドIDが正しくありません" }));
+    };
+    let Some(thread_info) = state.resolve_thread_info(thread_id).await else {
+        return json_response("404 Not Found", json!({ "error": format!("スレッド {} のマッピングがありません", thread_id) }));
+    };
+
+    let message: InboundMessage = match serde_json::from_slice(&request.body) {
+        Ok(message) => message,
+        Err(e) => return json_response("400 Bad Request", json!({ "error": format!("JSONを読み取れませんでした: {}", e) })),
+    };
+    if message.content.trim().is_empty() {
+        return json_response("400 Bad Request", json!({ "error": "content を指定してください" }));
+    }
+    let content = match message.username.as_deref().map(str::trim).filter(|name| !name.is_empty()) {
+        Some(username) => format!("**{}**\n{}", escape_markdown(username), message.content),
+        None => message.content,
+    };
+    if content.chars().count() > MAX_CONTENT_LENGTH {
+        return json_response("400 Bad Request", json!({ "error": format!("本文は{}文字以内にしてください", MAX_CONTENT_LENGTH) }));
+    }
+
+    let channel_id = match message.to {
+        InboundTarget::Thread => thread_id,
+        InboundTarget::Channel => thread_info.target_channel_id,
+    };
+    let allowed_mentions = AllowedMentions::default();
+    let request = match state.http.create_message(channel_id).content(&content) {
+        Ok(request) => request.allowed_mentions(Some(&allowed_mentions)),
+        Err(e) => return json_response("400 Bad Request", json!({ "error": e.to_string() })),
+    };
+    match request.await {
+        Ok(response) => {
+            println!("外部からのメッセージをチャンネル {} に投稿しました（スレッド {} のマッピング）", channel_id, thread_id);
+            let message_id = response.model().await.map(|message| message.id.to_string()).ok();
+            json_response("200 OK", json!({ "channel_id": channel_id.to_string(), "message_id": message_id }))
+        }
+        Err(e) => {
+            println!("外部からのメッセージをチャンネル {} に投稿できませんでした: {}", channel_id, e);
+            json_response("502 Bad Gateway", json!({ "error": e.to_string() }))
+        }
+    }
+}
+
@@ -0,0 +1,67 @@
+//! Discordのスレッドのメッセージを指定したチャンネルに転送するボット
+//!
+//! [`Thread2ChannelBot`] を使うと、転送の処理を他のプログラムに組み込むことができます。
+//! コマンドラインから起動する場合は `main.rs` を参照してください。
+
+mod alert;
+pub mod api;
+mod archive;
+mod attachments;
+mod audit;
+mod backfill;
+mod bot;
+pub mod cli;
+mod commands;
+pub mod config;
+mod dashboard;
+mod digest;
+pub mod dispatch;
+#[cfg(feature = "email")]
+mod email;
+mod embed;
+mod emoji;
+mod events;
+mod feed;
+mod filter;
+mod forwarding;
+mod github;
+mod health;
+mod heartbeat;
+mod help;
+mod history;
+mod inbound;
+mod links;
+pub mod mappings;
+mod markdown;
+mod mentions;
+mod pacing;
+mod permissions;
+mod poll;
+mod pool;
+mod publish;
+mod queue;
+mod redact;
+mod reload;
+mod reporting;
+mod retry;
+mod roles;
+mod setup;
+mod sink;
+mod slack;
+mod site;
+mod slash;
+mod source;
+pub mod state;
+mod stats;
+mod storage;
+mod supervisor;
+#[cfg(feature = "telegram")]
+mod telegram;
+mod template;
+mod transcript;
+mod transform;
+#[cfg(feature = "translation")]
+mod translate;
+mod watch;
+
+pub use bot::{Thread2ChannelBot, Thread2ChannelBotBuilder};
@@ -0,0 +1,217 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Mutex;
+use twilight_model::id::{
+    marker::{ChannelMarker, MessageMarker},
+    Id,
+};
+
+/// メモリ上に保持する転送記録の最大件数（超えた分は古いものから破棄）
+const MAX_CACHED_LINKS: usize = 10_000;
+
+/// 転送元メッセージと転送先メッセージの対応
+#[derive(Debug, Clone)]
+pub struct MessageLink {
+    /// 転送元のスレッド（マッピング）のチャンネルID
+    pub source_channel_id: Id<ChannelMarker>,
+    /// 転送先のチャンネルID
+    pub channel_id: Id<ChannelMarker>,
+    /// 転送先のメッセージID
+    pub message_id: Id<MessageMarker>,
+    /// Webhookで送信した場合はそのURL（編集にもWebhookを使う必要があるため）
+    pub webhook_url: Option<String>,
+    /// 転送した日時
+    pub forwarded_at: DateTime<Utc>,
+}
+
+/// メモリ上の転送記録（挿入順を保持して古いものから破棄する）
+#[derive(Default)]
+struct LinkCache {
+    links: HashMap<Id<MessageMarker>, MessageLink>,
+    order: VecDeque<Id<MessageMarker>>,
+}
+
+impl LinkCache {
+    fn insert(&mut self, source_message_id: Id<MessageMarker>, link: MessageLink) {
+        if self.links.insert(source_message_id, link).is_none() {
+            self.order.push_back(source_message_id);
+        }
+
+        while self.order.len() > MAX_CACHED_LINKS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.links.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// 転送したメッセージの対応を記録するストア
+///
+/// 編集の反映などに使用します。
+/// SQLiteを使用しない場合は、直近の記録だけをメモリ上に保持します（再起動すると失われます）。
+pub struct MessageLinkStore {
+    cache: Mutex<LinkCache>,
+    conn: Option<Mutex<Connection>>,
+}
+
+impl MessageLinkStore {
+    /// メモリ上だけに記録するストアを作成する
+    pub fn in_memory() -> Self {
+        Self {
+            cache: Mutex::new(LinkCache::default()),
+            conn: None,
+        }
+    }
+
+    /// SQLiteにも記録するストアを作成する（マッピングと同じデータベースを使用）
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = Connection::open(path)
+            .map_err(|e| format!("データベース {} を開けませんでした: {}", path.display(), e))?;
+
+        // 転送元スレッドを記録していない旧形式のテーブルは作り直す
+        let legacy: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'message_links')
+                AND NOT EXISTS (SELECT 1 FROM pragma_table_info('message_links') WHERE name = 'source_channel_id')",
+            [],
+            |row| row.get(0),
+        )?;
+        if legacy {
+            println!("旧形式の転送記録テーブルを作り直します");
+            conn.execute_batch("DROP TABLE message_links;")?;
+        }
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS message_links (
+                source_message_id INTEGER PRIMARY KEY,
+                source_channel_id INTEGER NOT NULL,
+                target_channel_id INTEGER NOT NULL,
+                mirror_message_id INTEGER NOT NULL,
+                webhook_url       TEXT,
+                forwarded_at      TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS message_links_source_channel
+                ON message_links (source_channel_id, source_message_id);",
+        )?;
+
+        Ok(Self {
+            cache: Mutex::new(LinkCache::default()),
+            conn: Some(Mutex::new(conn)),
+        })
+    }
+
+    /// 転送したメッセージの対応を記録する
+    pub fn record(
+        &self,
+        source_message_id: Id<MessageMarker>,
+        link: MessageLink,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(conn) = &self.conn {
+            conn.lock().unwrap().execute(
+                "INSERT OR REPLACE INTO message_links
+                    (source_message_id, source_channel_id, target_channel_id, mirror_message_id, webhook_url, forwarded_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    source_message_id.get() as i64,
+                    link.source_channel_id.get() as i64,
+                    link.channel_id.get() as i64,
+                    link.message_id.get() as i64,
+                    link.webhook_url,
+                    link.forwarded_at.to_rfc3339()
+                ],
+            )?;
+        }
+
+        self.cache.lock().unwrap().insert(source_message_id, link);
+        Ok(())
+    }
+
+    /// 転送元メッセージに対応する転送先メッセージを取得する
+    pub fn get(
+        &self,
+        source_message_id: Id<MessageMarker>,
+    ) -> Result<Option<MessageLink>, Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(link) = self.cache.lock().unwrap().links.get(&source_message_id) {
+            return Ok(Some(link.clone()));
+        }
+
+        let Some(conn) = &self.conn else {
+            return Ok(None);
+        };
+        let row = conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT source_channel_id, target_channel_id, mirror_message_id, webhook_url, forwarded_at
+                 FROM message_links WHERE source_message_id = ?1",
+                params![source_message_id.get() as i64],
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                        row.get::<_, String>(4)?,
+                    ))
+                },
+            )
+            .optional()?;
+
+        let Some((source_channel_id, channel_id, message_id, webhook_url, forwarded_at)) = row else {
+            return Ok(None);
+        };
+        let (Some(source_channel_id), Some(channel_id), Some(message_id)) = (
+            Id::new_checked(source_channel_id as u64),
+            Id::new_checked(channel_id as u64),
+            Id::new_checked(message_id as u64),
+        ) else {
+            println!("警告: データベースに無効な転送記録が保存されています: メッセージ {}", source_message_id);
+            return Ok(None);
+        };
+        let forwarded_at = DateTime::parse_from_rfc3339(&forwarded_at)
+            .map(|time| time.with_timezone(&Utc))
+            .unwrap_or_default();
+
+        Ok(Some(MessageLink {
+            source_channel_id,
+            channel_id,
+            message_id,
+            webhook_url,
+            forwarded_at,
+        }))
+    }
+
+    /// 転送元のチャンネルごとに、最後に転送したメッセージのIDを取得する
+    ///
+    /// ボットの停止中に投稿されたメッセージを取得する際の起点に使用します。
+    pub fn latest_per_channel(
+        &self,
+    ) -> Result<HashMap<Id<ChannelMarker>, Id<MessageMarker>>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut latest: HashMap<Id<ChannelMarker>, Id<MessageMarker>> = HashMap::new();
+        for (source_message_id, link) in &self.cache.lock().unwrap().links {
+            let entry = latest.entry(link.source_channel_id).or_insert(*source_message_id);
+            *entry = (*entry).max(*source_message_id);
+        }
+
+        let Some(conn) = &self.conn else {
+            return Ok(latest);
+        };
+        let conn = conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT source_channel_id, MAX(source_message_id) FROM message_links GROUP BY source_channel_id",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?;
+        for row in rows {
+            let (channel_id, message_id) = row?;
+            if let (Some(channel_id), Some(message_id)) =
+                (Id::new_checked(channel_id as u64), Id::new_checked(message_id as u64))
+            {
+                let entry = latest.entry(channel_id).or_insert(message_id);
+                *entry = (*entry).max(message_id);
+            }
+        }
+
+        Ok(latest)
+    }
+}
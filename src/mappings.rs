@@ -0,0 +1,102 @@
+use arc_swap::ArcSwap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{Mutex, MutexGuard};
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use crate::filter::MessagePattern;
+use crate::state::ThreadInfo;
+
+/// チャンネルID -> スレッド情報
+pub type MappingMap = HashMap<Id<ChannelMarker>, ThreadInfo>;
+
+/// スレッド名のパターンによるマッピング（設定ファイルの `name_pattern`）
+///
+/// サーバーへの接続時とスレッドの作成時に、名前が一致したスレッドをこのスレッド情報で個別のマッピングとして登録します。
+#[derive(Debug, Clone)]
+pub struct NameMapping {
+    /// スレッド名に一致させる正規表現
+    pub pattern: MessagePattern,
+    /// 一致したスレッドに使用するスレッド情報
+    pub info: ThreadInfo,
+}
+
+/// ロックを取らずに読み込めるマッピングの一覧
+///
+/// メッセージを受信するたびに参照されるため、読み込みは現在の一覧のスナップショットを返すだけにしています。
+/// 変更は一覧を複製して書き換えたものに差し替えます（マッピングの変更はまれなため、複製のコストは問題になりません）。
+/// 変更同士は [`MappingTable::write`] で直列化します。
+pub struct MappingTable {
+    /// 現在の一覧
+    current: ArcSwap<MappingMap>,
+    /// 変更を1つずつ行うためのロック（読み込みでは使用しない）
+    writer: Mutex<()>,
+}
+
+impl MappingTable {
+    /// 一覧を指定して作成する
+    pub fn new(mappings: MappingMap) -> Self {
+        Self {
+            current: ArcSwap::from_pointee(mappings),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// 現在の一覧のスナップショットを取得する
+    pub fn load(&self) -> Arc<MappingMap> {
+        self.current.load_full()
+    }
+
+    /// 指定したチャンネルのスレッド情報を取得する
+    pub fn get(&self, channel_id: Id<ChannelMarker>) -> Option<ThreadInfo> {
+        self.current.load().get(&channel_id).cloned()
+    }
+
+    /// 一覧を変更する（他の変更が終わるまで待つ）
+    pub async fn write(&self) -> MappingWriter<'_> {
+        MappingWriter {
+            table: self,
+            _guard: self.writer.lock().await,
+        }
+    }
+}
+
+/// マッピングの一覧の変更（保持している間は他の変更を待たせる）
+pub struct MappingWriter<'a> {
+    table: &'a MappingTable,
+    _guard: MutexGuard<'a, ()>,
+}
+
+impl MappingWriter<'_> {
+    /// 現在の一覧のスナップショットを取得する
+    pub fn load(&self) -> Arc<MappingMap> {
+        self.table.load()
+    }
+
+    /// 指定したチャンネルのスレッド情報を取得する
+    pub fn get(&self, channel_id: Id<ChannelMarker>) -> Option<ThreadInfo> {
+        self.table.get(channel_id)
+    }
+
+    /// マッピングを追加（または上書き）する
+    pub fn insert(&mut self, channel_id: Id<ChannelMarker>, info: ThreadInfo) {
+        let mut mappings = MappingMap::clone(&self.table.load());
+        mappings.insert(channel_id, info);
+        self.replace(mappings);
+    }
+
+    /// マッピングを削除する（削除した場合は true を返す）
+    pub fn remove(&mut self, channel_id: Id<ChannelMarker>) -> bool {
+        let mut mappings = MappingMap::clone(&self.table.load());
+        let removed = mappings.remove(&channel_id).is_some();
+        if removed {
+            self.replace(mappings);
+        }
+        removed
+    }
+
+    /// 一覧を新しい内容で置き換える
+    pub fn replace(&mut self, mappings: MappingMap) {
+        self.table.current.store(Arc::new(mappings));
+    }
+}
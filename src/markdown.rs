@@ -0,0 +1,16 @@
+/// Discordのマークダウンとして解釈される記号
+const MARKDOWN_SPECIAL_CHARS: [char; 8] = ['\\', '*', '_', '`', '~', '|', '>', '#'];
+
+/// ユーザー名やスレッド名などをマークダウンに埋め込むとき、書式として解釈されないようにエスケープする
+///
+/// 例えば `**{送信者名}**` に `*` や `_` を含む名前を入れると太字が崩れるため、記号の前に `\` を付けます。
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if MARKDOWN_SPECIAL_CHARS.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,153 @@
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use tokio::sync::RwLock;
+use twilight_http::Client as HttpClient;
+use twilight_model::channel::Message;
+use twilight_model::id::{
+    marker::{GuildMarker, RoleMarker, UserMarker},
+    Id,
+};
+
+use crate::markdown::escape_markdown;
+use crate::state::MentionPolicy;
+
+/// ユーザーメンション `<@123>` / `<@!123>` の正規表現
+fn user_mention_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"<@!?(\d+)>").unwrap())
+}
+
+/// ロールメンション `<@&123>` の正規表現
+fn role_mention_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"<@&(\d+)>").unwrap())
+}
+
+/// メンションを表示名に置き換える処理（取得した名前はキャッシュする）
+///
+/// 転送先のサーバーにいないユーザーやロールのメンションは `<@123>` のまま表示されて読めないため、
+/// 転送前に `@表示名` の形式に置き換えます。
+#[derive(Default)]
+pub struct MentionResolver {
+    /// ユーザーID -> 表示名
+    users: RwLock<HashMap<Id<UserMarker>, String>>,
+    /// ロールID -> ロール名
+    roles: RwLock<HashMap<Id<RoleMarker>, String>>,
+}
+
+impl MentionResolver {
+    /// 空のキャッシュで作成する
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ユーザーの表示名を取得する（メッセージのメンション情報 → キャッシュ → HTTP APIの順に探す）
+    async fn user_name(&self, http: &HttpClient, message: &Message, user_id: Id<UserMarker>) -> Option<String> {
+        // サーバーのニックネームはメッセージのメンション情報に含まれる
+        let mentioned = message.mentions.iter().find(|mention| mention.id == user_id);
+        if let Some(nick) = mentioned.and_then(|mention| mention.member.as_ref()).and_then(|member| member.nick.clone()) {
+            return Some(nick);
+        }
+
+        if let Some(name) = self.users.read().await.get(&user_id) {
+            return Some(name.clone());
+        }
+
+        let name = match http.user(user_id).await {
+            Ok(response) => match response.model().await {
+                Ok(user) => user.global_name.unwrap_or(user.name),
+                Err(e) => {
+                    println!("ユーザー {} の情報を読み込めませんでした: {}", user_id, e);
+                    mentioned.map(|mention| mention.name.clone())?
+                }
+            },
+            Err(e) => {
+                println!("ユーザー {} の情報を取得できませんでした: {}", user_id, e);
+                mentioned.map(|mention| mention.name.clone())?
+            }
+        };
+
+        self.users.write().await.insert(user_id, name.clone());
+        Some(name)
+    }
+
+    /// ロール名を取得する（キャッシュに無い場合はサーバーのロール一覧を取得する）
+    async fn role_name(&self, http: &HttpClient, guild_id: Option<Id<GuildMarker>>, role_id: Id<RoleMarker>) -> Option<String> {
+        if let Some(name) = self.roles.read().await.get(&role_id) {
+            return Some(name.clone());
+        }
+
+        let guild_id = guild_id?;
+        let roles = match http.roles(guild_id).await {
+            Ok(response) => match response.models().await {
+                Ok(roles) => roles,
+                Err(e) => {
+                    println!("サーバー {} のロール一覧を読み込めませんでした: {}", guild_id, e);
+                    return None;
+                }
+            },
+            Err(e) => {
+                println!("サーバー {} のロール一覧を取得できませんでした: {}", guild_id, e);
+                return None;
+            }
+        };
+
+        let mut cache = self.roles.write().await;
+        for role in roles {
+            cache.insert(role.id, role.name);
+        }
+        cache.get(&role_id).cloned()
+    }
+
+    /// 本文中のメンションを表示名に置き換える
+    ///
+    /// 通知を許可しているメンション（`mentions` の設定）は、通知が届くようにそのまま残します。
+    pub async fn resolve(&self, http: &HttpClient, message: &Message, policy: MentionPolicy, content: &str) -> String {
+        let mut user_names = HashMap::new();
+        if policy == MentionPolicy::None {
+            for captures in user_mention_pattern().captures_iter(content) {
+                let Some(user_id) = captures[1].parse().ok().and_then(Id::new_checked) else {
+                    continue;
+                };
+                if let Some(name) = self.user_name(http, message, user_id).await {
+                    user_names.insert(user_id, name);
+                }
+            }
+        }
+
+        let mut role_names = HashMap::new();
+        if policy != MentionPolicy::All {
+            for captures in role_mention_pattern().captures_iter(content) {
+                let Some(role_id) = captures[1].parse().ok().and_then(Id::new_checked) else {
+                    continue;
+                };
+                if let Some(name) = self.role_name(http, message.guild_id, role_id).await {
+                    role_names.insert(role_id, name);
+                }
+            }
+        }
+
+        // 名前が分からないメンションは元のまま残す
+        let content = user_mention_pattern().replace_all(content, |captures: &Captures| {
+            captures[1]
+                .parse()
+                .ok()
+                .and_then(Id::new_checked)
+                .and_then(|user_id: Id<UserMarker>| user_names.get(&user_id))
+                .map(|name| format!("@{}", escape_markdown(name)))
+                .unwrap_or_else(|| captures[0].to_string())
+        });
+        let content = role_mention_pattern().replace_all(&content, |captures: &Captures| {
+            captures[1]
+                .parse()
+                .ok()
+                .and_then(Id::new_checked)
+                .and_then(|role_id: Id<RoleMarker>| role_names.get(&role_id))
+                .map(|name| format!("@{}", escape_markdown(name)))
+                .unwrap_or_else(|| captures[0].to_string())
+        });
+
+        content.into_owned()
+    }
+}
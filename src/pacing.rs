@@ -0,0 +1,112 @@
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use std::sync::Mutex;
+use tokio::time::Duration;
+
+/// 過去メッセージの転送で、メッセージ間に入れる最小の待機時間
+const MIN_DELAY: Duration = Duration::from_millis(50);
+
+/// 過去メッセージの転送で、メッセージ間に入れる最大の待機時間
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// 残り回数がこれ以下になったら、リセットまでの時間に合わせて間隔を空ける
+const LOW_REMAINING: u64 = 2;
+
+/// Discordの応答ヘッダーから読み取ったレート制限の状態
+#[derive(Debug, Clone, Copy, Default)]
+struct RateLimitInfo {
+    /// バケットの残りのリクエスト数（`X-RateLimit-Remaining`）
+    remaining: Option<u64>,
+    /// バケットがリセットされるまでの時間（`X-RateLimit-Reset-After`）
+    reset_after: Option<Duration>,
+    /// レート制限（429）を受けた場合の再試行までの時間（`Retry-After`）
+    retry_after: Option<Duration>,
+}
+
+/// 最後に受け取ったレート制限の状態
+///
+/// Webhookの送信は twilight のレート制限の管理を通らないため、応答ヘッダーをここに記録して転送の間隔の調整に使います。
+static LATEST: Mutex<Option<RateLimitInfo>> = Mutex::new(None);
+
+/// 秒数のヘッダーを読み取る（小数を含む場合がある）
+fn header_secs(headers: &HeaderMap, name: &str) -> Option<Duration> {
+    let secs: f64 = headers.get(name)?.to_str().ok()?.parse().ok()?;
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+/// Discordの応答からレート制限の状態を記録し、レート制限を受けた場合は再試行までの時間を返す
+pub fn observe(status: StatusCode, headers: &HeaderMap) -> Option<Duration> {
+    let info = RateLimitInfo {
+        remaining: headers
+            .get("x-ratelimit-remaining")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok()),
+        reset_after: header_secs(headers, "x-ratelimit-reset-after"),
+        retry_after: if status == StatusCode::TOO_MANY_REQUESTS {
+            Some(header_secs(headers, "retry-after").unwrap_or(Duration::from_secs(1)))
+        } else {
+            None
+        },
+    };
+
+    let mut latest = LATEST.lock().unwrap();
+    // 429 の情報は、転送の間隔に反映されるまで上書きしない
+    if latest.is_some_and(|latest| latest.retry_after.is_some()) && info.retry_after.is_none() {
+        return None;
+    }
+    *latest = Some(info);
+    info.retry_after
+}
+
+/// 記録したレート制限の状態を取り出す
+fn take() -> Option<RateLimitInfo> {
+    LATEST.lock().unwrap().take()
+}
+
+/// 過去メッセージの転送で、レート制限の状態に合わせてメッセージ間の間隔を調整する
+///
+/// 余裕がある間は間隔を縮めて速く転送し、残り回数が少なくなったりレート制限を受けたりした場合は間隔を広げます。
+/// twilight で送信するメッセージは twilight がバケットごとに待機するため、ここでは最小の間隔だけを空けます。
+pub struct Pacer {
+    delay: Duration,
+}
+
+impl Default for Pacer {
+    fn default() -> Self {
+        Self { delay: MIN_DELAY }
+    }
+}
+
+impl Pacer {
+    /// 最小の間隔から始める
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 次のメッセージを送信する前に待機する
+    pub async fn pace(&mut self) {
+        let wait = match take() {
+            // レート制限を受けた場合は間隔を倍にし、少なくとも再試行までの時間は待つ
+            Some(RateLimitInfo { retry_after: Some(retry_after), .. }) => {
+                self.delay = (self.delay * 2).min(MAX_DELAY);
+                retry_after.max(self.delay)
+            }
+            // 残り回数が少ない場合は、リセットまでの時間を残りのリクエストで分け合う
+            Some(RateLimitInfo {
+                remaining: Some(remaining),
+                reset_after: Some(reset_after),
+                ..
+            }) if remaining <= LOW_REMAINING => {
+                self.delay = (reset_after / (remaining as u32 + 1)).clamp(MIN_DELAY, MAX_DELAY);
+                self.delay
+            }
+            // 余裕がある場合は間隔を半分にする
+            _ => {
+                self.delay = (self.delay / 2).max(MIN_DELAY);
+                self.delay
+            }
+        };
+
+        tokio::time::sleep(wait).await;
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::env;
+use tokio::sync::RwLock;
+use tokio::time::{Duration, Instant};
+use twilight_http::Client as HttpClient;
+use twilight_model::channel::permission_overwrite::PermissionOverwriteType;
+use twilight_model::channel::{Channel, Message};
+use twilight_model::guild::Permissions;
+use twilight_model::id::{
+    marker::{ChannelMarker, GuildMarker, RoleMarker, UserMarker},
+    Id,
+};
+
+use crate::config::ConfigFile;
+use crate::state::BotState;
+
+/// サーバーのロールの権限をキャッシュする期間
+const GUILD_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// 権限の必要なコマンドを実行できる権限
+const PRIVILEGED_PERMISSIONS: Permissions = Permissions::ADMINISTRATOR
+    .union(Permissions::MANAGE_MESSAGES)
+    .union(Permissions::MANAGE_THREADS);
+
+/// サーバーの所有者とロールごとの権限
+struct GuildPermissions {
+    owner_id: Id<UserMarker>,
+    roles: HashMap<Id<RoleMarker>, Permissions>,
+    fetched_at: Instant,
+}
+
+/// 過去メッセージの転送やマッピングの変更などのコマンドを実行できるか判定する
+///
+/// サーバーの所有者、メッセージの管理・スレッドの管理・管理者の権限を持つロール、設定した管理者ロールのいずれかを持つユーザーに許可します。
+/// 権限はサーバー全体のロールで判定します（チャンネルごとの権限の上書きは考慮しません）。
+#[derive(Default)]
+pub struct CommandPermissions {
+    /// 権限に関わらずコマンドを許可するロール
+    admin_roles: Vec<Id<RoleMarker>>,
+    /// サーバーID -> 所有者とロールの権限
+    guilds: RwLock<HashMap<Id<GuildMarker>, GuildPermissions>>,
+}
+
+impl CommandPermissions {
+    /// 環境変数 ADMIN_ROLE_IDS（カンマ区切り）と設定ファイルの `bot.admin_role_ids` から管理者ロールを読み込む
+    pub fn from_config(config: Option<&ConfigFile>) -> Self {
+        let mut admin_roles = Vec::new();
+
+        if let Ok(value) = env::var("ADMIN_ROLE_IDS") {
+            for part in value.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+                match part.parse::<u64>().ok().and_then(Id::new_checked) {
+                    Some(id) => admin_roles.push(id),
+                    None => println!("警告: ADMIN_ROLE_IDS に無効なIDが含まれています: {}", part),
+                }
+            }
+        }
+
+        if let Some(config) = config {
+            for id in &config.bot.admin_role_ids {
+                match Id::new_checked(*id) {
+                    Some(id) => admin_roles.push(id),
+                    None => println!("警告: bot.admin_role_ids に 0 は指定できません"),
+                }
+            }
+        }
+
+        Self {
+            admin_roles,
+            guilds: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// メッセージの送信者が権限の必要なコマンドを実行できるかどうか
+    pub async fn allows(&self, http: &HttpClient, message: &Message) -> bool {
+        let (Some(guild_id), Some(member)) = (message.guild_id, &message.member) else {
+            return false;
+        };
+        self.allows_member(http, guild_id, message.author.id, &member.roles).await
+    }
+
+    /// サーバーのメンバーが権限の必要な操作（コマンド・Issueの作成）を実行できるかどうか
+    pub async fn allows_member(
+        &self,
+        http: &HttpClient,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        roles: &[Id<RoleMarker>],
+    ) -> bool {
+        if roles.iter().any(|role| self.admin_roles.contains(role)) {
+            return true;
+        }
+
+        if let Err(e) = self.refresh_guild(http, guild_id).await {
+            println!("サーバー {} のロールの権限を取得できませんでした: {}", guild_id, e);
+            return false;
+        }
+        let guilds = self.guilds.read().await;
+        let Some(guild) = guilds.get(&guild_id) else {
+            return false;
+        };
+        if guild.owner_id == user_id {
+            return true;
+        }
+
+        // @everyone ロールのIDはサーバーIDと同じ
+        let permissions = std::iter::once(guild_id.cast())
+            .chain(roles.iter().copied())
+            .filter_map(|role| guild.roles.get(&role))
+            .fold(Permissions::empty(), |all, permissions| all | *permissions);
+        permissions.intersects(PRIVILEGED_PERMISSIONS)
+    }
+
+    /// ユーザーが別のサーバーでも権限の必要な操作を実行できるかどうか（別のサーバーへの転送の設定で使用）
+    ///
+    /// メッセージには実行したサーバーのロールしか含まれないため、そのサーバーでのロールを取得して判定します。
+    /// ユーザーがサーバーに参加していない場合は false を返します。
+    pub async fn allows_in_guild(&self, http: &HttpClient, guild_id: Id<GuildMarker>, user_id: Id<UserMarker>) -> bool {
+        let member = match http.guild_member(guild_id, user_id).await {
+            Ok(response) => response.model().await,
+            Err(e) => {
+                println!("サーバー {} のメンバー {} を取得できませんでした: {}", guild_id, user_id, e);
+                return false;
+            }
+        };
+        match member {
+            Ok(member) => self.allows_member(http, guild_id, user_id, &member.roles).await,
+            Err(e) => {
+                println!("サーバー {} のメンバー {} を読み込めませんでした: {}", guild_id, user_id, e);
+                false
+            }
+        }
+    }
+
+    /// サーバーのロールの権限を取得する（キャッシュが新しい場合は何もしない）
+    async fn refresh_guild(
+        &self,
+        http: &HttpClient,
+        guild_id: Id<GuildMarker>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self
+            .guilds
+            .read()
+            .await
+            .get(&guild_id)
+            .is_some_and(|guild| guild.fetched_at.elapsed() < GUILD_CACHE_TTL)
+        {
+            return Ok(());
+        }
+
+        let guild = http.guild(guild_id).await?.model().await?;
+        let permissions = GuildPermissions {
+            owner_id: guild.owner_id,
+            roles: guild.roles.iter().map(|role| (role.id, role.permissions)).collect(),
+            fetched_at: Instant::now(),
+        };
+        self.guilds.write().await.insert(guild_id, permissions);
+        Ok(())
+    }
+}
+
+/// ユーザーがチャンネルで持つ権限を計算する（`!test` で使用）
+///
+/// サーバーのロールの権限に、チャンネルの権限の上書き（@everyone・ロール・メンバーの順）を適用します。
+/// スレッドの場合は親チャンネルの権限の上書きを使用します。
+pub async fn channel_permissions(
+    http: &HttpClient,
+    channel_id: Id<ChannelMarker>,
+    user_id: Id<UserMarker>,
+) -> Result<Permissions, Box<dyn std::error::Error + Send + Sync>> {
+    let mut channel = http.channel(channel_id).await?.model().await?;
+    let guild_id = channel.guild_id.ok_or("サーバーのチャンネルではありません")?;
+    if channel.kind.is_thread() {
+        if let Some(parent_id) = channel.parent_id {
+            channel = http.channel(parent_id).await?.model().await?;
+        }
+    }
+
+    let guild = http.guild(guild_id).await?.model().await?;
+    if guild.owner_id == user_id {
+        return Ok(Permissions::all());
+    }
+    let member = http.guild_member(guild_id, user_id).await?.model().await?;
+
+    // @everyone ロールのIDはサーバーIDと同じ
+    let mut permissions = guild
+        .roles
+        .iter()
+        .filter(|role| role.id == guild_id.cast() || member.roles.contains(&role.id))
+        .fold(Permissions::empty(), |all, role| all | role.permissions);
+    if permissions.contains(Permissions::ADMINISTRATOR) {
+        return Ok(Permissions::all());
+    }
+
+    let overwrites = channel.permission_overwrites.unwrap_or_default();
+    if let Some(everyone) = overwrites.iter().find(|overwrite| overwrite.id == guild_id.cast()) {
+        permissions = (permissions & !everyone.deny) | everyone.allow;
+    }
+    let (role_allow, role_deny) = overwrites
+        .iter()
+        .filter(|overwrite| overwrite.kind == PermissionOverwriteType::Role && member.roles.contains(&overwrite.id.cast()))
+        .fold((Permissions::empty(), Permissions::empty()), |(allow, deny), overwrite| {
+            (allow | overwrite.allow, deny | overwrite.deny)
+        });
+    permissions = (permissions & !role_deny) | role_allow;
+    if let Some(own) = overwrites
+        .iter()
+        .find(|overwrite| overwrite.kind == PermissionOverwriteType::Member && overwrite.id == user_id.cast())
+    {
+        permissions = (permissions & !own.deny) | own.allow;
+    }
+
+    Ok(permissions)
+}
+
+/// 転送先チャンネルでボットに必要な権限
+const TARGET_PERMISSIONS: [(Permissions, &str); 2] = [
+    (Permissions::VIEW_CHANNEL, "チャンネルを見る"),
+    (Permissions::SEND_MESSAGES, "メッセージを送信"),
+];
+
+/// チャンネルを取得できなかった理由を説明する
+///
+/// ボットが参加していないサーバーのチャンネルは 403（Missing Access）、存在しないチャンネルは 404 になります。
+pub fn describe_access_error(error: &twilight_http::Error) -> String {
+    match error.kind() {
+        twilight_http::error::ErrorType::Response { status, .. } if status.get() == 403 => {
+            "ボットがチャンネルのサーバーに参加していないか、チャンネルを見る権限がありません".to_string()
+        }
+        twilight_http::error::ErrorType::Response { status, .. } if status.get() == 404 => {
+            "チャンネルが見つかりません".to_string()
+        }
+        _ => error.to_string(),
+    }
+}
+
+/// 転送先チャンネルにボットが転送できるか確認する（別のサーバーのチャンネルを含む）
+///
+/// チャンネルを取得できない場合（ボットが転送先のサーバーに参加していない場合を含む）や、
+/// 転送に必要な権限が無い場合は理由をエラーとして返します。確認できた場合は転送先のチャンネルを返します。
+pub async fn check_target_channel(
+    http: &HttpClient,
+    target_channel_id: Id<ChannelMarker>,
+    bot_user_id: Id<UserMarker>,
+) -> Result<Channel, String> {
+    let channel = match http.channel(target_channel_id).await {
+        Ok(response) => response.model().await.map_err(|e| e.to_string())?,
+        Err(e) => return Err(describe_access_error(&e)),
+    };
+    if channel.guild_id.is_none() {
+        return Err("サーバーのチャンネルではありません".to_string());
+    }
+
+    let granted = channel_permissions(http, target_channel_id, bot_user_id).await.map_err(|e| e.to_string())?;
+    let missing: Vec<&str> = TARGET_PERMISSIONS
+        .iter()
+        .filter(|(permission, _)| !granted.contains(*permission))
+        .map(|(_, name)| *name)
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("ボットに「{}」の権限がありません", missing.join("」「")));
+    }
+
+    Ok(channel)
+}
+
+/// マッピングを追加する前に転送先を確認する（`!thread2channel`・`/map add`・`/setup` で使用）
+///
+/// ボットが転送先のサーバーに参加していて、転送に必要な権限があるかを確認します。
+/// 転送先が転送元と別のサーバーのチャンネルの場合は、実行したユーザーが転送先のサーバーでも権限を持っている必要があります。
+pub async fn check_mapping_target(
+    state: &BotState,
+    source_id: Id<ChannelMarker>,
+    user_id: Id<UserMarker>,
+    target_channel_id: Id<ChannelMarker>,
+) -> Result<(), String> {
+    // チャンネル同士のミラーでは、転送元に転送すると転送したメッセージが繰り返し転送されてしまう
+    if target_channel_id == source_id {
+        return Err("転送元と同じチャンネルには転送できません".to_string());
+    }
+    let Some(bot_user_id) = state.bot_user_id() else {
+        return Ok(());
+    };
+    let target = check_target_channel(&state.http, target_channel_id, bot_user_id).await?;
+    state.cache_channel(&target).await;
+
+    let (Some(source_guild_id), Some(target_guild_id)) = (state.channel_guild(source_id).await, target.guild_id) else {
+        return Ok(());
+    };
+    if source_guild_id != target_guild_id {
+        if !state.permissions.allows_in_guild(&state.http, target_guild_id, user_id).await {
+            return Err("別のサーバーのチャンネルに転送するには、転送先のサーバーでもメッセージの管理またはスレッドの管理の権限（または管理者ロール）が必要です".to_string());
+        }
+        println!("別のサーバーへの転送を設定します: サーバー {} -> サーバー {}", source_guild_id, target_guild_id);
+    }
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use serde_json::Value;
+use twilight_http::Client as HttpClient;
+use twilight_model::channel::message::embed::EmbedField;
+use twilight_model::channel::message::Embed;
+use twilight_model::channel::Message;
+use twilight_model::id::{
+    marker::{ChannelMarker, MessageMarker},
+    Id,
+};
+
+/// 投票の選択肢
+#[derive(Debug, Clone)]
+pub struct PollAnswer {
+    /// 選択肢のテキスト（絵文字がある場合は先頭に付ける）
+    pub text: String,
+    /// 投票数（結果が取得できない場合は None）
+    pub count: Option<u64>,
+}
+
+/// 転送用にまとめた投票の内容
+///
+/// 使用しているtwilight-modelのバージョンは投票に対応していないため、APIのJSONから直接読み取ります。
+#[derive(Debug, Clone)]
+pub struct PollSummary {
+    /// 質問
+    pub question: String,
+    /// 選択肢の一覧
+    pub answers: Vec<PollAnswer>,
+    /// 複数選択が可能かどうか
+    pub allow_multiselect: bool,
+    /// 締め切り日時（ISO 8601形式）
+    pub expiry: Option<String>,
+    /// 投票が終了して結果が確定しているかどうか
+    pub finalized: bool,
+}
+
+/// 投票を含む可能性があるメッセージかどうか
+///
+/// 投票のメッセージは本文・添付ファイル・埋め込み・スタンプを持たないため、
+/// そのようなメッセージだけAPIから投票の内容を取得します。
+pub fn may_contain_poll(message: &Message) -> bool {
+    message.content.is_empty()
+        && message.attachments.is_empty()
+        && message.embeds.is_empty()
+        && message.sticker_items.is_empty()
+}
+
+/// 選択肢の絵文字を表示用の文字列にする
+fn format_poll_emoji(emoji: &Value) -> Option<String> {
+    let name = emoji["name"].as_str()?;
+    match emoji["id"].as_str() {
+        Some(id) if emoji["animated"].as_bool().unwrap_or(false) => Some(format!("<a:{}:{}>", name, id)),
+        Some(id) => Some(format!("<:{}:{}>", name, id)),
+        None => Some(name.to_string()),
+    }
+}
+
+/// メッセージのJSONから投票の内容を読み取る（投票が無い場合は None）
+pub fn parse_poll(message: &Value) -> Option<PollSummary> {
+    let poll = message.get("poll")?;
+    let question = poll["question"]["text"].as_str()?.to_string();
+    let results = poll.get("results");
+
+    let answers = poll["answers"]
+        .as_array()
+        .map(|answers| {
+            answers
+                .iter()
+                .map(|answer| {
+                    let media = &answer["poll_media"];
+                    let text = media["text"].as_str().unwrap_or_default();
+                    let text = match format_poll_emoji(&media["emoji"]) {
+                        Some(emoji) if text.is_empty() => emoji,
+                        Some(emoji) => format!("{} {}", emoji, text),
+                        None => text.to_string(),
+                    };
+
+                    // 結果に含まれない選択肢は投票数0として扱う
+                    let count = results.map(|results| {
+                        results["answer_counts"]
+                            .as_array()
+                            .and_then(|counts| counts.iter().find(|count| count["id"] == answer["answer_id"]))
+                            .and_then(|count| count["count"].as_u64())
+                            .unwrap_or(0)
+                    });
+
+                    PollAnswer { text, count }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Some(PollSummary {
+        question,
+        answers,
+        allow_multiselect: poll["allow_multiselect"].as_bool().unwrap_or(false),
+        expiry: poll["expiry"].as_str().map(str::to_string),
+        finalized: results.and_then(|results| results["is_finalized"].as_bool()).unwrap_or(false),
+    })
+}
+
+/// APIからメッセージを取得し、投票の内容を読み取る
+pub async fn fetch_poll(
+    http: &HttpClient,
+    channel_id: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+) -> Result<Option<PollSummary>, Box<dyn std::error::Error + Send + Sync>> {
+    let body = http.message(channel_id, message_id).await?.text().await?;
+    let message: Value = serde_json::from_str(&body)?;
+    Ok(parse_poll(&message))
+}
+
+/// 投票の内容を埋め込みにする
+///
+/// `show_results` が true で投票が終了している場合は、各選択肢の投票数も表示します。
+pub fn build_poll_embed(poll: &PollSummary, show_results: bool) -> Embed {
+    let show_results = show_results && poll.finalized;
+
+    let fields = poll
+        .answers
+        .iter()
+        .map(|answer| EmbedField {
+            inline: false,
+            name: answer.text.clone(),
+            value: match answer.count {
+                Some(count) if show_results => format!("{} 票", count),
+                _ => "\u{200b}".to_string(),
+            },
+        })
+        .collect();
+
+    let mut notes = Vec::new();
+    if poll.allow_multiselect {
+        notes.push("複数選択可".to_string());
+    }
+    if show_results {
+        notes.push("投票は終了しました".to_string());
+    } else if let Some(expiry) = &poll.expiry {
+        // Discordのタイムスタンプ記法で、閲覧者のタイムゾーンに合わせて表示する
+        let expiry = match chrono::DateTime::parse_from_rfc3339(expiry) {
+            Ok(time) => format!("<t:{}:f>", time.timestamp()),
+            Err(_) => expiry.clone(),
+        };
+        notes.push(format!("締め切り: {}", expiry));
+    }
+
+    Embed {
+        author: None,
+        color: None,
+        description: if notes.is_empty() { None } else { Some(notes.join(" / ")) },
+        fields,
+        footer: None,
+        image: None,
+        kind: "rich".to_string(),
+        provider: None,
+        thumbnail: None,
+        timestamp: None,
+        title: Some(format!("📊 {}", poll.question)),
+        url: None,
+        video: None,
+    }
+}
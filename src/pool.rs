@@ -0,0 +1,134 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use twilight_gateway::Event;
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use crate::dispatch::EventDispatcher;
+use crate::events::describe_event;
+use crate::state::BotState;
+use crate::stats::BotStats;
+use crate::supervisor;
+
+/// 1つの処理タスクで処理を待てるイベントの数（これを超えると次のイベントの受信を待たせる）
+const LANE_CAPACITY: usize = 64;
+
+/// イベントが関係するチャンネル（同じチャンネルのイベントは同じタスクで順番に処理する）
+fn event_channel(event: &Event) -> Option<Id<ChannelMarker>> {
+    match event {
+        Event::MessageCreate(message) => Some(message.channel_id),
+        Event::MessageUpdate(update) => Some(update.channel_id),
+        Event::ReactionAdd(reaction) => Some(reaction.channel_id),
+        Event::ReactionRemove(reaction) => Some(reaction.channel_id),
+        Event::ReactionRemoveAll(reaction) => Some(reaction.channel_id),
+        Event::ReactionRemoveEmoji(reaction) => Some(reaction.channel_id),
+        Event::InteractionCreate(interaction) => interaction.channel.as_ref().map(|channel| channel.id),
+        Event::ThreadCreate(thread) => Some(thread.id),
+        Event::ThreadUpdate(thread) => Some(thread.id),
+        Event::ThreadDelete(thread) => Some(thread.id),
+        Event::ChannelCreate(channel) => Some(channel.id),
+        Event::ChannelUpdate(channel) => Some(channel.id),
+        Event::ChannelDelete(channel) => Some(channel.id),
+        _ => None,
+    }
+}
+
+/// 処理の終わったイベントを数える（処理中にパニックした場合も数える）
+struct EventGuard<'a>(&'a BotStats);
+
+impl Drop for EventGuard<'_> {
+    fn drop(&mut self) {
+        self.0.finish_event();
+    }
+}
+
+/// 受信したイベントを決まった数のタスクで並行して処理する
+///
+/// イベントはチャンネルごとに同じタスクに割り振るため、同じスレッドのメッセージの順番は入れ替わりません。
+/// 処理が追いつかずに列が一杯になった場合は、空くまで次のイベントの受信を待たせます（タスクやメモリが際限なく増えないようにするため）。
+/// 処理中にパニックしたタスクは再起動し、列に残っているイベントの処理を続けます。
+pub struct EventPool {
+    lanes: Vec<mpsc::Sender<Event>>,
+    workers: Vec<JoinHandle<()>>,
+    state: Arc<BotState>,
+}
+
+impl EventPool {
+    /// `concurrency` 個の処理タスクを開始する
+    pub fn spawn(dispatcher: EventDispatcher, state: Arc<BotState>, concurrency: usize) -> Self {
+        let dispatcher = Arc::new(dispatcher);
+        let concurrency = concurrency.max(1);
+        let mut lanes = Vec::with_capacity(concurrency);
+        let mut workers = Vec::with_capacity(concurrency);
+
+        for lane in 0..concurrency {
+            let (sender, receiver) = mpsc::channel::<Event>(LANE_CAPACITY);
+            // 再起動したタスクが同じ列から受け取れるよう、受信側を共有する
+            let receiver = Arc::new(Mutex::new(receiver));
+            let dispatcher = Arc::clone(&dispatcher);
+            let worker_state = Arc::clone(&state);
+            workers.push(supervisor::supervise(&state, format!("イベントの処理 ({})", lane), move || {
+                let (receiver, dispatcher, state) = (Arc::clone(&receiver), Arc::clone(&dispatcher), Arc::clone(&worker_state));
+                async move {
+                    while let Some(event) = receiver.lock().await.recv().await {
+                        let _guard = EventGuard(&state.stats);
+                        let context = describe_event(&event);
+                        if let Err(e) = dispatcher.dispatch(event, Arc::clone(&state)).await {
+                            eprintln!("Error handling event: {:?}", e);
+                            state.stats.record_error();
+                            state.alerts.failure(&state.http, &context, &*e).await;
+                        }
+                    }
+                }
+            }));
+            lanes.push(sender);
+        }
+
+        Self { lanes, workers, state }
+    }
+
+    /// イベントを処理タスクに渡す（列が一杯の場合は空くまで待つ）
+    pub async fn submit(&self, event: Event) {
+        let lane = match event_channel(&event) {
+            Some(channel_id) => {
+                let mut hasher = DefaultHasher::new();
+                channel_id.hash(&mut hasher);
+                hasher.finish() as usize % self.lanes.len()
+            }
+            None => 0,
+        };
+        let sender = &self.lanes[lane];
+
+        self.state.stats.start_event();
+        let event = match sender.try_send(event) {
+            Ok(()) => return,
+            Err(mpsc::error::TrySendError::Full(event)) => {
+                self.state.stats.record_event_waited();
+                event
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                self.state.stats.finish_event();
+                return;
+            }
+        };
+        if sender.send(event).await.is_err() {
+            self.state.stats.finish_event();
+        }
+    }
+
+    /// 新しいイベントの受付をやめ、処理中のイベントが終わるまで待つ（時間内に終わった場合は true を返す）
+    pub async fn close(self, timeout: Duration) -> bool {
+        drop(self.lanes);
+        let workers = self.workers;
+        tokio::time::timeout(timeout, async move {
+            for worker in workers {
+                let _ = worker.await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+}
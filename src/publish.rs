@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::json;
+use twilight_model::channel::Message;
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use crate::retry::{self, SendError};
+use crate::sink::SinkRecord;
+use crate::state::{BotState, ThreadInfo};
+
+/// イベントを送信するサブジェクト（トピック）のデフォルトの接頭辞
+pub const DEFAULT_SUBJECT: &str = "thread2channel.forwarded";
+
+/// 転送したメッセージのイベント
+#[derive(Serialize)]
+struct ForwardedEvent<'a> {
+    /// イベントの種類（`message.forwarded`）
+    event: &'static str,
+    /// マッピングの転送元のスレッドID（Kafka のキー、NATS のサブジェクトの末尾）
+    mapping_id: Id<ChannelMarker>,
+    /// マッピングの転送先のチャンネルID
+    target_channel_id: Id<ChannelMarker>,
+    /// 転送先の種類（discord・file・http など）
+    sink: &'static str,
+    /// 転送したメッセージの内容（`http` の転送先と同じ形式）
+    #[serde(flatten)]
+    record: SinkRecord<'a>,
+}
+
+/// イベントの送信先
+///
+/// 新しい種類の送信先を追加する場合は、この trait を実装し、[`EventPublisher::connect`] で選択できるようにします。
+#[async_trait]
+pub trait EventBroker: Send + Sync {
+    /// 送信先の説明（ログ用、認証情報は含めない）
+    fn describe(&self) -> String;
+
+    /// イベントを送信する（`key` はマッピングのスレッドID）
+    async fn publish(&self, key: &str, payload: Vec<u8>) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// Kafka REST Proxy（API v2）経由で Kafka のトピックに送信する送信先
+///
+/// マッピングのスレッドIDをキーにするため、同じマッピングのイベントは同じパーティションに届いた順に入ります。
+pub struct KafkaRestBroker {
+    /// REST Proxy のURL（例: `http://localhost:8082`）
+    pub endpoint: String,
+    /// 送信先のトピック
+    pub topic: String,
+}
+
+#[async_trait]
+impl EventBroker for KafkaRestBroker {
+    fn describe(&self) -> String {
+        format!("Kafka のトピック {}（REST Proxy: {}）", self.topic, self.endpoint)
+    }
+
+    async fn publish(&self, key: &str, payload: Vec<u8>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let value: serde_json::Value = serde_json::from_slice(&payload)?;
+        let body = json!({ "records": [{ "key": key, "value": value }] });
+        let url = format!("{}/topics/{}", self.endpoint.trim_end_matches('/'), self.topic);
+        let client = reqwest::Client::new();
+
+        retry::with_backoff("Kafka へのイベントの送信", || async {
+            let response = client
+                .post(&url)
+                .header("Content-Type", "application/vnd.kafka.json.v2+json")
+                .json(&body)
+                .send()
+                .await
+                .map_err(|e| SendError::network(format!("Kafka へのイベントの送信に失敗しました: {}", e)))?;
+            let status = response.status();
+            if status.is_success() {
+                return Ok(());
+            }
+            let text = response.text().await.unwrap_or_default();
+            Err(SendError::status(status.as_u16(), format!("Kafka へのイベントの送信に失敗しました: {} {}", status, text)).into())
+        })
+        .await
+    }
+}
+
+/// NATS のサブジェクト（`<接頭辞>.<スレッドID>`）に送信する送信先
+#[cfg(feature = "nats")]
+pub struct NatsBroker {
+    client: async_nats::Client,
+    /// サブジェクトの接頭辞
+    subject: String,
+}
+
+#[cfg(feature = "nats")]
+impl NatsBroker {
+    /// NATS のサーバーに接続する
+    pub async fn connect(url: &str, subject: String) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let client = async_nats::connect(url).await?;
+        Ok(Self { client, subject })
+    }
+}
+
+#[cfg(feature = "nats")]
+#[async_trait]
+impl EventBroker for NatsBroker {
+    fn describe(&self) -> String {
+        format!("NATS のサブジェクト {}.<スレッドID>", self.subject)
+    }
+
+    async fn publish(&self, key: &str, payload: Vec<u8>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // 接続が切れている間のメッセージはクライアントがバッファし、再接続後に送信する
+        self.client.publish(format!("{}.{}", self.subject, key), payload.into()).await?;
+        Ok(())
+    }
+}
+
+/// 転送したメッセージのイベントの送信（`bot.event_publisher` を指定した場合のみ送信）
+#[derive(Default)]
+pub struct EventPublisher {
+    broker: Option<Box<dyn EventBroker>>,
+}
+
+impl EventPublisher {
+    /// 送信先のURLから送信先を選ぶ
+    ///
+    /// `nats://`（`nats` 機能が必要）は NATS、`kafka+http://`・`kafka+https://` は Kafka REST Proxy に送信します。
+    /// 接続できない場合は警告を出力し、イベントを送信しません。
+    pub async fn connect(url: Option<String>, subject: String) -> Self {
+        let Some(url) = url else {
+            return Self::default();
+        };
+
+        let broker: Box<dyn EventBroker> = if let Some(endpoint) = url.strip_prefix("kafka+") {
+            Box::new(KafkaRestBroker { endpoint: endpoint.to_string(), topic: subject })
+        } else if url.starts_with("nats://") || url.starts_with("tls://") {
+            #[cfg(feature = "nats")]
+            match NatsBroker::connect(&url, subject).await {
+                Ok(broker) => Box::new(broker),
+                Err(e) => {
+                    println!("警告: NATS に接続できませんでした: {}。イベントは送信しません", e);
+                    return Self::default();
+                }
+            }
+            #[cfg(not(feature = "nats"))]
+            {
+                println!("警告: NATS にイベントを送信するには nats 機能を有効にしてビルドしてください。イベントは送信しません");
+                return Self::default();
+            }
+        } else {
+            println!(
+                "警告: イベントの送信先 {} は使用できません（nats:// か kafka+http:// で指定してください）。イベントは送信しません",
+                url
+            );
+            return Self::default();
+        };
+
+        println!("転送したメッセージのイベントを {} に送信します", broker.describe());
+        Self { broker: Some(broker) }
+    }
+
+    /// 転送したメッセージのイベントを送信する（失敗しても転送には影響しないため、警告のみ出力する）
+    pub async fn publish(&self, state: &BotState, thread_info: &ThreadInfo, message: &Message) {
+        let Some(broker) = &self.broker else {
+            return;
+        };
+        let event = ForwardedEvent {
+            event: "message.forwarded",
+            mapping_id: message.channel_id,
+            target_channel_id: thread_info.target_channel_id,
+            sink: thread_info.sink.build(thread_info).name(),
+            record: SinkRecord::new(state, thread_info, message).await,
+        };
+        let payload = match serde_json::to_vec(&event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                println!("警告: メッセージ {} のイベントを作成できませんでした: {}", message.id, e);
+                return;
+            }
+        };
+        if let Err(e) = broker.publish(&message.channel_id.to_string(), payload).await {
+            println!("警告: メッセージ {} のイベントを送信できませんでした: {}", message.id, e);
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::Duration;
+use twilight_model::channel::Message;
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use crate::forwarding::{already_forwarded, transfer_single_message};
+use crate::state::{BotState, ThreadInfo};
+use crate::supervisor;
+
+/// 1つのマッピングで転送を待てるメッセージの数（これを超えるとイベントの受信を待たせる）
+const QUEUE_CAPACITY: usize = 256;
+
+/// 転送するメッセージが無い状態がこの時間続いたら、転送用のタスクを終了する
+const WORKER_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// 転送を待っているメッセージ
+struct ForwardJob {
+    thread_info: ThreadInfo,
+    message: Message,
+    /// データベースに保存した転送待ちのID（保存に失敗した場合は None）
+    pending_id: Option<i64>,
+}
+
+/// 1つのマッピングの転送待ちの列
+struct ForwardQueue {
+    sender: mpsc::Sender<ForwardJob>,
+    /// 転送を待っている（転送中を含む）メッセージの数
+    depth: Arc<AtomicUsize>,
+}
+
+/// マッピング（転送元のスレッド）ごとの転送待ちの列
+///
+/// マッピングごとに1つのタスクが届いた順にメッセージを転送するため、同じスレッドのメッセージの順番は入れ替わりません。
+/// 転送の遅いマッピング（`delay` の指定やレート制限など）が、他のマッピングの転送を待たせることもありません。
+/// 転送待ちのメッセージはデータベースにも保存し、転送が終わる前に終了した場合は次回の起動時に転送します。
+#[derive(Default)]
+pub struct ForwardQueues {
+    /// 転送元のスレッドID -> 転送待ちの列
+    queues: Mutex<HashMap<Id<ChannelMarker>, ForwardQueue>>,
+}
+
+impl ForwardQueues {
+    /// 空の状態を作成する
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// メッセージを転送待ちの列に追加する（列が一杯の場合は空くまで待つ）
+    pub async fn enqueue(&self, state: &Arc<BotState>, thread_info: ThreadInfo, message: Message) {
+        let pending_id = match state.save_pending_forward(&thread_info, &message) {
+            Ok(id) => Some(id),
+            Err(e) => {
+                println!("転送待ちのメッセージ {} をデータベースに保存できませんでした: {}", message.id, e);
+                None
+            }
+        };
+        self.push(state, ForwardJob { thread_info, message, pending_id }).await;
+    }
+
+    /// 前回の終了時に転送できなかったメッセージを転送待ちの列に追加する（新しいメッセージより先に転送する）
+    pub async fn restore(&self, state: &Arc<BotState>) {
+        let pending = match state.load_pending_forwards() {
+            Ok(pending) => pending,
+            Err(e) => {
+                println!("転送待ちのメッセージをデータベースから読み込めませんでした: {}", e);
+                return;
+            }
+        };
+        if pending.is_empty() {
+            return;
+        }
+
+        println!("前回の終了時に転送できなかったメッセージを {} 件転送します", pending.len());
+        for pending in pending {
+            let job = ForwardJob {
+                thread_info: pending.thread_info,
+                message: pending.message,
+                pending_id: Some(pending.id),
+            };
+            self.push(state, job).await;
+        }
+    }
+
+    /// 転送待ちの列に追加する
+    async fn push(&self, state: &Arc<BotState>, mut job: ForwardJob) {
+        let thread_id = job.message.channel_id;
+
+        loop {
+            let (sender, depth) = {
+                let mut queues = self.queues.lock().await;
+                let queue = queues
+                    .entry(thread_id)
+                    .or_insert_with(|| spawn_worker(Arc::clone(state), thread_id));
+                (queue.sender.clone(), Arc::clone(&queue.depth))
+            };
+
+            depth.fetch_add(1, Ordering::Relaxed);
+            match sender.send(job).await {
+                Ok(()) => return,
+                // 待機していたタスクが終了した直後の場合は、新しいタスクを作成して追加し直す
+                Err(mpsc::error::SendError(returned)) => {
+                    depth.fetch_sub(1, Ordering::Relaxed);
+                    let mut queues = self.queues.lock().await;
+                    if queues.get(&thread_id).is_some_and(|queue| queue.sender.same_channel(&sender)) {
+                        queues.remove(&thread_id);
+                    }
+                    job = returned;
+                }
+            }
+        }
+    }
+
+    /// 転送を待っているメッセージの数（マッピングごと、多い順）
+    pub async fn depths(&self) -> Vec<(Id<ChannelMarker>, usize)> {
+        let mut depths: Vec<_> = self
+            .queues
+            .lock()
+            .await
+            .iter()
+            .map(|(thread_id, queue)| (*thread_id, queue.depth.load(Ordering::Relaxed)))
+            .filter(|(_, depth)| *depth > 0)
+            .collect();
+        depths.sort_by_key(|(thread_id, depth)| (std::cmp::Reverse(*depth), *thread_id));
+        depths
+    }
+
+    /// 転送待ちのメッセージが無くなるまで待つ（時間内に転送が終わった場合は true を返す）
+    ///
+    /// 時間内に転送できなかったメッセージはデータベースに残り、次回の起動時に転送されます。
+    pub async fn wait_until_empty(&self, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while !self.depths().await.is_empty() {
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+        true
+    }
+
+    /// 転送待ちのタスクが無くなったため、列を取り除く（その間に追加されたメッセージがある場合は取り除かない）
+    ///
+    /// 確認してから取り除くまでの間に追加されたメッセージが失われないよう、受信側を閉じてから残りを取り出し、
+    /// 残っていた場合は新しいタスクの列の先頭に移します（列のロックを取ったまま移すため、順番は入れ替わりません）。
+    async fn retire(&self, state: &Arc<BotState>, thread_id: Id<ChannelMarker>, receiver: &mut mpsc::Receiver<ForwardJob>) -> bool {
+        let mut queues = self.queues.lock().await;
+        if !receiver.is_empty() {
+            return false;
+        }
+        receiver.close();
+        let Some(old) = queues.remove(&thread_id) else {
+            return true;
+        };
+
+        let mut remaining = Vec::new();
+        while let Ok(job) = receiver.try_recv() {
+            remaining.push(job);
+        }
+        if !remaining.is_empty() {
+            old.depth.fetch_sub(remaining.len(), Ordering::Relaxed);
+            let queue = spawn_worker(Arc::clone(state), thread_id);
+            queue.depth.fetch_add(remaining.len(), Ordering::Relaxed);
+            for job in remaining {
+                // 新しい列は空で、取り出した数は列の上限を超えないため、待たずに追加できる
+                if let Err(e) = queue.sender.try_send(job) {
+                    queue.depth.fetch_sub(1, Ordering::Relaxed);
+                    println!("転送待ちのメッセージ {} を列に戻せませんでした（データベースに残り、次回の起動時に転送します）", e.into_inner().message.id);
+                }
+            }
+            queues.insert(thread_id, queue);
+        }
+        true
+    }
+}
+
+/// 転送の終わったメッセージを数える（転送中にパニックした場合も数える）
+struct DepthGuard<'a>(&'a AtomicUsize);
+
+impl Drop for DepthGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// マッピングの転送用のタスクを開始する
+///
+/// 転送中にパニックした場合はタスクを再起動し、残りのメッセージの転送を続けます。
+/// パニックしたメッセージはデータベースの転送待ちに残り、次回の起動時に転送し直します。
+fn spawn_worker(state: Arc<BotState>, thread_id: Id<ChannelMarker>) -> ForwardQueue {
+    let (sender, receiver) = mpsc::channel::<ForwardJob>(QUEUE_CAPACITY);
+    // 再起動したタスクが同じ列から受け取れるよう、受信側を共有する
+    let receiver = Arc::new(Mutex::new(receiver));
+    let depth = Arc::new(AtomicUsize::new(0));
+    let worker_depth = Arc::clone(&depth);
+    let worker_state = Arc::clone(&state);
+
+    supervisor::supervise(&state, format!("<#{}> の転送", thread_id), move || {
+        let (state, receiver, depth) = (Arc::clone(&worker_state), Arc::clone(&receiver), Arc::clone(&worker_depth));
+        async move { run_worker(&state, thread_id, &receiver, &depth).await }
+    });
+
+    ForwardQueue { sender, depth }
+}
+
+/// 転送待ちの列のメッセージを届いた順に転送する（しばらく転送するメッセージが無ければ終了する）
+async fn run_worker(
+    state: &Arc<BotState>,
+    thread_id: Id<ChannelMarker>,
+    receiver: &Mutex<mpsc::Receiver<ForwardJob>>,
+    depth: &AtomicUsize,
+) {
+    loop {
+        let job = {
+            let mut receiver = receiver.lock().await;
+            match tokio::time::timeout(WORKER_IDLE_TIMEOUT, receiver.recv()).await {
+                Ok(Some(job)) => job,
+                Ok(None) => break,
+                Err(_) => {
+                    if state.queues.retire(state, thread_id, &mut receiver).await {
+                        break;
+                    }
+                    continue;
+                }
+            }
+        };
+        let _guard = DepthGuard(depth);
+
+        // 未転送のメッセージの回収（再接続時）と同じメッセージが列に入っている場合は、先に転送した方だけを送る
+        if already_forwarded(state, &job.thread_info, &job.message) {
+            println!("メッセージ {} は転送済みのためスキップします", job.message.id);
+        } else if let Err(e) = transfer_single_message(state, &job.thread_info, &job.message).await {
+            eprintln!("スレッド {} のメッセージの転送中にエラーが発生しました: {}", thread_id, e);
+            state.stats.record_error();
+            state.record_mapping_failure(thread_id);
+            // 再試行しても転送できなかったメッセージは、`!retry-failed` で再送できるように残す
+            if let Err(save_error) = state.save_failed_forward(&job.thread_info, &job.message, &e.to_string()) {
+                println!("転送に失敗したメッセージ {} をデータベースに保存できませんでした: {}", job.message.id, save_error);
+            }
+            let source = state.message_url(&job.message).await.unwrap_or_else(|| format!("<#{}>", thread_id));
+            let context = format!("{} の転送（`!retry-failed` で再送できます）", source);
+            state.alerts.mapping_failure(&state.http, &context, thread_id, &job.thread_info, &*e).await;
+        }
+        if let Some(id) = job.pending_id {
+            if let Err(e) = state.clear_pending_forward(id) {
+                println!("転送待ちのメッセージ {} をデータベースから削除できませんでした: {}", job.message.id, e);
+            }
+        }
+        if job.thread_info.star_emoji.is_some() {
+            state.release_starred_message(job.message.id).await;
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+use crate::transform::MessageTransform;
+
+/// 転送前に伏せ字にする機密情報の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SensitiveKind {
+    /// メールアドレス
+    Email,
+    /// 電話番号
+    Phone,
+    /// APIトークン・アクセスキー（Discord、GitHub、Slack、AWS など）
+    Token,
+}
+
+impl SensitiveKind {
+    /// 全ての種類
+    pub const ALL: [SensitiveKind; 3] = [Self::Email, Self::Phone, Self::Token];
+
+    /// オプションの値から種類を読み取る
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "email" => Some(Self::Email),
+            "phone" => Some(Self::Phone),
+            "token" => Some(Self::Token),
+            _ => None,
+        }
+    }
+
+    /// 一致させる正規表現
+    fn pattern(self) -> &'static Regex {
+        static EMAIL: OnceLock<Regex> = OnceLock::new();
+        static PHONE: OnceLock<Regex> = OnceLock::new();
+        static TOKEN: OnceLock<Regex> = OnceLock::new();
+
+        match self {
+            Self::Email => EMAIL.get_or_init(|| Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap()),
+            // 区切り付きの番号（090-1234-5678、+81 90 1234 5678 など）と、区切り無しの国内番号
+            Self::Phone => PHONE.get_or_init(|| {
+                Regex::new(r"(?:\+\d{1,3}[-\s]?)?\(?\d{2,4}\)?[-\s]\d{2,4}[-\s]\d{3,4}\b|\b0\d{9,10}\b").unwrap()
+            }),
+            Self::Token => TOKEN.get_or_init(|| {
+                Regex::new(concat!(
+                    r"[\w-]{24,}\.[\w-]{6}\.[\w-]{27,}",   // Discord Bot Token
+                    r"|\bgh[pousr]_[A-Za-z0-9]{36,}",      // GitHub
+                    r"|\bxox[abprs]-[A-Za-z0-9-]{10,}",    // Slack
+                    r"|\bAKIA[0-9A-Z]{16}\b",              // AWS アクセスキー
+                    r"|\bsk-[A-Za-z0-9_-]{20,}",           // OpenAI などのシークレットキー
+                ))
+                .unwrap()
+            }),
+        }
+    }
+
+    /// 伏せ字の表示
+    fn replacement(self) -> &'static str {
+        match self {
+            Self::Email => "[メールアドレス]",
+            Self::Phone => "[電話番号]",
+            Self::Token => "[トークン]",
+        }
+    }
+}
+
+/// メールアドレス・電話番号・トークンなどの機密情報を伏せ字にする
+pub struct MaskSensitive {
+    pub kinds: Vec<SensitiveKind>,
+}
+
+#[async_trait]
+impl MessageTransform for MaskSensitive {
+    fn name(&self) -> &'static str {
+        "mask"
+    }
+
+    async fn apply(&self, content: String) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        // トークンの一部が電話番号として置き換えられないよう、トークンから順に処理する
+        let mut content = content;
+        for kind in [SensitiveKind::Token, SensitiveKind::Email, SensitiveKind::Phone] {
+            if self.kinds.contains(&kind) {
+                content = kind.pattern().replace_all(&content, kind.replacement()).into_owned();
+            }
+        }
+        Ok(content)
+    }
+}
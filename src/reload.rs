@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::time::{interval, Duration};
+
+use crate::{config, supervisor};
+use crate::state::BotState;
+
+/// 設定ファイルの変更を確認する間隔
+const CONFIG_WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 設定ファイルの最終更新日時を取得する（ファイルが無い場合は None）
+fn config_modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// 環境変数と設定ファイルを再読み込みし、マッピングを差し替える
+///
+/// 設定に誤りがある場合は現在のマッピングをそのまま維持します。
+pub async fn reload_mappings(state: &BotState) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = config::load_config_file(&config::config_path())?;
+    let new_mappings = config::parse_thread_mappings(config.as_ref())?;
+    let new_parent_mappings = config::parse_parent_mappings(config.as_ref())?;
+    let new_name_mappings = config::parse_name_mappings(config.as_ref())?;
+
+    state.replace_configured_mappings(new_mappings).await;
+    state.replace_parent_mappings(new_parent_mappings).await;
+    state.replace_name_mappings(new_name_mappings).await;
+    state.debug_watch.log_mappings(&state.thread_mappings.load());
+    Ok(())
+}
+
+/// リロード処理を実行し、結果をログに出力する
+async fn reload_and_log(state: &BotState, reason: &str) {
+    println!("🔄 マッピング設定を再読み込みします（{}）", reason);
+    match reload_mappings(state).await {
+        Ok(()) => {
+            println!("✅ マッピング設定を再読み込みしました");
+            state.stats.set_reload_error(None);
+        }
+        Err(e) => {
+            eprintln!("❌ マッピング設定の再読み込みに失敗しました。現在の設定を維持します:\n{}", e);
+            state.stats.set_reload_error(Some(e.to_string()));
+        }
+    }
+}
+
+/// SIGHUP受信時と設定ファイル更新時にマッピングを再読み込みするタスクを起動する
+pub fn spawn_config_reloader(state: Arc<BotState>) {
+    // 設定ファイルの更新日時を定期的に確認
+    let watch_state = Arc::clone(&state);
+    supervisor::supervise(&state, "設定ファイルの監視", move || {
+        let state = Arc::clone(&watch_state);
+        async move {
+            let path = config::config_path();
+            let mut last_modified = config_modified_time(&path);
+            let mut ticker = interval(CONFIG_WATCH_INTERVAL);
+
+            loop {
+                ticker.tick().await;
+
+                let modified = config_modified_time(&path);
+                if modified != last_modified {
+                    last_modified = modified;
+                    reload_and_log(&state, "設定ファイルの変更を検知").await;
+                }
+            }
+        }
+    });
+
+    // SIGHUPによる手動リロード
+    #[cfg(unix)]
+    {
+        let hangup_state = Arc::clone(&state);
+        supervisor::supervise(&state, "SIGHUPの受信", move || {
+            let state = Arc::clone(&hangup_state);
+            async move {
+                use tokio::signal::unix::{signal, SignalKind};
+
+                let mut hangup = match signal(SignalKind::hangup()) {
+                    Ok(hangup) => hangup,
+                    Err(e) => {
+                        eprintln!("SIGHUPハンドラの登録に失敗しました: {}", e);
+                        return;
+                    }
+                };
+
+                while hangup.recv().await.is_some() {
+                    reload_and_log(&state, "SIGHUPを受信").await;
+                }
+            }
+        });
+    }
+}
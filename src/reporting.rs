@@ -0,0 +1,86 @@
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use crate::state::ThreadInfo;
+
+/// エラー報告（Sentry）の送信を続けるためのガード
+///
+/// 破棄するときに、送信していないエラーを送信してから終了します。`sentry` 機能を無効にしてビルドした場合は何もしません。
+pub struct ReportingGuard {
+    #[cfg(feature = "sentry")]
+    _guard: Option<sentry::ClientInitGuard>,
+}
+
+/// Sentry へのエラー報告を開始する（DSN を指定しない場合は何もしない）
+///
+/// パニック（バックグラウンドのタスクを含む）は自動で報告されます。
+/// 環境名は環境変数 `SENTRY_ENVIRONMENT` で指定できます。
+#[cfg(feature = "sentry")]
+pub fn init(dsn: Option<String>) -> ReportingGuard {
+    let Some(dsn) = dsn else {
+        return ReportingGuard { _guard: None };
+    };
+
+    let guard = sentry::init((
+        dsn,
+        sentry::ClientOptions {
+            release: sentry::release_name!(),
+            ..Default::default()
+        },
+    ));
+    if guard.is_enabled() {
+        println!("Sentry へのエラー報告を開始しました");
+    } else {
+        println!("警告: Sentry の DSN が無効なため、エラー報告は送信しません");
+    }
+    ReportingGuard { _guard: Some(guard) }
+}
+
+/// Sentry へのエラー報告を開始する（`sentry` 機能を無効にしてビルドした場合は、DSN を指定しても報告しない）
+#[cfg(not(feature = "sentry"))]
+pub fn init(dsn: Option<String>) -> ReportingGuard {
+    if dsn.is_some() {
+        println!("警告: Sentry の DSN が指定されていますが、sentry 機能を有効にしてビルドされていないため報告しません");
+    }
+    ReportingGuard {}
+}
+
+/// 処理中に発生したエラーを Sentry に報告する
+///
+/// `mapping` を指定した場合は、転送元のスレッド・転送先のチャンネル・ラベルをタグとして付けます。
+/// エラーの文章に含まれる Webhook のトークンは伏せて送信します。
+#[cfg(feature = "sentry")]
+pub fn capture_error(
+    context: &str,
+    mapping: Option<(Id<ChannelMarker>, &ThreadInfo)>,
+    error: &(dyn std::error::Error + Send + Sync + 'static),
+) {
+    use crate::alert::redact_webhook_tokens;
+
+    let mut event = sentry::event_from_error(error);
+    for exception in event.exception.values.iter_mut() {
+        exception.value = exception.value.as_deref().map(redact_webhook_tokens);
+    }
+
+    sentry::with_scope(
+        |scope| {
+            scope.set_tag("context", context);
+            if let Some((thread_id, thread_info)) = mapping {
+                scope.set_tag("thread_id", thread_id);
+                scope.set_tag("target_channel_id", thread_info.target_channel_id);
+                if let Some(label) = &thread_info.label {
+                    scope.set_tag("label", label);
+                }
+            }
+        },
+        || sentry::capture_event(event),
+    );
+}
+
+/// 処理中に発生したエラーを Sentry に報告する（`sentry` 機能を無効にしてビルドした場合は何もしない）
+#[cfg(not(feature = "sentry"))]
+pub fn capture_error(
+    _context: &str,
+    _mapping: Option<(Id<ChannelMarker>, &ThreadInfo)>,
+    _error: &(dyn std::error::Error + Send + Sync + 'static),
+) {
+}
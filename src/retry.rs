@@ -0,0 +1,111 @@
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use tokio::time::Duration;
+use twilight_http::error::ErrorType;
+
+/// 転送先への送信を試行する最大回数（最初の送信を含む）
+pub const MAX_SEND_ATTEMPTS: u32 = 4;
+
+/// 1回目の再試行までの待機時間（以降は再試行のたびに2倍にする）
+const BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// 再試行までの最大の待機時間
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// HTTPステータス付きの送信の失敗（再試行できるかどうかの判定に使う）
+#[derive(Debug)]
+pub struct SendError {
+    /// 応答のステータス（応答を受け取れなかった場合は None）
+    status: Option<u16>,
+    message: String,
+}
+
+impl SendError {
+    /// 応答を受け取れなかった（接続できなかった・タイムアウトした）送信の失敗
+    pub fn network(message: String) -> Self {
+        Self { status: None, message }
+    }
+
+    /// エラーのステータスが返ってきた送信の失敗
+    pub fn status(status: u16, message: String) -> Self {
+        Self { status: Some(status), message }
+    }
+}
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for SendError {}
+
+/// 再試行すれば成功する可能性のあるステータスか（サーバーのエラー・レート制限）
+fn is_retryable_status(status: u16) -> bool {
+    status == 429 || (500..600).contains(&status)
+}
+
+/// 一時的な失敗（5xx・429・通信エラー）で、再試行すれば成功する可能性があるか
+///
+/// 権限不足や内容の検証エラーなど、再試行しても結果が変わらない失敗は false を返します。
+pub fn is_retryable(error: &(dyn std::error::Error + 'static)) -> bool {
+    if let Some(error) = error.downcast_ref::<SendError>() {
+        return error.status.is_none_or(is_retryable_status);
+    }
+    if let Some(error) = error.downcast_ref::<twilight_http::Error>() {
+        return match error.kind() {
+            ErrorType::Response { status, .. } => is_retryable_status(status.get()),
+            ErrorType::RequestError
+            | ErrorType::RequestTimedOut
+            | ErrorType::ChunkingResponse
+            | ErrorType::ServiceUnavailable { .. } => true,
+            _ => false,
+        };
+    }
+    if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+        return match error.status() {
+            Some(status) => is_retryable_status(status.as_u16()),
+            None => error.is_timeout() || error.is_connect() || error.is_request() || error.is_body(),
+        };
+    }
+    false
+}
+
+/// `attempt` 回目の失敗の後、再試行するまでの待機時間（指数的に延ばし、揺らぎを加える）
+///
+/// 複数のマッピングが同時に失敗した場合に、再試行が同じ瞬間に重ならないよう、待機時間の半分から全体までの間でばらつかせます。
+pub fn backoff_delay(attempt: u32) -> Duration {
+    let delay = BASE_DELAY.saturating_mul(1 << attempt.saturating_sub(1).min(16)).min(MAX_DELAY);
+    let half = delay / 2;
+    let jitter = random_u64() % (half.as_millis() as u64 + 1);
+    half + Duration::from_millis(jitter)
+}
+
+/// 揺らぎに使う乱数（標準ライブラリのハッシュの初期値を利用する）
+fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// 一時的な失敗の場合に、待機時間を延ばしながら送信を再試行する
+///
+/// 再試行できない失敗の場合や、`MAX_SEND_ATTEMPTS` 回試行しても成功しなかった場合は最後のエラーを返します。
+pub async fn with_backoff<T, F, Fut>(label: &str, mut send: F) -> Result<T, Box<dyn std::error::Error + Send + Sync>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Box<dyn std::error::Error + Send + Sync>>>,
+{
+    let mut attempt = 1;
+    loop {
+        match send().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < MAX_SEND_ATTEMPTS && is_retryable(&*e) => {
+                let delay = backoff_delay(attempt);
+                println!("⏳ {}に失敗しました（{}回目）。{}ミリ秒後に再試行します: {}", label, attempt, delay.as_millis(), e);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
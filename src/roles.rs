@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use twilight_http::Client as HttpClient;
+use twilight_model::channel::Message;
+use twilight_model::id::{
+    marker::{GuildMarker, RoleMarker, UserMarker},
+    Id,
+};
+
+/// ロールのキャッシュのキー（サーバーID, ユーザーID）
+type MemberKey = (Id<GuildMarker>, Id<UserMarker>);
+
+/// メッセージの送信者が持つロールを取得する処理（取得したロールはキャッシュする）
+///
+/// ゲートウェイで受信したメッセージにはメンバー情報（ロール）が含まれますが、
+/// 過去のメッセージをHTTP APIで取得した場合は含まれないため、メンバー情報をAPIから取得します。
+#[derive(Default)]
+pub struct MemberRoleCache {
+    /// (サーバーID, ユーザーID) -> ロールID一覧
+    roles: RwLock<HashMap<MemberKey, Vec<Id<RoleMarker>>>>,
+}
+
+impl MemberRoleCache {
+    /// 空のキャッシュで作成する
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// メッセージの送信者が持つロールを取得する（取得できない場合は None）
+    pub async fn author_roles(
+        &self,
+        http: &HttpClient,
+        guild_id: Id<GuildMarker>,
+        message: &Message,
+    ) -> Option<Vec<Id<RoleMarker>>> {
+        let key = (guild_id, message.author.id);
+
+        // メッセージにメンバー情報が含まれる場合は最新のロールとしてキャッシュを更新する
+        if let Some(member) = &message.member {
+            self.roles.write().await.insert(key, member.roles.clone());
+            return Some(member.roles.clone());
+        }
+
+        if let Some(roles) = self.roles.read().await.get(&key) {
+            return Some(roles.clone());
+        }
+
+        let roles = match http.guild_member(guild_id, message.author.id).await {
+            Ok(response) => match response.model().await {
+                Ok(member) => member.roles,
+                Err(e) => {
+                    println!("ユーザー {} のメンバー情報を読み込めませんでした: {}", message.author.id, e);
+                    return None;
+                }
+            },
+            Err(e) => {
+                // サーバーを退出したユーザーなど
+                println!("ユーザー {} のメンバー情報を取得できませんでした: {}", message.author.id, e);
+                return None;
+            }
+        };
+
+        self.roles.write().await.insert(key, roles.clone());
+        Some(roles)
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+use twilight_http::Client as HttpClient;
+use twilight_model::application::command::{Command, CommandType};
+use twilight_model::application::interaction::message_component::MessageComponentInteractionData;
+use twilight_model::application::interaction::Interaction;
+use twilight_model::channel::message::component::{
+    ActionRow, Button, ButtonStyle, Component, SelectMenu, SelectMenuOption,
+};
+use twilight_model::channel::message::MessageFlags;
+use twilight_model::channel::{Channel, ChannelType};
+use twilight_model::guild::Permissions;
+use twilight_model::http::interaction::{InteractionResponse, InteractionResponseData, InteractionResponseType};
+use twilight_model::id::{
+    marker::{ChannelMarker, GuildMarker, UserMarker},
+    Id,
+};
+
+use crate::audit::{AuditEntry, AuditOutcome};
+use crate::permissions;
+use crate::state::{BotState, ThreadInfo};
+
+/// セットアップのコンポーネントの custom_id の接頭辞
+pub const CUSTOM_ID_PREFIX: &str = "setup:";
+
+/// セットアップを途中でやめた場合に、選択内容を破棄するまでの時間
+const SESSION_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// 選択メニューに表示できる選択肢の最大数（Discordの制限）
+const MAX_SELECT_OPTIONS: usize = 25;
+
+/// 選択肢のラベルの最大文字数（Discordの制限）
+const MAX_LABEL_CHARS: usize = 100;
+
+/// セットアップ中の選択内容
+struct SetupSession {
+    /// 転送元のスレッド・チャンネル
+    source: Option<Id<ChannelMarker>>,
+    /// 転送先のチャンネル
+    target: Option<Id<ChannelMarker>>,
+    /// セットアップを開始した時刻
+    started_at: Instant,
+}
+
+/// `/setup` でマッピングを設定中のユーザーの選択内容を管理する
+#[derive(Default)]
+pub struct SetupSessions {
+    /// ユーザーID -> 選択内容
+    sessions: Mutex<HashMap<Id<UserMarker>, SetupSession>>,
+}
+
+impl SetupSessions {
+    /// 空の状態を作成する
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// `/setup` コマンドの定義
+pub fn command() -> Command {
+    Command {
+        application_id: None,
+        default_member_permissions: Some(Permissions::MANAGE_GUILD),
+        dm_permission: Some(false),
+        description: "転送元のスレッド・チャンネルと転送先のチャンネルを選んでマッピングを設定します".to_string(),
+        description_localizations: None,
+        guild_id: None,
+        id: None,
+        kind: CommandType::ChatInput,
+        name: "setup".to_string(),
+        name_localizations: None,
+        nsfw: None,
+        options: Vec::new(),
+        version: Id::new(1),
+    }
+}
+
+/// 選択肢のラベルを作成する（長すぎる名前は切り詰める）
+fn option_label(prefix: &str, name: &str) -> String {
+    format!("{}{}", prefix, name).chars().take(MAX_LABEL_CHARS).collect()
+}
+
+/// 選択メニューを含む行を作成する
+fn select_row(custom_id: &str, placeholder: &str, options: Vec<SelectMenuOption>) -> Component {
+    Component::ActionRow(ActionRow {
+        components: vec![Component::SelectMenu(SelectMenu {
+            custom_id: format!("{}{}", CUSTOM_ID_PREFIX, custom_id),
+            disabled: false,
+            max_values: Some(1),
+            min_values: Some(1),
+            options,
+            placeholder: Some(placeholder.to_string()),
+        })],
+    })
+}
+
+/// 登録・キャンセルのボタンの行を作成する
+fn button_row() -> Component {
+    let button = |custom_id: &str, label: &str, style: ButtonStyle| {
+        Component::Button(Button {
+            custom_id: Some(format!("{}{}", CUSTOM_ID_PREFIX, custom_id)),
+            disabled: false,
+            emoji: None,
+            label: Some(label.to_string()),
+            style,
+            url: None,
+        })
+    };
+    Component::ActionRow(ActionRow {
+        components: vec![
+            button("register", "登録する", ButtonStyle::Success),
+            button("cancel", "キャンセル", ButtonStyle::Secondary),
+        ],
+    })
+}
+
+/// サーバーのスレッドとテキストチャンネルから選択メニューを作成する
+///
+/// twilight のこのバージョンはチャンネル選択メニューに対応していないため、サーバーのチャンネル一覧から選択肢を作成します。
+/// 選択肢は1つのメニューにつき25件までのため、それを超える分は表示されません。
+async fn build_components(
+    http: &HttpClient,
+    guild_id: Id<GuildMarker>,
+) -> Result<Vec<Component>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut threads = http.active_threads(guild_id).await?.model().await?.threads;
+    threads.sort_by_key(|thread| std::cmp::Reverse(thread.last_message_id));
+    let mut channels = http.guild_channels(guild_id).await?.models().await?;
+    channels.retain(|channel| matches!(channel.kind, ChannelType::GuildText | ChannelType::GuildAnnouncement));
+    channels.sort_by_key(|channel| channel.position);
+
+    let option = |prefix: &str, channel: &Channel| SelectMenuOption {
+        default: false,
+        description: None,
+        emoji: None,
+        label: option_label(prefix, channel.name.as_deref().unwrap_or("(名前なし)")),
+        value: channel.id.to_string(),
+    };
+    // 転送元はスレッドを優先し、残りの枠にテキストチャンネル（チャンネル同士のミラー用）を表示する
+    let source_options: Vec<SelectMenuOption> = threads
+        .iter()
+        .map(|thread| option("🧵 ", thread))
+        .chain(channels.iter().map(|channel| option("# ", channel)))
+        .take(MAX_SELECT_OPTIONS)
+        .collect();
+    let target_options: Vec<SelectMenuOption> = channels
+        .iter()
+        .take(MAX_SELECT_OPTIONS)
+        .map(|channel| option("# ", channel))
+        .collect();
+
+    if source_options.is_empty() || target_options.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(vec![
+        select_row("source", "転送元のスレッド・チャンネルを選択", source_options),
+        select_row("target", "転送先のチャンネルを選択", target_options),
+        button_row(),
+    ])
+}
+
+/// 選択内容を表示する文章を作成する
+fn session_text(session: &SetupSession) -> String {
+    let channel = |id: Option<Id<ChannelMarker>>| match id {
+        Some(id) => format!("<#{}>", id),
+        None => "未選択".to_string(),
+    };
+    format!(
+        "🛠️ **マッピングの設定**\n転送元のスレッド・チャンネル: {}\n転送先のチャンネル: {}\n\n選択したら「登録する」を押してください。",
+        channel(session.source),
+        channel(session.target)
+    )
+}
+
+/// インタラクションに応答する
+async fn respond(
+    http: &HttpClient,
+    interaction: &Interaction,
+    kind: InteractionResponseType,
+    content: String,
+    components: Option<Vec<Component>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let response = InteractionResponse {
+        kind,
+        data: Some(InteractionResponseData {
+            content: Some(content),
+            components,
+            flags: Some(MessageFlags::EPHEMERAL),
+            ..Default::default()
+        }),
+    };
+
+    http.interaction(interaction.application_id)
+        .create_response(interaction.id, &interaction.token, &response)
+        .await?;
+    Ok(())
+}
+
+/// `/setup` を実行した時の処理（選択メニューを表示する）
+pub async fn start(
+    state: &BotState,
+    interaction: &Interaction,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (Some(guild_id), Some(user_id)) = (interaction.guild_id, interaction.author_id()) else {
+        return Ok(());
+    };
+
+    let components = build_components(&state.http, guild_id).await?;
+    if components.is_empty() {
+        let content = "設定できるスレッドまたはチャンネルが見つかりませんでした。`/map add` でIDを指定して設定してください。";
+        return respond(&state.http, interaction, InteractionResponseType::ChannelMessageWithSource, content.to_string(), None).await;
+    }
+
+    let session = SetupSession {
+        source: None,
+        target: None,
+        started_at: Instant::now(),
+    };
+    let content = session_text(&session);
+    {
+        let mut sessions = state.setups.sessions.lock().await;
+        sessions.retain(|_, session| session.started_at.elapsed() < SESSION_TTL);
+        sessions.insert(user_id, session);
+    }
+
+    respond(&state.http, interaction, InteractionResponseType::ChannelMessageWithSource, content, Some(components)).await
+}
+
+/// セットアップの選択メニュー・ボタンを操作した時の処理
+pub async fn handle_component(
+    state: &BotState,
+    interaction: &Interaction,
+    data: &MessageComponentInteractionData,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(user_id) = interaction.author_id() else {
+        return Ok(());
+    };
+    let action = data.custom_id.strip_prefix(CUSTOM_ID_PREFIX).unwrap_or_default();
+    let selected = data
+        .values
+        .first()
+        .and_then(|value| value.parse::<u64>().ok())
+        .and_then(Id::new_checked);
+
+    let mut sessions = state.setups.sessions.lock().await;
+    let Some(session) = sessions
+        .get_mut(&user_id)
+        .filter(|session| session.started_at.elapsed() < SESSION_TTL)
+    else {
+        drop(sessions);
+        let content = "⌛ このセットアップは期限切れです。もう一度 `/setup` を実行してください。".to_string();
+        return respond(&state.http, interaction, InteractionResponseType::UpdateMessage, content, Some(Vec::new())).await;
+    };
+
+    match action {
+        "source" => session.source = selected,
+        "target" => session.target = selected,
+        "cancel" => {
+            sessions.remove(&user_id);
+            drop(sessions);
+            let content = "マッピングの設定をキャンセルしました。".to_string();
+            return respond(&state.http, interaction, InteractionResponseType::UpdateMessage, content, Some(Vec::new())).await;
+        }
+        "register" => {
+            let (Some(source), Some(target)) = (session.source, session.target) else {
+                let content = format!("{}\n\n⚠️ 転送元と転送先の両方を選択してください。", session_text(session));
+                drop(sessions);
+                return respond(&state.http, interaction, InteractionResponseType::UpdateMessage, content, None).await;
+            };
+            let text = session_text(session);
+            drop(sessions);
+
+            // 転送先にボットが転送できるか（別のサーバーの場合は実行したユーザーの権限も）確認する（選択内容は残して選び直せるようにする）
+            if let Err(reason) = permissions::check_mapping_target(state, source, user_id, target).await {
+                let content = format!("{}\n\n⚠️ 転送先のチャンネルに転送できません: {}", text, reason);
+                return respond(&state.http, interaction, InteractionResponseType::UpdateMessage, content, None).await;
+            }
+            state.setups.sessions.lock().await.remove(&user_id);
+
+            let thread_info = ThreadInfo::new(target);
+            let summary = thread_info.summary();
+            let result = state.add_thread_mapping(source, thread_info).await;
+            let command = format!("/setup source:<#{}> target:<#{}>", source, target);
+            let channel_id = interaction.channel.as_ref().map_or(source, |channel| channel.id);
+            state
+                .record_command(AuditEntry::new(user_id, channel_id, &command, AuditOutcome::from_result(&result)))
+                .await;
+            result?;
+            crate::source::prepare(state, source).await;
+            println!("/setup でマッピングを追加しました: 転送元 {} -> チャンネル {}", source, target);
+
+            let prefix = state.command_prefix(source, interaction.guild_id).await;
+            let content = format!(
+                "✅ <#{}> のメッセージを <#{}> に転送します {}\n過去のメッセージを転送するには転送元で `{}start` を実行してください。",
+                source, target, summary, prefix
+            );
+            return respond(&state.http, interaction, InteractionResponseType::UpdateMessage, content, Some(Vec::new())).await;
+        }
+        _ => return Ok(()),
+    }
+
+    let content = session_text(session);
+    drop(sessions);
+    respond(&state.http, interaction, InteractionResponseType::UpdateMessage, content, None).await
+}
@@ -0,0 +1,350 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use sha2::Sha256;
+use std::sync::Mutex;
+use twilight_model::channel::Message;
+use twilight_model::id::{
+    marker::{ChannelMarker, GuildMarker, MessageMarker, UserMarker},
+    Id,
+};
+use twilight_model::util::Timestamp;
+
+use crate::forwarding::{forward_content, DiscordChannelSink, DiscordWebhookSink};
+use crate::links::MessageLink;
+use crate::retry::{self, SendError};
+use crate::slack::{SlackSink, SlackTarget};
+use crate::state::{BotState, ThreadInfo};
+#[cfg(feature = "telegram")]
+use crate::telegram::TelegramSink;
+
+/// 転送メッセージの送信先
+///
+/// 新しい種類の転送先を追加する場合は、この trait を実装し、[`SinkSpec`] に設定用の種類を追加します。
+#[async_trait]
+pub trait MessageSink: Send + Sync {
+    /// 転送先の名前（ログ用）
+    fn name(&self) -> &'static str;
+
+    /// メッセージを転送先に送信する
+    ///
+    /// Discordに送信した場合は、編集の反映に使う転送先のメッセージを返します（Discord以外の転送先では None）。
+    async fn send(
+        &self,
+        state: &BotState,
+        thread_info: &ThreadInfo,
+        message: &Message,
+    ) -> Result<Option<MessageLink>, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// マッピングに設定する転送先の種類
+///
+/// 設定ファイルでは `sink = { type = "http", url = "..." }` のように指定します。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SinkSpec {
+    /// Discordのチャンネル（`webhook_url` を設定した場合はそのWebhook、それ以外は `format` に従って送信）
+    #[default]
+    Discord,
+    /// ファイルに1行1件のJSONで追記する
+    File { path: PathBuf },
+    /// HTTPエンドポイントにJSONをPOSTする（署名の鍵の環境変数が設定されている場合は本文に HMAC-SHA256 の署名を付ける）
+    Http {
+        url: String,
+        /// 署名の鍵を読み込む環境変数の名前（省略した場合は `HTTP_SINK_SECRET`）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        secret_env: Option<String>,
+    },
+    /// Slack のチャンネルに投稿する（Incoming Webhook の `webhook_url` か、Web API で投稿する `channel` のどちらか）
+    Slack {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        webhook_url: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        channel: Option<String>,
+    },
+    /// Telegram のチャットに投稿する（添付ファイルは再アップロードする）
+    #[cfg(feature = "telegram")]
+    Telegram { chat_id: String },
+}
+
+impl SinkSpec {
+    /// オプションの値（`discord`、`file=パス`、`http=URL`、`slack=Webhook URLまたはチャンネル`、`telegram=チャットID`）から読み取る
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let spec = match value.split_once('=') {
+            None if value == "discord" => Self::Discord,
+            Some(("file", path)) => Self::File { path: PathBuf::from(path) },
+            Some(("http", url)) => Self::Http {
+                url: url.to_string(),
+                secret_env: None,
+            },
+            Some(("slack", target)) if target.starts_with("https://") => Self::Slack {
+                webhook_url: Some(target.to_string()),
+                channel: None,
+            },
+            Some(("slack", channel)) => Self::Slack {
+                webhook_url: None,
+                channel: Some(channel.to_string()),
+            },
+            #[cfg(feature = "telegram")]
+            Some(("telegram", chat_id)) => Self::Telegram { chat_id: chat_id.to_string() },
+            #[cfg(not(feature = "telegram"))]
+            Some(("telegram", _)) => return Err("Telegram に転送するには telegram 機能を有効にしてビルドしてください".to_string()),
+            _ => {
+                return Err(format!(
+                    "sink には discord、file=パス、http=URL、slack=Webhook URLまたはチャンネル のいずれかを指定してください: {}",
+                    value
+                ))
+            }
+        };
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// 設定の内容を確認する
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Discord => Ok(()),
+            Self::File { path } if path.as_os_str().is_empty() => Err("sink の file にはパスを指定してください".to_string()),
+            Self::File { .. } => Ok(()),
+            Self::Http { url, .. } if !url.starts_with("http://") && !url.starts_with("https://") => {
+                Err(format!("sink の http のURLはhttp://またはhttps://で始まる必要があります: {}", url))
+            }
+            Self::Http { secret_env: Some(name), .. } if name.trim().is_empty() => Err("sink の http の secret_env が空です".to_string()),
+            Self::Http { .. } => Ok(()),
+            Self::Slack { webhook_url: Some(_), channel: Some(_) } | Self::Slack { webhook_url: None, channel: None } => {
+                Err("sink の slack には webhook_url と channel のどちらか一方を指定してください".to_string())
+            }
+            Self::Slack { webhook_url: Some(url), .. } if !url.starts_with("https://hooks.slack.com/") => {
+                Err("sink の slack の webhook_url は https://hooks.slack.com/ で始まる必要があります".to_string())
+            }
+            Self::Slack { channel: Some(channel), .. } if channel.trim().is_empty() => {
+                Err("sink の slack にはチャンネルを指定してください".to_string())
+            }
+            Self::Slack { .. } => Ok(()),
+            #[cfg(feature = "telegram")]
+            Self::Telegram { chat_id } if chat_id.trim().is_empty() => Err("sink の telegram にはチャットIDを指定してください".to_string()),
+            #[cfg(feature = "telegram")]
+            Self::Telegram { .. } => Ok(()),
+        }
+    }
+
+    /// マッピングの概要に表示する説明
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Discord => "Discord".to_string(),
+            Self::File { path } => format!("ファイル {}", path.display()),
+            Self::Http { url, secret_env: Some(name) } => format!("HTTP {}（署名の鍵: 環境変数 {}）", url, name),
+            Self::Http { url, secret_env: None } => format!("HTTP {}", url),
+            // Webhook のURLにはトークンが含まれるため表示しない
+            Self::Slack { channel: Some(channel), .. } => format!("Slack {}", channel),
+            Self::Slack { .. } => "Slack（Incoming Webhook）".to_string(),
+            #[cfg(feature = "telegram")]
+            Self::Telegram { chat_id } => format!("Telegram {}", chat_id),
+        }
+    }
+
+    /// 設定から転送先を作成する
+    pub fn build(&self, thread_info: &ThreadInfo) -> Box<dyn MessageSink> {
+        match self {
+            Self::Discord => match &thread_info.webhook_url {
+                Some(url) => Box::new(DiscordWebhookSink { url: url.clone() }),
+                None => Box::new(DiscordChannelSink),
+            },
+            Self::File { path } => Box::new(FileSink { path: path.clone() }),
+            Self::Http { url, secret_env } => Box::new(HttpSink {
+                url: url.clone(),
+                secret: std::env::var(secret_env.as_deref().unwrap_or(HTTP_SECRET_ENV))
+                    .ok()
+                    .filter(|secret| !secret.is_empty()),
+            }),
+            Self::Slack { webhook_url, channel } => {
+                let target = match (webhook_url, channel) {
+                    (Some(url), _) => SlackTarget::Webhook(url.clone()),
+                    (None, channel) => SlackTarget::Channel(channel.clone().unwrap_or_default()),
+                };
+                Box::new(SlackSink { target })
+            }
+            #[cfg(feature = "telegram")]
+            Self::Telegram { chat_id } => Box::new(TelegramSink { chat_id: chat_id.clone() }),
+        }
+    }
+}
+
+/// Discord以外の転送先に送る、添付ファイルの情報
+#[derive(Serialize)]
+struct SinkAttachment<'a> {
+    filename: &'a str,
+    url: &'a str,
+    /// ファイルサイズ（バイト）
+    size: u64,
+    content_type: Option<&'a str>,
+}
+
+/// Discord以外の転送先に送る、転送メッセージの内容
+#[derive(Serialize)]
+pub(crate) struct SinkRecord<'a> {
+    /// 転送元のサーバーのID
+    guild_id: Option<Id<GuildMarker>>,
+    /// 転送元のスレッド（チャンネル）のID
+    source_channel_id: Id<ChannelMarker>,
+    /// 転送元のメッセージのID
+    message_id: Id<MessageMarker>,
+    /// 送信者のID
+    author_id: Id<UserMarker>,
+    /// 送信者の名前
+    author_name: &'a str,
+    /// 送信者がボットかどうか
+    author_bot: bool,
+    /// マッピングのラベル
+    label: Option<&'a str>,
+    /// 変換・プレフィックスを適用した本文
+    content: String,
+    /// 添付ファイルのURL
+    attachments: Vec<&'a str>,
+    /// 添付ファイルのファイル名・URL・サイズ・種類
+    files: Vec<SinkAttachment<'a>>,
+    /// 元のメッセージへのリンク
+    jump_url: Option<String>,
+    /// 元のメッセージの投稿日時
+    timestamp: &'a Timestamp,
+    /// 元のメッセージを最後に編集した日時
+    edited_timestamp: Option<&'a Timestamp>,
+}
+
+impl<'a> SinkRecord<'a> {
+    pub(crate) async fn new(state: &BotState, thread_info: &'a ThreadInfo, message: &'a Message) -> SinkRecord<'a> {
+        SinkRecord {
+            guild_id: message.guild_id,
+            source_channel_id: message.channel_id,
+            message_id: message.id,
+            author_id: message.author.id,
+            author_name: &message.author.name,
+            author_bot: message.author.bot,
+            label: thread_info.label.as_deref(),
+            content: forward_content(state, thread_info, message).await,
+            attachments: message.attachments.iter().map(|attachment| attachment.url.as_str()).collect(),
+            files: message
+                .attachments
+                .iter()
+                .map(|attachment| SinkAttachment {
+                    filename: &attachment.filename,
+                    url: &attachment.url,
+                    size: attachment.size,
+                    content_type: attachment.content_type.as_deref(),
+                })
+                .collect(),
+            jump_url: state.message_url(message).await,
+            timestamp: &message.timestamp,
+            edited_timestamp: message.edited_timestamp.as_ref(),
+        }
+    }
+}
+
+/// 同じファイルへの追記が混ざらないようにするためのロック
+static FILE_LOCK: Mutex<()> = Mutex::new(());
+
+/// ファイルに1行1件のJSONで追記する転送先
+pub struct FileSink {
+    pub path: PathBuf,
+}
+
+#[async_trait]
+impl MessageSink for FileSink {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn send(
+        &self,
+        state: &BotState,
+        thread_info: &ThreadInfo,
+        message: &Message,
+    ) -> Result<Option<MessageLink>, Box<dyn std::error::Error + Send + Sync>> {
+        let line = serde_json::to_string(&SinkRecord::new(state, thread_info, message).await)?;
+
+        let _guard = FILE_LOCK.lock().unwrap();
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| format!("ディレクトリ {} を作成できませんでした: {}", dir.display(), e))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("ファイル {} を開けませんでした: {}", self.path.display(), e))?;
+        writeln!(file, "{}", line)?;
+        Ok(None)
+    }
+}
+
+/// HTTPの転送先の署名の鍵を指定する環境変数（マッピングに `secret_env` を指定していない場合に使用）
+const HTTP_SECRET_ENV: &str = "HTTP_SINK_SECRET";
+
+/// 署名の対象にした時刻（Unix時間の秒）を送るヘッダー
+const TIMESTAMP_HEADER: &str = "X-Thread2Channel-Timestamp";
+
+/// 署名を送るヘッダー
+const SIGNATURE_HEADER: &str = "X-Thread2Channel-Signature";
+
+/// `時刻.本文` の HMAC-SHA256 を `sha256=16進数` の形式で返す
+///
+/// 受信側は同じ鍵で計算した値と比較し、時刻が古すぎるリクエストを拒否することで、改ざんと再送を検出できます。
+fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMACの鍵は任意の長さを受け付ける");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    let signature: String = mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256={}", signature)
+}
+
+/// HTTPエンドポイントにJSONをPOSTする転送先
+pub struct HttpSink {
+    pub url: String,
+    /// 署名の鍵（None の場合は署名しない）
+    pub secret: Option<String>,
+}
+
+#[async_trait]
+impl MessageSink for HttpSink {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    async fn send(
+        &self,
+        state: &BotState,
+        thread_info: &ThreadInfo,
+        message: &Message,
+    ) -> Result<Option<MessageLink>, Box<dyn std::error::Error + Send + Sync>> {
+        let body = serde_json::to_vec(&SinkRecord::new(state, thread_info, message).await)?;
+        let client = reqwest::Client::new();
+
+        retry::with_backoff("HTTPエンドポイントへの送信", || async {
+            let mut request = client.post(&self.url).header(reqwest::header::CONTENT_TYPE, "application/json");
+            // 再送のたびに時刻を更新して署名し直す
+            if let Some(secret) = &self.secret {
+                let timestamp = Utc::now().timestamp();
+                request = request
+                    .header(TIMESTAMP_HEADER, timestamp.to_string())
+                    .header(SIGNATURE_HEADER, sign(secret, timestamp, &body));
+            }
+            let response = request
+                .body(body.clone())
+                .send()
+                .await
+                .map_err(|e| SendError::network(format!("{} への送信に失敗しました: {}", self.url, e)))?;
+            let status = response.status();
+            if !status.is_success() {
+                let body = response.text().await.unwrap_or_default();
+                return Err(SendError::status(status.as_u16(), format!("{} への送信が失敗しました: {} {}", self.url, status, body)).into());
+            }
+            Ok(())
+        })
+        .await?;
+        Ok(None)
+    }
+}
@@ -0,0 +1,180 @@
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::Path;
+
+use crate::history::HistoryEntry;
+use crate::transcript::escape_html;
+
+/// 全てのページに共通のスタイル
+const STYLE: &str = "body { font-family: sans-serif; max-width: 860px; margin: 2em auto; padding: 0 1em; color: #2e3338; }\n\
+    a { color: #006ce7; }\n\
+    .message { display: flex; gap: 0.75em; border-top: 1px solid #e3e5e8; padding: 0.75em 0; }\n\
+    .avatar { width: 40px; height: 40px; border-radius: 50%; flex-shrink: 0; }\n\
+    .body { min-width: 0; flex: 1; }\n\
+    .author { font-weight: bold; }\n\
+    .bot { background: #5865f2; color: #fff; font-size: 0.7em; border-radius: 3px; padding: 0 0.3em; margin-left: 0.3em; }\n\
+    .timestamp { color: #747f8d; font-size: 0.85em; margin-left: 0.5em; }\n\
+    .content { white-space: pre-wrap; overflow-wrap: anywhere; margin-top: 0.25em; }\n\
+    .attachment img { max-width: 100%; max-height: 400px; margin-top: 0.5em; border-radius: 4px; }\n\
+    .pages { margin: 1.5em 0; display: flex; gap: 0.5em; flex-wrap: wrap; }\n\
+    table { border-collapse: collapse; width: 100%; }\n\
+    td, th { text-align: left; padding: 0.5em; border-bottom: 1px solid #e3e5e8; }\n";
+
+/// サイトに含める1つのスレッド
+pub struct SiteThread {
+    pub thread_id: u64,
+    /// 表示名（マッピングのラベル・スレッド名、どちらも無ければID）
+    pub title: String,
+    /// メッセージ（古い順）
+    pub entries: Vec<HistoryEntry>,
+}
+
+/// ページ全体のHTML
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"ja\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{title}</title>\n<style>\n{style}</style>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        title = escape_html(title),
+        style = STYLE,
+        body = body
+    )
+}
+
+/// 送信者のアバターのURL（アバターを設定していない場合はデフォルトのアバター）
+fn avatar_url(entry: &HistoryEntry) -> String {
+    match &entry.author_avatar {
+        Some(hash) => format!("https://cdn.discordapp.com/avatars/{}/{}.webp?size=80", entry.author_id, hash),
+        None => format!("https://cdn.discordapp.com/embed/avatars/{}.png", entry.author_id % 5),
+    }
+}
+
+/// 日時を表示用の形式にする（読み取れない場合はそのまま）
+fn display_time(time: &str) -> String {
+    DateTime::parse_from_rfc3339(time)
+        .map(|time| time.with_timezone(&Utc).format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|_| time.to_string())
+}
+
+/// 1件のメッセージのHTML
+fn render_message(entry: &HistoryEntry) -> String {
+    let mut html = format!(
+        "<div class=\"message\" id=\"m{id}\">\n<img class=\"avatar\" src=\"{avatar}\" alt=\"\" loading=\"lazy\">\n<div class=\"body\">\n<div><span class=\"author\">{author}</span>{bot}<a class=\"timestamp\" href=\"#m{id}\">{time}</a>{edited}</div>\n",
+        id = entry.message_id,
+        avatar = escape_html(&avatar_url(entry)),
+        author = escape_html(&entry.author_name),
+        bot = if entry.author_bot { "<span class=\"bot\">BOT</span>" } else { "" },
+        time = escape_html(&display_time(&entry.created_at)),
+        edited = if entry.edited_at.is_some() { "<span class=\"timestamp\">（編集済み）</span>" } else { "" },
+    );
+    if !entry.content.is_empty() {
+        html.push_str(&format!("<div class=\"content\">{}</div>\n", escape_html(&entry.content)));
+    }
+    for attachment in &entry.attachments {
+        let url = escape_html(&attachment.url);
+        let name = escape_html(&attachment.filename);
+        let is_image = attachment.content_type.as_deref().is_some_and(|content_type| content_type.starts_with("image/"));
+        if is_image {
+            html.push_str(&format!(
+                "<div class=\"attachment\"><a href=\"{url}\"><img src=\"{url}\" alt=\"{name}\" loading=\"lazy\"></a></div>\n"
+            ));
+        } else {
+            html.push_str(&format!("<div class=\"attachment\">📎 <a href=\"{url}\">{name}</a></div>\n"));
+        }
+    }
+    html.push_str("</div>\n</div>\n");
+    html
+}
+
+/// スレッドのページのファイル名（1ページ目は index.html）
+fn page_file_name(page: usize) -> String {
+    if page == 1 {
+        "index.html".to_string()
+    } else {
+        format!("page-{}.html", page)
+    }
+}
+
+/// ページの移動のリンク
+fn render_pagination(current: usize, total: usize) -> String {
+    if total <= 1 {
+        return String::new();
+    }
+    let links: Vec<String> = (1..=total)
+        .map(|page| {
+            if page == current {
+                format!("<strong>{}</strong>", page)
+            } else {
+                format!("<a href=\"{}\">{}</a>", page_file_name(page), page)
+            }
+        })
+        .collect();
+    format!("<nav class=\"pages\">{}</nav>\n", links.join(" "))
+}
+
+/// スレッドのページを書き出す（書き出したページの数を返す）
+fn write_thread(output: &Path, thread: &SiteThread, per_page: usize) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let dir = output.join(thread.thread_id.to_string());
+    fs::create_dir_all(&dir).map_err(|e| format!("ディレクトリ {} を作成できませんでした: {}", dir.display(), e))?;
+
+    let chunks: Vec<&[HistoryEntry]> = thread.entries.chunks(per_page).collect();
+    let total = chunks.len().max(1);
+    for page_number in 1..=total {
+        let entries = chunks.get(page_number - 1).copied().unwrap_or_default();
+        let pagination = render_pagination(page_number, total);
+        let mut body = format!(
+            "<p><a href=\"../index.html\">← スレッドの一覧</a></p>\n<h1>{}</h1>\n<p>{}件のメッセージ（{}/{}ページ）</p>\n{}",
+            escape_html(&thread.title),
+            thread.entries.len(),
+            page_number,
+            total,
+            pagination
+        );
+        body.extend(entries.iter().map(render_message));
+        body.push_str(&pagination);
+
+        let title = if total > 1 { format!("{} ({}/{})", thread.title, page_number, total) } else { thread.title.clone() };
+        fs::write(dir.join(page_file_name(page_number)), page(&title, &body))?;
+    }
+    Ok(total)
+}
+
+/// スレッドの一覧のページを作成する
+fn render_index(site_title: &str, threads: &[SiteThread]) -> String {
+    let mut body = format!(
+        "<h1>{}</h1>\n<table>\n<tr><th>スレッド</th><th>メッセージ</th><th>最後の投稿</th></tr>\n",
+        escape_html(site_title)
+    );
+    for thread in threads {
+        let last = thread.entries.last().map(|entry| display_time(&entry.created_at)).unwrap_or_default();
+        body.push_str(&format!(
+            "<tr><td><a href=\"{}/index.html\">{}</a></td><td>{}</td><td>{}</td></tr>\n",
+            thread.thread_id,
+            escape_html(&thread.title),
+            thread.entries.len(),
+            escape_html(&last)
+        ));
+    }
+    body.push_str(&format!(
+        "</table>\n<p class=\"timestamp\">{} に Thread2Channel で作成</p>\n",
+        Utc::now().format("%Y-%m-%d %H:%M UTC")
+    ));
+    page(site_title, &body)
+}
+
+/// スレッドの履歴を静的なHTMLのサイトとして書き出す
+///
+/// `output/index.html` にスレッドの一覧、`output/<スレッドID>/` に `per_page` 件ごとのページを作成します。
+/// 書き出したページの合計を返します。
+pub fn write_site(
+    output: &Path,
+    site_title: &str,
+    threads: &[SiteThread],
+    per_page: usize,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    fs::create_dir_all(output).map_err(|e| format!("ディレクトリ {} を作成できませんでした: {}", output.display(), e))?;
+    let mut pages = 0;
+    for thread in threads {
+        pages += write_thread(output, thread, per_page.max(1))?;
+    }
+    fs::write(output.join("index.html"), render_index(site_title, threads))?;
+    Ok(pages + 1)
+}
@@ -0,0 +1,188 @@
+use async_trait::async_trait;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+use twilight_model::channel::Message;
+
+use crate::embed::{get_user_avatar_url, webhook_display_name};
+use crate::forwarding::forward_content;
+use crate::links::MessageLink;
+use crate::retry::{self, SendError};
+use crate::sink::MessageSink;
+use crate::state::{BotState, ThreadInfo};
+
+/// Slack の Web API でメッセージを投稿するエンドポイント
+const POST_MESSAGE_URL: &str = "https://slack.com/api/chat.postMessage";
+
+/// Web API で投稿する場合のボットトークンを指定する環境変数
+pub const BOT_TOKEN_ENV: &str = "SLACK_BOT_TOKEN";
+
+/// コードブロックとインラインコード（中身は変換しない）
+fn code_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"```[\s\S]*?```|`[^`\n]+`").unwrap())
+}
+
+/// Discord の書式を Slack の書式に置き換える規則（上から順に適用する）
+///
+/// 太字は斜体の `*` と区別するため、一旦 `\u{0}` に置き換えてから最後に `*` に戻します。
+fn format_rules() -> &'static [(Regex, &'static str)] {
+    static RULES: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    RULES.get_or_init(|| {
+        [
+            // 埋め込みを抑制したリンク <https://...>（エスケープ済み）
+            (r"&lt;(https?://[^\s&]+)&gt;", "<$1>"),
+            // マスクされたリンク [テキスト](URL)
+            (r"\[([^\]\n]+)\]\((https?://[^)\s]+)\)", "<$2|$1>"),
+            // 見出し（Slack には見出しが無いため太字にする）
+            (r"(?m)^#{1,3} +(.+)$", "\u{0}$1\u{0}"),
+            (r"\*\*(.+?)\*\*", "\u{0}$1\u{0}"),
+            (r"\*([^*\n]+)\*", "_${1}_"),
+            (r"~~(.+?)~~", "~$1~"),
+            // 下線は Slack に無いため、書式を外す
+            (r"__(.+?)__", "$1"),
+            (r"\u{0}", "*"),
+        ]
+        .into_iter()
+        .map(|(pattern, replacement)| (Regex::new(pattern).unwrap(), replacement))
+        .collect()
+    })
+}
+
+/// Slack で特別な意味を持つ文字（`&`、`<`、`>`）をエスケープする
+fn escape_slack(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// コード以外の部分の書式を置き換える
+fn convert_text(text: &str) -> String {
+    format_rules()
+        .iter()
+        .fold(escape_slack(text), |text, (pattern, replacement)| pattern.replace_all(&text, *replacement).into_owned())
+}
+
+/// Discord の書式（Markdown）を Slack の mrkdwn に変換する
+///
+/// 太字・斜体・取り消し線・リンク・見出しを置き換えます。コードブロックとインラインコードの中身はそのまま残します。
+pub fn to_slack_mrkdwn(text: &str) -> String {
+    let mut converted = String::with_capacity(text.len());
+    let mut last = 0;
+    for code in code_pattern().find_iter(text) {
+        converted.push_str(&convert_text(&text[last..code.start()]));
+        converted.push_str(&escape_slack(code.as_str()));
+        last = code.end();
+    }
+    converted.push_str(&convert_text(&text[last..]));
+    converted
+}
+
+/// Slack の投稿先
+#[derive(Debug, Clone)]
+pub enum SlackTarget {
+    /// Incoming Webhook のURL
+    Webhook(String),
+    /// Web API（chat.postMessage）で投稿するチャンネル（IDまたは名前）
+    Channel(String),
+}
+
+/// Slack のチャンネルに投稿する転送先
+pub struct SlackSink {
+    pub target: SlackTarget,
+}
+
+impl SlackSink {
+    /// 投稿するメッセージを作成する（添付ファイルはファイル名付きのリンクにする）
+    async fn payload(&self, state: &BotState, thread_info: &ThreadInfo, message: &Message) -> Value {
+        let mut lines = vec![to_slack_mrkdwn(&forward_content(state, thread_info, message).await)];
+        lines.extend(
+            message
+                .attachments
+                .iter()
+                .map(|attachment| format!("📎 <{}|{}>", attachment.url, escape_slack(&attachment.filename))),
+        );
+        if let Some(url) = state.message_url(message).await {
+            lines.push(format!("<{}|元のメッセージ>", url));
+        }
+
+        let avatar_hash = message.author.avatar.as_ref().map(|hash| hash.to_string());
+        let mut payload = json!({
+            "text": lines.join("\n"),
+            "username": webhook_display_name(&message.author.name, thread_info.label.as_deref()),
+            "icon_url": get_user_avatar_url(message.author.id, avatar_hash.as_deref()),
+            "unfurl_links": false,
+        });
+        if let SlackTarget::Channel(channel) = &self.target {
+            payload["channel"] = json!(channel);
+        }
+        payload
+    }
+}
+
+#[async_trait]
+impl MessageSink for SlackSink {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    async fn send(
+        &self,
+        state: &BotState,
+        thread_info: &ThreadInfo,
+        message: &Message,
+    ) -> Result<Option<MessageLink>, Box<dyn std::error::Error + Send + Sync>> {
+        let payload = self.payload(state, thread_info, message).await;
+        let client = reqwest::Client::new();
+
+        match &self.target {
+            // Webhook のURLにはトークンが含まれるため、エラーにURLを含めない
+            SlackTarget::Webhook(url) => {
+                retry::with_backoff("Slack への送信", || async {
+                    let response = client
+                        .post(url)
+                        .json(&payload)
+                        .send()
+                        .await
+                        .map_err(|e| SendError::network(format!("Slack の Incoming Webhook への送信に失敗しました: {}", e.without_url())))?;
+                    let status = response.status();
+                    if !status.is_success() {
+                        let body = response.text().await.unwrap_or_default();
+                        return Err(SendError::status(status.as_u16(), format!("Slack の Incoming Webhook への送信が失敗しました: {} {}", status, body)).into());
+                    }
+                    Ok(())
+                })
+                .await?;
+            }
+            SlackTarget::Channel(channel) => {
+                let token = std::env::var(BOT_TOKEN_ENV)
+                    .map_err(|_| format!("Slack のチャンネル {} に投稿するには環境変数 {} を設定してください", channel, BOT_TOKEN_ENV))?;
+                retry::with_backoff("Slack への送信", || async {
+                    let response = client
+                        .post(POST_MESSAGE_URL)
+                        .bearer_auth(&token)
+                        .json(&payload)
+                        .send()
+                        .await
+                        .map_err(|e| SendError::network(format!("Slack の Web API への送信に失敗しました: {}", e)))?;
+                    let status = response.status();
+                    if !status.is_success() {
+                        let body = response.text().await.unwrap_or_default();
+                        return Err(SendError::status(status.as_u16(), format!("Slack の Web API への送信が失敗しました: {} {}", status, body)).into());
+                    }
+
+                    // Web API はエラーでも 200 を返すため、応答の ok を確認する
+                    let body: Value = response
+                        .json()
+                        .await
+                        .map_err(|e| SendError::network(format!("Slack の Web API の応答を読み取れませんでした: {}", e)))?;
+                    if body["ok"].as_bool() != Some(true) {
+                        let error = body["error"].as_str().unwrap_or("unknown_error");
+                        return Err(format!("Slack のチャンネル {} への投稿が失敗しました: {}", channel, error).into());
+                    }
+                    Ok(())
+                })
+                .await?;
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,436 @@
+use std::sync::Arc;
+use twilight_http::Client as HttpClient;
+use twilight_model::application::command::{Command, CommandOption, CommandOptionType, CommandType};
+use twilight_model::application::interaction::application_command::{CommandData, CommandDataOption, CommandOptionValue};
+use twilight_model::application::interaction::{Interaction, InteractionData};
+use twilight_model::channel::message::MessageFlags;
+use twilight_model::channel::ChannelType;
+use twilight_model::guild::Permissions;
+use twilight_model::http::interaction::{InteractionResponse, InteractionResponseData, InteractionResponseType};
+use twilight_model::id::{
+    marker::{ApplicationMarker, ChannelMarker, UserMarker},
+    Id,
+};
+
+use crate::audit::{AuditEntry, AuditOutcome};
+use crate::config;
+use crate::help;
+use crate::permissions;
+use crate::setup;
+use crate::source;
+use crate::state::{BotState, ThreadInfo};
+use crate::stats;
+
+/// スラッシュコマンドのオプションを作成する
+fn command_option(
+    kind: CommandOptionType,
+    name: &str,
+    description: &str,
+    required: bool,
+    options: Option<Vec<CommandOption>>,
+) -> CommandOption {
+    // サブコマンドには required を指定できない
+    let required = match kind {
+        CommandOptionType::SubCommand | CommandOptionType::SubCommandGroup => None,
+        _ => Some(required),
+    };
+
+    // チャンネルの選択肢はテキストチャンネル・スレッド・フォーラムに限定する
+    let channel_types = (kind == CommandOptionType::Channel).then(|| {
+        vec![
+            ChannelType::GuildText,
+            ChannelType::GuildAnnouncement,
+            ChannelType::GuildForum,
+            ChannelType::PublicThread,
+            ChannelType::PrivateThread,
+            ChannelType::AnnouncementThread,
+        ]
+    });
+
+    CommandOption {
+        autocomplete: None,
+        channel_types,
+        choices: None,
+        description: description.to_string(),
+        description_localizations: None,
+        kind,
+        max_length: None,
+        max_value: None,
+        min_length: None,
+        min_value: None,
+        name: name.to_string(),
+        name_localizations: None,
+        options,
+        required,
+    }
+}
+
+/// `/map` コマンドの定義を作成する
+fn map_command() -> Command {
+    let add = command_option(
+        CommandOptionType::SubCommand,
+        "add",
+        "スレッドのメッセージを転送するチャンネルを設定します",
+        false,
+        Some(vec![
+            command_option(CommandOptionType::Channel, "target", "転送先のチャンネル", true, None),
+            command_option(CommandOptionType::Channel, "thread", "転送元のスレッド（省略時は現在のスレッド）", false, None),
+            command_option(CommandOptionType::String, "options", "マッピングのオプション（例: all format=embed label=サポート）", false, None),
+        ]),
+    );
+    let remove = command_option(
+        CommandOptionType::SubCommand,
+        "remove",
+        "スレッドのマッピングを削除します",
+        false,
+        Some(vec![command_option(
+            CommandOptionType::Channel,
+            "thread",
+            "転送元のスレッド（省略時は現在のスレッド）",
+            false,
+            None,
+        )]),
+    );
+    let pause = command_option(
+        CommandOptionType::SubCommand,
+        "pause",
+        "スレッドの転送を一時停止します",
+        false,
+        Some(vec![command_option(
+            CommandOptionType::Channel,
+            "thread",
+            "転送元のスレッド（省略時は現在のスレッド）",
+            false,
+            None,
+        )]),
+    );
+    let resume = command_option(
+        CommandOptionType::SubCommand,
+        "resume",
+        "一時停止したスレッドの転送を再開します",
+        false,
+        Some(vec![command_option(
+            CommandOptionType::Channel,
+            "thread",
+            "転送元のスレッド（省略時は現在のスレッド）",
+            false,
+            None,
+        )]),
+    );
+    let list = command_option(CommandOptionType::SubCommand, "list", "マッピングの一覧を表示します", false, Some(Vec::new()));
+
+    Command {
+        application_id: None,
+        // サーバー管理権限を持つユーザーのみ使用可能
+        default_member_permissions: Some(Permissions::MANAGE_GUILD),
+        dm_permission: Some(false),
+        description: "スレッドとチャンネルのマッピングを管理します".to_string(),
+        description_localizations: None,
+        guild_id: None,
+        id: None,
+        kind: CommandType::ChatInput,
+        name: "map".to_string(),
+        name_localizations: None,
+        nsfw: None,
+        options: vec![add, remove, pause, resume, list],
+        version: Id::new(1),
+    }
+}
+
+/// `/status` コマンドの定義を作成する
+fn status_command() -> Command {
+    Command {
+        application_id: None,
+        default_member_permissions: Some(Permissions::MANAGE_GUILD),
+        dm_permission: Some(false),
+        description: "ボットの稼働時間・転送数などの実行状況を表示します".to_string(),
+        description_localizations: None,
+        guild_id: None,
+        id: None,
+        kind: CommandType::ChatInput,
+        name: "status".to_string(),
+        name_localizations: None,
+        nsfw: None,
+        options: Vec::new(),
+        version: Id::new(1),
+    }
+}
+
+/// `/help` コマンドの定義を作成する
+fn help_command() -> Command {
+    Command {
+        application_id: None,
+        // 誰でも使用可能
+        default_member_permissions: None,
+        dm_permission: Some(false),
+        description: "このチャンネルで使用できるコマンドを表示します".to_string(),
+        description_localizations: None,
+        guild_id: None,
+        id: None,
+        kind: CommandType::ChatInput,
+        name: "help".to_string(),
+        name_localizations: None,
+        nsfw: None,
+        options: Vec::new(),
+        version: Id::new(1),
+    }
+}
+
+/// スラッシュコマンドを登録する（Ready受信時に呼び出す）
+pub async fn register_commands(
+    http: &HttpClient,
+    application_id: Id<ApplicationMarker>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    http.interaction(application_id)
+        .set_global_commands(&[map_command(), status_command(), help_command(), setup::command()])
+        .await?;
+
+    println!("✅ スラッシュコマンドを登録しました");
+    Ok(())
+}
+
+/// コマンドの実行者にだけ見えるメッセージで応答する
+async fn respond(
+    http: &HttpClient,
+    interaction: &Interaction,
+    content: String,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let response = InteractionResponse {
+        kind: InteractionResponseType::ChannelMessageWithSource,
+        data: Some(InteractionResponseData {
+            content: Some(content),
+            flags: Some(MessageFlags::EPHEMERAL),
+            ..Default::default()
+        }),
+    };
+
+    http.interaction(interaction.application_id)
+        .create_response(interaction.id, &interaction.token, &response)
+        .await?;
+    Ok(())
+}
+
+/// オプション一覧から指定した名前のチャンネルを取り出す
+fn channel_option(options: &[CommandDataOption], name: &str) -> Option<Id<ChannelMarker>> {
+    options.iter().find(|option| option.name == name).and_then(|option| match option.value {
+        CommandOptionValue::Channel(id) => Some(id),
+        _ => None,
+    })
+}
+
+/// オプション一覧から指定した名前の文字列を取り出す
+fn string_option<'a>(options: &'a [CommandDataOption], name: &str) -> Option<&'a str> {
+    options.iter().find(|option| option.name == name).and_then(|option| match &option.value {
+        CommandOptionValue::String(value) => Some(value.as_str()),
+        _ => None,
+    })
+}
+
+/// `/map add` を処理する
+async fn handle_map_add(
+    state: &BotState,
+    current_channel: Option<Id<ChannelMarker>>,
+    user_id: Option<Id<UserMarker>>,
+    options: &[CommandDataOption],
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let Some(thread_id) = channel_option(options, "thread").or(current_channel) else {
+        return Ok("転送元のスレッドを指定してください。".to_string());
+    };
+    let Some(target_channel_id) = channel_option(options, "target") else {
+        return Ok("転送先のチャンネルを指定してください。".to_string());
+    };
+
+    let mut thread_info = ThreadInfo::new(target_channel_id);
+    for option in string_option(options, "options").unwrap_or_default().split_whitespace() {
+        if let Err(reason) = config::apply_mapping_option(&mut thread_info, option) {
+            return Ok(format!("オプションが正しくありません: {}", reason));
+        }
+    }
+    if thread_info.forwards_to(thread_id) {
+        return Ok("転送元と同じチャンネルには転送できません。".to_string());
+    }
+    // 転送先にボットが転送できるか（別のサーバーの場合は実行したユーザーの権限も）確認する
+    if let Some(user_id) = user_id {
+        if let Err(reason) = permissions::check_mapping_target(state, thread_id, user_id, target_channel_id).await {
+            return Ok(format!("❌ 転送先のチャンネル {} に転送できません: {}", target_channel_id, reason));
+        }
+    }
+
+    let summary = thread_info.summary();
+    state.add_thread_mapping(thread_id, thread_info).await?;
+    source::prepare(state, thread_id).await;
+    println!("スラッシュコマンドでマッピングを追加しました: スレッド {} -> チャンネル {}", thread_id, target_channel_id);
+
+    Ok(format!("<#{}> のメッセージを <#{}> に転送します {}", thread_id, target_channel_id, summary))
+}
+
+/// `/map remove` を処理する
+async fn handle_map_remove(
+    state: &BotState,
+    current_channel: Option<Id<ChannelMarker>>,
+    options: &[CommandDataOption],
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let Some(thread_id) = channel_option(options, "thread").or(current_channel) else {
+        return Ok("転送元のスレッドを指定してください。".to_string());
+    };
+
+    if state.remove_thread_mapping(thread_id).await? {
+        println!("スラッシュコマンドでマッピングを削除しました: スレッド {}", thread_id);
+        Ok(format!("<#{}> のマッピングを削除しました。", thread_id))
+    } else {
+        Ok(format!("<#{}> にはマッピングが設定されていません。", thread_id))
+    }
+}
+
+/// `/map pause` と `/map resume` を処理する
+async fn handle_map_pause(
+    state: &BotState,
+    current_channel: Option<Id<ChannelMarker>>,
+    options: &[CommandDataOption],
+    active: bool,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let Some(thread_id) = channel_option(options, "thread").or(current_channel) else {
+        return Ok("転送元のスレッドを指定してください。".to_string());
+    };
+
+    match state.set_mapping_active(thread_id, active).await? {
+        Some(info) if active => {
+            println!("スラッシュコマンドで転送を再開しました: スレッド {}", thread_id);
+            Ok(format!("<#{}> から <#{}> への転送を再開しました。", thread_id, info.target_channel_id))
+        }
+        Some(info) => {
+            println!("スラッシュコマンドで転送を一時停止しました: スレッド {}", thread_id);
+            Ok(format!("<#{}> から <#{}> への転送を一時停止しました。", thread_id, info.target_channel_id))
+        }
+        None => Ok(format!("<#{}> にはマッピングが設定されていません。", thread_id)),
+    }
+}
+
+/// `/map list` を処理する
+async fn handle_map_list(state: &BotState) -> String {
+    let mut lines = Vec::new();
+
+    let mut thread_mappings: Vec<_> = state
+        .thread_mappings
+        .load()
+        .iter()
+        .map(|(thread_id, info)| (*thread_id, info.clone()))
+        .collect();
+    thread_mappings.sort_by_key(|(thread_id, _)| *thread_id);
+    for (thread_id, info) in &thread_mappings {
+        lines.push(format!("- <#{}> → <#{}> {}", thread_id, info.target_channel_id, info.summary()));
+    }
+
+    let mut parent_mappings: Vec<_> = state
+        .parent_mappings
+        .load()
+        .iter()
+        .map(|(parent_id, info)| (*parent_id, info.clone()))
+        .collect();
+    parent_mappings.sort_by_key(|(parent_id, _)| *parent_id);
+    for (parent_id, info) in &parent_mappings {
+        lines.push(format!("- <#{}> 配下の全スレッド → <#{}> {}", parent_id, info.target_channel_id, info.summary()));
+    }
+
+    if lines.is_empty() {
+        "マッピングは設定されていません。".to_string()
+    } else {
+        format!("**マッピング一覧** ({}件)\n{}", lines.len(), lines.join("\n"))
+    }
+}
+
+/// `/map` コマンドを処理する
+async fn handle_map_command(
+    state: &BotState,
+    interaction: &Interaction,
+    data: &CommandData,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let current_channel = interaction.channel.as_ref().map(|channel| channel.id);
+
+    let Some(subcommand) = data.options.first() else {
+        return Ok("サブコマンドを指定してください。".to_string());
+    };
+    let CommandOptionValue::SubCommand(options) = &subcommand.value else {
+        return Ok("サブコマンドを指定してください。".to_string());
+    };
+
+    match subcommand.name.as_str() {
+        "add" => handle_map_add(state, current_channel, interaction.author_id(), options).await,
+        "remove" => handle_map_remove(state, current_channel, options).await,
+        "pause" => handle_map_pause(state, current_channel, options, false).await,
+        "resume" => handle_map_pause(state, current_channel, options, true).await,
+        "list" => Ok(handle_map_list(state).await),
+        other => Ok(format!("不明なサブコマンドです: {}", other)),
+    }
+}
+
+/// `/map list` かどうか
+fn is_list(data: &CommandData) -> bool {
+    data.options.first().is_some_and(|subcommand| subcommand.name == "list")
+}
+
+/// 実行記録用にスラッシュコマンドを文字列にする（例: `/map add target:<#123> options:all`）
+fn command_text(data: &CommandData) -> String {
+    fn push_options(parts: &mut Vec<String>, options: &[CommandDataOption]) {
+        for option in options {
+            match &option.value {
+                CommandOptionValue::SubCommand(options) | CommandOptionValue::SubCommandGroup(options) => {
+                    parts.push(option.name.clone());
+                    push_options(parts, options);
+                }
+                CommandOptionValue::Channel(id) => parts.push(format!("{}:<#{}>", option.name, id)),
+                CommandOptionValue::String(value) => parts.push(format!("{}:{}", option.name, value)),
+                other => parts.push(format!("{}:{:?}", option.name, other)),
+            }
+        }
+    }
+
+    let mut parts = vec![format!("/{}", data.name)];
+    push_options(&mut parts, &data.options);
+    parts.join(" ")
+}
+
+/// インタラクション（スラッシュコマンド）を処理する
+pub async fn handle_interaction(
+    interaction: Interaction,
+    state: Arc<BotState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let data = match &interaction.data {
+        Some(InteractionData::ApplicationCommand(data)) => data,
+        // `/setup` の選択メニュー・ボタン
+        Some(InteractionData::MessageComponent(data)) if data.custom_id.starts_with(setup::CUSTOM_ID_PREFIX) => {
+            return setup::handle_component(&state, &interaction, data).await;
+        }
+        _ => return Ok(()),
+    };
+
+    let content = match data.name.as_str() {
+        "map" => {
+            let result = handle_map_command(&state, &interaction, data).await;
+            // 一覧の表示以外はマッピングを変更するため、実行記録を残す
+            if let (Some(user_id), Some(channel), false) = (interaction.author_id(), &interaction.channel, is_list(data)) {
+                let entry = AuditEntry::new(user_id, channel.id, &command_text(data), AuditOutcome::from_result(&result));
+                state.record_command(entry).await;
+            }
+            match result {
+                Ok(content) => content,
+                Err(e) => {
+                    println!("スラッシュコマンドの処理中にエラーが発生しました: {}", e);
+                    format!("⚠️ エラーが発生しました: {}", e)
+                }
+            }
+        }
+        "status" => stats::status_report(&state).await,
+        "help" => help::help_text(&state, interaction.channel.as_ref().map(|channel| channel.id)).await,
+        "setup" => match setup::start(&state, &interaction).await {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                println!("/setup の処理中にエラーが発生しました: {}", e);
+                format!("⚠️ エラーが発生しました: {}", e)
+            }
+        },
+        _ => return Ok(()),
+    };
+
+    respond(&state.http, &interaction, content).await
+}
@@ -0,0 +1,167 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use twilight_model::channel::Message;
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use crate::markdown::escape_markdown;
+use crate::state::BotState;
+
+/// 転送元のチャンネルの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    /// 通常のテキストチャンネル（お知らせチャンネルを含む）
+    Channel,
+    /// テキストチャンネルのスレッド
+    Thread,
+    /// フォーラムチャンネルの投稿
+    ForumPost,
+}
+
+impl SourceKind {
+    /// 種類に応じた転送元を作成する
+    pub fn build(self, channel_id: Id<ChannelMarker>) -> Box<dyn MessageSource> {
+        match self {
+            Self::Channel => Box::new(ChannelSource),
+            Self::Thread => Box::new(ThreadSource { channel_id }),
+            Self::ForumPost => Box::new(ForumPostSource { channel_id }),
+        }
+    }
+}
+
+/// 転送元（マッピングの対象になるチャンネル）
+///
+/// スレッドへの参加やフォーラムの投稿のタイトルの表示など、転送元の種類によって異なる処理をまとめます。
+/// 新しい種類の転送元を追加する場合は、この trait を実装し、[`SourceKind`] に種類を追加します。
+#[async_trait]
+pub trait MessageSource: Send + Sync {
+    /// 転送元の種類の呼び方（「スレッド」など、メッセージ用）
+    fn noun(&self) -> &'static str;
+
+    /// 転送元を示すアイコン
+    fn icon(&self) -> &'static str;
+
+    /// マッピングを登録したとき、転送元のメッセージを受信できるように準備する
+    async fn prepare(&self, state: &BotState) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+    /// 転送する本文の先頭に付ける見出し（付けない場合は None）
+    async fn heading(&self, state: &BotState, message: &Message) -> Option<String>;
+}
+
+/// 通常のテキストチャンネル
+pub struct ChannelSource;
+
+#[async_trait]
+impl MessageSource for ChannelSource {
+    fn noun(&self) -> &'static str {
+        "チャンネル"
+    }
+
+    fn icon(&self) -> &'static str {
+        "#️⃣"
+    }
+
+    async fn prepare(&self, _state: &BotState) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Ok(())
+    }
+
+    async fn heading(&self, _state: &BotState, _message: &Message) -> Option<String> {
+        None
+    }
+}
+
+/// スレッドに参加する（参加していないプライベートスレッドのメッセージは受信できないため）
+async fn join_thread(state: &BotState, channel_id: Id<ChannelMarker>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    state.http.join_thread(channel_id).await?;
+    Ok(())
+}
+
+/// テキストチャンネルのスレッド
+pub struct ThreadSource {
+    pub channel_id: Id<ChannelMarker>,
+}
+
+#[async_trait]
+impl MessageSource for ThreadSource {
+    fn noun(&self) -> &'static str {
+        "スレッド"
+    }
+
+    fn icon(&self) -> &'static str {
+        "🧵"
+    }
+
+    async fn prepare(&self, state: &BotState) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        join_thread(state, self.channel_id).await
+    }
+
+    async fn heading(&self, _state: &BotState, _message: &Message) -> Option<String> {
+        None
+    }
+}
+
+/// フォーラムチャンネルの投稿
+///
+/// 投稿の最初のメッセージ（投稿と同じID）を転送するときは、投稿のタイトルを見出しとして付けます。
+pub struct ForumPostSource {
+    pub channel_id: Id<ChannelMarker>,
+}
+
+#[async_trait]
+impl MessageSource for ForumPostSource {
+    fn noun(&self) -> &'static str {
+        "フォーラムの投稿"
+    }
+
+    fn icon(&self) -> &'static str {
+        "📝"
+    }
+
+    async fn prepare(&self, state: &BotState) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        join_thread(state, self.channel_id).await
+    }
+
+    async fn heading(&self, state: &BotState, message: &Message) -> Option<String> {
+        if message.id.get() != self.channel_id.get() {
+            return None;
+        }
+        let title = state.channel_name(self.channel_id).await?;
+        Some(format!("📝 **{}**", escape_markdown(&title)))
+    }
+}
+
+/// チャンネルの種類を調べて、転送元を作成する
+pub async fn resolve(state: &BotState, channel_id: Id<ChannelMarker>) -> Box<dyn MessageSource> {
+    state.source_kind(channel_id).await.build(channel_id)
+}
+
+/// マッピングを登録した転送元の準備をして、転送元を返す（準備に失敗してもマッピングはそのまま使用する）
+pub async fn prepare(state: &BotState, channel_id: Id<ChannelMarker>) -> Box<dyn MessageSource> {
+    let source = resolve(state, channel_id).await;
+    if let Err(e) = source.prepare(state).await {
+        println!("{} {} の準備に失敗しました: {}", source.noun(), channel_id, e);
+    }
+    source
+}
+
+/// 起動時に、マッピングが設定された全ての転送元の準備をする（スレッド・フォーラムの投稿に参加する）
+///
+/// 参加していないスレッドのメッセージは受信できない場合があるため、マッピングの登録時だけでなく起動のたびに参加し直します。
+/// アーカイブされたスレッドには参加できないため、アーカイブが解除されたときに参加します。
+pub fn spawn_prepare_mapped_sources(state: &Arc<BotState>) {
+    let state = Arc::clone(state);
+    tokio::spawn(async move {
+        let channel_ids: Vec<_> = state.thread_mappings.load().keys().copied().collect();
+        let mut prepared = 0;
+        for channel_id in channel_ids {
+            let source = resolve(&state, channel_id).await;
+            if state.cached_thread_state(channel_id).await.is_some_and(|thread| thread.archived) {
+                continue;
+            }
+            match source.prepare(&state).await {
+                Ok(()) => prepared += 1,
+                Err(e) => println!("{} {} の準備に失敗しました: {}", source.noun(), channel_id, e),
+            }
+        }
+        println!("マッピングが設定された {} 個の転送元の準備をしました", prepared);
+    });
+}
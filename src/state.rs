@@ -0,0 +1,1287 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::RwLock;
+use twilight_http::Client as HttpClient;
+use twilight_model::channel::message::{AllowedMentions, MentionType, MessageType};
+use twilight_model::channel::{Channel, ChannelType, Message};
+use twilight_model::id::{
+    marker::{ApplicationMarker, ChannelMarker, GuildMarker, MessageMarker, RoleMarker, UserMarker, WebhookMarker},
+    Id,
+};
+
+use crate::api::DiscordApi;
+use crate::archive::MessageArchive;
+use crate::attachments::AttachmentArchive;
+use crate::alert::AdminAlerts;
+use crate::audit::{AuditEntry, AuditLog};
+use crate::backfill::{BackfillConfirmations, BackfillTracker};
+use crate::setup::SetupSessions;
+use crate::digest::DigestQueue;
+use crate::filter::MessagePattern;
+use crate::github::GitHubIssues;
+use crate::history::MessageHistory;
+use crate::links::MessageLinkStore;
+use crate::mappings::{MappingTable, NameMapping};
+use crate::markdown::escape_markdown;
+use crate::mentions::MentionResolver;
+use crate::permissions::CommandPermissions;
+use crate::publish::EventPublisher;
+use crate::queue::ForwardQueues;
+use crate::roles::MemberRoleCache;
+use crate::sink::SinkSpec;
+use crate::source::SourceKind;
+use crate::stats::BotStats;
+use crate::storage::{FailedForward, MappingStats, MappingStore, PendingForward};
+use crate::transform::TransformSpec;
+use crate::watch::DebugWatch;
+
+/// 転送メッセージの形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageFormat {
+    /// `**送信者名**` に続けて本文を送るテキスト形式
+    #[default]
+    Plain,
+    /// 送信者名・アバター・本文を埋め込みにまとめる形式
+    Embed,
+    /// 転送先チャンネルに自動作成したWebhookで、元の送信者の名前とアバターを使って送る形式
+    Webhook,
+}
+
+/// 転送メッセージで通知（メンション）を許可する範囲
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MentionPolicy {
+    /// 全てのメンションを無効化する（転送先で誰にも通知しない）
+    #[default]
+    None,
+    /// ユーザーへのメンションのみ許可する（@everyone・@here・ロールは通知しない）
+    Users,
+    /// 全てのメンションを許可する
+    All,
+}
+
+impl MentionPolicy {
+    /// 送信時に指定する allowed_mentions を作成する
+    pub fn allowed_mentions(self) -> AllowedMentions {
+        let parse = match self {
+            MentionPolicy::None => Vec::new(),
+            MentionPolicy::Users => vec![MentionType::Users],
+            MentionPolicy::All => vec![MentionType::Users, MentionType::Roles, MentionType::Everyone],
+        };
+        AllowedMentions {
+            parse,
+            ..Default::default()
+        }
+    }
+}
+
+/// 転送元サーバーのカスタム絵文字の表示方法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmojiStyle {
+    /// `<:name:123>` のまま転送する（転送先でも使える絵文字の場合）
+    #[default]
+    Keep,
+    /// `:name:` のテキストに置き換える
+    Text,
+    /// 絵文字の画像へのリンク付きの `:name:` に置き換える
+    Link,
+}
+
+/// 元のメッセージを機械可読な形式で保存する方法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveMode {
+    /// 保存しない
+    #[default]
+    Off,
+    /// 転送メッセージに元のメッセージのJSONファイルを添付する
+    Attach,
+    /// 元のメッセージをJSONLファイルに追記する
+    Jsonl,
+}
+
+/// まとめをメールで送信する頻度
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailSchedule {
+    /// 毎日
+    #[default]
+    Daily,
+    /// 毎週月曜日
+    Weekly,
+}
+
+/// 転送できるシステムメッセージの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SystemMessageKind {
+    /// メッセージのピン留め
+    Pin,
+    /// メンバーの参加・スレッドへの追加・削除
+    Join,
+    /// サーバーのブースト
+    Boost,
+    /// スレッドの作成
+    Thread,
+    /// チャンネル名（スレッド名）の変更
+    Rename,
+}
+
+impl SystemMessageKind {
+    /// オプションの値から種類を読み取る
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pin" => Some(Self::Pin),
+            "join" => Some(Self::Join),
+            "boost" => Some(Self::Boost),
+            "thread" => Some(Self::Thread),
+            "rename" => Some(Self::Rename),
+            _ => None,
+        }
+    }
+
+    /// メッセージの種類に対応するシステムメッセージの種類（対応しない場合は None）
+    pub fn of(kind: MessageType) -> Option<Self> {
+        match kind {
+            MessageType::ChannelMessagePinned => Some(Self::Pin),
+            MessageType::UserJoin | MessageType::RecipientAdd | MessageType::RecipientRemove => Some(Self::Join),
+            MessageType::GuildBoost
+            | MessageType::GuildBoostTier1
+            | MessageType::GuildBoostTier2
+            | MessageType::GuildBoostTier3 => Some(Self::Boost),
+            MessageType::ThreadCreated => Some(Self::Thread),
+            MessageType::ChannelNameChange => Some(Self::Rename),
+            _ => None,
+        }
+    }
+}
+
+/// 本文に応じて転送先を切り替えるルール
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Route {
+    /// 本文に一致させる正規表現
+    pub pattern: MessagePattern,
+    /// 一致した場合の転送先チャンネルID
+    pub channel_id: Id<ChannelMarker>,
+}
+
+/// 自動作成・再利用するWebhookの名前
+const MANAGED_WEBHOOK_NAME: &str = "Thread2Channel";
+
+/// スレッド情報を保持する構造体
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadInfo {
+    /// メッセージのコピー先チャンネルID
+    pub target_channel_id: Id<ChannelMarker>,
+    /// 過去のメッセージを全て取得して転送するかどうか
+    pub transfer_all_messages: bool,
+    /// Webhook URL (オプション)
+    pub webhook_url: Option<String>,
+    /// 転送メッセージの形式（webhook_url 未設定時のみ有効）
+    #[serde(default)]
+    pub format: MessageFormat,
+    /// 転送前に待機する時間（ミリ秒）
+    #[serde(default)]
+    pub forward_delay_ms: u64,
+    /// ボット・Webhookのメッセージも転送するかどうか（このボット自身のメッセージは常に除外）
+    #[serde(default)]
+    pub include_bots: bool,
+    /// 転送メッセージの本文の先頭に付けるテキスト
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// 転送元を示すラベル（複数のスレッドを同じチャンネルに転送する場合の識別用）
+    #[serde(default)]
+    pub label: Option<String>,
+    /// 親チャンネル配下に新しいスレッドが作成されたとき、転送先に通知するかどうか
+    #[serde(default)]
+    pub notify_new_threads: bool,
+    /// 投票が終了したとき、転送先のメッセージに結果を反映するかどうか
+    #[serde(default)]
+    pub poll_results: bool,
+    /// 転送メッセージで通知（メンション）を許可する範囲
+    #[serde(default)]
+    pub mentions: MentionPolicy,
+    /// 転送元サーバーのカスタム絵文字の表示方法
+    #[serde(default)]
+    pub emoji: EmojiStyle,
+    /// 転送するシステムメッセージの種類（空の場合は転送しない）
+    #[serde(default)]
+    pub system_messages: Vec<SystemMessageKind>,
+    /// 元のメッセージを機械可読な形式で保存する方法
+    #[serde(default)]
+    pub archive: ArchiveMode,
+    /// 本文がいずれかに一致するメッセージのみ転送する正規表現（空の場合は全て転送）
+    #[serde(default)]
+    pub include_patterns: Vec<MessagePattern>,
+    /// 本文がいずれかに一致するメッセージを転送しない正規表現
+    #[serde(default)]
+    pub exclude_patterns: Vec<MessagePattern>,
+    /// メッセージを転送するユーザー（空の場合は全員）
+    #[serde(default)]
+    pub allowed_users: Vec<Id<UserMarker>>,
+    /// メッセージを転送しないユーザー
+    #[serde(default)]
+    pub blocked_users: Vec<Id<UserMarker>>,
+    /// 送信者がいずれかを持っている場合のみ転送するロール（空の場合は全員）
+    #[serde(default)]
+    pub required_roles: Vec<Id<RoleMarker>>,
+    /// 本文に応じて転送先を切り替えるルール（最初に一致したルールを使い、どれにも一致しない場合は target_channel_id）
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    /// テキスト形式・Webhookの本文のテンプレート（未設定の場合は標準の形式）
+    #[serde(default)]
+    pub template: Option<String>,
+    /// 転送前に本文に適用する変換（設定した順に適用）
+    #[serde(default)]
+    pub transforms: Vec<TransformSpec>,
+    /// この件数のメッセージが溜まったらまとめて投稿する（0 の場合は件数で区切らない）
+    #[serde(default)]
+    pub digest_count: usize,
+    /// 最初のメッセージからこの時間（分）が経ったらまとめて投稿する（0 の場合は時間で区切らない）
+    #[serde(default)]
+    pub digest_minutes: u64,
+    /// スターボード形式で転送する場合のリアクションの絵文字（設定した場合、リアクションが集まったメッセージのみ転送）
+    #[serde(default)]
+    pub star_emoji: Option<String>,
+    /// スターボード形式で転送するのに必要なリアクションの数
+    #[serde(default = "default_star_threshold")]
+    pub star_threshold: u64,
+    /// このスレッドで使用するコマンドのプレフィックス（未指定の場合はサーバーごとの設定またはデフォルトを使用）
+    #[serde(default)]
+    pub command_prefix: Option<String>,
+    /// 転送メッセージの送信先（デフォルトは target_channel_id のDiscordチャンネル）
+    #[serde(default)]
+    pub sink: SinkSpec,
+    /// 転送したメッセージを RSS フィード（`/feeds/<スレッドID>.xml`）で公開するかどうか
+    #[serde(default)]
+    pub feed: bool,
+    /// 🐛 のリアクションで Issue を作成する GitHub のリポジトリ（`owner/repo`）
+    #[serde(default)]
+    pub github_repo: Option<String>,
+    /// 転送したメッセージのまとめをメールで送信する宛先
+    #[serde(default)]
+    pub email_recipients: Vec<String>,
+    /// まとめをメールで送信する頻度（daily、weekly）
+    #[serde(default)]
+    pub email_schedule: EmailSchedule,
+    /// スレッドがアーカイブされている間、転送を一時停止するかどうか
+    #[serde(default = "default_pause_on_archive")]
+    pub pause_on_archive: bool,
+    /// 転送が有効かどうか（false の間は一時停止）
+    #[serde(default = "default_active")]
+    pub active: bool,
+    /// スレッドのアーカイブによって一時停止したかどうか（アーカイブが解除されたら再開する）
+    #[serde(default)]
+    pub paused_by_archive: bool,
+}
+
+/// スターボード形式で転送するのに必要なリアクションの数のデフォルト
+pub fn default_star_threshold() -> u64 {
+    3
+}
+
+/// アーカイブ中の一時停止のデフォルト（以前からの動作に合わせて一時停止する）
+pub fn default_pause_on_archive() -> bool {
+    true
+}
+
+/// 保存済みのマッピングに active が無い場合は有効として扱う
+fn default_active() -> bool {
+    true
+}
+
+impl ThreadInfo {
+    /// デフォルト設定でスレッド情報を作成する
+    pub fn new(target_channel_id: Id<ChannelMarker>) -> Self {
+        Self {
+            target_channel_id,
+            transfer_all_messages: false,
+            webhook_url: None,
+            format: MessageFormat::default(),
+            forward_delay_ms: 0,
+            include_bots: false,
+            prefix: None,
+            label: None,
+            notify_new_threads: false,
+            poll_results: false,
+            mentions: MentionPolicy::default(),
+            emoji: EmojiStyle::default(),
+            system_messages: Vec::new(),
+            archive: ArchiveMode::default(),
+            include_patterns: Vec::new(),
+            exclude_patterns: Vec::new(),
+            allowed_users: Vec::new(),
+            blocked_users: Vec::new(),
+            required_roles: Vec::new(),
+            routes: Vec::new(),
+            template: None,
+            transforms: Vec::new(),
+            digest_count: 0,
+            digest_minutes: 0,
+            star_emoji: None,
+            star_threshold: default_star_threshold(),
+            command_prefix: None,
+            sink: SinkSpec::default(),
+            feed: false,
+            github_repo: None,
+            email_recipients: Vec::new(),
+            email_schedule: EmailSchedule::Daily,
+            pause_on_archive: default_pause_on_archive(),
+            active: true,
+            paused_by_archive: false,
+        }
+    }
+
+    /// メッセージを1件ずつではなくまとめて投稿するかどうか
+    pub fn digest_enabled(&self) -> bool {
+        self.digest_count > 0 || self.digest_minutes > 0
+    }
+
+    /// 転送先または振り分け先にチャンネルが含まれているかどうか
+    pub fn forwards_to(&self, channel_id: Id<ChannelMarker>) -> bool {
+        self.target_channel_id == channel_id || self.routes.iter().any(|route| route.channel_id == channel_id)
+    }
+
+    /// 本文に一致する振り分けルールがあれば、転送先をそのチャンネルに切り替えたスレッド情報を返す
+    ///
+    /// Webhook URLは元の転送先チャンネルのものなので、切り替えた場合は使用しません。
+    pub fn routed(&self, content: &str) -> Cow<'_, ThreadInfo> {
+        let Some(route) = self.routes.iter().find(|route| route.pattern.is_match(content)) else {
+            return Cow::Borrowed(self);
+        };
+        if route.channel_id == self.target_channel_id {
+            return Cow::Borrowed(self);
+        }
+
+        let mut info = self.clone();
+        info.target_channel_id = route.channel_id;
+        info.webhook_url = None;
+        Cow::Owned(info)
+    }
+
+    /// マッピングの設定内容を一覧表示用にまとめる
+    pub fn summary(&self) -> String {
+        let mut parts = vec![format!("形式: {:?}", self.format)];
+        if !self.active {
+            parts.push("一時停止中".to_string());
+        }
+        if self.webhook_url.is_some() {
+            parts.push("Webhook".to_string());
+        }
+        if self.transfer_all_messages {
+            parts.push("全メッセージ転送".to_string());
+        }
+        if self.forward_delay_ms > 0 {
+            parts.push(format!("待機: {}ms", self.forward_delay_ms));
+        }
+        if self.include_bots {
+            parts.push("ボットを含む".to_string());
+        }
+        if let Some(prefix) = &self.prefix {
+            parts.push(format!("プレフィックス: {}", prefix));
+        }
+        if let Some(label) = &self.label {
+            parts.push(format!("ラベル: {}", label));
+        }
+        if self.notify_new_threads {
+            parts.push("新規スレッド通知".to_string());
+        }
+        if self.poll_results {
+            parts.push("投票結果を反映".to_string());
+        }
+        if self.mentions != MentionPolicy::None {
+            parts.push(format!("メンション: {:?}", self.mentions));
+        }
+        if self.emoji != EmojiStyle::Keep {
+            parts.push(format!("絵文字: {:?}", self.emoji));
+        }
+        if !self.system_messages.is_empty() {
+            parts.push(format!("システムメッセージ: {:?}", self.system_messages));
+        }
+        if self.archive != ArchiveMode::Off {
+            parts.push(format!("アーカイブ: {:?}", self.archive));
+        }
+        if !self.include_patterns.is_empty() || !self.exclude_patterns.is_empty() {
+            parts.push(format!(
+                "絞り込み: 一致 {} 件 / 除外 {} 件",
+                self.include_patterns.len(),
+                self.exclude_patterns.len()
+            ));
+        }
+        if !self.allowed_users.is_empty() {
+            parts.push(format!("許可ユーザー: {} 人", self.allowed_users.len()));
+        }
+        if !self.blocked_users.is_empty() {
+            parts.push(format!("除外ユーザー: {} 人", self.blocked_users.len()));
+        }
+        if !self.required_roles.is_empty() {
+            parts.push(format!("必要なロール: {} 件", self.required_roles.len()));
+        }
+        if !self.routes.is_empty() {
+            parts.push(format!("振り分けルール: {} 件", self.routes.len()));
+        }
+        if self.template.is_some() {
+            parts.push("テンプレート".to_string());
+        }
+        if !self.transforms.is_empty() {
+            parts.push(format!("変換: {} 件", self.transforms.len()));
+        }
+        if self.digest_enabled() {
+            parts.push(format!("まとめ投稿: {}件 / {}分", self.digest_count, self.digest_minutes));
+        }
+        if let Some(emoji) = &self.star_emoji {
+            parts.push(format!("スターボード: {} {}個以上", emoji, self.star_threshold));
+        }
+        if self.sink != SinkSpec::Discord {
+            parts.push(format!("転送先: {}", self.sink.describe()));
+        }
+        if let Some(prefix) = &self.command_prefix {
+            parts.push(format!("コマンド: {}", prefix));
+        }
+        if self.feed {
+            parts.push("RSSフィード".to_string());
+        }
+        if let Some(repo) = &self.github_repo {
+            parts.push(format!("Issue: {}", repo));
+        }
+        if !self.email_recipients.is_empty() {
+            let schedule = match self.email_schedule {
+                EmailSchedule::Daily => "毎日",
+                EmailSchedule::Weekly => "毎週",
+            };
+            parts.push(format!("メール({}): {}", schedule, self.email_recipients.join(", ")));
+        }
+        if !self.pause_on_archive {
+            parts.push("アーカイブ中も転送".to_string());
+        }
+        format!("({})", parts.join(", "))
+    }
+}
+
+/// Webhook URL からWebhookのIDを取り出す
+fn webhook_id_from_url(url: &str) -> Option<Id<WebhookMarker>> {
+    let (_, rest) = url.split_once("/webhooks/")?;
+    rest.split('/').next()?.parse().ok().and_then(Id::new_checked)
+}
+
+/// キャッシュしたチャンネルの情報
+#[derive(Debug, Clone)]
+struct CachedChannel {
+    /// 親チャンネルのマッピングを探すチャンネルID（スレッドは親チャンネル、テキストチャンネルはカテゴリー、それ以外は None）
+    parent_id: Option<Id<ChannelMarker>>,
+    /// チャンネル名（スレッド名）
+    name: Option<String>,
+    /// チャンネルが属するサーバーのID
+    guild_id: Option<Id<GuildMarker>>,
+    /// チャンネルの種類
+    kind: ChannelType,
+    /// スレッドのアーカイブ・ロックの状態（スレッドでないチャンネルは None）
+    thread_state: Option<ThreadState>,
+}
+
+impl CachedChannel {
+    fn from_channel(channel: &Channel) -> Self {
+        Self {
+            // テキストチャンネルはカテゴリーのマッピングの対象（チャンネル同士のミラー用）、それ以外のチャンネルは対象外
+            parent_id: match channel.kind {
+                kind if kind.is_thread() => channel.parent_id,
+                ChannelType::GuildText | ChannelType::GuildAnnouncement => channel.parent_id,
+                _ => None,
+            },
+            name: channel.name.clone(),
+            guild_id: channel.guild_id,
+            kind: channel.kind,
+            thread_state: channel.thread_metadata.as_ref().map(|metadata| ThreadState {
+                archived: metadata.archived,
+                locked: metadata.locked,
+            }),
+        }
+    }
+}
+
+/// スレッドのアーカイブ・ロックの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadState {
+    /// アーカイブされているかどうか
+    pub archived: bool,
+    /// ロックされているかどうか（ロック中はスレッドの管理の権限を持つメンバーしか投稿できない）
+    pub locked: bool,
+}
+
+/// ボット全体で共有する状態
+pub struct BotState {
+    /// Discord HTTPクライアント
+    pub http: Arc<HttpClient>,
+    /// メッセージの送信・取得に使用するAPI（通常は `http` と同じクライアント）
+    pub api: Arc<dyn DiscordApi>,
+    /// ボット自身のユーザーIDとアプリケーションID（Ready受信時に設定）
+    identity: OnceLock<(Id<UserMarker>, Id<ApplicationMarker>)>,
+    /// スレッドID -> スレッド情報のマッピング（メッセージごとに参照するため、ロックを取らずに読み込む）
+    pub thread_mappings: MappingTable,
+    /// 親チャンネルID -> スレッド情報のマッピング（配下の全スレッドに適用）
+    pub parent_mappings: MappingTable,
+    /// スレッド名のパターンによるマッピング（一致したスレッドを個別のマッピングとして登録）
+    name_mappings: RwLock<Vec<NameMapping>>,
+    /// 転送先チャンネルID -> 自動作成・再利用するWebhook URL のキャッシュ
+    channel_webhooks: RwLock<HashMap<Id<ChannelMarker>, String>>,
+    /// チャンネルID -> 親チャンネル・名前・サーバーID のキャッシュ（ゲートウェイイベントで更新し、無い場合のみHTTP APIで取得する）
+    channels: RwLock<HashMap<Id<ChannelMarker>, CachedChannel>>,
+    /// 環境変数・設定ファイル由来のスレッドID（リロード時にコマンドで追加したマッピングと区別するため）
+    configured_thread_ids: RwLock<HashSet<Id<ChannelMarker>>>,
+    /// 実行時に削除されたスレッドID（設定に含まれていても読み込まない）
+    removed_thread_ids: RwLock<HashSet<Id<ChannelMarker>>>,
+    /// スターボード形式のマッピングで転送待ち・転送中のメッセージ（転送が終わるまでに続けてリアクションが付いても1回だけ転送するため）
+    starred_messages: RwLock<HashSet<Id<MessageMarker>>>,
+    /// 実行時に追加されたマッピングの保存先
+    store: MappingStore,
+    /// 転送したメッセージの対応の記録
+    pub message_links: MessageLinkStore,
+    /// 元のメッセージのJSONLアーカイブ（`archive=jsonl` のマッピングで使用）
+    pub archive: MessageArchive,
+    /// 転送したメッセージの添付ファイルの保存先（`bot.attachment_archive` を指定した場合のみ保存）
+    pub attachments: AttachmentArchive,
+    /// 転送したメッセージの履歴（`bot.message_history` で有効にした場合のみ保存）
+    pub history: MessageHistory,
+    /// 転送したメッセージのイベントの送信先（`bot.event_publisher` を指定した場合のみ送信）
+    pub events: EventPublisher,
+    /// メンションを表示名に置き換える処理
+    pub mentions: MentionResolver,
+    /// 送信者のロールのキャッシュ（ロールによる絞り込みで使用）
+    pub member_roles: MemberRoleCache,
+    /// まとめ投稿を待っているメッセージ
+    pub digests: DigestQueue,
+    /// 過去メッセージの転送中のスレッド
+    pub backfills: BackfillTracker,
+    /// 確認ボタンが押されるのを待っている過去メッセージの転送
+    pub confirmations: BackfillConfirmations,
+    /// マッピングごとの転送待ちのメッセージ
+    pub queues: ForwardQueues,
+    /// 起動後の実行状況
+    pub stats: BotStats,
+    /// デバッグ用の監視対象ID
+    pub debug_watch: DebugWatch,
+    /// 転送しないコマンドのプレフィックス一覧
+    command_prefixes: Vec<String>,
+    /// ボットのコマンドのデフォルトのプレフィックス
+    command_prefix: String,
+    /// サーバーID -> そのサーバーで使用するコマンドのプレフィックス
+    guild_command_prefixes: HashMap<Id<GuildMarker>, String>,
+    /// `all` を指定したマッピングの過去メッセージを自動で転送するかどうか
+    pub auto_backfill: bool,
+    /// 権限の必要なコマンドを実行できるかの判定
+    pub permissions: CommandPermissions,
+    /// メッセージから作成した GitHub の Issue の記録（`github` を指定したマッピングで使用）
+    pub github_issues: GitHubIssues,
+    /// 管理用チャンネルへの障害の通知
+    pub alerts: AdminAlerts,
+    /// `/setup` でマッピングを設定中のユーザーの選択内容
+    pub setups: SetupSessions,
+    /// 管理用のコマンドの実行記録の投稿先
+    audit_log: AuditLog,
+}
+
+impl BotState {
+    /// データベースに保存されたマッピングと設定から読み込んだマッピングで状態を作成する
+    ///
+    /// データベースのマッピングは実行時の変更を反映したものなので、設定より優先されます。
+    /// 実行時に削除したマッピングは、設定に含まれていても読み込みません。
+    /// データベースが空の場合は環境変数・設定ファイルのマッピングだけで起動します。
+    pub fn new(
+        http: Arc<HttpClient>,
+        store: MappingStore,
+        message_links: MessageLinkStore,
+        archive: MessageArchive,
+        configured_mappings: HashMap<Id<ChannelMarker>, ThreadInfo>,
+        parent_mappings: HashMap<Id<ChannelMarker>, ThreadInfo>,
+        debug_watch: DebugWatch,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let stored_mappings = store.load_all()?;
+        let removed_thread_ids = store.load_removed()?;
+        if stored_mappings.is_empty() {
+            println!("データベースにマッピングが無いため、環境変数・設定ファイルのマッピングを使用します");
+        } else {
+            println!("データベースから {} 個のマッピングを読み込みました", stored_mappings.len());
+        }
+
+        let configured_mappings: HashMap<_, _> = configured_mappings
+            .into_iter()
+            .filter(|(thread_id, _)| !removed_thread_ids.contains(thread_id))
+            .collect();
+        let configured_thread_ids = configured_mappings
+            .keys()
+            .filter(|thread_id| !stored_mappings.contains_key(thread_id))
+            .copied()
+            .collect();
+
+        let mut thread_mappings = configured_mappings;
+        thread_mappings.extend(stored_mappings);
+        debug_watch.log_mappings(&thread_mappings);
+
+        Ok(Self {
+            api: http.clone(),
+            http,
+            identity: OnceLock::new(),
+            thread_mappings: MappingTable::new(thread_mappings),
+            parent_mappings: MappingTable::new(parent_mappings),
+            name_mappings: RwLock::new(Vec::new()),
+            channel_webhooks: RwLock::new(HashMap::new()),
+            channels: RwLock::new(HashMap::new()),
+            configured_thread_ids: RwLock::new(configured_thread_ids),
+            removed_thread_ids: RwLock::new(removed_thread_ids),
+            starred_messages: RwLock::new(HashSet::new()),
+            store,
+            message_links,
+            archive,
+            attachments: AttachmentArchive::default(),
+            history: MessageHistory::default(),
+            events: EventPublisher::default(),
+            mentions: MentionResolver::new(),
+            member_roles: MemberRoleCache::new(),
+            digests: DigestQueue::new(),
+            backfills: BackfillTracker::new(),
+            confirmations: BackfillConfirmations::new(),
+            queues: ForwardQueues::new(),
+            stats: BotStats::new(),
+            debug_watch,
+            command_prefixes: Vec::new(),
+            command_prefix: "!".to_string(),
+            guild_command_prefixes: HashMap::new(),
+            auto_backfill: true,
+            permissions: CommandPermissions::default(),
+            github_issues: GitHubIssues::default(),
+            alerts: AdminAlerts::default(),
+            setups: SetupSessions::new(),
+            audit_log: AuditLog::default(),
+        })
+    }
+
+    /// メッセージの送信・取得に使用するAPIを差し替える（Discordに接続せずに動作を確かめる場合など）
+    pub fn with_api(mut self, api: Arc<dyn DiscordApi>) -> Self {
+        self.api = api;
+        self
+    }
+
+    /// 転送しないコマンドのプレフィックスを設定する
+    pub fn with_command_prefixes(mut self, command_prefixes: Vec<String>) -> Self {
+        self.command_prefixes = command_prefixes;
+        self
+    }
+
+    /// ボットのコマンドのプレフィックス（デフォルトとサーバーごと）を設定する
+    pub fn with_command_prefix(
+        mut self,
+        command_prefix: String,
+        guild_command_prefixes: HashMap<Id<GuildMarker>, String>,
+    ) -> Self {
+        self.command_prefix = command_prefix;
+        self.guild_command_prefixes = guild_command_prefixes;
+        self
+    }
+
+    /// スレッド名のパターンによるマッピングを設定する
+    pub fn with_name_mappings(mut self, name_mappings: Vec<NameMapping>) -> Self {
+        self.name_mappings = RwLock::new(name_mappings);
+        self
+    }
+
+    /// `all` を指定したマッピングの過去メッセージを自動で転送するかどうかを設定する
+    pub fn with_auto_backfill(mut self, auto_backfill: bool) -> Self {
+        self.auto_backfill = auto_backfill;
+        self
+    }
+
+    /// 添付ファイルの保存先を設定する
+    pub fn with_attachment_archive(mut self, attachments: AttachmentArchive) -> Self {
+        self.attachments = attachments;
+        self
+    }
+
+    /// メッセージの履歴の保存先を設定する
+    pub fn with_history(mut self, history: MessageHistory) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// 転送したメッセージのイベントの送信先を設定する
+    pub fn with_event_publisher(mut self, events: EventPublisher) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// メッセージから作成した GitHub の Issue の記録先を設定する
+    pub fn with_github_issues(mut self, github_issues: GitHubIssues) -> Self {
+        self.github_issues = github_issues;
+        self
+    }
+
+    /// 権限の必要なコマンドを実行できるかの判定を設定する
+    pub fn with_permissions(mut self, permissions: CommandPermissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// 障害を通知する管理用チャンネルを設定する
+    pub fn with_alerts(mut self, alerts: AdminAlerts) -> Self {
+        self.alerts = alerts;
+        self
+    }
+
+    /// 管理用のコマンドの実行記録を投稿するチャンネルを設定する
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
+    /// コマンドの呼び出し（ボット自身や他のボットのコマンド）かどうか
+    pub fn is_command(&self, content: &str, thread_info: &ThreadInfo) -> bool {
+        let content = content.trim_start();
+        self.command_prefixes
+            .iter()
+            .chain(std::iter::once(&self.command_prefix))
+            .chain(self.guild_command_prefixes.values())
+            .chain(thread_info.command_prefix.iter())
+            .any(|prefix| content.starts_with(prefix.as_str()))
+    }
+
+    /// ボットのコマンドのデフォルトのプレフィックス
+    pub fn default_command_prefix(&self) -> &str {
+        &self.command_prefix
+    }
+
+    /// チャンネルで使用するコマンドのプレフィックス
+    ///
+    /// マッピングの `command_prefix`、サーバーごとの設定、デフォルトの順に優先します。
+    pub async fn command_prefix(&self, channel_id: Id<ChannelMarker>, guild_id: Option<Id<GuildMarker>>) -> String {
+        if let Some(prefix) = self
+            .resolve_thread_info(channel_id)
+            .await
+            .and_then(|info| info.command_prefix)
+        {
+            return prefix;
+        }
+
+        let guild_id = match guild_id {
+            Some(guild_id) => Some(guild_id),
+            None => self.channel_guild(channel_id).await,
+        };
+        guild_id
+            .and_then(|guild_id| self.guild_command_prefixes.get(&guild_id).cloned())
+            .unwrap_or_else(|| self.command_prefix.clone())
+    }
+
+    /// ボット自身のユーザーIDとアプリケーションIDを記録する
+    pub fn set_identity(&self, user_id: Id<UserMarker>, application_id: Id<ApplicationMarker>) {
+        // 再接続時にも Ready を受信するが、IDは変わらないので最初の値を使う
+        let _ = self.identity.set((user_id, application_id));
+    }
+
+    /// ボット自身のユーザーID（Ready受信前は None）
+    pub fn bot_user_id(&self) -> Option<Id<UserMarker>> {
+        self.identity.get().map(|(user_id, _)| *user_id)
+    }
+
+    /// このボット自身が送信したメッセージかどうか
+    ///
+    /// ボット本人のメッセージに加え、ボットが作成したWebhookや転送に使用しているWebhookのメッセージも含みます。
+    /// 転送先が別のマッピングの転送元になっている場合に、転送が無限に繰り返されるのを防ぎます。
+    pub async fn is_own_message(&self, message: &Message) -> bool {
+        if let Some((user_id, application_id)) = self.identity.get() {
+            if message.author.id == *user_id {
+                return true;
+            }
+            if message.webhook_id.is_some() && message.application_id == Some(*application_id) {
+                return true;
+            }
+        }
+
+        let Some(webhook_id) = message.webhook_id else {
+            return false;
+        };
+
+        if self
+            .channel_webhooks
+            .read()
+            .await
+            .values()
+            .any(|url| webhook_id_from_url(url) == Some(webhook_id))
+        {
+            return true;
+        }
+
+        let uses_webhook = |mappings: &HashMap<Id<ChannelMarker>, ThreadInfo>| {
+            mappings
+                .values()
+                .filter_map(|info| info.webhook_url.as_deref())
+                .any(|url| webhook_id_from_url(url) == Some(webhook_id))
+        };
+        uses_webhook(&self.thread_mappings.load()) || uses_webhook(&self.parent_mappings.load())
+    }
+
+    /// 指定したスレッドのスレッド情報を取得する
+    pub async fn get_thread_info(&self, thread_id: Id<ChannelMarker>) -> Option<ThreadInfo> {
+        self.thread_mappings.get(thread_id)
+    }
+
+    /// スレッド情報を取得する（個別のマッピングが無い場合は親チャンネルのマッピングを使用）
+    ///
+    /// テキストチャンネルでは、属するカテゴリーのマッピングを親チャンネルのマッピングとして使用します。
+    /// 親チャンネルが分からないスレッド・チャンネルは、HTTP APIで取得してキャッシュします。
+    /// 転送先（振り分け先を含む）のチャンネル自体は、転送したメッセージを再び転送しないよう対象外にします。
+    pub async fn resolve_thread_info(&self, channel_id: Id<ChannelMarker>) -> Option<ThreadInfo> {
+        if let Some(info) = self.get_thread_info(channel_id).await {
+            return (!info.forwards_to(channel_id)).then_some(info);
+        }
+
+        // 親チャンネルのマッピングが無ければ問い合わせる必要はない
+        if self.parent_mappings.load().is_empty() {
+            return None;
+        }
+
+        let parent_id = self.cached_channel(channel_id).await?.parent_id?;
+        self.parent_mappings.get(parent_id).filter(|info| !info.forwards_to(channel_id))
+    }
+
+    /// チャンネルの情報をキャッシュから取得する（無ければHTTP APIで取得してキャッシュする）
+    async fn cached_channel(&self, channel_id: Id<ChannelMarker>) -> Option<CachedChannel> {
+        if let Some(channel) = self.channels.read().await.get(&channel_id) {
+            return Some(channel.clone());
+        }
+
+        let channel = match self.api.channel(channel_id).await {
+            Ok(channel) => channel,
+            Err(e) => {
+                println!("チャンネル {} の情報を取得できませんでした: {}", channel_id, e);
+                return None;
+            }
+        };
+
+        let cached = CachedChannel::from_channel(&channel);
+        self.channels.write().await.insert(channel_id, cached.clone());
+        Some(cached)
+    }
+
+    /// キャッシュしているチャンネル名を取得する（キャッシュに無い場合も問い合わせない）
+    ///
+    /// スレッドの更新イベントで、変更前の名前と比べるために使用します。
+    pub async fn cached_channel_name(&self, channel_id: Id<ChannelMarker>) -> Option<String> {
+        self.channels.read().await.get(&channel_id)?.name.clone()
+    }
+
+    /// キャッシュしているスレッドのアーカイブ・ロックの状態を取得する（キャッシュに無い場合も問い合わせない）
+    pub async fn cached_thread_state(&self, channel_id: Id<ChannelMarker>) -> Option<ThreadState> {
+        self.channels.read().await.get(&channel_id)?.thread_state
+    }
+
+    /// ゲートウェイイベントで受け取ったチャンネル（スレッドを含む）の情報をキャッシュする
+    pub async fn cache_channel(&self, channel: &Channel) {
+        self.channels.write().await.insert(channel.id, CachedChannel::from_channel(channel));
+    }
+
+    /// 参加したサーバーの全チャンネル・スレッドの情報をキャッシュする
+    ///
+    /// メッセージを受信するたびにHTTP APIでチャンネルを問い合わせずに済むよう、接続時にまとめて登録します。
+    /// サーバーの作成イベントに含まれるチャンネルにはサーバーIDが入っていないため、ここで補います。
+    pub async fn cache_guild_channels(&self, guild_id: Id<GuildMarker>, channels: &[Channel]) {
+        let mut cache = self.channels.write().await;
+        for channel in channels {
+            let mut cached = CachedChannel::from_channel(channel);
+            cached.guild_id.get_or_insert(guild_id);
+            cache.insert(channel.id, cached);
+        }
+    }
+
+    /// チャンネル名（スレッド名）を取得する
+    pub async fn channel_name(&self, channel_id: Id<ChannelMarker>) -> Option<String> {
+        self.cached_channel(channel_id).await?.name
+    }
+
+    /// 転送元のチャンネルの種類を調べる
+    ///
+    /// フォーラムの投稿かどうかは親チャンネルの種類で判断します。チャンネルの情報を取得できない場合はスレッドとして扱います。
+    pub async fn source_kind(&self, channel_id: Id<ChannelMarker>) -> SourceKind {
+        let Some(channel) = self.cached_channel(channel_id).await else {
+            return SourceKind::Thread;
+        };
+        if !channel.kind.is_thread() {
+            return SourceKind::Channel;
+        }
+        let Some(parent_id) = channel.parent_id else {
+            return SourceKind::Thread;
+        };
+        match self.cached_channel(parent_id).await {
+            Some(parent) if parent.kind == ChannelType::GuildForum => SourceKind::ForumPost,
+            _ => SourceKind::Thread,
+        }
+    }
+
+    /// チャンネルが属するサーバーのIDを取得する
+    pub async fn channel_guild(&self, channel_id: Id<ChannelMarker>) -> Option<Id<GuildMarker>> {
+        self.cached_channel(channel_id).await?.guild_id
+    }
+
+    /// メッセージが投稿されたサーバーのIDを取得する
+    ///
+    /// HTTP APIで取得した過去のメッセージにはサーバーIDが含まれないため、チャンネルの情報から調べます。
+    pub async fn message_guild(&self, message: &Message) -> Option<Id<GuildMarker>> {
+        match message.guild_id {
+            Some(guild_id) => Some(guild_id),
+            None => self.cached_channel(message.channel_id).await?.guild_id,
+        }
+    }
+
+    /// メッセージへのリンクを作成する（サーバーが分からない場合は None）
+    pub async fn message_url(&self, message: &Message) -> Option<String> {
+        let guild_id = self.message_guild(message).await?;
+        Some(format!("https://discord.com/channels/{}/{}/{}", guild_id, message.channel_id, message.id))
+    }
+
+    /// スレッド名のパターンをもとに、スレッドのマッピングを登録する
+    ///
+    /// スレッドに個別のマッピングが既にある場合、親チャンネルのマッピングが適用される場合、
+    /// 実行時に削除したマッピングの場合、どのパターンにも一致しない場合は何もせず None を返します。
+    /// 登録したマッピングはデータベースに保存され、パターンを削除した後も維持されます。
+    pub async fn auto_map_thread_by_name(
+        &self,
+        thread_id: Id<ChannelMarker>,
+        parent_id: Option<Id<ChannelMarker>>,
+        name: &str,
+    ) -> Result<Option<ThreadInfo>, Box<dyn std::error::Error + Send + Sync>> {
+        if self.thread_mappings.load().contains_key(&thread_id)
+            || parent_id.is_some_and(|parent_id| self.parent_mappings.load().contains_key(&parent_id))
+            || self.removed_thread_ids.read().await.contains(&thread_id)
+        {
+            return Ok(None);
+        }
+        let Some(info) = self
+            .name_mappings
+            .read()
+            .await
+            .iter()
+            .find(|mapping| mapping.pattern.is_match(name))
+            .map(|mapping| mapping.info.clone())
+        else {
+            return Ok(None);
+        };
+
+        self.add_thread_mapping(thread_id, info.clone()).await?;
+        Ok(Some(info))
+    }
+
+    /// 別のチャンネルに投稿するメッセージで、チャンネルを参照するリンクを作成する
+    ///
+    /// 同じサーバーのチャンネル（またはサーバーが分からない場合）は `<#ID>` を返します。
+    /// 別のサーバーのチャンネルは `<#ID>` では表示されないため、チャンネル名付きのジャンプリンクを返します。
+    pub async fn channel_link(&self, channel_id: Id<ChannelMarker>, from_channel_id: Id<ChannelMarker>) -> String {
+        let (Some(guild_id), Some(from_guild_id)) =
+            (self.channel_guild(channel_id).await, self.channel_guild(from_channel_id).await)
+        else {
+            return format!("<#{}>", channel_id);
+        };
+        if guild_id == from_guild_id {
+            return format!("<#{}>", channel_id);
+        }
+
+        let url = format!("https://discord.com/channels/{}/{}", guild_id, channel_id);
+        match self.channel_name(channel_id).await {
+            Some(name) => format!("[#{}]({})", escape_markdown(&name), url),
+            None => url,
+        }
+    }
+
+    /// 親チャンネルのマッピングをもとに、新しく作成されたスレッドのマッピングを登録する
+    ///
+    /// 親チャンネルにマッピングが無い場合や、スレッドに個別のマッピングが既にある場合は何もせず None を返します。
+    /// 登録したマッピングはデータベースに保存され、親チャンネルのマッピングを削除した後も維持されます。
+    pub async fn auto_map_thread(
+        &self,
+        thread_id: Id<ChannelMarker>,
+        parent_id: Id<ChannelMarker>,
+    ) -> Result<Option<ThreadInfo>, Box<dyn std::error::Error + Send + Sync>> {
+        if self.thread_mappings.load().contains_key(&thread_id) {
+            return Ok(None);
+        }
+        let Some(info) = self.parent_mappings.get(parent_id) else {
+            return Ok(None);
+        };
+
+        self.add_thread_mapping(thread_id, info.clone()).await?;
+        Ok(Some(info))
+    }
+
+    /// 転送先チャンネルのWebhook URLを取得する
+    ///
+    /// ボットが以前作成したWebhookがあれば再利用し、無ければ新しく作成します（Webhookの管理権限が必要です）。
+    pub async fn channel_webhook_url(
+        &self,
+        channel_id: Id<ChannelMarker>,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(url) = self.channel_webhooks.read().await.get(&channel_id) {
+            return Ok(url.clone());
+        }
+
+        // 同時に複数のWebhookを作成しないよう、書き込みロックを取ってから確認し直す
+        let mut channel_webhooks = self.channel_webhooks.write().await;
+        if let Some(url) = channel_webhooks.get(&channel_id) {
+            return Ok(url.clone());
+        }
+
+        let webhooks = self.http.channel_webhooks(channel_id).await?.models().await?;
+        let existing = webhooks
+            .into_iter()
+            .find(|webhook| webhook.name.as_deref() == Some(MANAGED_WEBHOOK_NAME) && webhook.token.is_some());
+        let webhook = match existing {
+            Some(webhook) => webhook,
+            None => {
+                println!("チャンネル {} に転送用のWebhookを作成します", channel_id);
+                self.http.create_webhook(channel_id, MANAGED_WEBHOOK_NAME)?.await?.model().await?
+            }
+        };
+
+        let token = webhook
+            .token
+            .ok_or_else(|| format!("チャンネル {} のWebhookのトークンを取得できませんでした", channel_id))?;
+        let url = format!("https://discord.com/api/webhooks/{}/{}", webhook.id, token);
+        channel_webhooks.insert(channel_id, url.clone());
+        Ok(url)
+    }
+
+    /// キャッシュしたWebhook URLを破棄する（次回の送信時に取得し直す）
+    pub async fn forget_channel_webhook(&self, channel_id: Id<ChannelMarker>) {
+        self.channel_webhooks.write().await.remove(&channel_id);
+    }
+
+    /// スターボードで転送するメッセージとして登録する（既に登録済みの場合は false）
+    ///
+    /// 転送の記録は転送が終わってから保存されるため、それまでに付いたリアクションで二重に転送しないよう先に登録します。
+    /// 転送が終わったら [`BotState::release_starred_message`] で登録を外します（以降は転送の記録で判断する）。
+    pub async fn claim_starred_message(&self, message_id: Id<MessageMarker>) -> bool {
+        self.starred_messages.write().await.insert(message_id)
+    }
+
+    /// スターボードで転送するメッセージの登録を外す（転送に失敗した場合は、次のリアクションで転送し直せる）
+    pub async fn release_starred_message(&self, message_id: Id<MessageMarker>) {
+        self.starred_messages.write().await.remove(&message_id);
+    }
+
+    /// 削除されたチャンネル（スレッドを含む）をキャッシュから取り除く
+    pub async fn forget_channel(&self, channel_id: Id<ChannelMarker>) {
+        self.channels.write().await.remove(&channel_id);
+    }
+
+    /// 親チャンネルのマッピングを新しい内容で置き換える
+    pub async fn replace_parent_mappings(&self, new_mappings: HashMap<Id<ChannelMarker>, ThreadInfo>) {
+        self.parent_mappings.write().await.replace(new_mappings);
+    }
+
+    /// スレッド名のパターンによるマッピングを新しい設定で置き換える（登録済みのスレッドのマッピングはそのまま）
+    pub async fn replace_name_mappings(&self, name_mappings: Vec<NameMapping>) {
+        *self.name_mappings.write().await = name_mappings;
+    }
+
+    /// スレッドマッピングを追加（または上書き）し、データベースに保存する
+    pub async fn add_thread_mapping(
+        &self,
+        thread_id: Id<ChannelMarker>,
+        info: ThreadInfo,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut thread_mappings = self.thread_mappings.write().await;
+        self.store.save(thread_id, &info)?;
+
+        // 以降は実行時に追加されたマッピングとして扱う
+        self.configured_thread_ids.write().await.remove(&thread_id);
+        self.removed_thread_ids.write().await.remove(&thread_id);
+        thread_mappings.insert(thread_id, info);
+        Ok(())
+    }
+
+    /// 既存のスレッドマッピングを変更し、データベースに保存する
+    ///
+    /// マッピングが存在しない場合は何もせず false を返します。
+    pub async fn update_thread_mapping(
+        &self,
+        thread_id: Id<ChannelMarker>,
+        update: impl FnOnce(&mut ThreadInfo),
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let mut thread_mappings = self.thread_mappings.write().await;
+        let Some(info) = thread_mappings.get(thread_id) else {
+            return Ok(false);
+        };
+
+        // 保存に成功した場合のみメモリ上の内容を更新する
+        let mut updated = info.clone();
+        update(&mut updated);
+        self.store.save(thread_id, &updated)?;
+
+        self.configured_thread_ids.write().await.remove(&thread_id);
+        thread_mappings.insert(thread_id, updated);
+        Ok(true)
+    }
+
+    /// スレッドの転送を一時停止・再開し、データベースに保存する
+    ///
+    /// 親チャンネルのマッピングで転送しているスレッドは、そのスレッド個別のマッピングとして登録します。
+    /// マッピングが無い場合は None を返します。
+    pub async fn set_mapping_active(
+        &self,
+        thread_id: Id<ChannelMarker>,
+        active: bool,
+    ) -> Result<Option<ThreadInfo>, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(mut info) = self.resolve_thread_info(thread_id).await else {
+            return Ok(None);
+        };
+
+        // 手動で一時停止・再開した場合は、アーカイブの解除で自動的に再開しない
+        info.active = active;
+        info.paused_by_archive = false;
+        self.add_thread_mapping(thread_id, info.clone()).await?;
+        Ok(Some(info))
+    }
+
+    /// スレッドマッピングを削除し、削除済みとしてデータベースに記録する
+    ///
+    /// 環境変数・設定ファイル由来のマッピングも、再起動や設定の再読み込みで復活しません。
+    /// 削除した場合は true を返します。
+    pub async fn remove_thread_mapping(
+        &self,
+        thread_id: Id<ChannelMarker>,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let mut thread_mappings = self.thread_mappings.write().await;
+        self.store.delete(thread_id)?;
+
+        self.configured_thread_ids.write().await.remove(&thread_id);
+        self.removed_thread_ids.write().await.insert(thread_id);
+        Ok(thread_mappings.remove(thread_id))
+    }
+
+    /// 途中で中断した過去メッセージの転送で、最後に処理したメッセージIDを取得する
+    pub fn backfill_checkpoint(
+        &self,
+        thread_id: Id<ChannelMarker>,
+    ) -> Result<Option<Id<MessageMarker>>, Box<dyn std::error::Error + Send + Sync>> {
+        self.store.load_checkpoint(thread_id)
+    }
+
+    /// 過去メッセージの転送で、最後に処理したメッセージIDを記録する
+    pub fn save_backfill_checkpoint(
+        &self,
+        thread_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.store.save_checkpoint(thread_id, message_id)
+    }
+
+    /// 過去メッセージの転送が完了したため、記録を削除する
+    pub fn clear_backfill_checkpoint(&self, thread_id: Id<ChannelMarker>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.store.clear_checkpoint(thread_id)
+    }
+
+    /// 転送待ちのメッセージをデータベースに保存する（転送が終わる前に終了した場合に、再起動後に転送し直すため）
+    pub fn save_pending_forward(
+        &self,
+        info: &ThreadInfo,
+        message: &Message,
+    ) -> Result<i64, Box<dyn std::error::Error + Send + Sync>> {
+        self.store.save_pending_forward(info, message)
+    }
+
+    /// 転送が終わったメッセージをデータベースの転送待ちから取り除く
+    pub fn clear_pending_forward(&self, id: i64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.store.delete_pending_forward(id)
+    }
+
+    /// 前回の終了時に転送できなかったメッセージを読み込む
+    pub fn load_pending_forwards(&self) -> Result<Vec<PendingForward>, Box<dyn std::error::Error + Send + Sync>> {
+        self.store.load_pending_forwards()
+    }
+
+    /// 再試行しても転送できなかったメッセージをデータベースに保存する（`!retry-failed` で再送するため）
+    pub fn save_failed_forward(
+        &self,
+        info: &ThreadInfo,
+        message: &Message,
+        error: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.store.save_failed_forward(info, message, error)
+    }
+
+    /// 転送に失敗したメッセージを取り出す（`thread_id` を省略した場合は全てのスレッド）
+    pub fn take_failed_forwards(
+        &self,
+        thread_id: Option<Id<ChannelMarker>>,
+    ) -> Result<Vec<FailedForward>, Box<dyn std::error::Error + Send + Sync>> {
+        self.store.take_failed_forwards(thread_id)
+    }
+
+    /// 転送に失敗したメッセージの件数
+    pub fn failed_forward_count(&self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        self.store.count_failed_forwards()
+    }
+
+    /// 転送したメッセージをマッピングごとの集計に加算する（集計できなくても転送は成功しているため、警告のみ出力する）
+    pub fn record_mapping_forwards(&self, thread_id: Id<ChannelMarker>, forwarded: u64, bytes: u64, attachments: u64) {
+        if let Err(e) = self.store.record_mapping_forwards(thread_id, forwarded, bytes, attachments) {
+            println!("警告: スレッド {} の転送の集計を記録できませんでした: {}", thread_id, e);
+        }
+    }
+
+    /// 転送に失敗したメッセージをマッピングごとの集計に加算する
+    pub fn record_mapping_failure(&self, thread_id: Id<ChannelMarker>) {
+        if let Err(e) = self.store.record_mapping_failure(thread_id) {
+            println!("警告: スレッド {} の転送の失敗を集計に記録できませんでした: {}", thread_id, e);
+        }
+    }
+
+    /// マッピングごとの転送の集計（転送したメッセージの多い順）
+    pub fn mapping_stats(&self) -> Result<Vec<MappingStats>, Box<dyn std::error::Error + Send + Sync>> {
+        self.store.load_mapping_stats()
+    }
+
+    /// 管理用のコマンドの実行をデータベースと監査ログチャンネルに記録する
+    pub async fn record_command(&self, entry: AuditEntry) {
+        println!(
+            "コマンドの実行: {} / チャンネル {} / {} ({})",
+            entry.actor,
+            entry.channel_id,
+            entry.command,
+            entry.outcome.describe()
+        );
+        if let Err(e) = self.store.record_audit(&entry) {
+            println!("コマンドの実行記録を保存できませんでした: {}", e);
+        }
+        self.audit_log.post(&self.http, &entry).await;
+    }
+
+    /// 設定由来のマッピングを新しい内容で置き換える
+    ///
+    /// コマンドで追加されたマッピングは保持したまま、設定由来のマッピングだけを一度に差し替えます。
+    /// 差し替えは一度に行うため、途中の状態が他のタスクから見えることはありません。
+    pub async fn replace_configured_mappings(&self, new_mappings: HashMap<Id<ChannelMarker>, ThreadInfo>) {
+        let mut thread_mappings = self.thread_mappings.write().await;
+        let mut configured_thread_ids = self.configured_thread_ids.write().await;
+
+        // 前回の設定に含まれていたマッピングを削除してから新しい設定を反映
+        let mut merged: HashMap<_, _> = thread_mappings
+            .load()
+            .iter()
+            .map(|(thread_id, info)| (*thread_id, info.clone()))
+            .filter(|(thread_id, _)| !configured_thread_ids.contains(thread_id))
+            .collect();
+
+        // 実行時に追加されたマッピングは設定より優先し、実行時に削除されたマッピングは読み込まない
+        let removed_thread_ids = self.removed_thread_ids.read().await;
+        let new_mappings: HashMap<_, _> = new_mappings
+            .into_iter()
+            .filter(|(thread_id, _)| !merged.contains_key(thread_id) && !removed_thread_ids.contains(thread_id))
+            .collect();
+
+        *configured_thread_ids = new_mappings.keys().copied().collect();
+        merged.extend(new_mappings);
+
+        thread_mappings.replace(merged);
+    }
+}
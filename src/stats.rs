@@ -0,0 +1,361 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use twilight_model::id::{marker::{ChannelMarker, MessageMarker}, Id};
+
+use crate::markdown::escape_markdown;
+use crate::state::BotState;
+use crate::storage::MappingStats;
+
+/// 「直近のエラー」として数える期間
+const RECENT_ERROR_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// `!stats` に表示するマッピングの最大の数（Discordのメッセージの長さの制限のため）
+const MAX_STATS_LINES: usize = 20;
+
+/// 記録しておく直近の転送の数（ダッシュボードに表示）
+const MAX_RECENT_FORWARDS: usize = 50;
+
+/// 直近に転送したメッセージ（ダッシュボードに表示）
+#[derive(Debug, Clone, Serialize)]
+pub struct RecentForward {
+    pub thread_id: Id<ChannelMarker>,
+    pub message_id: Id<MessageMarker>,
+    /// 送信者名
+    pub author: String,
+    /// 転送した日時（RFC 3339）
+    pub forwarded_at: String,
+}
+
+/// 起動後の実行状況（`!status` で表示）
+pub struct BotStats {
+    /// 起動した時刻
+    started_at: Instant,
+    /// 起動後に転送したメッセージの数（まとめ投稿に含めたメッセージも含む）
+    forwarded: AtomicU64,
+    /// 起動後に発生したエラーの数
+    errors: AtomicU64,
+    /// 直近のエラーの発生時刻
+    recent_errors: Mutex<VecDeque<Instant>>,
+    /// 直近に転送したメッセージ（新しいものが末尾）
+    recent_forwards: Mutex<VecDeque<RecentForward>>,
+    /// ゲートウェイの平均遅延
+    latency: Mutex<Option<Duration>>,
+    /// 受信して処理が終わっていないイベントの数
+    events_pending: AtomicUsize,
+    /// 処理が追いつかず、受信を待たせたイベントの数
+    events_waited: AtomicU64,
+    /// シャードごとの接続状態（接続して認証が済んでいれば true）
+    shards: Mutex<Vec<bool>>,
+    /// 最後にゲートウェイからイベントを受信した時刻
+    last_event: Mutex<Option<Instant>>,
+    /// 直近のマッピングの再読み込みで発生したエラー（成功した場合は None）
+    reload_error: Mutex<Option<String>>,
+}
+
+impl Default for BotStats {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            forwarded: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            recent_errors: Mutex::new(VecDeque::new()),
+            recent_forwards: Mutex::new(VecDeque::new()),
+            latency: Mutex::new(None),
+            events_pending: AtomicUsize::new(0),
+            events_waited: AtomicU64::new(0),
+            shards: Mutex::new(Vec::new()),
+            last_event: Mutex::new(None),
+            reload_error: Mutex::new(None),
+        }
+    }
+}
+
+impl BotStats {
+    /// 現在時刻を起動時刻として作成する
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 転送したメッセージの数を加算する
+    pub fn record_forwarded(&self, count: u64) {
+        self.forwarded.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// 転送したメッセージを直近の転送として記録する（古いものから破棄する）
+    pub fn record_recent_forward(&self, forward: RecentForward) {
+        let mut recent_forwards = self.recent_forwards.lock().unwrap();
+        recent_forwards.push_back(forward);
+        while recent_forwards.len() > MAX_RECENT_FORWARDS {
+            recent_forwards.pop_front();
+        }
+    }
+
+    /// 直近に転送したメッセージ（新しい順）
+    pub fn recent_forwards(&self) -> Vec<RecentForward> {
+        self.recent_forwards.lock().unwrap().iter().rev().cloned().collect()
+    }
+
+    /// エラーの発生を記録する
+    pub fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+
+        let now = Instant::now();
+        let mut recent_errors = self.recent_errors.lock().unwrap();
+        recent_errors.push_back(now);
+        while recent_errors
+            .front()
+            .is_some_and(|time| now.duration_since(*time) > RECENT_ERROR_WINDOW)
+        {
+            recent_errors.pop_front();
+        }
+    }
+
+    /// ゲートウェイの平均遅延を記録する
+    pub fn set_latency(&self, latency: Option<Duration>) {
+        *self.latency.lock().unwrap() = latency;
+    }
+
+    /// イベントの処理の開始を記録する（処理を待っている間も含む）
+    pub fn start_event(&self) {
+        self.events_pending.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// イベントの処理の終了を記録する
+    pub fn finish_event(&self) {
+        self.events_pending.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// 処理が追いつかず、イベントの受信を待たせたことを記録する
+    pub fn record_event_waited(&self) {
+        self.events_waited.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 接続するシャードの数を設定する（接続が済むまでは未接続として扱う）
+    pub fn set_shard_total(&self, total: usize) {
+        *self.shards.lock().unwrap() = vec![false; total];
+    }
+
+    /// ゲートウェイからのイベントの受信と、受信したシャードの接続状態を記録する
+    pub fn record_gateway_event(&self, shard: usize, connected: bool) {
+        *self.last_event.lock().unwrap() = Some(Instant::now());
+        if let Some(status) = self.shards.lock().unwrap().get_mut(shard) {
+            *status = connected;
+        }
+    }
+
+    /// マッピングの再読み込みの結果を記録する
+    pub fn set_reload_error(&self, error: Option<String>) {
+        *self.reload_error.lock().unwrap() = error;
+    }
+
+    /// 起動してからの時間
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// 接続済みのシャードの数と、全てのシャードの数
+    pub fn connected_shards(&self) -> (usize, usize) {
+        let shards = self.shards.lock().unwrap();
+        (shards.iter().filter(|connected| **connected).count(), shards.len())
+    }
+
+    /// 最後にゲートウェイからイベントを受信してからの時間（まだ受信していない場合は None）
+    pub fn last_event_age(&self) -> Option<Duration> {
+        self.last_event.lock().unwrap().map(|time| time.elapsed())
+    }
+
+    /// 直近のマッピングの再読み込みで発生したエラー
+    pub fn reload_error(&self) -> Option<String> {
+        self.reload_error.lock().unwrap().clone()
+    }
+
+    /// 起動後に転送したメッセージの数
+    pub fn forwarded_count(&self) -> u64 {
+        self.forwarded.load(Ordering::Relaxed)
+    }
+
+    /// 起動後に発生したエラーの数
+    pub fn error_count(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// 直近1時間のエラーの数
+    pub fn recent_error_count(&self) -> usize {
+        let now = Instant::now();
+        self.recent_errors
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|time| now.duration_since(**time) <= RECENT_ERROR_WINDOW)
+            .count()
+    }
+}
+
+/// 稼働時間を「3日 4時間 5分」の形式にする
+pub fn format_uptime(uptime: Duration) -> String {
+    let minutes = uptime.as_secs() / 60;
+    let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+    match (days, hours) {
+        (0, 0) => format!("{}分", minutes),
+        (0, _) => format!("{}時間 {}分", hours, minutes),
+        _ => format!("{}日 {}時間 {}分", days, hours, minutes),
+    }
+}
+
+/// ボットの実行状況をまとめた文章を作成する（`!status` と `/status` で使用）
+pub async fn status_report(state: &BotState) -> String {
+    let stats = &state.stats;
+    let (mapping_count, paused_count) = {
+        let thread_mappings = state.thread_mappings.load();
+        let paused = thread_mappings.values().filter(|info| !info.active).count();
+        (thread_mappings.len(), paused)
+    };
+    let parent_count = state.parent_mappings.load().len();
+    let queue_depths = state.queues.depths().await;
+    let queued = match queue_depths.first() {
+        Some((thread_id, depth)) => format!(
+            "{}件（最も多いのは <#{}> の {}件）",
+            queue_depths.iter().map(|(_, depth)| depth).sum::<usize>(),
+            thread_id,
+            depth
+        ),
+        None => "0件".to_string(),
+    };
+    let failed = match state.failed_forward_count() {
+        Ok(0) => "0件".to_string(),
+        Ok(count) => format!("{}件（`!retry-failed all` で再送できます）", count),
+        Err(e) => format!("取得できませんでした（{}）", e),
+    };
+    let latency = match *stats.latency.lock().unwrap() {
+        Some(latency) => format!("{}ms", latency.as_millis()),
+        None => "計測中".to_string(),
+    };
+
+    [
+        "📊 **Thread2Channel の状態**".to_string(),
+        format!("稼働時間: {}", format_uptime(stats.started_at.elapsed())),
+        format!("ゲートウェイの遅延: {}", latency),
+        format!(
+            "マッピング: スレッド {}件（一時停止中 {}件）、親チャンネル {}件",
+            mapping_count, paused_count, parent_count
+        ),
+        format!("起動後に転送したメッセージ: {}件", stats.forwarded.load(Ordering::Relaxed)),
+        format!("転送待ちのメッセージ: {}", queued),
+        format!("転送に失敗したメッセージ: {}", failed),
+        format!(
+            "処理中のイベント: {}件（処理が追いつかず受信を待たせたイベント: 起動後 {}件）",
+            stats.events_pending.load(Ordering::Relaxed),
+            stats.events_waited.load(Ordering::Relaxed)
+        ),
+        format!(
+            "エラー: 直近1時間 {}件（起動後 {}件）",
+            stats.recent_error_count(),
+            stats.errors.load(Ordering::Relaxed)
+        ),
+    ]
+    .join("\n")
+}
+
+/// バイト数を「1.2 MB」の形式にする
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// マッピングごとの転送の集計をまとめた文章を作成する（`!stats` で使用）
+///
+/// 転送したメッセージの多い順に表示します。集計は再起動後も引き継がれます。
+pub async fn mapping_stats_report(state: &BotState) -> String {
+    let stats = match state.mapping_stats() {
+        Ok(stats) => stats,
+        Err(e) => return format!("転送の集計を取得できませんでした: {}", e),
+    };
+    if stats.is_empty() {
+        return "まだ転送したメッセージはありません。".to_string();
+    }
+
+    let thread_mappings = state.thread_mappings.load();
+    let mut lines = vec!["📈 **マッピングごとの転送の集計**".to_string()];
+    for entry in stats.iter().take(MAX_STATS_LINES) {
+        let mapping = match thread_mappings.get(&entry.thread_id) {
+            Some(info) => match &info.label {
+                Some(label) => format!("<#{}> → <#{}> `[{}]`", entry.thread_id, info.target_channel_id, escape_markdown(label)),
+                None => format!("<#{}> → <#{}>", entry.thread_id, info.target_channel_id),
+            },
+            None => format!("<#{}>", entry.thread_id),
+        };
+        let last_forwarded = entry
+            .last_forwarded_at
+            .as_deref()
+            .and_then(|time| NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").ok())
+            .map(|time| format!("、最終転送 <t:{}:R>", time.and_utc().timestamp()))
+            .unwrap_or_default();
+        lines.push(format!(
+            "{}: 転送 {}件（{}・添付ファイル {}件）、失敗 {}件{}",
+            mapping,
+            entry.forwarded,
+            format_bytes(entry.bytes),
+            entry.attachments,
+            entry.failures,
+            last_forwarded
+        ));
+    }
+    if stats.len() > MAX_STATS_LINES {
+        lines.push(format!("ほか {}件のマッピング", stats.len() - MAX_STATS_LINES));
+    }
+
+    lines.join("\n")
+}
+
+/// Prometheus のラベルの値をエスケープする
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// `/metrics` で返す集計の項目（名前・説明・値の取り出し方）
+type MetricField = (&'static str, &'static str, fn(&MappingStats) -> u64);
+
+/// マッピングごとの転送の集計を Prometheus のテキスト形式にする（`/metrics` で使用）
+pub fn metrics_text(state: &BotState) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let stats = state.mapping_stats()?;
+    let thread_mappings = state.thread_mappings.load();
+
+    let metrics: [MetricField; 4] = [
+        ("thread2channel_forwarded_messages_total", "転送したメッセージの数", |entry| entry.forwarded),
+        ("thread2channel_forwarded_bytes_total", "転送した本文と添付ファイルのバイト数", |entry| entry.bytes),
+        ("thread2channel_forwarded_attachments_total", "転送した添付ファイルの数", |entry| entry.attachments),
+        ("thread2channel_failed_messages_total", "転送に失敗したメッセージの数", |entry| entry.failures),
+    ];
+
+    let mut text = String::new();
+    for (name, help, value) in metrics {
+        text.push_str(&format!("# HELP {} {}\n# TYPE {} counter\n", name, help, name));
+        for entry in &stats {
+            let label = thread_mappings
+                .get(&entry.thread_id)
+                .and_then(|info| info.label.as_deref())
+                .unwrap_or_default();
+            text.push_str(&format!(
+                "{}{{thread_id=\"{}\",label=\"{}\"}} {}\n",
+                name,
+                entry.thread_id,
+                escape_label(label),
+                value(entry)
+            ));
+        }
+    }
+    Ok(text)
+}
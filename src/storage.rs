@@ -0,0 +1,423 @@
+use rusqlite::{params, Connection};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Mutex;
+use twilight_model::channel::Message;
+use twilight_model::id::{
+    marker::{ChannelMarker, MessageMarker},
+    Id,
+};
+
+use crate::audit::AuditEntry;
+use crate::state::ThreadInfo;
+
+/// データベースに保存された転送待ちのメッセージ
+pub struct PendingForward {
+    pub id: i64,
+    pub thread_info: ThreadInfo,
+    pub message: Message,
+}
+
+/// データベースに保存された転送に失敗したメッセージ
+pub struct FailedForward {
+    pub thread_info: ThreadInfo,
+    pub message: Message,
+}
+
+/// データベースに保存されたマッピングごとの転送の集計
+pub struct MappingStats {
+    pub thread_id: Id<ChannelMarker>,
+    /// 転送したメッセージの数
+    pub forwarded: u64,
+    /// 転送した本文と添付ファイルのバイト数
+    pub bytes: u64,
+    /// 転送した添付ファイルの数
+    pub attachments: u64,
+    /// 転送に失敗したメッセージの数
+    pub failures: u64,
+    /// 最後に転送した日時（UTC、まだ転送していない場合は None）
+    pub last_forwarded_at: Option<String>,
+}
+
+/// スレッドマッピングをSQLiteに永続化するストア
+///
+/// コマンドなど実行時に追加・変更されたマッピングを保存し、再起動後も復元できるようにします。
+/// 環境変数・設定ファイル由来のマッピングを実行時に削除した場合は、削除済みとして記録します。
+/// 過去メッセージの転送の進捗、管理用のコマンドの実行記録、転送待ち・転送に失敗したメッセージ、マッピングごとの転送の集計も保存します。
+pub struct MappingStore {
+    conn: Mutex<Connection>,
+}
+
+
+impl MappingStore {
+    /// データベースを開く（存在しない場合は作成する）
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = Connection::open(path)
+            .map_err(|e| format!("データベース {} を開けませんでした: {}", path.display(), e))?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS thread_mappings (
+                thread_id  INTEGER PRIMARY KEY,
+                info       TEXT NOT NULL,
+                updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE IF NOT EXISTS removed_thread_mappings (
+                thread_id  INTEGER PRIMARY KEY,
+                removed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE IF NOT EXISTS backfill_checkpoints (
+                thread_id       INTEGER PRIMARY KEY,
+                last_message_id INTEGER NOT NULL,
+                updated_at      TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE IF NOT EXISTS command_audit_log (
+                id         INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id    INTEGER NOT NULL,
+                actor      TEXT NOT NULL DEFAULT 'user',
+                channel_id INTEGER NOT NULL,
+                command    TEXT NOT NULL,
+                outcome    TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE IF NOT EXISTS pending_forwards (
+                id         INTEGER PRIMARY KEY AUTOINCREMENT,
+                thread_id  INTEGER NOT NULL,
+                info       TEXT NOT NULL,
+                message    TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE IF NOT EXISTS failed_forwards (
+                id         INTEGER PRIMARY KEY AUTOINCREMENT,
+                thread_id  INTEGER NOT NULL,
+                info       TEXT NOT NULL,
+                message    TEXT NOT NULL,
+                error      TEXT NOT NULL,
+                failed_at  TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE IF NOT EXISTS mapping_stats (
+                thread_id         INTEGER PRIMARY KEY,
+                forwarded         INTEGER NOT NULL DEFAULT 0,
+                bytes             INTEGER NOT NULL DEFAULT 0,
+                attachments       INTEGER NOT NULL DEFAULT 0,
+                failures          INTEGER NOT NULL DEFAULT 0,
+                last_forwarded_at TEXT
+            );",
+        )?;
+
+        // 実行した人の種類（ユーザー・管理画面）を記録していない旧形式のテーブルには列を追加する
+        let has_actor: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM pragma_table_info('command_audit_log') WHERE name = 'actor')",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_actor {
+            conn.execute_batch("ALTER TABLE command_audit_log ADD COLUMN actor TEXT NOT NULL DEFAULT 'user';")?;
+        }
+
+        println!("データベースを開きました: {}", path.display());
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// 保存されているマッピングを全て読み込む
+    pub fn load_all(&self) -> Result<HashMap<Id<ChannelMarker>, ThreadInfo>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT thread_id, info FROM thread_mappings")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut thread_mappings = HashMap::new();
+        for row in rows {
+            let (thread_id, info) = row?;
+
+            // 壊れた行があっても他のマッピングは読み込む
+            let thread_id = match Id::new_checked(thread_id as u64) {
+                Some(id) => id,
+                None => {
+                    println!("警告: データベースに無効なスレッドIDが保存されています: {}", thread_id);
+                    continue;
+                }
+            };
+            match serde_json::from_str::<ThreadInfo>(&info) {
+                Ok(info) => {
+                    thread_mappings.insert(thread_id, info);
+                }
+                Err(e) => println!("警告: スレッド {} の保存データを読み込めませんでした: {}", thread_id, e),
+            }
+        }
+
+        Ok(thread_mappings)
+    }
+
+    /// 実行時に削除されたマッピングのスレッドIDを全て読み込む
+    pub fn load_removed(&self) -> Result<HashSet<Id<ChannelMarker>>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT thread_id FROM removed_thread_mappings")?;
+        let rows = stmt.query_map([], |row| row.get::<_, i64>(0))?;
+
+        let mut removed = HashSet::new();
+        for row in rows {
+            if let Some(thread_id) = Id::new_checked(row? as u64) {
+                removed.insert(thread_id);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// マッピングを保存（または上書き）する
+    pub fn save(&self, thread_id: Id<ChannelMarker>, info: &ThreadInfo) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let info = serde_json::to_string(info)?;
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO thread_mappings (thread_id, info, updated_at) VALUES (?1, ?2, CURRENT_TIMESTAMP)
+             ON CONFLICT(thread_id) DO UPDATE SET info = excluded.info, updated_at = excluded.updated_at",
+            params![thread_id.get() as i64, info],
+        )?;
+        tx.execute("DELETE FROM removed_thread_mappings WHERE thread_id = ?1", params![thread_id.get() as i64])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// マッピングを削除し、削除済みとして記録する
+    pub fn delete(&self, thread_id: Id<ChannelMarker>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM thread_mappings WHERE thread_id = ?1", params![thread_id.get() as i64])?;
+        tx.execute(
+            "INSERT OR REPLACE INTO removed_thread_mappings (thread_id, removed_at) VALUES (?1, CURRENT_TIMESTAMP)",
+            params![thread_id.get() as i64],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 途中で中断した過去メッセージの転送で、最後に処理したメッセージIDを読み込む
+    pub fn load_checkpoint(
+        &self,
+        thread_id: Id<ChannelMarker>,
+    ) -> Result<Option<Id<MessageMarker>>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT last_message_id FROM backfill_checkpoints WHERE thread_id = ?1")?;
+        let mut rows = stmt.query(params![thread_id.get() as i64])?;
+
+        match rows.next()? {
+            Some(row) => Ok(Id::new_checked(row.get::<_, i64>(0)? as u64)),
+            None => Ok(None),
+        }
+    }
+
+    /// 過去メッセージの転送で、最後に処理したメッセージIDを記録する
+    pub fn save_checkpoint(
+        &self,
+        thread_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO backfill_checkpoints (thread_id, last_message_id, updated_at) VALUES (?1, ?2, CURRENT_TIMESTAMP)
+             ON CONFLICT(thread_id) DO UPDATE SET last_message_id = excluded.last_message_id, updated_at = excluded.updated_at",
+            params![thread_id.get() as i64, message_id.get() as i64],
+        )?;
+        Ok(())
+    }
+
+    /// 過去メッセージの転送が完了したため、記録を削除する
+    pub fn clear_checkpoint(&self, thread_id: Id<ChannelMarker>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM backfill_checkpoints WHERE thread_id = ?1", params![thread_id.get() as i64])?;
+        Ok(())
+    }
+
+    /// 管理用のコマンドの実行記録を追加する
+    pub fn record_audit(&self, entry: &AuditEntry) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO command_audit_log (user_id, actor, channel_id, command, outcome) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                entry.actor.user_id() as i64,
+                entry.actor.kind(),
+                entry.channel_id.get() as i64,
+                entry.command,
+                entry.outcome.describe()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 転送待ちのメッセージを保存する（転送が終わるまで残し、再起動後に転送し直す）
+    pub fn save_pending_forward(
+        &self,
+        info: &ThreadInfo,
+        message: &Message,
+    ) -> Result<i64, Box<dyn std::error::Error + Send + Sync>> {
+        let info = serde_json::to_string(info)?;
+        let message_json = serde_json::to_string(message)?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO pending_forwards (thread_id, info, message) VALUES (?1, ?2, ?3)",
+            params![message.channel_id.get() as i64, info, message_json],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// 転送が終わったメッセージを転送待ちから取り除く
+    pub fn delete_pending_forward(&self, id: i64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM pending_forwards WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// 転送待ちのメッセージを保存した順に全て読み込む
+    pub fn load_pending_forwards(&self) -> Result<Vec<PendingForward>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, info, message FROM pending_forwards ORDER BY id")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        })?;
+
+        let mut pending = Vec::new();
+        let mut broken = Vec::new();
+        for row in rows {
+            let (id, info, message) = row?;
+            match (serde_json::from_str::<ThreadInfo>(&info), serde_json::from_str::<Message>(&message)) {
+                (Ok(thread_info), Ok(message)) => pending.push(PendingForward { id, thread_info, message }),
+                // 読み込めない行は転送できないため取り除く
+                (Err(e), _) | (_, Err(e)) => {
+                    println!("警告: 転送待ちのメッセージ (ID: {}) を読み込めなかったため破棄します: {}", id, e);
+                    broken.push(id);
+                }
+            }
+        }
+        drop(stmt);
+
+        for id in broken {
+            conn.execute("DELETE FROM pending_forwards WHERE id = ?1", params![id])?;
+        }
+        Ok(pending)
+    }
+
+    /// 再試行しても転送できなかったメッセージを、原因のエラーとあわせて保存する
+    pub fn save_failed_forward(
+        &self,
+        info: &ThreadInfo,
+        message: &Message,
+        error: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let info = serde_json::to_string(info)?;
+        let message_json = serde_json::to_string(message)?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO failed_forwards (thread_id, info, message, error) VALUES (?1, ?2, ?3, ?4)",
+            params![message.channel_id.get() as i64, info, message_json, error],
+        )?;
+        Ok(())
+    }
+
+    /// 転送に失敗したメッセージを保存した順に取り出す（取り出したメッセージは削除する）
+    ///
+    /// `thread_id` を指定した場合は、そのスレッドのメッセージだけを取り出します。
+    pub fn take_failed_forwards(
+        &self,
+        thread_id: Option<Id<ChannelMarker>>,
+    ) -> Result<Vec<FailedForward>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let rows: Vec<(i64, String, String)> = {
+            let mut stmt = tx.prepare(
+                "SELECT id, info, message FROM failed_forwards WHERE ?1 IS NULL OR thread_id = ?1 ORDER BY id",
+            )?;
+            let rows = stmt.query_map(params![thread_id.map(|id| id.get() as i64)], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+            rows.collect::<Result<_, _>>()?
+        };
+
+        let mut failed = Vec::new();
+        for (id, info, message) in rows {
+            match (serde_json::from_str::<ThreadInfo>(&info), serde_json::from_str::<Message>(&message)) {
+                (Ok(thread_info), Ok(message)) => failed.push(FailedForward { thread_info, message }),
+                // 読み込めない行は再送できないため取り除く
+                (Err(e), _) | (_, Err(e)) => {
+                    println!("警告: 転送に失敗したメッセージ (ID: {}) を読み込めなかったため破棄します: {}", id, e);
+                }
+            }
+            tx.execute("DELETE FROM failed_forwards WHERE id = ?1", params![id])?;
+        }
+        tx.commit()?;
+        Ok(failed)
+    }
+
+    /// 転送に失敗したメッセージの件数
+    pub fn count_failed_forwards(&self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM failed_forwards", [], |row| row.get(0))?;
+        Ok(count as u64)
+    }
+
+    /// マッピングの転送の集計に、転送したメッセージを加算する
+    pub fn record_mapping_forwards(
+        &self,
+        thread_id: Id<ChannelMarker>,
+        forwarded: u64,
+        bytes: u64,
+        attachments: u64,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO mapping_stats (thread_id, forwarded, bytes, attachments, last_forwarded_at)
+             VALUES (?1, ?2, ?3, ?4, CURRENT_TIMESTAMP)
+             ON CONFLICT(thread_id) DO UPDATE SET
+                forwarded = forwarded + excluded.forwarded,
+                bytes = bytes + excluded.bytes,
+                attachments = attachments + excluded.attachments,
+                last_forwarded_at = excluded.last_forwarded_at",
+            params![thread_id.get() as i64, forwarded as i64, bytes as i64, attachments as i64],
+        )?;
+        Ok(())
+    }
+
+    /// マッピングの転送の集計に、転送に失敗したメッセージを加算する
+    pub fn record_mapping_failure(&self, thread_id: Id<ChannelMarker>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO mapping_stats (thread_id, failures) VALUES (?1, 1)
+             ON CONFLICT(thread_id) DO UPDATE SET failures = failures + 1",
+            params![thread_id.get() as i64],
+        )?;
+        Ok(())
+    }
+
+    /// マッピングごとの転送の集計を、転送したメッセージの多い順に全て読み込む
+    pub fn load_mapping_stats(&self) -> Result<Vec<MappingStats>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT thread_id, forwarded, bytes, attachments, failures, last_forwarded_at
+             FROM mapping_stats ORDER BY forwarded DESC, thread_id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, Option<String>>(5)?,
+            ))
+        })?;
+
+        let mut stats = Vec::new();
+        for row in rows {
+            let (thread_id, forwarded, bytes, attachments, failures, last_forwarded_at) = row?;
+            if let Some(thread_id) = Id::new_checked(thread_id as u64) {
+                stats.push(MappingStats {
+                    thread_id,
+                    forwarded: forwarded as u64,
+                    bytes: bytes as u64,
+                    attachments: attachments as u64,
+                    failures: failures as u64,
+                    last_forwarded_at,
+                });
+            }
+        }
+        Ok(stats)
+    }
+}
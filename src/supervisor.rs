@@ -0,0 +1,85 @@
+use std::any::Any;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+
+use crate::alert::AlertKind;
+use crate::state::BotState;
+
+/// 異常終了したタスクを最初に再起動するまでの待機時間（続けて異常終了するたびに2倍にする）
+const INITIAL_RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// 再起動するまでの最大の待機時間
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// この時間以上動き続けた後の異常終了は、続けて異常終了したものとして数えない
+const STABLE_RUNTIME: Duration = Duration::from_secs(10 * 60);
+
+/// 続けてこの回数だけ異常終了したら、管理用チャンネルに通知する
+const ALERT_AFTER_CRASHES: u32 = 2;
+
+/// パニックの内容を文字列にする
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "(内容不明のパニック)".to_string(),
+        },
+    }
+}
+
+/// 長時間動き続けるタスクを起動し、パニックで異常終了した場合は待機時間を延ばしながら再起動する
+///
+/// `task` は起動のたびに呼び出され、新しいタスクの処理を返します。処理が正常に終わった場合は再起動しません。
+/// 続けて異常終了した場合は、発生した場所とパニックの内容を管理用チャンネルに通知します。
+/// 返り値のハンドルは、タスクが正常に終わるまで完了しません。
+pub fn supervise<F, Fut>(state: &Arc<BotState>, name: impl Into<String>, task: F) -> JoinHandle<()>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let state = Arc::clone(state);
+    let name = name.into();
+
+    tokio::spawn(async move {
+        let mut crashes = 0;
+        let mut delay = INITIAL_RESTART_DELAY;
+
+        loop {
+            let started = Instant::now();
+            let panic = match tokio::spawn(task()).await {
+                Ok(()) => return,
+                Err(e) if e.is_panic() => panic_message(e.into_panic()),
+                Err(_) => return,
+            };
+
+            if started.elapsed() >= STABLE_RUNTIME {
+                crashes = 0;
+                delay = INITIAL_RESTART_DELAY;
+            }
+            crashes += 1;
+
+            eprintln!(
+                "タスク「{}」が異常終了しました（{}回目）。{}秒後に再起動します: {}",
+                name,
+                crashes,
+                delay.as_secs(),
+                panic
+            );
+            state.stats.record_error();
+            if crashes >= ALERT_AFTER_CRASHES {
+                state.alerts.report(
+                    &state.http,
+                    AlertKind::TaskCrashed,
+                    &name,
+                    &format!("{}回続けて異常終了しました。{}秒後に再起動します: {}", crashes, delay.as_secs(), panic),
+                ).await;
+            }
+
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RESTART_DELAY);
+        }
+    })
+}
@@ -0,0 +1,207 @@
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::multipart::{Form, Part};
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+use twilight_model::channel::{Attachment, Message};
+
+use crate::embed::webhook_display_name;
+use crate::forwarding::forward_content;
+use crate::links::MessageLink;
+use crate::retry::{self, SendError};
+use crate::sink::MessageSink;
+use crate::state::{BotState, ThreadInfo};
+
+/// Telegram のボットのトークンを指定する環境変数
+pub const BOT_TOKEN_ENV: &str = "TELEGRAM_BOT_TOKEN";
+
+/// ボットが送信できるファイルの最大サイズ（これより大きいファイルはリンクで送る）
+const MAX_UPLOAD_BYTES: u64 = 50 * 1024 * 1024;
+
+/// 画像として送信できるファイルの最大サイズ（これより大きい画像はファイルとして送る）
+const MAX_PHOTO_BYTES: u64 = 10 * 1024 * 1024;
+
+/// コードブロックとインラインコード（中身は変換しない）
+fn code_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"```(?:[\w+-]*\n)?([\s\S]*?)```|`([^`\n]+)`").unwrap())
+}
+
+/// Discord の書式を Telegram の HTML に置き換える規則（上から順に適用する）
+///
+/// 太字は斜体の `*` と区別するため、先に置き換えます。
+fn format_rules() -> &'static [(Regex, &'static str)] {
+    static RULES: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    RULES.get_or_init(|| {
+        [
+            // 埋め込みを抑制したリンク <https://...>（エスケープ済み）
+            (r"&lt;(https?://[^\s&]+)&gt;", "$1"),
+            // マスクされたリンク [テキスト](URL)
+            (r#"\[([^\]\n]+)\]\((https?://[^)\s"]+)\)"#, r#"<a href="$2">$1</a>"#),
+            // 見出し（Telegram には見出しが無いため太字にする）
+            (r"(?m)^#{1,3} +(.+)$", "<b>$1</b>"),
+            (r"\*\*(.+?)\*\*", "<b>$1</b>"),
+            (r"__(.+?)__", "<u>$1</u>"),
+            (r"\*([^*\n]+)\*", "<i>$1</i>"),
+            (r"\b_([^_\n]+)_\b", "<i>$1</i>"),
+            (r"~~(.+?)~~", "<s>$1</s>"),
+            (r"\|\|(.+?)\|\|", "<tg-spoiler>$1</tg-spoiler>"),
+        ]
+        .into_iter()
+        .map(|(pattern, replacement)| (Regex::new(pattern).unwrap(), replacement))
+        .collect()
+    })
+}
+
+/// HTML で特別な意味を持つ文字（`&`、`<`、`>`）をエスケープする
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// コード以外の部分の書式を置き換える
+fn convert_text(text: &str) -> String {
+    format_rules()
+        .iter()
+        .fold(escape_html(text), |text, (pattern, replacement)| pattern.replace_all(&text, *replacement).into_owned())
+}
+
+/// Discord の書式（Markdown）を Telegram の HTML に変換する
+///
+/// 太字・斜体・下線・取り消し線・ネタバレ・リンク・見出し・コードを置き換えます。
+pub fn to_telegram_html(text: &str) -> String {
+    let mut converted = String::with_capacity(text.len());
+    let mut last = 0;
+    for code in code_pattern().captures_iter(text) {
+        let whole = code.get(0).unwrap();
+        converted.push_str(&convert_text(&text[last..whole.start()]));
+        match (code.get(1), code.get(2)) {
+            (Some(block), _) => converted.push_str(&format!("<pre>{}</pre>", escape_html(block.as_str()))),
+            (None, Some(inline)) => converted.push_str(&format!("<code>{}</code>", escape_html(inline.as_str()))),
+            (None, None) => {}
+        }
+        last = whole.end();
+    }
+    converted.push_str(&convert_text(&text[last..]));
+    converted
+}
+
+/// 画像として送信する添付ファイルかどうか
+fn is_photo(attachment: &Attachment) -> bool {
+    attachment.size <= MAX_PHOTO_BYTES
+        && attachment
+            .content_type
+            .as_deref()
+            .is_some_and(|content_type| matches!(content_type, "image/png" | "image/jpeg" | "image/webp"))
+}
+
+/// Telegram のチャットに投稿する転送先
+pub struct TelegramSink {
+    /// チャットのID（`-100` で始まる数値）またはチャンネルのユーザー名（`@channel`）
+    pub chat_id: String,
+}
+
+impl TelegramSink {
+    /// Bot API のメソッドのURL
+    fn method_url(token: &str, method: &str) -> String {
+        format!("https://api.telegram.org/bot{}/{}", token, method)
+    }
+
+    /// Bot API の応答を確認する（429・5xx は再送の対象）
+    ///
+    /// URLにはトークンが含まれるため、エラーにURLを含めません。
+    async fn check_response(method: &str, response: reqwest::Response) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or_default();
+        if status.is_success() && body["ok"].as_bool() == Some(true) {
+            return Ok(());
+        }
+        let description = body["description"].as_str().unwrap_or("unknown error");
+        Err(SendError::status(status.as_u16(), format!("Telegram の {} が失敗しました: {} {}", method, status, description)).into())
+    }
+
+    /// 本文を送信する
+    async fn send_text(&self, client: &reqwest::Client, token: &str, text: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let payload = json!({
+            "chat_id": self.chat_id,
+            "text": text,
+            "parse_mode": "HTML",
+            "disable_web_page_preview": true,
+        });
+        retry::with_backoff("Telegram への送信", || async {
+            let response = client
+                .post(Self::method_url(token, "sendMessage"))
+                .json(&payload)
+                .send()
+                .await
+                .map_err(|e| SendError::network(format!("Telegram への送信に失敗しました: {}", e.without_url())))?;
+            Self::check_response("sendMessage", response).await
+        })
+        .await
+    }
+
+    /// 添付ファイルをダウンロードして Telegram に再アップロードする
+    async fn upload_attachment(
+        &self,
+        client: &reqwest::Client,
+        token: &str,
+        attachment: &Attachment,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let bytes = client.get(&attachment.url).send().await?.error_for_status()?.bytes().await?;
+        let (method, field) = if is_photo(attachment) { ("sendPhoto", "photo") } else { ("sendDocument", "document") };
+
+        retry::with_backoff("Telegram へのファイルの送信", || async {
+            let form = Form::new()
+                .text("chat_id", self.chat_id.clone())
+                .part(field, Part::bytes(bytes.to_vec()).file_name(attachment.filename.clone()));
+            let response = client
+                .post(Self::method_url(token, method))
+                .multipart(form)
+                .send()
+                .await
+                .map_err(|e| SendError::network(format!("Telegram へのファイルの送信に失敗しました: {}", e.without_url())))?;
+            Self::check_response(method, response).await
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl MessageSink for TelegramSink {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    async fn send(
+        &self,
+        state: &BotState,
+        thread_info: &ThreadInfo,
+        message: &Message,
+    ) -> Result<Option<MessageLink>, Box<dyn std::error::Error + Send + Sync>> {
+        let token = std::env::var(BOT_TOKEN_ENV)
+            .map_err(|_| format!("Telegram のチャット {} に投稿するには環境変数 {} を設定してください", self.chat_id, BOT_TOKEN_ENV))?;
+        let client = reqwest::Client::new();
+
+        // 送信者名を先頭に付ける（Telegram ではメッセージごとに送信者を変えられないため）
+        let author = webhook_display_name(&message.author.name, thread_info.label.as_deref());
+        let mut lines = vec![format!("<b>{}</b>", escape_html(&author))];
+        let content = forward_content(state, thread_info, message).await;
+        if !content.is_empty() {
+            lines.push(to_telegram_html(&content));
+        }
+        // 大きすぎて再アップロードできないファイルはリンクで送る
+        for attachment in message.attachments.iter().filter(|attachment| attachment.size > MAX_UPLOAD_BYTES) {
+            lines.push(format!("📎 <a href=\"{}\">{}</a>", escape_html(&attachment.url), escape_html(&attachment.filename)));
+        }
+        if let Some(url) = state.message_url(message).await {
+            lines.push(format!("<a href=\"{}\">元のメッセージ</a>", url));
+        }
+        self.send_text(&client, &token, &lines.join("\n")).await?;
+
+        for attachment in message.attachments.iter().filter(|attachment| attachment.size <= MAX_UPLOAD_BYTES) {
+            if let Err(e) = self.upload_attachment(&client, &token, attachment).await {
+                println!("警告: 添付ファイル {} を Telegram に送信できませんでした: {}", attachment.filename, e);
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,57 @@
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+/// テンプレートのプレースホルダー `{name}` の正規表現
+fn placeholder_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\{(\w+)\}").unwrap())
+}
+
+/// テンプレートで使用できるプレースホルダー
+pub const PLACEHOLDERS: [&str; 7] = ["author", "thread_name", "timestamp", "relative_time", "jump_url", "label", "content"];
+
+/// テンプレートに埋め込む値
+pub struct TemplateValues<'a> {
+    /// 送信者名（エスケープ済み）
+    pub author: &'a str,
+    /// 転送元のスレッド名（エスケープ済み）
+    pub thread_name: &'a str,
+    /// 元の投稿日時（JST）
+    pub timestamp: &'a str,
+    /// 元の投稿日時の相対表示（`<t:UNIX時間:R>`）
+    pub relative_time: &'a str,
+    /// 元のメッセージへのリンク
+    pub jump_url: &'a str,
+    /// マッピングのラベル
+    pub label: &'a str,
+    /// 本文
+    pub content: &'a str,
+}
+
+/// テンプレートに含まれる不明なプレースホルダーを探す（設定の検証用）
+pub fn unknown_placeholder(template: &str) -> Option<String> {
+    placeholder_pattern()
+        .captures_iter(template)
+        .map(|captures| captures[1].to_string())
+        .find(|name| !PLACEHOLDERS.contains(&name.as_str()))
+}
+
+/// テンプレートのプレースホルダーを値に置き換える
+///
+/// 置き換えは1回だけ行うため、本文に `{author}` などが含まれていても展開されません。
+/// `\n` と書いた部分は改行になります。
+pub fn render(template: &str, values: &TemplateValues) -> String {
+    let template = template.replace("\\n", "\n");
+    placeholder_pattern()
+        .replace_all(&template, |captures: &Captures| match &captures[1] {
+            "author" => values.author.to_string(),
+            "thread_name" => values.thread_name.to_string(),
+            "timestamp" => values.timestamp.to_string(),
+            "relative_time" => values.relative_time.to_string(),
+            "jump_url" => values.jump_url.to_string(),
+            "label" => values.label.to_string(),
+            "content" => values.content.to_string(),
+            _ => captures[0].to_string(),
+        })
+        .into_owned()
+}
@@ -0,0 +1,121 @@
+/// 書き出す記録の形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TranscriptFormat {
+    /// Markdown（Discordやエディタでそのまま読める）
+    #[default]
+    Markdown,
+    /// HTML（ブラウザで読める）
+    Html,
+}
+
+impl TranscriptFormat {
+    /// コマンドの引数から形式を読み取る
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "md" | "markdown" => Some(Self::Markdown),
+            "html" => Some(Self::Html),
+            _ => None,
+        }
+    }
+
+    /// ファイルの拡張子
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+        }
+    }
+}
+
+/// 記録に含める1件のメッセージ
+pub struct TranscriptEntry {
+    /// 送信者名
+    pub author: String,
+    /// 投稿日時（表示用）
+    pub timestamp: String,
+    /// 本文
+    pub content: String,
+    /// 添付ファイルの (ファイル名, URL)
+    pub attachments: Vec<(String, String)>,
+}
+
+/// HTMLの特殊文字をエスケープする
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// スレッドの記録を指定した形式で作成する
+pub fn render(format: TranscriptFormat, title: &str, entries: &[TranscriptEntry]) -> String {
+    match format {
+        TranscriptFormat::Markdown => render_markdown(title, entries),
+        TranscriptFormat::Html => render_html(title, entries),
+    }
+}
+
+/// Markdown形式の記録を作成する
+fn render_markdown(title: &str, entries: &[TranscriptEntry]) -> String {
+    let mut output = format!("# {}\n\n{}件のメッセージ\n", title, entries.len());
+
+    for entry in entries {
+        output.push_str(&format!("\n---\n\n**{}** `{}`\n\n", entry.author, entry.timestamp));
+        if !entry.content.is_empty() {
+            output.push_str(&entry.content);
+            output.push('\n');
+        }
+        for (filename, url) in &entry.attachments {
+            output.push_str(&format!("\n📎 [{}]({})\n", filename, url));
+        }
+    }
+
+    output
+}
+
+/// HTML形式の記録を作成する
+fn render_html(title: &str, entries: &[TranscriptEntry]) -> String {
+    let title = escape_html(title);
+    let mut output = format!(
+        "<!DOCTYPE html>\n<html lang=\"ja\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n\
+         body {{ font-family: sans-serif; max-width: 800px; margin: 2em auto; color: #2e3338; }}\n\
+         .message {{ border-top: 1px solid #e3e5e8; padding: 0.75em 0; }}\n\
+         .author {{ font-weight: bold; }}\n\
+         .timestamp {{ color: #747f8d; font-size: 0.85em; margin-left: 0.5em; }}\n\
+         .content {{ white-space: pre-wrap; margin-top: 0.25em; }}\n\
+         </style>\n</head>\n<body>\n<h1>{title}</h1>\n<p>{count}件のメッセージ</p>\n",
+        title = title,
+        count = entries.len()
+    );
+
+    for entry in entries {
+        output.push_str("<div class=\"message\">\n");
+        output.push_str(&format!(
+            "<div><span class=\"author\">{}</span><span class=\"timestamp\">{}</span></div>\n",
+            escape_html(&entry.author),
+            escape_html(&entry.timestamp)
+        ));
+        if !entry.content.is_empty() {
+            output.push_str(&format!("<div class=\"content\">{}</div>\n", escape_html(&entry.content)));
+        }
+        for (filename, url) in &entry.attachments {
+            output.push_str(&format!(
+                "<div>📎 <a href=\"{}\">{}</a></div>\n",
+                escape_html(url),
+                escape_html(filename)
+            ));
+        }
+        output.push_str("</div>\n");
+    }
+
+    output.push_str("</body>\n</html>\n");
+    output
+}
@@ -0,0 +1,212 @@
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+use crate::filter::MessagePattern;
+use crate::redact::{MaskSensitive, SensitiveKind};
+#[cfg(feature = "translation")]
+use crate::translate::{Translate, TranslationProvider};
+
+/// 転送前に本文を変換する処理
+///
+/// 新しい変換を追加する場合は、この trait を実装し、[`TransformSpec`] に設定用の種類を追加します。
+#[async_trait]
+pub trait MessageTransform: Send + Sync {
+    /// 変換の名前（ログ用）
+    fn name(&self) -> &'static str;
+
+    /// 本文を変換する
+    async fn apply(&self, content: String) -> Result<String, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// URLの正規表現
+fn link_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"<?https?://[^\s>]+>?").unwrap())
+}
+
+/// 本文からリンクを取り除く
+pub struct StripLinks;
+
+#[async_trait]
+impl MessageTransform for StripLinks {
+    fn name(&self) -> &'static str {
+        "strip_links"
+    }
+
+    async fn apply(&self, content: String) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(link_pattern().replace_all(&content, "").trim().to_string())
+    }
+}
+
+/// 正規表現に一致した部分を置き換える
+pub struct Redact {
+    pub pattern: MessagePattern,
+    pub replacement: String,
+}
+
+#[async_trait]
+impl MessageTransform for Redact {
+    fn name(&self) -> &'static str {
+        "redact"
+    }
+
+    async fn apply(&self, content: String) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.pattern.replace_all(&content, &self.replacement))
+    }
+}
+
+/// 本文の前後にテキストを付ける
+pub struct Affix {
+    pub prefix: String,
+    pub suffix: String,
+}
+
+#[async_trait]
+impl MessageTransform for Affix {
+    fn name(&self) -> &'static str {
+        "affix"
+    }
+
+    async fn apply(&self, content: String) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(format!("{}{}{}", self.prefix, content, self.suffix))
+    }
+}
+
+/// 伏せ字のデフォルト
+fn default_replacement() -> String {
+    "[伏せ字]".to_string()
+}
+
+/// 伏せ字にする機密情報の種類のデフォルト（全て）
+fn all_sensitive_kinds() -> Vec<SensitiveKind> {
+    SensitiveKind::ALL.to_vec()
+}
+
+/// マッピングに設定する変換の種類
+///
+/// 設定ファイルでは `transforms = [{ type = "redact", pattern = "..." }]` のように指定します。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TransformSpec {
+    /// リンクを取り除く
+    StripLinks,
+    /// 正規表現に一致した部分を伏せ字にする
+    Redact {
+        pattern: MessagePattern,
+        #[serde(default = "default_replacement")]
+        replacement: String,
+    },
+    /// メールアドレス・電話番号・トークンを伏せ字にする（種類を省略した場合は全て）
+    Mask {
+        #[serde(default = "all_sensitive_kinds")]
+        kinds: Vec<SensitiveKind>,
+    },
+    /// 本文の先頭にテキストを付ける
+    Prefix { text: String },
+    /// 本文の末尾にテキストを付ける
+    Suffix { text: String },
+    /// 本文を翻訳する（原文はネタバレとして残す）
+    #[cfg(feature = "translation")]
+    Translate {
+        target_lang: String,
+        #[serde(default)]
+        provider: TranslationProvider,
+    },
+}
+
+impl TransformSpec {
+    /// オプションの値（`strip_links`、`redact=正規表現`、`mask[=種類,...]`、`prefix=テキスト`、`suffix=テキスト`、`translate=言語[,サービス]`）から読み取る
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (kind, argument) = match value.split_once('=') {
+            Some((kind, argument)) => (kind, Some(argument)),
+            None => (value, None),
+        };
+
+        match (kind, argument) {
+            ("strip_links", None) => Ok(Self::StripLinks),
+            ("redact", Some(pattern)) => Ok(Self::Redact {
+                pattern: MessagePattern::new(pattern)?,
+                replacement: default_replacement(),
+            }),
+            ("mask", None) => Ok(Self::Mask { kinds: all_sensitive_kinds() }),
+            ("mask", Some(kinds)) => Ok(Self::Mask {
+                kinds: kinds
+                    .split(',')
+                    .map(|kind| {
+                        SensitiveKind::parse(kind.trim())
+                            .ok_or_else(|| format!("mask には email、phone、token をカンマ区切りで指定してください: {}", kind))
+                    })
+                    .collect::<Result<_, _>>()?,
+            }),
+            ("prefix", Some(text)) => Ok(Self::Prefix { text: text.to_string() }),
+            ("suffix", Some(text)) => Ok(Self::Suffix { text: text.to_string() }),
+            #[cfg(feature = "translation")]
+            ("translate", Some(argument)) => {
+                // translate=言語 または translate=言語,サービス
+                let (target_lang, provider) = match argument.split_once(',') {
+                    Some((target_lang, provider)) => (
+                        target_lang,
+                        TranslationProvider::parse(provider)
+                            .ok_or_else(|| format!("翻訳サービスには deepl または google を指定してください: {}", provider))?,
+                    ),
+                    None => (argument, TranslationProvider::default()),
+                };
+                if target_lang.is_empty() {
+                    return Err("translate には翻訳先の言語を指定してください（例: translate=EN）".to_string());
+                }
+                Ok(Self::Translate {
+                    target_lang: target_lang.to_string(),
+                    provider,
+                })
+            }
+            #[cfg(not(feature = "translation"))]
+            ("translate", _) => Err("翻訳を使用するには translation 機能を有効にしてビルドしてください".to_string()),
+            _ => Err(format!(
+                "transform には strip_links、redact=正規表現、mask[=種類,...]、prefix=テキスト、suffix=テキスト のいずれかを指定してください: {}",
+                value
+            )),
+        }
+    }
+
+    /// 設定から変換処理を作成する
+    pub fn build(&self) -> Box<dyn MessageTransform> {
+        match self {
+            Self::StripLinks => Box::new(StripLinks),
+            Self::Redact { pattern, replacement } => Box::new(Redact {
+                pattern: pattern.clone(),
+                replacement: replacement.clone(),
+            }),
+            Self::Mask { kinds } => Box::new(MaskSensitive { kinds: kinds.clone() }),
+            Self::Prefix { text } => Box::new(Affix {
+                prefix: format!("{} ", text),
+                suffix: String::new(),
+            }),
+            Self::Suffix { text } => Box::new(Affix {
+                prefix: String::new(),
+                suffix: format!(" {}", text),
+            }),
+            #[cfg(feature = "translation")]
+            Self::Translate { target_lang, provider } => Box::new(Translate {
+                target_lang: target_lang.clone(),
+                provider: *provider,
+            }),
+        }
+    }
+}
+
+/// 設定された順に変換を適用する
+///
+/// 失敗した変換はスキップし、変換前の本文のまま次の変換に進みます。
+pub async fn apply_pipeline(specs: &[TransformSpec], content: String) -> String {
+    let mut content = content;
+    for spec in specs {
+        let transform = spec.build();
+        match transform.apply(content.clone()).await {
+            Ok(transformed) => content = transformed,
+            Err(e) => println!("警告: 本文の変換 {} に失敗しました: {}", transform.name(), e),
+        }
+    }
+    content
+}
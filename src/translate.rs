@@ -0,0 +1,136 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::env;
+
+use crate::transform::MessageTransform;
+
+/// 翻訳した本文と原文を合わせた最大文字数（Discordの制限は2000文字、送信者名・タイムスタンプなどの分を残す）
+const MAX_TRANSLATED_CHARS: usize = 1800;
+
+/// 使用する翻訳サービス
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranslationProvider {
+    /// DeepL API（環境変数 DEEPL_API_KEY）
+    #[default]
+    Deepl,
+    /// Google Cloud Translation API（環境変数 GOOGLE_TRANSLATE_API_KEY）
+    Google,
+}
+
+impl TranslationProvider {
+    /// オプションの値から翻訳サービスを読み取る
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "deepl" => Some(Self::Deepl),
+            "google" => Some(Self::Google),
+            _ => None,
+        }
+    }
+}
+
+/// 本文を翻訳し、原文はネタバレ（クリックで表示）として下に残す
+pub struct Translate {
+    pub target_lang: String,
+    pub provider: TranslationProvider,
+}
+
+impl Translate {
+    /// DeepL APIで翻訳する
+    async fn translate_deepl(&self, content: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let api_key = env::var("DEEPL_API_KEY").map_err(|_| "環境変数 DEEPL_API_KEY が設定されていません")?;
+        // 無料版のキーは末尾が :fx で、エンドポイントが異なる
+        let endpoint = if api_key.ends_with(":fx") {
+            "https://api-free.deepl.com/v2/translate"
+        } else {
+            "https://api.deepl.com/v2/translate"
+        };
+
+        let response: Value = reqwest::Client::new()
+            .post(endpoint)
+            .header("Authorization", format!("DeepL-Auth-Key {}", api_key))
+            .json(&json!({ "text": [content], "target_lang": self.target_lang.to_uppercase() }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        response["translations"][0]["text"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| "DeepL APIの応答に翻訳結果が含まれていません".into())
+    }
+
+    /// Google Cloud Translation APIで翻訳する
+    async fn translate_google(&self, content: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let api_key = env::var("GOOGLE_TRANSLATE_API_KEY")
+            .map_err(|_| "環境変数 GOOGLE_TRANSLATE_API_KEY が設定されていません")?;
+
+        let response: Value = reqwest::Client::new()
+            .post("https://translation.googleapis.com/language/translate/v2")
+            .query(&[("key", api_key.as_str())])
+            .json(&json!({ "q": content, "target": self.target_lang.to_lowercase(), "format": "text" }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        response["data"]["translations"][0]["translatedText"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| "Google翻訳APIの応答に翻訳結果が含まれていません".into())
+    }
+}
+
+#[async_trait]
+impl MessageTransform for Translate {
+    fn name(&self) -> &'static str {
+        "translate"
+    }
+
+    async fn apply(&self, content: String) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        if content.trim().is_empty() {
+            return Ok(content);
+        }
+
+        let translated = match self.provider {
+            TranslationProvider::Deepl => self.translate_deepl(&content).await?,
+            TranslationProvider::Google => self.translate_google(&content).await?,
+        };
+        if translated == content {
+            return Ok(content);
+        }
+
+        Ok(with_original(translated, &content))
+    }
+}
+
+/// 翻訳した本文の下に原文をネタバレとして付ける
+///
+/// 長いメッセージで Discord の文字数の制限を超えないよう、原文は残りの文字数に収まる分だけ付け、収まらない部分は省略します。
+/// 翻訳した本文だけで制限に近い場合は原文を付けません。
+fn with_original(translated: String, content: &str) -> String {
+    const LABEL: &str = "\n-# 原文: ||||";
+    let budget = MAX_TRANSLATED_CHARS.saturating_sub(translated.chars().count() + LABEL.chars().count());
+
+    // 原文の `||` でネタバレが途切れないよう、原文側の `|` はエスケープする
+    let escaped = content.replace('|', "\\|");
+    let original = if escaped.chars().count() <= budget {
+        escaped
+    } else {
+        // 省略記号の分を残し、エスケープの途中で切らないようにする
+        let mut original: String = escaped.chars().take(budget.saturating_sub(1)).collect();
+        if original.ends_with('\\') {
+            original.pop();
+        }
+        original.push('…');
+        original
+    };
+    if original.trim_end_matches('…').is_empty() {
+        return translated;
+    }
+    format!("{}\n-# 原文: ||{}||", translated, original)
+}
@@ -0,0 +1,86 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use twilight_model::channel::Message;
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use crate::config::ConfigFile;
+use crate::state::ThreadInfo;
+
+/// デバッグ用に詳細ログを出力するスレッド・チャンネルの一覧
+///
+/// 環境変数 DEBUG_THREAD_IDS（カンマ区切り）と設定ファイルの `bot.debug_thread_ids` を合わせて使用します。
+#[derive(Debug, Default)]
+pub struct DebugWatch {
+    ids: HashSet<Id<ChannelMarker>>,
+}
+
+impl DebugWatch {
+    /// 環境変数と設定ファイルから監視対象のIDを読み込む
+    pub fn from_config(config: Option<&ConfigFile>) -> Self {
+        let mut ids = HashSet::new();
+
+        if let Ok(value) = env::var("DEBUG_THREAD_IDS") {
+            for part in value.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+                match part.parse::<u64>().ok().and_then(Id::new_checked) {
+                    Some(id) => {
+                        ids.insert(id);
+                    }
+                    None => println!("警告: DEBUG_THREAD_IDS に無効なIDが含まれています: {}", part),
+                }
+            }
+        }
+
+        if let Some(config) = config {
+            for id in &config.bot.debug_thread_ids {
+                match Id::new_checked(*id) {
+                    Some(id) => {
+                        ids.insert(id);
+                    }
+                    None => println!("警告: bot.debug_thread_ids に 0 は指定できません"),
+                }
+            }
+        }
+
+        if !ids.is_empty() {
+            println!("🔍 デバッグ監視対象: {} 個のID", ids.len());
+        }
+
+        Self { ids }
+    }
+
+    /// 指定したIDが監視対象かどうか
+    pub fn is_watched(&self, id: Id<ChannelMarker>) -> bool {
+        self.ids.contains(&id)
+    }
+
+    /// 監視対象のIDがマッピングに含まれているかをログに出力する
+    pub fn log_mappings(&self, mappings: &HashMap<Id<ChannelMarker>, ThreadInfo>) {
+        for id in &self.ids {
+            match mappings.get(id) {
+                Some(info) => println!("🔍 [監視] スレッド {} はチャンネル {} にマッピングされています: {:?}", id, info.target_channel_id, info),
+                None => println!("🔍 [監視] スレッド {} はマッピングに含まれていません", id),
+            }
+        }
+    }
+
+    /// 監視対象のチャンネルで受信したメッセージの詳細をログに出力する
+    pub fn log_message(&self, message: &Message, thread_info: Option<&ThreadInfo>) {
+        if !self.is_watched(message.channel_id) {
+            return;
+        }
+
+        println!("🔍 [監視] チャンネル {} でメッセージを受信: ID={}, 送信者={} (bot: {}), 種類={:?}, 本文 {} 文字, 添付 {} 件",
+            message.channel_id,
+            message.id,
+            message.author.name,
+            message.author.bot,
+            message.kind,
+            message.content.chars().count(),
+            message.attachments.len()
+        );
+        match thread_info {
+            Some(info) => println!("🔍 [監視] 転送先: チャンネル {} ({:?})", info.target_channel_id, info),
+            None => println!("🔍 [監視] このチャンネルにはマッピングがありません"),
+        }
+    }
+}